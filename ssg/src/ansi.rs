@@ -0,0 +1,252 @@
+//! Utility for rendering terminal transcripts containing ANSI escape sequences as styled HTML.
+//!
+//! Select Graphic Rendition (SGR) sequences (e.g. `ESC[1;31m` for bold red text) are converted to `<span>` elements
+//! with inline styles. Other escape sequences, such as cursor movement and window titles, are removed.
+
+use crate::component::escape_html;
+use std::fmt::Write;
+
+// The name of the fenced code block language for terminal transcripts
+pub(crate) const ANSI_LANGUAGE: &str = "ansi";
+
+const ESCAPE: char = '\u{1b}';
+const BELL: char = '\u{7}';
+
+// Colors of the 16 standard and bright terminal colors, from the default xterm palette
+const BASIC_COLORS: [Rgb; 16] = [
+    Rgb(0x00, 0x00, 0x00),
+    Rgb(0xcd, 0x00, 0x00),
+    Rgb(0x00, 0xcd, 0x00),
+    Rgb(0xcd, 0xcd, 0x00),
+    Rgb(0x00, 0x00, 0xee),
+    Rgb(0xcd, 0x00, 0xcd),
+    Rgb(0x00, 0xcd, 0xcd),
+    Rgb(0xe5, 0xe5, 0xe5),
+    Rgb(0x7f, 0x7f, 0x7f),
+    Rgb(0xff, 0x00, 0x00),
+    Rgb(0x00, 0xff, 0x00),
+    Rgb(0xff, 0xff, 0x00),
+    Rgb(0x5c, 0x5c, 0xff),
+    Rgb(0xff, 0x00, 0xff),
+    Rgb(0x00, 0xff, 0xff),
+    Rgb(0xff, 0xff, 0xff),
+];
+// Intensities of the 6x6x6 color cube in the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct Rgb(u8, u8, u8);
+
+// Each text attribute is set and reset independently by its own SGR parameters
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+struct AnsiStyle {
+    foreground: Option<Rgb>,
+    background: Option<Rgb>,
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl AnsiStyle {
+    /// Returns the value of a `style` attribute for text in this style.
+    fn css(self) -> String {
+        let mut css = String::new();
+        if let Some(Rgb(r, g, b)) = self.foreground {
+            write!(css, "color:#{r:02x}{g:02x}{b:02x};")
+                .expect("writing to a string should succeed");
+        }
+        if let Some(Rgb(r, g, b)) = self.background {
+            write!(css, "background-color:#{r:02x}{g:02x}{b:02x};")
+                .expect("writing to a string should succeed");
+        }
+        if self.bold {
+            css.push_str("font-weight:bold;");
+        }
+        if self.dim {
+            css.push_str("opacity:0.7;");
+        }
+        if self.italic {
+            css.push_str("font-style:italic;");
+        }
+        if self.underline {
+            css.push_str("text-decoration:underline;");
+        }
+        css
+    }
+
+    /// Applies the parameters of an SGR sequence (e.g. `1;31` for `ESC[1;31m`). Unsupported parameters are ignored.
+    fn apply(&mut self, params: &str) {
+        let mut params = params
+            .split([';', ':'])
+            .map(|param| param.parse().unwrap_or(0));
+        while let Some(param) = params.next() {
+            match param {
+                0 => *self = Self::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.foreground = Some(BASIC_COLORS[usize::from(param - 30)]),
+                38 => self.foreground = extended_color(&mut params),
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(BASIC_COLORS[usize::from(param - 40)]),
+                48 => self.background = extended_color(&mut params),
+                49 => self.background = None,
+                90..=97 => self.foreground = Some(BASIC_COLORS[usize::from(param - 90 + 8)]),
+                100..=107 => self.background = Some(BASIC_COLORS[usize::from(param - 100 + 8)]),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Reads the color of an extended color parameter (e.g. `5;208` or `2;255;128;0` after `38`).
+fn extended_color(params: &mut impl Iterator<Item = u8>) -> Option<Rgb> {
+    match params.next()? {
+        5 => {
+            let index = params.next()?;
+            Some(match index {
+                0..=15 => BASIC_COLORS[usize::from(index)],
+                16..=231 => {
+                    let index = usize::from(index - 16);
+                    Rgb(
+                        CUBE_LEVELS[index / 36],
+                        CUBE_LEVELS[index / 6 % 6],
+                        CUBE_LEVELS[index % 6],
+                    )
+                }
+                _ => {
+                    let level = 8 + (index - 232) * 10;
+                    Rgb(level, level, level)
+                }
+            })
+        }
+        2 => Some(Rgb(params.next()?, params.next()?, params.next()?)),
+        _ => None,
+    }
+}
+
+/// Converter of terminal transcripts to HTML, one line at a time.
+/// Styles carry over from one line to the next, like in a terminal.
+#[derive(Default)]
+pub(crate) struct AnsiRenderer {
+    style: AnsiStyle,
+}
+
+impl AnsiRenderer {
+    /// Appends HTML for a line of a terminal transcript to `output`.
+    /// Styled text is wrapped in `<span>` elements that are closed by the end of the line.
+    pub(crate) fn push_line(&mut self, output: &mut String, line: &str) {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let mut is_span_open = self.open_span(output);
+        let mut text = String::new();
+
+        let mut chars = content.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != ESCAPE {
+                text.push(c);
+                continue;
+            }
+            match chars.next() {
+                // Control Sequence Introducer, followed by parameters and a final byte
+                Some('[') => {
+                    let mut params = String::new();
+                    let mut final_byte = None;
+                    for c in chars.by_ref() {
+                        if ('\u{40}'..='\u{7e}').contains(&c) {
+                            final_byte = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    if final_byte != Some('m') {
+                        continue;
+                    }
+                    let mut style = self.style;
+                    style.apply(&params);
+                    if style != self.style {
+                        output.push_str(&escape_html(&text));
+                        text.clear();
+                        if is_span_open {
+                            output.push_str("</span>");
+                        }
+                        self.style = style;
+                        is_span_open = self.open_span(output);
+                    }
+                }
+                // Operating System Command (e.g. setting the window title), terminated by BEL or ST (`ESC\`)
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == BELL || (c == ESCAPE && chars.next_if_eq(&'\\').is_some()) {
+                            break;
+                        }
+                    }
+                }
+                // Other escape sequences consist of a single character
+                _ => {}
+            }
+        }
+
+        output.push_str(&escape_html(&text));
+        if is_span_open {
+            output.push_str("</span>");
+        }
+        output.push_str(&line[content.len()..]);
+    }
+
+    /// Opens a `<span>` element for the current style, if it isn't the default style.
+    /// Returns a Boolean indicating if an element was opened.
+    fn open_span(&self, output: &mut String) -> bool {
+        if self.style == AnsiStyle::default() {
+            return false;
+        }
+        write!(output, "<span style=\"{}\">", self.style.css())
+            .expect("writing to a string should succeed");
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AnsiRenderer;
+
+    fn render(text: &str) -> String {
+        let mut renderer = AnsiRenderer::default();
+        let mut output = String::new();
+        for line in text.split_inclusive('\n') {
+            renderer.push_line(&mut output, line);
+        }
+        output
+    }
+
+    #[test]
+    fn ansi() {
+        assert_eq!(render("plain <text>\n"), "plain &lt;text&gt;\n");
+        assert_eq!(
+            render("\u{1b}[1;31merror\u{1b}[0m: oops"),
+            "<span style=\"color:#cd0000;font-weight:bold;\">error</span>: oops"
+        );
+        // Styles carry over to the next line, with spans closed at the end of each line
+        assert_eq!(
+            render("\u{1b}[4ma\nb\u{1b}[24m c"),
+            "<span style=\"text-decoration:underline;\">a</span>\n\
+            <span style=\"text-decoration:underline;\">b</span> c"
+        );
+        assert_eq!(
+            render("\u{1b}[38;5;208ma\u{1b}[39;48;2;1;2;3mb\u{1b}[38;5;244;49mc\u{1b}[m"),
+            "<span style=\"color:#ff8700;\">a</span>\
+            <span style=\"background-color:#010203;\">b</span>\
+            <span style=\"color:#808080;\">c</span>"
+        );
+        // Other escape sequences are removed
+        assert_eq!(
+            render("\u{1b}]0;title\u{7}\u{1b}[2K\u{1b}[1Gdone\u{1b}]8;;\u{1b}\\"),
+            "done"
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! Code for converting and copying the images in an article while it is built, processing every image only once.
+
+#[cfg(feature = "svg")]
+use crate::svg::minify_svg;
+use crate::{
+    config::Config,
+    image::{
+        ActiveImageState, DEFAULT_IMAGE_SIZES, Dimensions, ImageVariants, OUTPUT_IMAGE_EXTENSION,
+        convert_image, convert_image_variants, convert_image_width_variants, convert_remote_image,
+        is_remote_image_src, output_size, parse_image_src, validate_image_src,
+    },
+    profile::{ArticleProfile, Stage},
+    report::ArticleMetrics,
+    vfs::Vfs,
+};
+use anyhow::{Context, Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use foldhash::{HashMap, HashMapExt};
+use pulldown_cmark::CowStr;
+use same_file::Handle;
+use std::collections::hash_map::Entry;
+
+/// Images of an article being built, which are converted to AVIF (or copied) to the article's output directory
+pub struct ArticleImages<'a> {
+    input_dir: &'a Utf8Path,
+    output_dir: &'a Utf8Path,
+    slug: &'a str,
+    config: &'a Config,
+    vfs: &'a dyn Vfs,
+    // Converted images, keyed by their file and display width, to avoid redundant processing
+    local: HashMap<(ImageKey, Option<u32>), ConvertedImage>,
+    // Converted images with a URL as their source, keyed by URL
+    remote: HashMap<Box<str>, ConvertedImage>,
+}
+
+impl<'a> ArticleImages<'a> {
+    /// Sets up the processing of images in the article with `slug`, whose files are in `input_dir`.
+    #[must_use]
+    pub fn new(
+        input_dir: &'a Utf8Path,
+        output_dir: &'a Utf8Path,
+        slug: &'a str,
+        config: &'a Config,
+        vfs: &'a dyn Vfs,
+    ) -> Self {
+        Self {
+            input_dir,
+            output_dir,
+            slug,
+            config,
+            vfs,
+            local: HashMap::new(),
+            remote: HashMap::new(),
+        }
+    }
+
+    /// Converts or copies the image with the source `dest_url`, unless it was already processed,
+    /// and returns the state for rendering the image once its alt text has been parsed.
+    /// The image is added to `metrics`, and its path is added to `inputs` if it is a local file.
+    ///
+    /// # Errors
+    /// This function returns an error if the image source is invalid or the image cannot be processed.
+    pub fn start_image<'b>(
+        &mut self,
+        dest_url: &str,
+        title: CowStr<'b>,
+        id: CowStr<'b>,
+        metrics: &mut ArticleMetrics,
+        inputs: &mut Vec<Utf8PathBuf>,
+        profile: &mut ArticleProfile,
+    ) -> Result<ActiveImageState<'b>> {
+        let Self {
+            input_dir,
+            output_dir,
+            slug,
+            config,
+            vfs,
+            ..
+        } = *self;
+        let asset_urls = config.asset_urls();
+
+        if is_remote_image_src(dest_url) {
+            if !config.remote_images {
+                bail!(
+                    "image source is a URL ({dest_url}); remote images require `remote_images` to be enabled"
+                );
+            }
+
+            let ConvertedImage {
+                src,
+                dimensions,
+                placeholder,
+                ..
+            } = match self.remote.entry(dest_url.into()) {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(entry) => {
+                    let converted = profile
+                        .time(Stage::ImageEncode, || {
+                            convert_remote_article_image(dest_url, output_dir, config, vfs)
+                        })
+                        .context("failed to process image")?;
+                    metrics.images_converted += 1;
+                    metrics.image_bytes += converted.output_bytes;
+                    entry.insert(converted).clone()
+                }
+            };
+
+            return Ok(ActiveImageState::new(
+                asset_urls
+                    .article_local_asset(slug, &src)
+                    .into_owned()
+                    .into(),
+                Some(dimensions),
+                None,
+                None,
+                config.image_fallback,
+                title,
+                id,
+            )
+            .with_placeholder(placeholder));
+        }
+
+        let (src, display_width) = parse_image_src(dest_url).context("image source is invalid")?;
+        validate_image_src(src).context("image source is invalid")?;
+
+        let input_path = input_dir.join(src);
+        if !vfs.is_file(&input_path) {
+            bail!("failed to open file at {input_path}");
+        }
+        // Files on disk are compared by identity, so different paths to the same image are only converted once
+        let input_key = match Handle::from_path(&input_path) {
+            Ok(handle) => ImageKey::Handle(handle),
+            Err(_) => ImageKey::Path(input_path.clone()),
+        };
+        inputs.push(input_path.clone());
+
+        if input_path
+            .extension()
+            .is_some_and(|ext| ext == OUTPUT_IMAGE_EXTENSION || ext == "svg")
+        {
+            if display_width.is_some() {
+                bail!("image display widths are only supported for images converted to AVIF");
+            }
+
+            metrics.image_bytes += copy_article_image(&input_path, &output_dir.join(src), vfs)
+                .context("failed to process image")?;
+            metrics.images_copied += 1;
+
+            let src = asset_urls.article_local_asset(slug, dest_url);
+            return Ok(ActiveImageState::new(
+                src.into_owned().into(),
+                None,
+                None,
+                None,
+                None,
+                title,
+                id,
+            ));
+        }
+
+        let ConvertedImage {
+            src,
+            dimensions,
+            srcset,
+            sizes,
+            placeholder,
+            ..
+        } = match self.local.entry((input_key, display_width)) {
+            Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(entry) => {
+                let converted = profile
+                    .time(Stage::ImageEncode, || {
+                        convert_article_image(
+                            input_dir,
+                            output_dir,
+                            src,
+                            display_width,
+                            config,
+                            vfs,
+                        )
+                    })
+                    .context("failed to process image")?;
+                metrics.images_converted += 1;
+                metrics.image_bytes += converted.output_bytes;
+                entry.insert(converted).clone()
+            }
+        };
+
+        let srcset = srcset.map(|srcset| asset_urls.article_local_srcset(slug, &srcset).into());
+        Ok(ActiveImageState::new(
+            asset_urls
+                .article_local_asset(slug, &src)
+                .into_owned()
+                .into(),
+            Some(dimensions),
+            srcset,
+            sizes,
+            config.image_fallback,
+            title,
+            id,
+        )
+        .with_placeholder(placeholder))
+    }
+}
+
+/// Identity of an image file in an article, for converting every image only once
+#[derive(PartialEq, Eq, Hash)]
+enum ImageKey {
+    Handle(Handle),
+    // Files that aren't on disk are identified by their path
+    Path(Utf8PathBuf),
+}
+
+/// Output of converting an image in an article to AVIF
+#[derive(Clone)]
+struct ConvertedImage {
+    src: Box<str>,
+    dimensions: Dimensions,
+    srcset: Option<Box<str>>,
+    // Value of the `sizes` attribute, for images with width variants
+    sizes: Option<Box<str>>,
+    // CSS `background` value for a placeholder shown while the image loads
+    placeholder: Option<Box<str>>,
+    output_bytes: u64,
+}
+
+/// Converts an image in an article to AVIF, with pixel density variants if it has a fixed display width,
+/// or with width variants if widths are configured.
+fn convert_article_image(
+    input_dir: &Utf8Path,
+    output_dir: &Utf8Path,
+    src: &str,
+    display_width: Option<u32>,
+    config: &Config,
+    vfs: &dyn Vfs,
+) -> Result<ConvertedImage> {
+    if let Some(display_width) = display_width {
+        let ImageVariants {
+            src,
+            srcset,
+            dimensions,
+            placeholder,
+            output_bytes,
+        } = convert_image_variants(
+            input_dir,
+            output_dir,
+            src,
+            display_width,
+            config.image_options(),
+            vfs,
+        )?;
+
+        Ok(ConvertedImage {
+            src: src.into(),
+            dimensions,
+            srcset: Some(srcset.into()),
+            sizes: None,
+            placeholder: placeholder.map(Into::into),
+            output_bytes,
+        })
+    } else if !config.image_widths.is_empty() {
+        let ImageVariants {
+            src,
+            srcset,
+            dimensions,
+            placeholder,
+            output_bytes,
+        } = convert_image_width_variants(
+            input_dir,
+            output_dir,
+            src,
+            &config.image_widths,
+            config.image_options(),
+            vfs,
+        )?;
+
+        Ok(ConvertedImage {
+            src: src.into(),
+            dimensions,
+            srcset: Some(srcset.into()),
+            sizes: Some(
+                config
+                    .image_sizes
+                    .as_deref()
+                    .unwrap_or(DEFAULT_IMAGE_SIZES)
+                    .into(),
+            ),
+            placeholder: placeholder.map(Into::into),
+            output_bytes,
+        })
+    } else {
+        let (dimensions, placeholder) =
+            convert_image(input_dir, output_dir, src, config.image_options(), vfs)?;
+        let output_src = Utf8Path::new(src)
+            .with_extension(OUTPUT_IMAGE_EXTENSION)
+            .into_string();
+        let output_bytes = output_size(&output_dir.join(&output_src), config.image_fallback, vfs)?;
+
+        Ok(ConvertedImage {
+            src: output_src.into(),
+            dimensions,
+            srcset: None,
+            sizes: None,
+            placeholder: placeholder.map(Into::into),
+            output_bytes,
+        })
+    }
+}
+
+/// Copies an image that isn't converted to the output directory, minifying it if it is an SVG image.
+/// This function outputs the size of the output file.
+fn copy_article_image(input_path: &Utf8Path, output_path: &Utf8Path, vfs: &dyn Vfs) -> Result<u64> {
+    #[cfg(feature = "svg")]
+    if input_path.extension() == Some("svg") {
+        let svg = vfs
+            .read_to_string(input_path)
+            .with_context(|| format!("failed to read SVG from {input_path}"))?;
+        let svg =
+            minify_svg(&svg).with_context(|| format!("failed to minify SVG at {input_path}"))?;
+        vfs.write(output_path, svg.as_bytes(), false)
+            .with_context(|| format!("failed to write SVG to {output_path}"))?;
+        return Ok(svg.len() as u64);
+    }
+
+    vfs.copy(input_path, output_path)
+        .with_context(|| format!("failed to copy file from {input_path} to {output_path}"))
+}
+
+/// Downloads an image with a URL as its source and converts it to AVIF.
+/// Display widths and width variants aren't supported, since the URL may have its own query string.
+fn convert_remote_article_image(
+    url: &str,
+    output_dir: &Utf8Path,
+    config: &Config,
+    vfs: &dyn Vfs,
+) -> Result<ConvertedImage> {
+    let (src, dimensions, placeholder) =
+        convert_remote_image(url, output_dir, config.image_options(), vfs)?;
+    let output_bytes = output_size(&output_dir.join(&src), config.image_fallback, vfs)?;
+
+    Ok(ConvertedImage {
+        src: src.into(),
+        dimensions,
+        srcset: None,
+        sizes: None,
+        placeholder: placeholder.map(Into::into),
+        output_bytes,
+    })
+}
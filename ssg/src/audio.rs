@@ -0,0 +1,136 @@
+//! Utility for embedding local audio files in articles.
+
+use crate::{
+    builder::{create_el_with_attrs, create_text, tree_to_html},
+    local::{copy_local_file, validate_local_src},
+    shortcode::Shortcode,
+    url::AssetUrls,
+    vfs::Vfs,
+};
+use anyhow::{Result, bail};
+use camino::Utf8Path;
+use ego_tree::tree;
+
+pub struct Audio<'a> {
+    src: &'a str,
+    title: Option<&'a str>,
+}
+
+impl<'a> Audio<'a> {
+    /// Interprets a shortcode of the form `{{ audio src="..." title="..." }}` as an audio player.
+    /// This function returns `Ok(None)` if the shortcode is not named `audio`.
+    ///
+    /// # Errors
+    /// This function returns an error if the shortcode has missing, unknown, or invalid arguments.
+    pub fn from_shortcode(shortcode: &Shortcode<'a>) -> Result<Option<Self>> {
+        if shortcode.name != "audio" {
+            return Ok(None);
+        }
+
+        shortcode.expect_args(&["src", "title"])?;
+        let src = shortcode.require("src")?;
+        validate_local_src(src, "audio source")?;
+
+        Ok(Some(Self {
+            src,
+            title: shortcode.get("title"),
+        }))
+    }
+
+    /// Consumes the audio player, returning an `<audio>` element as a string of HTML for the page of the article with `slug`.
+    /// The audio file is copied from `input_dir` to `output_dir`.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the audio file is not an MP3, AAC, Ogg, Opus, FLAC, or WAV file
+    /// - the audio file cannot be copied
+    pub fn into_html(
+        self,
+        input_dir: &Utf8Path,
+        output_dir: &Utf8Path,
+        slug: &str,
+        asset_urls: &AssetUrls,
+        vfs: &dyn Vfs,
+    ) -> Result<String> {
+        let mime = audio_mime(self.src)?;
+        copy_local_file(self.src, input_dir, output_dir, vfs)?;
+        Ok(self.html(mime, slug, asset_urls))
+    }
+
+    /// Returns an `<audio>` element with controls, which loads only the audio's metadata until it is played.
+    /// Browsers that can't play the audio show a download link instead.
+    fn html(&self, mime: &str, slug: &str, asset_urls: &AssetUrls) -> String {
+        let src = asset_urls.article_local_asset(slug, self.src);
+
+        let mut attrs = vec![
+            ("class", "__audio"),
+            ("controls", ""),
+            ("preload", "metadata"),
+        ];
+        attrs.extend(self.title.map(|title| ("aria-label", title)));
+
+        tree_to_html(tree! {
+            create_el_with_attrs("audio", &attrs) => {
+                create_el_with_attrs("source", &[("src", &src), ("type", mime)]),
+                create_el_with_attrs("a", &[("href", &src), ("download", "")]) => {
+                    create_text("Download audio")
+                }
+            }
+        })
+    }
+}
+
+/// Returns the MIME type of an audio file based on its file extension.
+fn audio_mime(src: &str) -> Result<&'static str> {
+    let extension = Utf8Path::new(src)
+        .extension()
+        .unwrap_or_default()
+        .to_lowercase();
+    Ok(match extension.as_str() {
+        "mp3" => "audio/mpeg",
+        "m4a" | "aac" => "audio/mp4",
+        "ogg" | "oga" => "audio/ogg",
+        "opus" => "audio/ogg; codecs=opus",
+        "flac" => "audio/flac",
+        "wav" => "audio/wav",
+        _ => bail!("audio must be an MP3, AAC, Ogg, Opus, FLAC, or WAV file ({src})"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Audio, audio_mime};
+    use crate::{shortcode::Shortcode, url::AssetUrls};
+
+    #[test]
+    fn audio() {
+        let audio_with_urls = |input, asset_urls: &AssetUrls| {
+            Audio::from_shortcode(&Shortcode::parse(input).unwrap().unwrap()).map(|audio| {
+                audio
+                    .unwrap()
+                    .html(audio_mime("a.mp3").unwrap(), "post", asset_urls)
+            })
+        };
+        let audio = |input| audio_with_urls(input, &AssetUrls::default());
+
+        assert_eq!(
+            audio(r#"{{ audio src="clips/a.mp3" title="A & B" }}"#).unwrap(),
+            r#"<audio class="__audio" controls="" preload="metadata" aria-label="A &amp; B"><source src="clips/a.mp3" type="audio/mpeg"><a href="clips/a.mp3" download="">Download audio</a></audio>"#
+        );
+        // Files are served from the asset base URL if one is configured
+        assert_eq!(
+            audio_with_urls(
+                r#"{{ audio src="a.mp3" }}"#,
+                &AssetUrls::new(Some("https://cdn.example.com/"))
+            )
+            .unwrap(),
+            r#"<audio class="__audio" controls="" preload="metadata"><source src="https://cdn.example.com/writing/post/a.mp3" type="audio/mpeg"><a href="https://cdn.example.com/writing/post/a.mp3" download="">Download audio</a></audio>"#
+        );
+        assert!(audio(r#"{{ audio src="/a.mp3" }}"#).is_err());
+        assert!(audio(r#"{{ audio src="clips/../../a.mp3" }}"#).is_err());
+        assert!(audio(r#"{{ audio src="a.mp3" loop="true" }}"#).is_err());
+        assert!(audio("{{ audio }}").is_err());
+        assert_eq!(audio_mime("a.OPUS").unwrap(), "audio/ogg; codecs=opus");
+        assert!(audio_mime("a.mp4").is_err());
+    }
+}
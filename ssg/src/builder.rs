@@ -1,44 +1,154 @@
 //! Code for building complete HTML pages from article bodies.
 
-use crate::{OUTPUT_SITE_CSS_FILE_ABSOLUTE, css::Font};
+use crate::{
+    OUTPUT_ATOM_FEED_FILE, OUTPUT_CHANGELOG_DIR, OUTPUT_CONTENT_DIR, OUTPUT_KATEX_CSS_FILE,
+    OUTPUT_RSS_FEED_FILE, OUTPUT_SITE_CSS_FILE_ABSOLUTE, OUTPUT_TAGS_DIR, TAG_ATOM_FEED_FILE_NAME,
+    TAG_RSS_FEED_FILE_NAME,
+    cache::stable_hash,
+    config::{Config, FeedConfig},
+    frontmatter::{Frontmatter, License},
+    image::{Dimensions, FallbackFormat},
+    manifest::OUTPUT_MANIFEST_FILE,
+    partial::{Template, resolve_includes},
+    url::{AssetUrls, article_url, page_url, tag_url},
+};
 use anyhow::{Context, Error, Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
 use ego_tree::{NodeId, NodeMut, Tree, tree};
-use jiff::civil::Date;
+use jiff::{Timestamp, Unit, civil::Date, fmt::rfc2822::DateTimePrinter, tz::TimeZone};
 use markup5ever::{Attribute, QualName, interface::QuirksMode, ns, tendril::Tendril};
 use scraper::{
-    Html,
+    ElementRef, Html,
     node::{Doctype, Element, Node, Text},
 };
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Write},
+};
 
-const OUTPUT_KATEX_CSS_FILE: &str = "/stylesheets/katex.css";
+// Elements that articles can add to `<head>` of their pages
+const PAGE_HEAD_ELEMENTS: [&str; 5] = ["link", "meta", "noscript", "script", "style"];
+
+// Attribute marking elements of the body template that named content pieces are slotted into
+const SLOT_ATTR: &str = "data-slot";
+
+// Name of the element in templates that is replaced by the time of the build
+const BUILD_STAMP_TAG: &str = "build-stamp";
+
+// Attribute of elements wrapping code blocks that get a copy button
+const COPY_CODE_ATTRIBUTE: &str = "data-copy-code";
+
+// Script adding a button for copying code to every element with the `data-copy-code` attribute.
+// Visualized whitespace is turned back into the spaces and tabs it stands for before copying.
+const COPY_CODE_SCRIPT: &str = r#"for (const block of document.querySelectorAll("[data-copy-code]")) {
+  const button = document.createElement("button");
+  button.type = "button";
+  button.className = "__code-copy-button";
+  button.textContent = "Copy";
+  button.addEventListener("click", async () => {
+    const code = block.querySelector("pre").cloneNode(true);
+    for (const whitespace of code.querySelectorAll(".__whitespace")) {
+      whitespace.textContent = whitespace.textContent.replace(/→ */g, "\t").replace(/·/g, " ");
+    }
+    try {
+      await navigator.clipboard.writeText(code.textContent);
+      button.textContent = "Copied";
+    } catch {
+      button.textContent = "Failed to copy";
+    }
+    setTimeout(() => { button.textContent = "Copy"; }, 2000);
+  });
+  block.append(button);
+}"#;
 
 pub struct PageBuilder {
     html: Tree<Node>,
     head_id: NodeId,
     slot_id: NodeId,
+    // Elements of the body template for slotting named content pieces, keyed by slot name
+    named_slot_ids: BTreeMap<Box<str>, NodeId>,
+    age_warning: Option<AgeWarning>,
+    // Absent if math doesn't need the KaTeX stylesheet (e.g. if it's rendered as MathML only)
+    katex_css_loading: Option<KatexCssLoading>,
+    katex_css_href: String,
+    // Absolute URL of the site root without a trailing slash, for canonical links; absent if not configured
+    base_url: Option<Box<str>>,
+    // Whether article pages include a breadcrumb trail
+    breadcrumbs: bool,
+    // Canonical paths of partial files included by the templates
+    partials: BTreeSet<Utf8PathBuf>,
+}
+
+/// Strategy for loading the KaTeX stylesheet on pages containing math.
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum KatexCssLoading {
+    /// Load the stylesheet with a render-blocking `<link>`.
+    /// Math is never displayed without styles, but the page is not rendered until the stylesheet loads.
+    #[default]
+    Blocking,
+    /// Preload the stylesheet and apply it once loaded, without blocking rendering.
+    /// Math may briefly be displayed without styles, which suits pages where math appears far below the fold.
+    Deferred,
+}
+
+/// What `<build-stamp>` elements in templates are replaced with.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildStamp {
+    /// The time of the build, taken from the `SOURCE_DATE_EPOCH` environment variable if set
+    #[default]
+    Clock,
+    /// Nothing, so pages don't change between builds of the same input
+    Omit,
+}
+
+/// A font file preloaded by every page.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct Font {
+    pub(crate) path: Box<str>,
+    pub(crate) mime: Option<&'static str>,
+}
+
+#[derive(Clone, Copy)]
+struct AgeWarning {
+    threshold_years: i16,
+    today: Date,
 }
 
 impl PageBuilder {
     /// Initializes a webpage HTML builder. Every page built:
     /// - includes `<head>` elements from the input head template
     /// - includes `<body>` elements from the input body template
+    /// - can fill elements of the body template marked with `data-slot="<name>"` with named content pieces
+    /// - includes the contents of partial files in place of `<include src="...">` tags in the templates
     /// - specifies preloaded fonts from the input list of font sources
     /// - contains inlined styles from the input stylesheet
     ///
     /// # Errors
     /// This function returns an error if:
+    /// - partials included by the input templates cannot be read or include each other in a cycle
     /// - the input templates cannot be successfully parsed as no-quirks HTML
     /// - the input body template does not contain a `<main>` element for slotting page content
+    /// - multiple elements of the input body template have the same slot name, or an empty slot name
     pub fn new(
-        head_template: &str,
-        body_template: &str,
+        head_template: Template<'_>,
+        body_template: Template<'_>,
         site_fonts: &[Font],
         inline_styles: &str,
     ) -> Result<Self> {
+        let mut partials = BTreeSet::new();
+        let head_template = resolve_includes(head_template, &mut partials)
+            .context("failed to resolve partials in head HTML template")?;
+        let body_template = resolve_includes(body_template, &mut partials)
+            .context("failed to resolve partials in body HTML template")?;
+
         let head_template =
-            parse_html(head_template).context("failed to parse head HTML template")?;
+            parse_html(&head_template).context("failed to parse head HTML template")?;
         let body_template =
-            parse_html(body_template).context("failed to parse body HTML template")?;
+            parse_html(&body_template).context("failed to parse body HTML template")?;
 
         let mut html = Html::new_document();
         let mut root_node = html.tree.root_mut();
@@ -102,6 +212,7 @@ impl PageBuilder {
 
         // Add body template within `<body>`
         append_fragment(&mut body_el_node, body_template);
+        let body_id = body_el_node.id();
 
         // Find element in body template for slotting page content
         // We search in reverse insertion order because the body template's HTML nodes were inserted last.
@@ -114,48 +225,526 @@ impl PageBuilder {
             bail!("body template does not have a `<main>` element for slotting page content");
         };
 
+        // Find elements in body template for slotting named content pieces
+        let mut named_slot_ids = BTreeMap::new();
+        // SAFETY: The ID is valid because it was obtained from appending a node to the tree.
+        for node in unsafe { html.tree.get_unchecked(body_id) }.descendants() {
+            let Some(name) = node.value().as_element().and_then(|el| el.attr(SLOT_ATTR)) else {
+                continue;
+            };
+            if name.is_empty() {
+                bail!("body template has an element with an empty `{SLOT_ATTR}` attribute");
+            } else if named_slot_ids.insert(name.into(), node.id()).is_some() {
+                bail!("body template has multiple elements for the slot \"{name}\"");
+            }
+        }
+
         Ok(Self {
             html: html.tree,
             head_id,
             slot_id,
+            named_slot_ids,
+            age_warning: None,
+            katex_css_loading: Some(KatexCssLoading::default()),
+            katex_css_href: page_url(OUTPUT_KATEX_CSS_FILE),
+            base_url: None,
+            breadcrumbs: false,
+            partials,
         })
     }
 
-    /// Outputs a string containing a complete HTML document based on the provided document title and body
-    /// (and article metadata if the page is an article).
+    /// Returns the canonical paths of the partial files included by the templates.
+    pub fn partials(&self) -> impl Iterator<Item = &Utf8Path> {
+        self.partials.iter().map(AsRef::as_ref)
+    }
+
+    /// Enables age warnings for article pages. Every article page built afterward
+    /// includes a notice if its last-updated date (or creation date, if it was never updated)
+    /// is at least `threshold_years` years before `today`.
+    pub fn set_age_warning(&mut self, threshold_years: i16, today: Date) {
+        self.age_warning = Some(AgeWarning {
+            threshold_years,
+            today,
+        });
+    }
+
+    /// Replaces every `<build-stamp>` element in the templates with a `<time>` element
+    /// showing the date of `time`, or removes them if `time` is `None`.
+    /// The replacement is the same on every page, so it is only done once per build.
+    pub fn set_build_stamp(&mut self, time: Option<Timestamp>) {
+        let stamp_ids: Vec<_> = self
+            .html
+            .nodes()
+            .filter(|node| {
+                node.value()
+                    .as_element()
+                    .is_some_and(|el| el.name() == BUILD_STAMP_TAG)
+            })
+            .map(|node| node.id())
+            .collect();
+
+        let strings = time.map(|time| {
+            (
+                time.strftime("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                time.to_zoned(TimeZone::UTC).date().to_string(),
+            )
+        });
+
+        for id in stamp_ids {
+            // SAFETY: The ID is valid because it was just obtained from the tree.
+            let mut stamp = unsafe { self.html.get_unchecked_mut(id) };
+            if let Some((datetime, date)) = &strings {
+                stamp
+                    .insert_before(create_el_with_attrs("time", &[("datetime", datetime)]))
+                    .append(create_text(date));
+            }
+            stamp.detach();
+        }
+    }
+
+    /// Sets the strategy for loading the KaTeX stylesheet on pages containing math.
+    /// With `None`, pages never load the stylesheet.
+    pub fn set_katex_css_loading(&mut self, loading: Option<KatexCssLoading>) {
+        self.katex_css_loading = loading;
+    }
+
+    /// Serves the KaTeX stylesheet (and the fonts it references) from the asset base URL, if one is configured.
+    /// If assets are served from another origin, every page built afterward
+    /// also includes a `<link rel="preconnect">` to that origin.
+    pub fn set_asset_urls(&mut self, asset_urls: &AssetUrls) {
+        self.katex_css_href = asset_urls.asset(OUTPUT_KATEX_CSS_FILE);
+        if let Some(base_url) = asset_urls.base_url() {
+            // Fonts are always fetched with CORS, so the connection needs to be made in CORS mode too
+            // SAFETY: The ID is valid because it was generated in the constructor `PageBuilder::new()`.
+            unsafe { self.html.get_unchecked_mut(self.head_id) }.append(create_el_with_attrs(
+                "link",
+                &[
+                    ("rel", "preconnect"),
+                    ("href", base_url),
+                    ("crossorigin", "anonymous"),
+                ],
+            ));
+        }
+    }
+
+    /// Replaces the `<link>` to the site CSS file within `<head>` with a `<style>` element containing `css`.
+    /// Every page built afterward renders without waiting for a stylesheet request, at the cost of a larger page.
+    pub fn inline_site_css(&mut self, css: &str) {
+        // SAFETY: The ID is valid because it was generated in the constructor `PageBuilder::new()`.
+        let link_id = unsafe { self.html.get_unchecked(self.head_id) }
+            .children()
+            .find(|node| {
+                node.value().as_element().is_some_and(|el| {
+                    el.name() == "link" && el.attr("href") == Some(OUTPUT_SITE_CSS_FILE_ABSOLUTE)
+                })
+            })
+            .map(|node| node.id());
+
+        if let Some(link_id) = link_id {
+            // SAFETY: The ID is valid because it was just obtained from the tree.
+            let mut link_node = unsafe { self.html.get_unchecked_mut(link_id) };
+            link_node
+                .insert_before(create_el("style"))
+                .append(create_text(css));
+            link_node.detach();
+        }
+    }
+
+    /// Sets the absolute URL of the site root. Every page built afterward includes a `<link rel="canonical">`
+    /// and an `og:url` with the page's absolute URL.
+    pub fn set_base_url(&mut self, base_url: Option<&str>) {
+        self.base_url = base_url.map(|url| url.trim_end_matches('/').into());
+    }
+
+    /// Enables breadcrumb trails for article pages. Every article page built afterward includes a `__breadcrumbs`
+    /// `<nav>` linking to the site root and the article archive, along with `BreadcrumbList` JSON-LD if the base URL
+    /// is set (since structured data needs absolute URLs).
+    pub fn set_breadcrumbs(&mut self, enabled: bool) {
+        self.breadcrumbs = enabled;
+    }
+
+    /// Appends a `<link rel="alternate">` to a feed within `<head>`.
+    /// Every page built afterward includes the link, which lets feed readers discover the feed.
+    pub fn add_feed_link(&mut self, title: &str, href: &str, mime: &str) {
+        // SAFETY: The ID is valid because it was generated in the constructor `PageBuilder::new()`.
+        unsafe { self.html.get_unchecked_mut(self.head_id) }
+            .append(create_feed_link(title, href, mime));
+    }
+
+    /// Appends a `<link>` with a resource hint (e.g. `preconnect`, `dns-prefetch`, or `modulepreload`) within `<head>`.
+    /// Every page built afterward includes the hint.
+    pub fn add_resource_hint(&mut self, rel: &str, href: &str) {
+        // SAFETY: The ID is valid because it was generated in the constructor `PageBuilder::new()`.
+        unsafe { self.html.get_unchecked_mut(self.head_id) }.append(create_el_with_attrs(
+            "link",
+            &[("rel", rel), ("href", href)],
+        ));
+    }
+
+    /// Appends a `<link rel="manifest">` to the web app manifest within `<head>`.
+    /// Every page built afterward includes the link.
+    pub fn add_manifest_link(&mut self, href: &str) {
+        // SAFETY: The ID is valid because it was generated in the constructor `PageBuilder::new()`.
+        unsafe { self.html.get_unchecked_mut(self.head_id) }.append(create_el_with_attrs(
+            "link",
+            &[("rel", "manifest"), ("href", href)],
+        ));
+    }
+
+    /// Appends a `<meta name="theme-color">` within `<head>`, which browsers use to color their UI around the page.
+    /// Every page built afterward includes the element.
+    pub fn add_theme_color(&mut self, color: &str) {
+        // SAFETY: The ID is valid because it was generated in the constructor `PageBuilder::new()`.
+        unsafe { self.html.get_unchecked_mut(self.head_id) }.append(create_el_with_attrs(
+            "meta",
+            &[("name", "theme-color"), ("content", color)],
+        ));
+    }
+
+    /// Appends a link to the last `<footer>` element in the body template, if one exists.
+    /// Every page built afterward includes the link.
+    pub fn add_footer_link(&mut self, text: &str, href: &str) {
+        let footer_id = self.html.nodes().rev().find_map(|node| {
+            node.value()
+                .as_element()
+                .is_some_and(|el| el.name() == "footer")
+                .then(|| node.id())
+        });
+
+        if let Some(footer_id) = footer_id {
+            // SAFETY: The ID is valid because it was obtained from iterating over the nodes of the tree.
+            unsafe { self.html.get_unchecked_mut(footer_id) }.append_subtree(tree! {
+                create_el_with_attrs("a", &[("href", href)]) => { create_text(text) }
+            });
+        }
+    }
+
+    /// Sets up the builder for a build of the site with the settings in the config file, inlining `site_css` if configured.
+    /// Anything that depends on the time of the build (build stamps and age warnings) uses `now`.
+    pub fn configure(&mut self, config: &Config, site_css: &str, now: Timestamp) {
+        // Warn readers about old articles, relative to the time of the build
+        if let Some(years) = config.age_warning_years {
+            self.set_age_warning(years, now.to_zoned(TimeZone::UTC).date());
+        }
+
+        self.set_katex_css_loading(config.katex_css_loading());
+        if config.inline_site_css {
+            self.inline_site_css(site_css);
+        }
+        self.set_asset_urls(&config.asset_urls());
+        self.set_base_url(config.base_url.as_deref());
+        self.set_breadcrumbs(config.breadcrumbs);
+        self.set_build_stamp(match config.build_stamp {
+            BuildStamp::Clock => Some(now),
+            BuildStamp::Omit => None,
+        });
+
+        // Let feed readers discover the feeds from every page
+        if let Some(feed_config) = &config.feed {
+            self.add_feed_link(
+                &feed_config.title,
+                &page_url(OUTPUT_ATOM_FEED_FILE),
+                "application/atom+xml",
+            );
+            if feed_config.rss {
+                self.add_feed_link(
+                    &feed_config.title,
+                    &page_url(OUTPUT_RSS_FEED_FILE),
+                    "application/rss+xml",
+                );
+            }
+        }
+
+        // Let browsers install the site as an app
+        if let Some(manifest) = &config.manifest {
+            self.add_manifest_link(&page_url(OUTPUT_MANIFEST_FILE));
+            if let Some(color) = &manifest.theme_color {
+                self.add_theme_color(color);
+            }
+        }
+
+        // Let browsers connect to origins and fetch scripts before pages request them
+        let hints = &config.resource_hints;
+        for (rel, hrefs) in [
+            ("preconnect", &hints.preconnect),
+            ("dns-prefetch", &hints.dns_prefetch),
+            ("modulepreload", &hints.modulepreload),
+        ] {
+            for href in hrefs {
+                self.add_resource_hint(rel, href);
+            }
+        }
+
+        // Link to the changelog page from every page
+        if config.changelog_entries.is_some() {
+            self.add_footer_link("Changelog", &page_url(OUTPUT_CHANGELOG_DIR));
+        }
+    }
+
+    /// Returns a stable hash of the page template, which covers the input templates, inlined styles,
+    /// preloaded fonts, added links, KaTeX stylesheet loading strategy, and base URL. Fragment pages built
+    /// with the same title, URL, and body by builders with the same fingerprint are identical.
+    #[must_use]
+    pub fn fingerprint(&self) -> String {
+        stable_hash(&[
+            tree_to_html(self.html.clone()).as_bytes(),
+            &[self
+                .katex_css_loading
+                .map_or(u8::MAX, |loading| loading as u8)],
+            self.katex_css_href.as_bytes(),
+            self.base_url.as_deref().unwrap_or_default().as_bytes(),
+        ])
+    }
+
+    /// Outputs a string containing a complete HTML document based on the provided document title, root-relative URL,
+    /// and body (and article metadata if the page is an article).
     ///
     /// # Errors
     /// This function returns an error if the input body cannot be successfully parsed as no-quirks HTML.
-    pub fn build_page(&self, title: &str, body: &str, kind: PageKind) -> Result<String> {
+    pub fn build_page(
+        &self,
+        title: &str,
+        url: &str,
+        body: &str,
+        kind: PageKind<'_>,
+    ) -> Result<String> {
+        self.build_page_with_slots(title, url, body, kind, &[])
+    }
+
+    /// Like [`Self::build_page()`], but also fills slots of the body template with named content pieces,
+    /// given as pairs of slot names and HTML. The content of a slot replaces the element's content in the template,
+    /// so slots without content keep the template's content as a default.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the input body, the article's head HTML, or a content piece cannot be successfully parsed as no-quirks HTML
+    /// - the body template has no slot with the name of a content piece
+    pub fn build_page_with_slots(
+        &self,
+        title: &str,
+        url: &str,
+        body: &str,
+        kind: PageKind<'_>,
+        slots: &[(&str, &str)],
+    ) -> Result<String> {
         let body = parse_html(body)?;
-        Ok(self.build_page_inner(title, body, kind))
+        let slots = slots
+            .iter()
+            .map(|&(name, content)| {
+                let id = *self.named_slot_ids.get(name).with_context(|| {
+                    format!("body template does not have an element for the slot \"{name}\"")
+                })?;
+                let content = parse_html(content)
+                    .with_context(|| format!("failed to parse content of the slot \"{name}\""))?;
+                Ok((id, content))
+            })
+            .collect::<Result<_>>()?;
+        let head = match kind {
+            PageKind::Article {
+                head: Some(head), ..
+            } => Some(parse_html(head).context("failed to parse head HTML of article")?),
+            _ => None,
+        };
+        Ok(self.build_page_inner(title, url, body, kind, head, slots))
     }
 
-    fn build_page_inner(&self, title: &str, body: Tree<Node>, kind: PageKind) -> String {
+    // `head` is parsed HTML appended within `<head>` of this page only,
+    // and `slots` are pairs of named slot elements and the content replacing their children
+    fn build_page_inner(
+        &self,
+        title: &str,
+        url: &str,
+        body: Tree<Node>,
+        kind: PageKind<'_>,
+        head: Option<Tree<Node>>,
+        slots: Vec<(NodeId, Tree<Node>)>,
+    ) -> String {
         let mut html = self.html.clone();
 
+        // Replace the content of named slots
+        for (id, content) in slots {
+            // SAFETY: The ID is valid because it was found in the constructor `PageBuilder::new()`.
+            let mut slot_node = unsafe { html.get_unchecked_mut(id) };
+            while let Some(mut child) = slot_node.first_child() {
+                child.detach();
+            }
+            append_fragment(&mut slot_node, content);
+        }
+
+        // The article's author replaces the site's author from the head template
+        let author = match kind {
+            PageKind::Article { author, .. } => author,
+            PageKind::Fragment => None,
+        };
+        if author.is_some() {
+            // SAFETY: The ID is valid because it was generated in the constructor `PageBuilder::new()`.
+            let author_meta_ids: Vec<_> = unsafe { html.get_unchecked(self.head_id) }
+                .children()
+                .filter(|node| {
+                    node.value().as_element().is_some_and(|el| {
+                        el.name() == "meta" && el.attr("name").is_some_and(|name| name == "author")
+                    })
+                })
+                .map(|node| node.id())
+                .collect();
+            for id in author_meta_ids {
+                // SAFETY: The ID is valid because it was just obtained from the tree.
+                unsafe { html.get_unchecked_mut(id) }.detach();
+            }
+        }
+
         // Add page content within `<head>`
         // SAFETY: The ID is valid because it was generated in the constructor `PageBuilder::new()`.
         let mut head_node = unsafe { html.get_unchecked_mut(self.head_id) };
 
-        if contains_math(&body, kind) {
+        if let Some(author) = author {
+            head_node.append(create_el_with_attrs(
+                "meta",
+                &[("name", "author"), ("content", author)],
+            ));
+        }
+
+        if let PageKind::Article { noindex: true, .. } = kind {
+            head_node.append(create_el_with_attrs(
+                "meta",
+                &[("name", "robots"), ("content", "noindex")],
+            ));
+        }
+
+        // Module scripts run after the document is parsed, so the script finds every code block
+        if contains_copyable_code(&body) {
+            head_node.append_subtree(tree! {
+                create_el_with_attrs("script", &[("type", "module")]) => { create_text(COPY_CODE_SCRIPT) }
+            });
+        }
+
+        if let Some(loading) = self.katex_css_loading
+            && contains_math(&body, kind)
+        {
+            match loading {
+                KatexCssLoading::Blocking => {
+                    head_node.append(create_el_with_attrs(
+                        "link",
+                        &[("rel", "stylesheet"), ("href", &self.katex_css_href)],
+                    ));
+                }
+                KatexCssLoading::Deferred => {
+                    // The stylesheet initially applies to no screen media, so it does not block rendering.
+                    // Once it loads, it is switched to apply to all media.
+                    // https://www.filamentgroup.com/lab/load-css-simpler/
+                    head_node.append(create_el_with_attrs(
+                        "link",
+                        &[
+                            ("rel", "preload"),
+                            ("href", &self.katex_css_href),
+                            ("as", "style"),
+                        ],
+                    ));
+                    head_node.append(create_el_with_attrs(
+                        "link",
+                        &[
+                            ("rel", "stylesheet"),
+                            ("href", &self.katex_css_href),
+                            ("media", "print"),
+                            ("onload", "this.media='all'"),
+                        ],
+                    ));
+                    head_node.append_subtree(tree! {
+                        create_el("noscript") => {
+                            create_el_with_attrs("link", &[("rel", "stylesheet"), ("href", &self.katex_css_href)])
+                        }
+                    });
+                }
+            }
+        }
+
+        if let PageKind::Article {
+            stylesheet_url: Some(stylesheet_url),
+            ..
+        } = kind
+        {
             head_node.append(create_el_with_attrs(
                 "link",
-                &[("rel", "stylesheet"), ("href", OUTPUT_KATEX_CSS_FILE)],
+                &[("rel", "stylesheet"), ("href", stylesheet_url)],
             ));
         }
 
         head_node.append_subtree(tree! {
             create_el("title") => { create_text(title) }
         });
-        head_node.append(create_el_with_attrs(
-            "meta",
-            &[("property", "og:title"), ("content", title)],
-        ));
+        let absolute_url = self
+            .base_url
+            .as_ref()
+            .map(|base_url| format!("{base_url}{url}"));
+        if let Some(absolute_url) = &absolute_url {
+            head_node.append(create_el_with_attrs(
+                "link",
+                &[("rel", "canonical"), ("href", absolute_url)],
+            ));
+        }
+        append_social_meta(&mut head_node, title, absolute_url.as_deref(), kind);
+
+        let breadcrumbs = match kind {
+            PageKind::Article { .. } if self.breadcrumbs => {
+                vec![
+                    ("Home", page_url("")),
+                    ("Writing", page_url(OUTPUT_CONTENT_DIR)),
+                    (title, url.to_owned()),
+                ]
+            }
+            _ => Vec::new(),
+        };
+        if let Some(base_url) = &self.base_url
+            && !breadcrumbs.is_empty()
+        {
+            head_node.append_subtree(tree! {
+                create_el_with_attrs("script", &[("type", "application/ld+json")]) => {
+                    create_text(&breadcrumb_json_ld(base_url, &breadcrumbs))
+                }
+            });
+        }
+        if let PageKind::Article {
+            created,
+            updated,
+            license: Some(license),
+            ..
+        } = kind
+        {
+            head_node.append_subtree(tree! {
+                create_el_with_attrs("script", &[("type", "application/ld+json")]) => {
+                    create_text(&article_json_ld(title, absolute_url.as_deref(), created, updated, license))
+                }
+            });
+        }
+
+        // Page-specific head content comes last, after everything the builder adds
+        if let Some(head) = head {
+            append_fragment(&mut head_node, head);
+        }
 
         // Add page content within body template slot
         // SAFETY: The ID is valid because it was generated in the constructor `PageBuilder::new()`.
         let mut slot_node = unsafe { html.get_unchecked_mut(self.slot_id) };
+        if let Some(((page_name, _), links)) = breadcrumbs.split_last() {
+            let mut nav_node = slot_node.append_subtree(tree! {
+                create_el_with_attrs("nav", &[("class", "__breadcrumbs"), ("aria-label", "Breadcrumb")]) => {
+                    create_el("ol")
+                }
+            });
+            let mut list_node = nav_node
+                .first_child()
+                .expect("breadcrumb trail should have a list");
+            for (name, href) in links {
+                list_node.append_subtree(tree! {
+                    create_el("li") => {
+                        create_el_with_attrs("a", &[("href", href)]) => { create_text(name) }
+                    }
+                });
+            }
+            list_node.append_subtree(tree! {
+                create_el_with_attrs("li", &[("aria-current", "page")]) => { create_text(page_name) }
+            });
+        }
         let mut slot_node = match kind {
             PageKind::Fragment => slot_node,
             PageKind::Article { .. } => slot_node.append(create_el("article")),
@@ -163,7 +752,10 @@ impl PageBuilder {
 
         // Add heading section with title and created/last-updated dates for article pages
         if let PageKind::Article {
-            created, updated, ..
+            created,
+            updated,
+            reading_minutes,
+            ..
         } = kind
         {
             let created_date_string = created.to_string();
@@ -208,29 +800,119 @@ impl PageBuilder {
                 });
             }
 
+            if let Some(author) = author {
+                article_heading_root.append_subtree(tree! {
+                    create_el_with_attrs("p", &[("class", "__article-author")]) => {
+                        create_text(&format!("By {author}"))
+                    }
+                });
+            }
+
+            if let Some(minutes) = reading_minutes {
+                article_heading_root.append_subtree(tree! {
+                    create_el_with_attrs("p", &[("class", "__reading-time")]) => {
+                        create_text(&reading_time_text(minutes))
+                    }
+                });
+            }
+
+            // Add age warning if the article is old enough
+            if let Some(AgeWarning {
+                threshold_years,
+                today,
+            }) = self.age_warning
+            {
+                let age_years = years_between(updated.unwrap_or(created), today);
+
+                if age_years >= threshold_years {
+                    let noun = if age_years == 1 { "year" } else { "years" };
+                    next.append_subtree(tree! {
+                        create_el_with_attrs("p", &[("class", "__article-age-warning"), ("role", "note")]) => {
+                            create_text(&format!("This article is over {age_years} {noun} old."))
+                        }
+                    });
+                }
+            }
+
             append_fragment(&mut slot_node, article_heading);
         }
 
         append_fragment(&mut slot_node, body);
 
+        // Add license information for article pages
+        if let PageKind::Article {
+            license: Some(license),
+            ..
+        } = kind
+        {
+            let mut footer_node = slot_node.append_subtree(tree! {
+                create_el_with_attrs("footer", &[("class", "__article-license")]) => {
+                    create_el("p") => { create_text("This article is licensed under ") }
+                }
+            });
+            let mut paragraph_node = footer_node
+                .first_child()
+                .expect("license footer should have a paragraph");
+
+            match license.url() {
+                Some(url) => {
+                    paragraph_node.append_subtree(tree! {
+                        create_el_with_attrs("a", &[("rel", "license"), ("href", &url)]) => {
+                            create_text(license.name())
+                        }
+                    });
+                }
+                None => {
+                    paragraph_node.append(create_text(license.name()));
+                }
+            }
+
+            paragraph_node.append(create_text("."));
+        }
+
         // Serialize document tree
         tree_to_html(html)
     }
 }
 
 #[derive(Clone, Copy)]
-pub enum PageKind {
+pub enum PageKind<'a> {
     Fragment,
     Article {
         contains_math: bool,
         created: Date,
         updated: Option<Date>,
+        license: Option<&'a License>,
+        // Summary of the article for link previews on social platforms
+        description: Option<&'a str>,
+        // Absolute URL of the image for link previews on social platforms
+        image_url: Option<&'a str>,
+        // Estimated time to read the article in minutes; absent if reading times are disabled
+        reading_minutes: Option<usize>,
+        // Author of the article, if not the site's author (e.g. for guest posts)
+        author: Option<&'a str>,
+        // Whether search engines are asked not to index the article
+        noindex: bool,
+        // HTML appended within `<head>` of the article's page only (see `validate_head_html()`)
+        head: Option<&'a str>,
+        // URL of a stylesheet linked from the article's page only, which applies after the site CSS
+        stylesheet_url: Option<&'a str>,
     },
 }
 
-/// Returns an `<img>` element with the provided attributes as a string of HTML.
-pub(crate) fn create_img_html(attrs: &[(&str, &str)]) -> String {
-    tree_to_html(Tree::new(create_el_with_attrs("img", attrs)))
+/// Returns a `<picture>` element containing `<source>` elements with the provided attributes in order,
+/// followed by an `<img>` element with the provided attributes.
+pub(crate) fn create_picture(
+    sources: &[Vec<(&str, &str)>],
+    img_attrs: &[(&str, &str)],
+) -> Tree<Node> {
+    let mut tree = Tree::new(create_el("picture"));
+    let mut picture_node = tree.root_mut();
+    for source_attrs in sources {
+        picture_node.append(create_el_with_attrs("source", source_attrs));
+    }
+    picture_node.append(create_el_with_attrs("img", img_attrs));
+    tree
 }
 
 pub struct ArchiveBuilder(Vec<ArticlePreview>);
@@ -239,6 +921,23 @@ struct ArticlePreview {
     title: Box<str>,
     slug: String,
     created: Date,
+    tags: Vec<Box<str>>,
+    thumbnail: Option<Thumbnail>,
+    excerpt: Option<Box<str>>,
+    reading_minutes: Option<usize>,
+}
+
+/// A small version of an article's cover image, displayed next to the article in article lists.
+#[derive(Clone)]
+pub struct Thumbnail {
+    // Absolute URL path of the 1x variant
+    pub src: String,
+    // Value for the `srcset` attribute listing all variants with absolute URL paths
+    pub srcset: String,
+    // Display dimensions of the thumbnail in CSS pixels
+    pub dimensions: Dimensions,
+    // Format of the fallback images saved next to the variants, if any
+    pub fallback: Option<FallbackFormat>,
 }
 
 impl ArchiveBuilder {
@@ -249,17 +948,103 @@ impl ArchiveBuilder {
         Self(Vec::new())
     }
 
-    /// Adds an article's metadata (title, slug, and creation date) to the builder.
-    pub fn add_article(&mut self, title: Box<str>, slug: String, created: Date) {
+    /// Adds an article's metadata (title, slug, creation date, and tags) to the builder,
+    /// along with an optional thumbnail, an optional excerpt displayed when the article's entry is hovered or focused,
+    /// and an optional reading time in minutes.
+    pub fn add_article(
+        &mut self,
+        frontmatter: &Frontmatter,
+        thumbnail: Option<Thumbnail>,
+        excerpt: Option<Box<str>>,
+        reading_minutes: Option<usize>,
+    ) {
         self.0.push(ArticlePreview {
-            title,
-            slug,
-            created,
+            title: frontmatter.title.clone(),
+            slug: frontmatter.slug.clone(),
+            created: frontmatter.created,
+            tags: frontmatter.tags.clone(),
+            thumbnail,
+            excerpt,
+            reading_minutes,
         });
     }
 
+    /// Outputs a complete HTML document for every tag used by articles, listing the articles with that tag.
+    /// Each document is paired with its tag, which is also the name of its output directory.
+    /// If tag feeds are enabled in the feed config, each document links to the feeds of its tag.
+    #[must_use]
+    pub fn tag_pages_html(
+        &self,
+        builder: &PageBuilder,
+        feed_config: Option<&FeedConfig>,
+    ) -> Vec<(Box<str>, String)> {
+        self.tag_counts()
+            .into_keys()
+            .map(|tag| {
+                let title = format!("Tagged \u{201C}{tag}\u{201D}");
+
+                let mut html = Tree::new(Node::Fragment);
+                let mut root_node = html.root_mut();
+                let root_node = root_node.append_subtree(tree! {
+                    Node::Fragment => {
+                        create_el("h1") => { create_text(&title) },
+                        create_el("p") => {
+                            create_text("Posts are in reverse chronological order. "),
+                            create_el_with_attrs("a", &[("href", &page_url(OUTPUT_CONTENT_DIR))]) => {
+                                create_text("See all posts.")
+                            }
+                        },
+                    }
+                });
+
+                append_article_list(
+                    root_node,
+                    self.0
+                        .iter()
+                        .filter(|article| article.tags.iter().any(|t| **t == *tag)),
+                );
+
+                let feed_links = feed_config.filter(|config| config.tags).map(|config| {
+                    let scope = FeedScope::Tag(tag);
+                    let feed_title = scope.title(config);
+
+                    // Create the feed links with the following structure:
+                    // Node::Fragment -> { Node::Fragment -> { <contents> }}
+                    let mut feed_links = Tree::new(Node::Fragment);
+                    let mut root_node = feed_links.root_mut();
+                    let mut links_node = root_node.append(Node::Fragment);
+                    links_node.append(create_feed_link(
+                        &feed_title,
+                        &page_url(&scope.atom_path()),
+                        "application/atom+xml",
+                    ));
+                    if config.rss {
+                        links_node.append(create_feed_link(
+                            &feed_title,
+                            &page_url(&scope.rss_path()),
+                            "application/rss+xml",
+                        ));
+                    }
+                    feed_links
+                });
+
+                (
+                    tag.into(),
+                    builder.build_page_inner(
+                        &title,
+                        &tag_url(tag),
+                        html,
+                        PageKind::Fragment,
+                        feed_links,
+                        Vec::new(),
+                    ),
+                )
+            })
+            .collect()
+    }
+
     /// Consumes the builder, outputting a string containing a complete HTML document for the archive page.
-    pub fn into_html(mut self, builder: &PageBuilder) -> String {
+    pub fn into_html(self, builder: &PageBuilder) -> String {
         const TITLE: &str = "Writing";
 
         // Add heading section with title and page description
@@ -273,80 +1058,774 @@ impl ArchiveBuilder {
             }
         });
 
-        // Sort articles by creation date in reverse chronological order,
-        // then by title in reverse lexicographical order
-        self.0
-            .sort_unstable_by(|a, b| b.created.cmp(&a.created).then(b.title.cmp(&a.title)));
+        // Add list of tags with the number of articles for each
+        let tag_counts = self.tag_counts();
+        if !tag_counts.is_empty() {
+            // We add `role="list"` to `<ul>` because of https://bugs.webkit.org/show_bug.cgi?id=170179
+            let mut tag_list_node = root_node.append(create_el_with_attrs(
+                "ul",
+                &[("class", "__tag-list"), ("role", "list")],
+            ));
 
-        // Add list of articles
-        // We add `role="list"` to `<ol>` because of https://bugs.webkit.org/show_bug.cgi?id=170179
-        let mut list_node = root_node.append(create_el_with_attrs(
-            "ol",
-            &[
-                ("reversed", ""),
-                ("class", "__article-list"),
-                ("role", "list"),
-            ],
-        ));
+            for (tag, count) in tag_counts {
+                let href = tag_url(tag);
+                let count = format!(" ({count})");
 
-        for mut article in self.0 {
-            article.slug.reserve_exact(1);
-            article.slug.push('/');
+                tag_list_node.append_subtree(tree! {
+                    create_el("li") => {
+                        create_el_with_attrs("a", &[("href", &href)]) => { create_text(tag) },
+                        create_text(&count),
+                    }
+                });
+            }
+        }
 
-            let date_string = article.created.to_string();
+        append_article_list(root_node, self.0.iter());
 
-            list_node.append_subtree(tree! {
-                create_el("li") => {
-                    create_el_with_attrs("p", &[("class", "__article-date")]) => {
-                        create_el_with_attrs("time", &[("datetime", &date_string)]) => { create_text(&date_string) }
-                    },
-                    create_el_with_attrs("div", &[("class", "__article-link")]) => {
-                        create_el_with_attrs("a", &[("href", &article.slug)]) => {
-                            create_text(&article.title)
+        builder.build_page_inner(
+            TITLE,
+            &page_url(OUTPUT_CONTENT_DIR),
+            html,
+            PageKind::Fragment,
+            None,
+            Vec::new(),
+        )
+    }
+
+    /// Returns the number of articles with each tag, ordered by tag.
+    fn tag_counts(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for tag in self.0.iter().flat_map(|article| &article.tags) {
+            *counts.entry(&**tag).or_default() += 1;
+        }
+        counts
+    }
+}
+
+/// Appends a list of articles, sorted in reverse chronological order, to a node.
+fn append_article_list<'a>(
+    mut node: NodeMut<'_, Node>,
+    articles: impl Iterator<Item = &'a ArticlePreview>,
+) {
+    // Sort articles by creation date in reverse chronological order,
+    // then by title in reverse lexicographical order
+    let mut articles: Vec<_> = articles.collect();
+    articles.sort_unstable_by(|a, b| b.created.cmp(&a.created).then(b.title.cmp(&a.title)));
+
+    // Add list of articles
+    // We add `role="list"` to `<ol>` because of https://bugs.webkit.org/show_bug.cgi?id=170179
+    let mut list_node = node.append(create_el_with_attrs(
+        "ol",
+        &[
+            ("reversed", ""),
+            ("class", "__article-list"),
+            ("role", "list"),
+        ],
+    ));
+
+    for article in articles {
+        let href = article_url(&article.slug);
+        let date_string = article.created.to_string();
+
+        let mut item_node = list_node.append_subtree(tree! {
+            create_el("li") => {
+                create_el_with_attrs("p", &[("class", "__article-date")]) => {
+                    create_el_with_attrs("time", &[("datetime", &date_string)]) => { create_text(&date_string) }
+                }
+            }
+        });
+        if let Some(minutes) = article.reading_minutes {
+            item_node
+                .first_child()
+                .expect("article list entry should have a date paragraph")
+                .append_subtree(tree! {
+                    Node::Fragment => {
+                        create_text(" \u{b7} "),
+                        create_el_with_attrs("span", &[("class", "__reading-time")]) => {
+                            create_text(&reading_time_text(minutes))
                         }
                     }
+                });
+        }
+        let mut link_node =
+            item_node.append(create_el_with_attrs("div", &[("class", "__article-link")]));
+
+        // The thumbnail is decorative, since the article title is right next to it
+        if let Some(Thumbnail {
+            src,
+            srcset,
+            dimensions: Dimensions { width, height },
+            fallback,
+        }) = &article.thumbnail
+        {
+            let (width, height) = (width.to_string(), height.to_string());
+            let attrs = [
+                ("class", "__article-thumbnail"),
+                ("alt", ""),
+                ("decoding", "async"),
+                ("loading", "lazy"),
+                ("width", &width),
+                ("height", &height),
+            ];
+
+            if let Some(fallback) = fallback {
+                link_node.append_subtree(fallback.picture(src, Some(srcset), None, &attrs));
+            } else {
+                let mut attrs = attrs.to_vec();
+                attrs.splice(1..1, [("src", src.as_str()), ("srcset", srcset.as_str())]);
+                link_node.append(create_el_with_attrs("img", &attrs));
+            }
+        }
+
+        link_node.append_subtree(tree! {
+            create_el_with_attrs("a", &[("href", &href)]) => {
+                create_text(&article.title)
+            }
+        });
+
+        if let Some(excerpt) = &article.excerpt {
+            link_node.append_subtree(tree! {
+                create_el_with_attrs("p", &[("class", "__article-excerpt")]) => {
+                    create_text(excerpt)
                 }
             });
         }
-
-        builder.build_page_inner(TITLE, html, PageKind::Fragment)
     }
 }
 
-fn parse_html(input: &str) -> Result<Tree<Node>> {
-    let html = Html::parse_fragment(input);
-
-    // `Html::parse_fragment()` does not return a `Result` because
-    // the parser is supposed to be resilient and fall back to HTML quirks mode upon encountering errors.
-    // So, after parsing, we have to check for any errors encountered ourselves.
-    if html.errors.is_empty() {
-        Ok(html.tree)
-    } else {
-        Err(Error::msg(html.errors.join("\n")).context("failed to parse input as valid HTML"))
-    }
+/// Returns the text describing an estimated reading time (e.g. "5 min read").
+fn reading_time_text(minutes: usize) -> String {
+    format!("{minutes} min read")
 }
 
-fn contains_math(html: &Tree<Node>, kind: PageKind) -> bool {
-    match kind {
-        PageKind::Fragment => {
-            html.values().any(|node| {
-                node.as_element().is_some_and(|el| {
-                    (el.name() == "span" && el.classes().any(|c| c == "katex")) // element is `<span class="katex">`
-                        || el.name.ns == ns!(mathml) // element is MathML
-                })
-            })
-        }
-        PageKind::Article { contains_math, .. } => contains_math,
-    }
+pub struct ChangelogBuilder(Vec<ChangelogEntry>);
+
+struct ChangelogEntry {
+    title: Box<str>,
+    slug: Box<str>,
+    date: Date,
+    kind: ChangeKind,
 }
 
-fn create_el(name: &str) -> Node {
-    Node::Element(Element::new(create_name(name, NameKind::Element), vec![]))
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ChangeKind {
+    Created,
+    Updated,
 }
 
-fn create_el_with_attrs(name: &str, attrs: &[(&str, &str)]) -> Node {
-    let attrs = attrs
-        .iter()
+impl ChangelogBuilder {
+    /// Initializes a changelog page builder.
+    /// The page includes a list of recent article creations and updates, grouped by month.
+    #[must_use]
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Adds an article's metadata (title, slug, and creation and last-updated dates) to the builder.
+    /// Every article contributes a creation entry and, if it was updated, an update entry.
+    pub fn add_article(&mut self, title: &str, slug: &str, created: Date, updated: Option<Date>) {
+        self.0.push(ChangelogEntry {
+            title: title.into(),
+            slug: slug.into(),
+            date: created,
+            kind: ChangeKind::Created,
+        });
+
+        if let Some(updated) = updated {
+            self.0.push(ChangelogEntry {
+                title: title.into(),
+                slug: slug.into(),
+                date: updated,
+                kind: ChangeKind::Updated,
+            });
+        }
+    }
+
+    /// Consumes the builder, outputting a string containing a complete HTML document for the changelog page.
+    /// Only the `limit` most recent entries are included.
+    pub fn into_html(mut self, limit: usize, builder: &PageBuilder) -> String {
+        const TITLE: &str = "Changelog";
+
+        let mut html = Tree::new(Node::Fragment);
+
+        let mut root_node = html.root_mut();
+        let mut root_node = root_node.append_subtree(tree! {
+            Node::Fragment => {
+                create_el("h1") => { create_text(TITLE) },
+                create_el("p") => { create_text("Recently created and updated articles.") },
+            }
+        });
+
+        // Sort entries by date in reverse chronological order,
+        // then by kind (updates first), then by title in reverse lexicographical order
+        self.0.sort_unstable_by(|a, b| {
+            b.date
+                .cmp(&a.date)
+                .then(b.kind.cmp(&a.kind))
+                .then(b.title.cmp(&a.title))
+        });
+        self.0.truncate(limit);
+
+        // Group entries by month; entries are sorted, so each group is contiguous
+        for month_entries in self
+            .0
+            .chunk_by(|a, b| (a.date.year(), a.date.month()) == (b.date.year(), b.date.month()))
+        {
+            let month = month_entries[0].date;
+            let month_datetime = month.strftime("%Y-%m").to_string();
+            let month_name = month.strftime("%B %Y").to_string();
+
+            let mut month_node = root_node.append_subtree(tree! {
+                create_el_with_attrs("section", &[("class", "__changelog-month")]) => {
+                    create_el("h2") => {
+                        create_el_with_attrs("time", &[("datetime", &month_datetime)]) => { create_text(&month_name) }
+                    }
+                }
+            });
+
+            // We add `role="list"` to `<ul>` because of https://bugs.webkit.org/show_bug.cgi?id=170179
+            let mut list_node = month_node.append(create_el_with_attrs(
+                "ul",
+                &[("class", "__changelog-list"), ("role", "list")],
+            ));
+
+            for entry in month_entries {
+                let date_string = entry.date.to_string();
+                let href = article_url(&entry.slug);
+                let kind = match entry.kind {
+                    ChangeKind::Created => "created",
+                    ChangeKind::Updated => "updated",
+                };
+
+                list_node.append_subtree(tree! {
+                    create_el("li") => {
+                        create_el_with_attrs("time", &[("datetime", &date_string)]) => { create_text(&date_string) },
+                        create_text(" "),
+                        create_el_with_attrs("a", &[("href", &href)]) => { create_text(&entry.title) },
+                        create_text(" "),
+                        create_el_with_attrs("span", &[("class", "__changelog-kind")]) => { create_text(kind) },
+                    }
+                });
+            }
+        }
+
+        builder.build_page_inner(
+            TITLE,
+            &page_url(OUTPUT_CHANGELOG_DIR),
+            html,
+            PageKind::Fragment,
+            None,
+            Vec::new(),
+        )
+    }
+}
+
+pub struct FeedBuilder(Vec<FeedEntry>);
+
+struct FeedEntry {
+    title: Box<str>,
+    slug: Box<str>,
+    tags: Vec<Box<str>>,
+    created: Date,
+    updated: Option<Date>,
+    rights: Option<Box<str>>,
+    // Author of the article, if not the feed's author
+    author: Option<Box<str>>,
+    body: String,
+}
+
+impl FeedBuilder {
+    /// Initializes a feed builder.
+    /// The feed includes the full content of every article.
+    #[must_use]
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Adds an article's metadata and rendered HTML body to the builder.
+    /// Relative URLs in the body are resolved against the article's absolute URL,
+    /// since feed readers display content outside of the site.
+    ///
+    /// # Errors
+    /// This function returns an error if the body cannot be successfully parsed as no-quirks HTML.
+    pub fn add_article(
+        &mut self,
+        config: &FeedConfig,
+        frontmatter: &Frontmatter,
+        license: Option<&License>,
+        body: &str,
+    ) -> Result<()> {
+        let site_url = config.site_url.trim_end_matches('/');
+        let article_url = format!("{site_url}/{OUTPUT_CONTENT_DIR}{}/", frontmatter.slug);
+
+        let mut body = parse_html(body)?;
+        let node_ids: Vec<_> = body.nodes().map(|node| node.id()).collect();
+        for id in node_ids {
+            // SAFETY: The ID is valid because it was obtained from iterating over the nodes of the tree.
+            if let Node::Element(el) = unsafe { body.get_unchecked_mut(id) }.value() {
+                for (name, value) in &mut el.attrs {
+                    let resolved = match &*name.local {
+                        "href" | "src" => resolve_url(value, site_url, &article_url),
+                        "srcset" => value
+                            .split(',')
+                            .map(|candidate| {
+                                let candidate = candidate.trim();
+                                let (url, descriptor) =
+                                    candidate.split_once(' ').unwrap_or((candidate, ""));
+                                let url = resolve_url(url, site_url, &article_url);
+                                if descriptor.is_empty() {
+                                    url
+                                } else {
+                                    format!("{url} {descriptor}")
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        _ => continue,
+                    };
+                    *value = resolved.into();
+                }
+            }
+        }
+
+        // Serialize the contents of the fragment's `<html>` element, without the element itself
+        let body = body
+            .root()
+            .first_child()
+            .and_then(ElementRef::wrap)
+            .map(|el| el.inner_html())
+            .unwrap_or_default();
+
+        self.0.push(FeedEntry {
+            title: frontmatter.title.clone(),
+            slug: frontmatter.slug.as_str().into(),
+            tags: frontmatter.tags.clone(),
+            created: frontmatter.created,
+            updated: frontmatter.updated,
+            rights: license.map(|license| license.name().into()),
+            author: frontmatter.author.clone(),
+            body,
+        });
+
+        Ok(())
+    }
+
+    /// Consumes the builder, outputting a string containing an Atom feed document and,
+    /// if enabled in the config, a string containing an RSS 2.0 feed document.
+    ///
+    /// # Panics
+    /// This function panics if writing to a string fails, which is not expected to happen.
+    #[must_use]
+    pub fn into_xml(mut self, config: &FeedConfig) -> (String, Option<String>) {
+        self.sort();
+        self.scope_xml(config, FeedScope::All)
+    }
+
+    /// Outputs feed documents for every tag used by articles, containing only the articles with that tag.
+    /// Each Atom feed document and, if enabled in the config, RSS 2.0 feed document is paired with its tag.
+    ///
+    /// # Panics
+    /// This function panics if writing to a string fails, which is not expected to happen.
+    #[must_use]
+    pub fn tag_xml(&mut self, config: &FeedConfig) -> Vec<(Box<str>, String, Option<String>)> {
+        self.sort();
+        let tags: BTreeSet<_> = self.0.iter().flat_map(|entry| &entry.tags).collect();
+        tags.into_iter()
+            .map(|tag| {
+                let (atom, rss) = self.scope_xml(config, FeedScope::Tag(tag));
+                (tag.clone(), atom, rss)
+            })
+            .collect()
+    }
+
+    // Sorts entries by creation date in reverse chronological order,
+    // then by title in reverse lexicographical order
+    fn sort(&mut self) {
+        self.0
+            .sort_unstable_by(|a, b| b.created.cmp(&a.created).then(b.title.cmp(&a.title)));
+    }
+
+    fn scope_xml(&self, config: &FeedConfig, scope: FeedScope<'_>) -> (String, Option<String>) {
+        let entries: Vec<_> = self
+            .0
+            .iter()
+            .filter(|entry| scope.includes(entry))
+            .collect();
+
+        let mut atom = String::new();
+        write_atom(&mut atom, config, scope, &entries).expect("writing to a string should succeed");
+
+        let rss = config.rss.then(|| {
+            let mut rss = String::new();
+            write_rss(&mut rss, config, scope, &entries)
+                .expect("writing to a string should succeed");
+            rss
+        });
+
+        (atom, rss)
+    }
+}
+
+// Set of articles included in a feed
+#[derive(Clone, Copy)]
+enum FeedScope<'a> {
+    All,
+    Tag(&'a str),
+}
+
+impl FeedScope<'_> {
+    fn includes(self, entry: &FeedEntry) -> bool {
+        match self {
+            Self::All => true,
+            Self::Tag(tag) => entry.tags.iter().any(|t| **t == *tag),
+        }
+    }
+
+    fn title(self, config: &FeedConfig) -> String {
+        match self {
+            Self::All => config.title.to_string(),
+            Self::Tag(tag) => format!("{} (tagged \u{201C}{tag}\u{201D})", config.title),
+        }
+    }
+
+    // Path of the page listing the feed's articles, relative to the output directory
+    fn listing_path(self) -> String {
+        match self {
+            Self::All => OUTPUT_CONTENT_DIR.to_string(),
+            Self::Tag(tag) => format!("{OUTPUT_TAGS_DIR}{tag}/"),
+        }
+    }
+
+    fn atom_path(self) -> String {
+        match self {
+            Self::All => OUTPUT_ATOM_FEED_FILE.to_string(),
+            Self::Tag(_) => self.listing_path() + TAG_ATOM_FEED_FILE_NAME,
+        }
+    }
+
+    fn rss_path(self) -> String {
+        match self {
+            Self::All => OUTPUT_RSS_FEED_FILE.to_string(),
+            Self::Tag(_) => self.listing_path() + TAG_RSS_FEED_FILE_NAME,
+        }
+    }
+}
+
+fn last_updated(entries: &[&FeedEntry]) -> Option<Date> {
+    entries
+        .iter()
+        .map(|entry| entry.updated.unwrap_or(entry.created))
+        .max()
+}
+
+// https://www.rfc-editor.org/rfc/rfc4287
+fn write_atom(
+    out: &mut String,
+    config: &FeedConfig,
+    scope: FeedScope<'_>,
+    entries: &[&FeedEntry],
+) -> fmt::Result {
+    let site_url = escape_xml(config.site_url.trim_end_matches('/'));
+    let archive_url = format!("{site_url}/{OUTPUT_CONTENT_DIR}");
+    let listing_url = format!("{site_url}/{}", escape_xml(&scope.listing_path()));
+
+    write!(
+        out,
+        r#"<?xml version="1.0" encoding="utf-8"?><feed xmlns="http://www.w3.org/2005/Atom"><title>{}</title><link href="{listing_url}"/><link rel="self" href="{site_url}/{}"/><id>{listing_url}</id><updated>{}</updated><author><name>{}</name></author>"#,
+        escape_xml(&scope.title(config)),
+        escape_xml(&scope.atom_path()),
+        date_to_timestamp(last_updated(entries).unwrap_or(Date::ZERO)),
+        escape_xml(&config.author),
+    )?;
+
+    for entry in entries {
+        let url = format!("{archive_url}{}/", entry.slug);
+        write!(
+            out,
+            r#"<entry><title>{}</title><link href="{url}"/><id>{url}</id><published>{}</published><updated>{}</updated>"#,
+            escape_xml(&entry.title),
+            date_to_timestamp(entry.created),
+            date_to_timestamp(entry.updated.unwrap_or(entry.created)),
+        )?;
+        if let Some(author) = &entry.author {
+            write!(out, "<author><name>{}</name></author>", escape_xml(author))?;
+        }
+        if let Some(rights) = &entry.rights {
+            write!(out, "<rights>{}</rights>", escape_xml(rights))?;
+        }
+        write!(
+            out,
+            r#"<content type="html">{}</content></entry>"#,
+            escape_xml(&entry.body)
+        )?;
+    }
+
+    out.write_str("</feed>")
+}
+
+// https://www.rssboard.org/rss-specification
+fn write_rss(
+    out: &mut String,
+    config: &FeedConfig,
+    scope: FeedScope<'_>,
+    entries: &[&FeedEntry],
+) -> fmt::Result {
+    let site_url = escape_xml(config.site_url.trim_end_matches('/'));
+    let archive_url = format!("{site_url}/{OUTPUT_CONTENT_DIR}");
+    let title = escape_xml(&scope.title(config));
+
+    write!(
+        out,
+        r#"<?xml version="1.0" encoding="utf-8"?><rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom"><channel><title>{title}</title><link>{site_url}/{}</link><description>{title}</description><atom:link rel="self" type="application/rss+xml" href="{site_url}/{}"/>"#,
+        escape_xml(&scope.listing_path()),
+        escape_xml(&scope.rss_path()),
+    )?;
+
+    if let Some(last_updated) = last_updated(entries) {
+        write!(
+            out,
+            "<lastBuildDate>{}</lastBuildDate>",
+            date_to_rfc2822(last_updated)
+        )?;
+    }
+
+    for entry in entries {
+        let url = format!("{archive_url}{}/", entry.slug);
+        write!(
+            out,
+            r#"<item><title>{}</title><link>{url}</link><guid isPermaLink="true">{url}</guid><pubDate>{}</pubDate><description>{}</description></item>"#,
+            escape_xml(&entry.title),
+            date_to_rfc2822(entry.created),
+            escape_xml(&entry.body),
+        )?;
+    }
+
+    out.write_str("</channel></rss>")
+}
+
+/// Returns a `<link rel="alternate">` element to a feed, which lets feed readers discover the feed.
+fn create_feed_link(title: &str, href: &str, mime: &str) -> Node {
+    create_el_with_attrs(
+        "link",
+        &[
+            ("rel", "alternate"),
+            ("type", mime),
+            ("title", title),
+            ("href", href),
+        ],
+    )
+}
+
+/// Resolves a URL from an attribute in an article body to an absolute URL.
+fn resolve_url(url: &str, site_url: &str, article_url: &str) -> String {
+    let has_scheme = url.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    });
+
+    if has_scheme || url.starts_with("//") {
+        url.to_owned()
+    } else if url.starts_with('/') {
+        format!("{site_url}{url}")
+    } else {
+        format!("{article_url}{url}")
+    }
+}
+
+/// Escapes a string for use as XML text or within a double-quoted XML attribute value.
+fn escape_xml(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Formats a date as an RFC 3339 timestamp at midnight UTC (e.g. `2024-01-01T00:00:00Z`).
+fn date_to_timestamp(date: Date) -> Timestamp {
+    date.to_zoned(TimeZone::UTC)
+        .expect("date at midnight UTC should be representable")
+        .timestamp()
+}
+
+/// Formats a date as an RFC 2822 timestamp at midnight UTC (e.g. `Mon, 1 Jan 2024 00:00:00 -0000`).
+fn date_to_rfc2822(date: Date) -> String {
+    DateTimePrinter::new()
+        .timestamp_to_string(&date_to_timestamp(date))
+        .expect("timestamp should be representable in RFC 2822 format")
+}
+
+/// Checks that HTML to be appended within `<head>` of a page is valid and only contains
+/// `<link>`, `<meta>`, `<noscript>`, `<script>`, and `<style>` elements (along with whitespace and comments).
+///
+/// # Errors
+/// This function returns an error if the HTML cannot be successfully parsed as no-quirks HTML
+/// or contains other elements or text.
+pub(crate) fn validate_head_html(input: &str) -> Result<()> {
+    let tree = parse_html(input)?;
+    let html_node = tree
+        .root()
+        .first_child()
+        .expect("parsed fragment should have a root element");
+    for node in html_node.children() {
+        match node.value() {
+            Node::Element(el) if !PAGE_HEAD_ELEMENTS.contains(&el.name()) => {
+                bail!(
+                    "found `<{}>` element, which doesn't belong in `<head>`",
+                    el.name()
+                );
+            }
+            Node::Text(text) if !text.trim().is_empty() => {
+                bail!("found text \"{}\" outside of elements", text.trim());
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn parse_html(input: &str) -> Result<Tree<Node>> {
+    let html = Html::parse_fragment(input);
+
+    // `Html::parse_fragment()` does not return a `Result` because
+    // the parser is supposed to be resilient and fall back to HTML quirks mode upon encountering errors.
+    // So, after parsing, we have to check for any errors encountered ourselves.
+    if html.errors.is_empty() {
+        Ok(html.tree)
+    } else {
+        Err(Error::msg(html.errors.join("\n")).context("failed to parse input as valid HTML"))
+    }
+}
+
+fn contains_math(html: &Tree<Node>, kind: PageKind<'_>) -> bool {
+    match kind {
+        PageKind::Fragment => {
+            html.values().any(|node| {
+                node.as_element().is_some_and(|el| {
+                    (el.name() == "span" && el.classes().any(|c| c == "katex")) // element is `<span class="katex">`
+                        || el.name.ns == ns!(mathml) // element is MathML
+                })
+            })
+        }
+        PageKind::Article { contains_math, .. } => contains_math,
+    }
+}
+
+/// Appends OpenGraph and Twitter card `<meta>` elements, which social platforms use for link previews.
+fn append_social_meta(
+    head_node: &mut NodeMut<'_, Node>,
+    title: &str,
+    url: Option<&str>,
+    kind: PageKind<'_>,
+) {
+    let (page_type, description, image_url) = match kind {
+        PageKind::Fragment => ("website", None, None),
+        PageKind::Article {
+            description,
+            image_url,
+            ..
+        } => ("article", description, image_url),
+    };
+
+    let mut properties = vec![("og:title", title), ("og:type", page_type)];
+    properties.extend(url.map(|url| ("og:url", url)));
+    properties.extend(description.map(|description| ("og:description", description)));
+    properties.extend(image_url.map(|url| ("og:image", url)));
+    for (property, content) in properties {
+        head_node.append(create_el_with_attrs(
+            "meta",
+            &[("property", property), ("content", content)],
+        ));
+    }
+
+    // Platforms that support Twitter cards display a large preview only for pages with an image
+    let card = if image_url.is_some() {
+        "summary_large_image"
+    } else {
+        "summary"
+    };
+    head_node.append(create_el_with_attrs(
+        "meta",
+        &[("name", "twitter:card"), ("content", card)],
+    ));
+}
+
+/// Returns the contents of a JSON-LD `<script>` describing a breadcrumb trail, given as pairs of names and
+/// root-relative URLs, for search engines.
+fn breadcrumb_json_ld(base_url: &str, breadcrumbs: &[(&str, String)]) -> String {
+    let items: Vec<_> = breadcrumbs
+        .iter()
+        .enumerate()
+        .map(|(i, (name, url))| {
+            json!({
+                "@type": "ListItem",
+                "position": i + 1,
+                "name": name,
+                "item": format!("{base_url}{url}"),
+            })
+        })
+        .collect();
+    // Escape `</` so a name can't close the `<script>` element
+    json!({
+        "@context": "https://schema.org",
+        "@type": "BreadcrumbList",
+        "itemListElement": items,
+    })
+    .to_string()
+    .replace("</", r"<\/")
+}
+
+/// Returns the contents of a JSON-LD `<script>` describing a licensed article, for search engines.
+/// The license is given by its URL if one is known, and by its name otherwise.
+fn article_json_ld(
+    title: &str,
+    absolute_url: Option<&str>,
+    created: Date,
+    updated: Option<Date>,
+    license: &License,
+) -> String {
+    let mut article = json!({
+        "@context": "https://schema.org",
+        "@type": "Article",
+        "headline": title,
+        "datePublished": created.to_string(),
+        "license": license.url().as_deref().unwrap_or(license.name()),
+    });
+    if let Some(updated) = updated {
+        article["dateModified"] = updated.to_string().into();
+    }
+    if let Some(url) = absolute_url {
+        article["url"] = url.into();
+    }
+    // Escape `</` so the title can't close the `<script>` element
+    article.to_string().replace("</", r"<\/")
+}
+
+/// Returns a Boolean indicating if an HTML tree contains a code block that gets a copy button.
+fn contains_copyable_code(html: &Tree<Node>) -> bool {
+    html.values().any(|node| {
+        node.as_element()
+            .is_some_and(|el| el.attr(COPY_CODE_ATTRIBUTE).is_some())
+    })
+}
+
+/// Returns the number of whole years from `start` to `end`.
+fn years_between(start: Date, end: Date) -> i16 {
+    start
+        .until((Unit::Year, end))
+        .expect("date difference in years should be representable")
+        .get_years()
+}
+
+pub(crate) fn create_el(name: &str) -> Node {
+    Node::Element(Element::new(create_name(name, NameKind::Element), vec![]))
+}
+
+pub(crate) fn create_el_with_attrs(name: &str, attrs: &[(&str, &str)]) -> Node {
+    let attrs = attrs
+        .iter()
         .map(|(key, value)| Attribute {
             name: create_name(key, NameKind::Attr),
             value: (*value).into(),
@@ -373,7 +1852,7 @@ enum NameKind {
     Attr,
 }
 
-fn create_text(text: &str) -> Node {
+pub(crate) fn create_text(text: &str) -> Node {
     Node::Text(Text { text: text.into() })
 }
 
@@ -395,7 +1874,7 @@ fn append_fragment(node: &mut NodeMut<'_, Node>, fragment_tree: Tree<Node>) {
 }
 
 /// Serializes a tree of HTML nodes as a string of HTML.
-fn tree_to_html(tree: Tree<Node>) -> String {
+pub(crate) fn tree_to_html(tree: Tree<Node>) -> String {
     Html {
         errors: Vec::new(),
         quirks_mode: QuirksMode::NoQuirks,
@@ -406,14 +1885,31 @@ fn tree_to_html(tree: Tree<Node>) -> String {
 
 #[cfg(test)]
 mod test {
-    use super::{PageKind, contains_math, create_el, create_el_with_attrs, parse_html};
-    use jiff::civil::Date;
+    use super::{
+        ArchiveBuilder, ChangelogBuilder, FeedBuilder, KatexCssLoading, PageBuilder, PageKind,
+        Thumbnail, contains_math, create_el, create_el_with_attrs, parse_html, resolve_url,
+        validate_head_html, years_between,
+    };
+    use crate::{
+        config::FeedConfig,
+        frontmatter::{Frontmatter, License},
+        image::Dimensions,
+        latex::KatexOverrides,
+        partial::Template,
+        vfs::RealFs,
+    };
+    use camino::Utf8Path;
+    use jiff::{
+        Timestamp,
+        civil::{Date, date},
+    };
     use scraper::{Html, Node};
+    use std::collections::BTreeMap;
 
     #[test]
     fn contains_math_markup() {
         /// Utility function for converting a string of HTML to a tree of HTML nodes
-        fn html_contains_math(html: &str, kind: PageKind, expected: bool) {
+        fn html_contains_math(html: &str, kind: PageKind<'_>, expected: bool) {
             assert_eq!(contains_math(&parse_html(html).unwrap(), kind), expected);
         }
 
@@ -427,6 +1923,14 @@ mod test {
                 contains_math: false,
                 created: Date::default(),
                 updated: Option::default(),
+                license: None,
+                description: None,
+                image_url: None,
+                reading_minutes: None,
+                author: None,
+                noindex: false,
+                head: None,
+                stylesheet_url: None,
             },
             false,
         );
@@ -436,12 +1940,835 @@ mod test {
                 contains_math: true,
                 created: Date::default(),
                 updated: Option::default(),
+                license: None,
+                description: None,
+                image_url: None,
+                reading_minutes: None,
+                author: None,
+                noindex: false,
+                head: None,
+                stylesheet_url: None,
             },
             true,
         );
     }
 
+    #[test]
+    fn build_stamp() {
+        let footer = |time| {
+            let template = |text| Template {
+                text,
+                path: Utf8Path::new("template.html"),
+                vfs: &RealFs,
+            };
+            let mut builder = PageBuilder::new(
+                template(""),
+                template("<main></main><footer>Built <build-stamp></build-stamp></footer>"),
+                &[],
+                "",
+            )
+            .unwrap();
+            builder.set_build_stamp(time);
+            let html = builder.build_page("", "/", "", PageKind::Fragment).unwrap();
+            html[html.find("<footer>").unwrap()..html.find("</footer>").unwrap()].to_owned()
+        };
+
+        assert_eq!(
+            footer(Some(Timestamp::from_second(86_400).unwrap())),
+            "<footer>Built <time datetime=\"1970-01-02T00:00:00Z\">1970-01-02</time>"
+        );
+        assert_eq!(footer(None), "<footer>Built ");
+    }
+
+    #[test]
+    fn named_slots() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let builder = PageBuilder::new(
+            template(""),
+            template(r#"<aside data-slot="sidebar">Default</aside><main></main><nav data-slot="links"></nav>"#),
+            &[],
+            "",
+        )
+        .unwrap();
+        let build =
+            |slots| builder.build_page_with_slots("", "/", "Body", PageKind::Fragment, slots);
+
+        assert!(
+            build(&[("sidebar", "<p>Side</p>")])
+                .unwrap()
+                .contains(r#"<aside data-slot="sidebar"><p>Side</p></aside><main>Body</main><nav data-slot="links"></nav>"#)
+        );
+        assert!(
+            build(&[])
+                .unwrap()
+                .contains(r#"<aside data-slot="sidebar">Default</aside>"#)
+        );
+        assert!(build(&[("footer", "")]).is_err());
+        assert!(
+            PageBuilder::new(
+                template(""),
+                template(r#"<div data-slot="a"></div><div data-slot="a"></div><main></main>"#),
+                &[],
+                ""
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn copy_code_script() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let builder = PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let build = |body| {
+            builder
+                .build_page("", "/", body, PageKind::Fragment)
+                .unwrap()
+        };
+
+        assert!(
+            build(r#"<div class="__code-block" data-copy-code><pre>a</pre></div>"#)
+                .contains(r#"<script type="module">for (const block of"#)
+        );
+        assert!(!build("<pre>a</pre>").contains("<script"));
+    }
+
+    #[test]
+    fn katex_css_loading() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let mut builder =
+            PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let math = "<math><mi>x</mi></math>";
+
+        assert!(
+            builder
+                .build_page("", "/", math, PageKind::Fragment)
+                .unwrap()
+                .contains(r#"<link rel="stylesheet" href="/stylesheets/katex.css">"#)
+        );
+
+        // Math rendered as MathML only doesn't need the stylesheet
+        builder.set_katex_css_loading(None);
+        assert!(
+            !builder
+                .build_page("", "/", math, PageKind::Fragment)
+                .unwrap()
+                .contains("katex.css")
+        );
+    }
+
+    #[test]
+    fn deferred_katex_css() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let mut builder =
+            PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        builder.set_katex_css_loading(Some(KatexCssLoading::Deferred));
+
+        let html = builder
+            .build_page("", "/", "<math><mi>x</mi></math>", PageKind::Fragment)
+            .unwrap();
+        // The stylesheet is preloaded and only applied once loaded, unless scripts are disabled
+        assert!(html.contains(concat!(
+            r#"<link rel="preload" href="/stylesheets/katex.css" as="style">"#,
+            r#"<link rel="stylesheet" href="/stylesheets/katex.css" media="print" onload="this.media='all'">"#,
+            r#"<noscript><link rel="stylesheet" href="/stylesheets/katex.css"></noscript>"#
+        )));
+        assert!(
+            !builder
+                .build_page("", "/", "<p>x</p>", PageKind::Fragment)
+                .unwrap()
+                .contains("katex.css")
+        );
+    }
+
+    #[test]
+    fn social_meta() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let mut builder =
+            PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let article = |builder: &PageBuilder, description, image_url| {
+            builder
+                .build_page(
+                    "A & B",
+                    "/writing/a/",
+                    "",
+                    PageKind::Article {
+                        contains_math: false,
+                        created: date(2024, 1, 2),
+                        updated: None,
+                        license: None,
+                        description,
+                        image_url,
+                        reading_minutes: None,
+                        author: None,
+                        noindex: false,
+                        head: None,
+                        stylesheet_url: None,
+                    },
+                )
+                .unwrap()
+        };
+
+        let html = article(
+            &builder,
+            Some("About \"A\""),
+            Some("https://example.com/writing/a/__social.png"),
+        );
+        assert!(html.contains(r#"<meta property="og:title" content="A &amp; B">"#));
+        assert!(html.contains(r#"<meta property="og:type" content="article">"#));
+        assert!(html.contains(r#"<meta property="og:description" content="About &quot;A&quot;">"#));
+        assert!(html.contains(
+            r#"<meta property="og:image" content="https://example.com/writing/a/__social.png">"#
+        ));
+        assert!(html.contains(r#"<meta name="twitter:card" content="summary_large_image">"#));
+
+        let html = article(&builder, None, None);
+        assert!(!html.contains("og:description") && !html.contains("og:image"));
+        assert!(html.contains(r#"<meta name="twitter:card" content="summary">"#));
+        assert!(!html.contains("canonical") && !html.contains("og:url"));
+
+        let html = builder.build_page("", "/", "", PageKind::Fragment).unwrap();
+        assert!(html.contains(r#"<meta property="og:type" content="website">"#));
+
+        // Pages get their absolute URL once the base URL is set
+        builder.set_base_url(Some("https://example.com/"));
+        let html = article(&builder, None, None);
+        assert!(html.contains(r#"<link rel="canonical" href="https://example.com/writing/a/">"#));
+        assert!(
+            html.contains(r#"<meta property="og:url" content="https://example.com/writing/a/">"#)
+        );
+    }
+
+    #[test]
+    fn canonical_links() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let mut builder =
+            PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let fingerprint = builder.fingerprint();
+
+        // The trailing slash of the base URL is trimmed
+        builder.set_base_url(Some("https://example.com/"));
+        assert_ne!(builder.fingerprint(), fingerprint);
+        let html = builder.build_page("", "/", "", PageKind::Fragment).unwrap();
+        assert!(html.contains(r#"<link rel="canonical" href="https://example.com/">"#));
+        assert!(html.contains(r#"<meta property="og:url" content="https://example.com/">"#));
+
+        // Generated pages link to their own URL
+        let frontmatter = Frontmatter::from_text(
+            "---\ntitle: Post\nslug: post\ncreated: 2024-01-02\ntags: [rust]\n---",
+        )
+        .unwrap();
+        let mut archive = ArchiveBuilder::new();
+        archive.add_article(&frontmatter, None, None, None);
+        assert!(
+            archive.tag_pages_html(&builder, None)[0].1.contains(
+                r#"<link rel="canonical" href="https://example.com/writing/tags/rust/">"#
+            )
+        );
+        let mut changelog = ChangelogBuilder::new();
+        changelog.add_article("Post", "post", date(2024, 1, 2), None);
+        assert!(
+            changelog
+                .into_html(1, &builder)
+                .contains(r#"<link rel="canonical" href="https://example.com/changelog/">"#)
+        );
+
+        builder.set_base_url(None);
+        assert_eq!(builder.fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn reading_time() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let builder = PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let html = builder
+            .build_page(
+                "Post",
+                "/writing/post/",
+                "",
+                PageKind::Article {
+                    contains_math: false,
+                    created: date(2024, 1, 2),
+                    updated: None,
+                    license: None,
+                    description: None,
+                    image_url: None,
+                    reading_minutes: Some(5),
+                    author: None,
+                    noindex: false,
+                    head: None,
+                    stylesheet_url: None,
+                },
+            )
+            .unwrap();
+        assert!(html.contains(r#"</p><p class="__reading-time">5 min read</p></hgroup>"#));
+
+        let frontmatter =
+            Frontmatter::from_text("---\ntitle: Post\nslug: post\ncreated: 2024-01-02\n---")
+                .unwrap();
+        let mut archive = ArchiveBuilder::new();
+        archive.add_article(&frontmatter, None, None, Some(3));
+        assert!(archive.into_html(&builder).contains(
+            r#"<time datetime="2024-01-02">2024-01-02</time> · <span class="__reading-time">3 min read</span></p>"#
+        ));
+    }
+
+    #[test]
+    fn changelog() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let builder = PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+
+        let mut changelog = ChangelogBuilder::new();
+        changelog.add_article("Old", "old", date(2023, 12, 31), None);
+        changelog.add_article("A", "a", date(2024, 1, 2), Some(date(2024, 2, 3)));
+        changelog.add_article("B", "b", date(2024, 2, 3), None);
+        let html = changelog.into_html(3, &builder);
+
+        // Entries are grouped by month, most recent first, with updates before creations on the same day
+        let entry = |date: &str, slug: &str, title: &str, kind: &str| {
+            format!(
+                r#"<li><time datetime="{date}">{date}</time> <a href="/writing/{slug}/">{title}</a> <span class="__changelog-kind">{kind}</span></li>"#
+            )
+        };
+        assert!(html.contains(&format!(
+            r#"<section class="__changelog-month"><h2><time datetime="2024-02">February 2024</time></h2><ul class="__changelog-list" role="list">{}{}</ul></section>"#,
+            entry("2024-02-03", "a", "A", "updated"),
+            entry("2024-02-03", "b", "B", "created")
+        )));
+        assert!(html.contains(&format!(
+            r#"<h2><time datetime="2024-01">January 2024</time></h2><ul class="__changelog-list" role="list">{}</ul>"#,
+            entry("2024-01-02", "a", "A", "created")
+        )));
+        // Only the most recent entries are included
+        assert!(!html.contains("Old"));
+        assert!(!html.contains("December 2023"));
+    }
+
+    #[test]
+    fn article_previews() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let builder = PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let frontmatter = |slug| {
+            Frontmatter::from_text(&format!(
+                "---\ntitle: Post {slug}\nslug: {slug}\ncreated: 2024-01-02\n---"
+            ))
+            .unwrap()
+        };
+
+        let mut archive = ArchiveBuilder::new();
+        archive.add_article(
+            &frontmatter("a"),
+            Some(Thumbnail {
+                src: "/writing/a/cover-thumb.avif".into(),
+                srcset: "/writing/a/cover-thumb.avif 1x, /writing/a/cover-thumb-2x.avif 2x".into(),
+                dimensions: Dimensions {
+                    width: 96,
+                    height: 64,
+                },
+                fallback: None,
+            }),
+            Some("A & B".into()),
+            None,
+        );
+        archive.add_article(&frontmatter("b"), None, None, None);
+        let html = archive.into_html(&builder);
+
+        // Thumbnails are decorative and loaded lazily, and excerpts follow the title
+        assert!(html.contains(
+            r#"<div class="__article-link"><img class="__article-thumbnail" src="/writing/a/cover-thumb.avif" srcset="/writing/a/cover-thumb.avif 1x, /writing/a/cover-thumb-2x.avif 2x" alt="" decoding="async" loading="lazy" width="96" height="64"><a href="/writing/a/">Post a</a><p class="__article-excerpt">A &amp; B</p></div>"#
+        ));
+        // Articles without a cover image or an excerpt only have a title
+        assert!(
+            html.contains(r#"<div class="__article-link"><a href="/writing/b/">Post b</a></div>"#)
+        );
+    }
+
+    #[test]
+    fn article_author() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let builder = PageBuilder::new(
+            template(r#"<meta name="author" content="Me">"#),
+            template("<main></main>"),
+            &[],
+            "",
+        )
+        .unwrap();
+        let article = |author| {
+            builder
+                .build_page(
+                    "Post",
+                    "/writing/post/",
+                    "",
+                    PageKind::Article {
+                        contains_math: false,
+                        created: date(2024, 1, 2),
+                        updated: None,
+                        license: None,
+                        description: None,
+                        image_url: None,
+                        reading_minutes: None,
+                        author,
+                        noindex: false,
+                        head: None,
+                        stylesheet_url: None,
+                    },
+                )
+                .unwrap()
+        };
+
+        let html = article(Some("Guest & Co"));
+        assert!(!html.contains(r#"content="Me""#));
+        assert!(html.contains(r#"<meta name="author" content="Guest &amp; Co">"#));
+        assert!(html.contains(r#"<p class="__article-author">By Guest &amp; Co</p></hgroup>"#));
+
+        let html = article(None);
+        assert!(html.contains(r#"<meta name="author" content="Me">"#));
+        assert!(!html.contains("__article-author"));
+    }
+
+    #[test]
+    fn noindex() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let builder = PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let article = |noindex| {
+            builder
+                .build_page(
+                    "Post",
+                    "/writing/post/",
+                    "",
+                    PageKind::Article {
+                        contains_math: false,
+                        created: date(2024, 1, 2),
+                        updated: None,
+                        license: None,
+                        description: None,
+                        image_url: None,
+                        reading_minutes: None,
+                        author: None,
+                        noindex,
+                        head: None,
+                        stylesheet_url: None,
+                    },
+                )
+                .unwrap()
+        };
+
+        assert!(article(true).contains(r#"<meta name="robots" content="noindex">"#));
+        assert!(!article(false).contains(r#"name="robots""#));
+    }
+
+    #[test]
+    fn inline_site_css() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let mut builder =
+            PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let build =
+            |builder: &PageBuilder| builder.build_page("", "/", "", PageKind::Fragment).unwrap();
+
+        assert!(
+            build(&builder).contains(r#"<link rel="stylesheet" href="/stylesheets/site.css">"#)
+        );
+        builder.inline_site_css("p>a{color:red}");
+        let html = build(&builder);
+        assert!(html.contains(
+            r#"<meta name="format-detection" content="telephone=no"><style>p>a{color:red}</style>"#
+        ));
+        assert!(!html.contains("site.css"));
+    }
+
+    #[test]
+    fn page_head() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let builder = PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let article = |head| {
+            builder.build_page(
+                "Post",
+                "/writing/post/",
+                "",
+                PageKind::Article {
+                    contains_math: false,
+                    created: date(2024, 1, 2),
+                    updated: None,
+                    license: None,
+                    description: None,
+                    image_url: None,
+                    reading_minutes: None,
+                    author: None,
+                    noindex: false,
+                    head,
+                    stylesheet_url: None,
+                },
+            )
+        };
+
+        let head = r#"<link rel="preconnect" href="https://example.com">
+<style>p { color: red; }</style>"#;
+        assert!(validate_head_html(head).is_ok());
+        assert!(article(Some(head)).unwrap().contains(
+            r#"<link rel="preconnect" href="https://example.com">
+<style>p { color: red; }</style></head>"#
+        ));
+        assert!(validate_head_html("<!-- a -->\n<meta name=\"a\" content=\"b\">").is_ok());
+        assert!(validate_head_html("<title>A</title>").is_err());
+        assert!(validate_head_html("<p>A</p>").is_err());
+        assert!(validate_head_html("A").is_err());
+        assert!(validate_head_html("<meta name=a").is_err());
+    }
+
+    #[test]
+    fn article_stylesheet() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let builder = PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let html = builder
+            .build_page(
+                "Post",
+                "/writing/post/",
+                "",
+                PageKind::Article {
+                    contains_math: false,
+                    created: date(2024, 1, 2),
+                    updated: None,
+                    license: None,
+                    description: None,
+                    image_url: None,
+                    reading_minutes: None,
+                    author: None,
+                    noindex: false,
+                    head: None,
+                    stylesheet_url: Some("/writing/post/style.css"),
+                },
+            )
+            .unwrap();
+
+        // The article's stylesheet is linked after the site CSS, so its rules take precedence
+        let site_css = html.find(r#"href="/stylesheets/site.css""#).unwrap();
+        let article_css = html
+            .find(r#"<link rel="stylesheet" href="/writing/post/style.css">"#)
+            .unwrap();
+        assert!(site_css < article_css);
+    }
+
+    #[test]
+    fn breadcrumbs() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let mut builder =
+            PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let article = |builder: &PageBuilder| {
+            builder
+                .build_page(
+                    "A </script>",
+                    "/writing/a/",
+                    "",
+                    PageKind::Article {
+                        contains_math: false,
+                        created: date(2024, 1, 2),
+                        updated: None,
+                        license: None,
+                        description: None,
+                        image_url: None,
+                        reading_minutes: None,
+                        author: None,
+                        noindex: false,
+                        head: None,
+                        stylesheet_url: None,
+                    },
+                )
+                .unwrap()
+        };
+
+        assert!(!article(&builder).contains("__breadcrumbs"));
+
+        builder.set_breadcrumbs(true);
+        let html = article(&builder);
+        assert!(html.contains(
+            r#"<main><nav class="__breadcrumbs" aria-label="Breadcrumb"><ol><li><a href="/">Home</a></li><li><a href="/writing/">Writing</a></li><li aria-current="page">A &lt;/script&gt;</li></ol></nav><article>"#
+        ));
+        assert!(!html.contains("application/ld+json"));
+        assert!(
+            !builder
+                .build_page("", "/", "", PageKind::Fragment)
+                .unwrap()
+                .contains("__breadcrumbs")
+        );
+
+        builder.set_base_url(Some("https://example.com/"));
+        assert!(article(&builder).contains(
+            r#"<script type="application/ld+json">{"@context":"https://schema.org","@type":"BreadcrumbList","itemListElement":[{"@type":"ListItem","item":"https://example.com/","name":"Home","position":1},{"@type":"ListItem","item":"https://example.com/writing/","name":"Writing","position":2},{"@type":"ListItem","item":"https://example.com/writing/a/","name":"A <\/script>","position":3}]}</script>"#
+        ));
+    }
+
+    #[test]
+    fn license() {
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let mut builder =
+            PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let article = |builder: &PageBuilder, license, updated| {
+            builder
+                .build_page(
+                    "A </script>",
+                    "/writing/a/",
+                    "",
+                    PageKind::Article {
+                        contains_math: false,
+                        created: date(2024, 1, 2),
+                        updated,
+                        license,
+                        description: None,
+                        image_url: None,
+                        reading_minutes: None,
+                        author: None,
+                        noindex: false,
+                        head: None,
+                        stylesheet_url: None,
+                    },
+                )
+                .unwrap()
+        };
+
+        // SPDX identifiers link to the SPDX license list, in the footer and in JSON-LD
+        let spdx = License::Name("CC-BY-4.0".into());
+        let html = article(&builder, Some(&spdx), None);
+        assert!(html.contains(
+            r#"<footer class="__article-license"><p>This article is licensed under <a rel="license" href="https://spdx.org/licenses/CC-BY-4.0.html">CC-BY-4.0</a>.</p></footer>"#
+        ));
+        assert!(html.contains(
+            r#"<script type="application/ld+json">{"@context":"https://schema.org","@type":"Article","datePublished":"2024-01-02","headline":"A <\/script>","license":"https://spdx.org/licenses/CC-BY-4.0.html"}</script>"#
+        ));
+
+        // Licenses without a URL are given by name, and the absolute URL is included once the base URL is set
+        builder.set_base_url(Some("https://example.com/"));
+        let name = License::Name("All rights reserved".into());
+        let html = article(&builder, Some(&name), Some(date(2024, 3, 4)));
+        assert!(html.contains(
+            r#"<footer class="__article-license"><p>This article is licensed under All rights reserved.</p></footer>"#
+        ));
+        assert!(html.contains(
+            r#"{"@context":"https://schema.org","@type":"Article","dateModified":"2024-03-04","datePublished":"2024-01-02","headline":"A <\/script>","license":"All rights reserved","url":"https://example.com/writing/a/"}"#
+        ));
+
+        let html = article(&builder, None, None);
+        assert!(!html.contains("__article-license") && !html.contains(r#""@type":"Article""#));
+    }
+
+    #[test]
+    fn whole_years_between_dates() {
+        assert_eq!(years_between(date(2000, 1, 1), date(2000, 1, 1)), 0);
+        assert_eq!(years_between(date(2000, 1, 1), date(2000, 12, 31)), 0);
+        assert_eq!(years_between(date(2000, 1, 1), date(2001, 1, 1)), 1);
+        assert_eq!(years_between(date(2000, 2, 29), date(2003, 2, 28)), 2);
+        assert_eq!(years_between(date(2000, 6, 15), date(2010, 6, 16)), 10);
+    }
+
     /// Utility function for asserting that the HTML representation of `element` is equal to `expected`
+    #[test]
+    fn resolve_relative_urls() {
+        const SITE: &str = "https://example.com";
+        const ARTICLE: &str = "https://example.com/writing/abc/";
+
+        assert_eq!(
+            resolve_url("image.avif", SITE, ARTICLE),
+            "https://example.com/writing/abc/image.avif"
+        );
+        assert_eq!(
+            resolve_url("#fn-1", SITE, ARTICLE),
+            "https://example.com/writing/abc/#fn-1"
+        );
+        assert_eq!(
+            resolve_url("/about/", SITE, ARTICLE),
+            "https://example.com/about/"
+        );
+        assert_eq!(
+            resolve_url("https://a.com/b", SITE, ARTICLE),
+            "https://a.com/b"
+        );
+        assert_eq!(
+            resolve_url("mailto:a@b.com", SITE, ARTICLE),
+            "mailto:a@b.com"
+        );
+        assert_eq!(resolve_url("//a.com/b", SITE, ARTICLE), "//a.com/b");
+    }
+
+    #[test]
+    fn feed() {
+        let config = FeedConfig {
+            site_url: "https://example.com/".into(),
+            title: "A & B".into(),
+            author: "Me".into(),
+            rss: true,
+            tags: true,
+        };
+        let frontmatter = Frontmatter {
+            title: "Post".into(),
+            slug: "post".into(),
+            created: date(2024, 1, 2),
+            updated: Some(date(2024, 3, 4)),
+            license: None,
+            figure_index: None,
+            quote_locale: None,
+            tags: vec!["rust".into()],
+            draft: false,
+            noindex: false,
+            head: None,
+            toc: false,
+            cover: None,
+            description: None,
+            excerpt: None,
+            author: Some("Guest".into()),
+            template: None,
+            slots: BTreeMap::new(),
+            disable_markdown: Vec::new(),
+            aliases: Vec::new(),
+            katex: KatexOverrides::default(),
+        };
+
+        let mut builder = FeedBuilder::new();
+        builder
+            .add_article(
+                &config,
+                &frontmatter,
+                Some(&License::Name("CC0-1.0".into())),
+                r#"<p><img src="a.avif" srcset="a.avif 1x, b.avif 2x"></p>"#,
+            )
+            .unwrap();
+        let (atom, rss) = builder.into_xml(&config);
+        let rss = rss.unwrap();
+
+        assert!(atom.contains("<title>A &amp; B</title>"));
+        assert!(atom.contains(r#"<link rel="self" href="https://example.com/writing/feed.xml"/>"#));
+        assert!(atom.contains("<updated>2024-03-04T00:00:00Z</updated><author>"));
+        assert!(atom.contains("<id>https://example.com/writing/post/</id>"));
+        assert!(atom.contains("<published>2024-01-02T00:00:00Z</published>"));
+        assert!(atom.contains("<author><name>Guest</name></author><rights>CC0-1.0</rights>"));
+        assert!(atom.contains(
+            "&lt;img src=&quot;https://example.com/writing/post/a.avif&quot; \
+            srcset=&quot;https://example.com/writing/post/a.avif 1x, https://example.com/writing/post/b.avif 2x&quot;&gt;"
+        ));
+
+        assert!(rss.contains("<pubDate>Tue, 2 Jan 2024 00:00:00 -0000</pubDate>"));
+        assert!(
+            rss.contains(r#"<guid isPermaLink="true">https://example.com/writing/post/</guid>"#)
+        );
+    }
+
+    #[test]
+    fn tag_feeds() {
+        let config = FeedConfig {
+            site_url: "https://example.com/".into(),
+            title: "A & B".into(),
+            author: "Me".into(),
+            rss: true,
+            tags: true,
+        };
+        let frontmatter = Frontmatter::from_text(
+            "---\ntitle: Post\nslug: post\ncreated: 2024-01-02\ntags: [rust]\n---",
+        )
+        .unwrap();
+
+        let mut builder = FeedBuilder::new();
+        builder
+            .add_article(&config, &frontmatter, None, "<p>Text</p>")
+            .unwrap();
+        let tag_feeds = builder.tag_xml(&config);
+        assert_eq!(tag_feeds.len(), 1);
+        let (tag, atom, rss) = &tag_feeds[0];
+        assert_eq!(&**tag, "rust");
+        assert!(atom.contains("<title>A &amp; B (tagged \u{201C}rust\u{201D})</title>"));
+        assert!(atom.contains(r#"<link href="https://example.com/writing/tags/rust/"/>"#));
+        assert!(atom.contains(
+            r#"<link rel="self" href="https://example.com/writing/tags/rust/feed.xml"/>"#
+        ));
+        assert!(
+            rss.as_ref()
+                .unwrap()
+                .contains(r#"href="https://example.com/writing/tags/rust/rss.xml"/>"#)
+        );
+
+        // Tag pages link to the feeds of their tag
+        let template = |text| Template {
+            text,
+            path: Utf8Path::new("template.html"),
+            vfs: &RealFs,
+        };
+        let page_builder =
+            PageBuilder::new(template(""), template("<main></main>"), &[], "").unwrap();
+        let mut archive = ArchiveBuilder::new();
+        archive.add_article(&frontmatter, None, None, None);
+        let tag_pages = archive.tag_pages_html(&page_builder, Some(&config));
+        assert_eq!(tag_pages.len(), 1);
+        assert!(tag_pages[0].1.contains(concat!(
+            r#"<link rel="alternate" type="application/atom+xml" title="A &amp; B (tagged “rust”)" href="/writing/tags/rust/feed.xml">"#,
+            r#"<link rel="alternate" type="application/rss+xml" title="A &amp; B (tagged “rust”)" href="/writing/tags/rust/rss.xml">"#
+        )));
+        assert!(
+            !archive.tag_pages_html(&page_builder, None)[0]
+                .1
+                .contains("rel=\"alternate\"")
+        );
+    }
+
     fn assert_eq_serialized(element: Node, expected: &str) {
         let mut html = Html::new_fragment();
         html.tree.root_mut().append(element);
@@ -0,0 +1,424 @@
+//! Code for reusing build outputs from previous builds.
+
+use crate::{
+    latex::{KATEX_VERSION, KatexOptions, KatexOverrides, RenderMode},
+    vfs::Vfs,
+};
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize;
+use siphasher::sip128::{Hasher128, SipHasher13};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display, Formatter},
+    hash::Hasher,
+    io::ErrorKind,
+};
+
+// Subdirectory of the cache directory containing built fragment pages
+const FRAGMENTS_DIR: &str = "fragments";
+// File in the cache directory containing rendered math expressions
+const MATH_FILE: &str = "math.json";
+
+/// Returns a stable hash of a sequence of byte strings, as a hex string.
+/// Unlike the hashers in `std`, the output does not change between builds or versions of `ssg`,
+/// so it can be used as a key for data persisted between builds.
+pub(crate) fn stable_hash(parts: &[&[u8]]) -> String {
+    let mut hasher = SipHasher13::new();
+    for part in parts {
+        // Prefix every part with its length, so different splits of the same bytes hash differently
+        hasher.write(&(part.len() as u64).to_le_bytes());
+        hasher.write(part);
+    }
+    format!("{:032x}", hasher.finish128().as_u128())
+}
+
+/// Numbers of cache lookups that found and did not find a cached output.
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl Display for CacheStats {
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "hit rates are only displayed approximately"
+    )]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let lookups = self.hits + self.misses;
+        write!(f, "{} hit(s), {} miss(es)", self.hits, self.misses)?;
+        if lookups > 0 {
+            write!(
+                f,
+                " ({:.0}% hit rate)",
+                self.hits as f64 * 100.0 / lookups as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A cache of built fragment pages, stored in the cache directory.
+///
+/// Pages are keyed by a hash of the fragment's title, URL, and content, plus a fingerprint of the page builder
+/// (which covers the templates and the inlined site CSS, including font declarations).
+/// Cached pages that are not used during a build are removed when the build finishes.
+pub struct FragmentCache<'a> {
+    vfs: &'a dyn Vfs,
+    // Directory containing cached pages; no pages are cached if absent
+    dir: Option<Utf8PathBuf>,
+    used_keys: BTreeSet<String>,
+    stats: CacheStats,
+}
+
+impl<'a> FragmentCache<'a> {
+    /// Initializes a fragment cache within `cache_dir` of a file system,
+    /// or a cache that never stores anything if `cache_dir` is `None`.
+    ///
+    /// # Errors
+    /// This function returns an error if the cache directory cannot be created.
+    pub fn new(cache_dir: Option<&Utf8Path>, vfs: &'a dyn Vfs) -> Result<Self> {
+        let dir = cache_dir.map(|cache_dir| cache_dir.join(FRAGMENTS_DIR));
+        if let Some(dir) = &dir {
+            vfs.create_dir_all(dir)
+                .with_context(|| format!("failed to create fragment cache directory at {dir}"))?;
+        }
+
+        Ok(Self {
+            vfs,
+            dir,
+            used_keys: BTreeSet::new(),
+            stats: CacheStats::default(),
+        })
+    }
+
+    /// Returns the cache key for a fragment page with the given content of named slots,
+    /// built by a page builder with the given fingerprint (see `PageBuilder::fingerprint()`).
+    #[must_use]
+    pub fn key(
+        title: &str,
+        url: &str,
+        text: &str,
+        slots: &[(&str, &str)],
+        builder_fingerprint: &str,
+    ) -> String {
+        let mut parts = vec![title.as_bytes(), url.as_bytes(), text.as_bytes()];
+        for (name, content) in slots {
+            parts.extend([name.as_bytes(), content.as_bytes()]);
+        }
+        parts.push(builder_fingerprint.as_bytes());
+        stable_hash(&parts)
+    }
+
+    /// Copies the cached page with the given key to `output_path`, returning whether a cached page was found.
+    ///
+    /// # Errors
+    /// This function returns an error if the cached page exists but cannot be copied.
+    pub fn copy_to(&mut self, key: &str, output_path: &Utf8Path) -> Result<bool> {
+        self.used_keys.insert(key.into());
+
+        let Some(path) = self.entry_path(key).filter(|path| self.vfs.is_file(path)) else {
+            self.stats.misses += 1;
+            return Ok(false);
+        };
+
+        self.vfs.copy(&path, output_path).with_context(|| {
+            format!("failed to copy cached fragment page from {path} to {output_path}")
+        })?;
+        self.stats.hits += 1;
+        Ok(true)
+    }
+
+    /// Stores a built page in the cache.
+    ///
+    /// # Errors
+    /// This function returns an error if the page cannot be written to the cache.
+    pub fn insert(&self, key: &str, html: &str) -> Result<()> {
+        if let Some(path) = self.entry_path(key) {
+            self.vfs
+                .write(&path, html.as_bytes(), false)
+                .with_context(|| format!("failed to write cached fragment page at {path}"))?;
+        }
+        Ok(())
+    }
+
+    /// Removes cached pages that were not looked up during this build, returning the cache's hit statistics.
+    ///
+    /// # Errors
+    /// This function returns an error if the cache directory cannot be read or an unused page cannot be removed.
+    pub fn finish(self) -> Result<CacheStats> {
+        let Some(dir) = &self.dir else {
+            return Ok(self.stats);
+        };
+
+        for path in self.vfs.files_in(dir)? {
+            let is_used = path
+                .file_stem()
+                .is_some_and(|stem| self.used_keys.contains(stem));
+
+            if !is_used {
+                self.vfs
+                    .remove_file(&path)
+                    .with_context(|| format!("failed to remove unused cached page at {path}"))?;
+            }
+        }
+
+        Ok(self.stats)
+    }
+
+    fn entry_path(&self, key: &str) -> Option<Utf8PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(format!("{key}.html")))
+    }
+}
+
+/// A cache of math expressions rendered by KaTeX, stored in the cache directory.
+///
+/// Expressions are keyed by a hash of their source, their render mode, the KaTeX version, the site's KaTeX options,
+/// and the KaTeX settings of the article they are in.
+/// Cached expressions that are not used during a build can be removed when the build finishes.
+pub struct MathCache<'a> {
+    vfs: &'a dyn Vfs,
+    // File containing cached expressions; no expressions are cached if absent
+    path: Option<Utf8PathBuf>,
+    // Fingerprint of the KaTeX options expressions are rendered with
+    options_fingerprint: String,
+    // Rendered HTML of expressions cached by previous builds
+    cached: BTreeMap<String, String>,
+    // Rendered HTML of expressions used during this build
+    used: BTreeMap<String, String>,
+    stats: CacheStats,
+}
+
+impl<'a> MathCache<'a> {
+    /// Loads the math cache within `cache_dir` of a file system,
+    /// or initializes a cache that never stores anything if `cache_dir` is `None`.
+    /// Expressions are looked up for rendering with `options`.
+    ///
+    /// # Errors
+    /// This function returns an error if the cache file exists but cannot be read or parsed.
+    pub fn load(
+        cache_dir: Option<&Utf8Path>,
+        options: &KatexOptions,
+        vfs: &'a dyn Vfs,
+    ) -> Result<Self> {
+        let path = cache_dir.map(|cache_dir| cache_dir.join(MATH_FILE));
+        let cached = match &path {
+            Some(path) => match vfs.read_to_string(path) {
+                Ok(text) => serde_json::from_str(&text)
+                    .with_context(|| format!("failed to parse math cache at {path}"))?,
+                Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+                Err(e) => {
+                    return Err(e).with_context(|| format!("failed to read math cache at {path}"));
+                }
+            },
+            None => BTreeMap::new(),
+        };
+
+        Ok(Self {
+            vfs,
+            path,
+            options_fingerprint: options.fingerprint(),
+            cached,
+            used: BTreeMap::new(),
+            stats: CacheStats::default(),
+        })
+    }
+
+    /// Returns the cached HTML of a math expression, or renders it with `render` and caches the result.
+    ///
+    /// # Errors
+    /// This function returns an error if the expression is not cached and `render` fails.
+    pub fn get_or_render(
+        &mut self,
+        src: &str,
+        mode: RenderMode,
+        render: impl FnOnce() -> Result<String>,
+    ) -> Result<String> {
+        self.get_or_render_with_overrides(src, mode, &KatexOverrides::default(), render)
+    }
+
+    /// Returns the cached HTML of a math expression in an article with its own KaTeX settings,
+    /// or renders it with `render` and caches the result.
+    ///
+    /// # Errors
+    /// This function returns an error if the expression is not cached and `render` fails.
+    pub fn get_or_render_with_overrides(
+        &mut self,
+        src: &str,
+        mode: RenderMode,
+        overrides: &KatexOverrides,
+        render: impl FnOnce() -> Result<String>,
+    ) -> Result<String> {
+        let mode: &[u8] = match mode {
+            RenderMode::Inline => b"inline",
+            RenderMode::Display => b"display",
+        };
+        let key = stable_hash(&[
+            src.as_bytes(),
+            mode,
+            KATEX_VERSION.trim().as_bytes(),
+            self.options_fingerprint.as_bytes(),
+            overrides.fingerprint().as_bytes(),
+        ]);
+
+        if let Some(html) = self.used.get(&key) {
+            self.stats.hits += 1;
+            return Ok(html.clone());
+        }
+        let html = if let Some(html) = self.cached.remove(&key) {
+            self.stats.hits += 1;
+            html
+        } else {
+            self.stats.misses += 1;
+            render()?
+        };
+
+        self.used.insert(key, html.clone());
+        Ok(html)
+    }
+
+    /// Writes the expressions used during this build to the cache file, returning the cache's hit statistics.
+    /// Expressions cached by previous builds but not used during this build are kept unless `prune_unused` is set.
+    ///
+    /// # Errors
+    /// This function returns an error if the cache file cannot be written.
+    pub fn finish(mut self, prune_unused: bool) -> Result<CacheStats> {
+        if let Some(path) = &self.path {
+            if !prune_unused {
+                self.used.append(&mut self.cached);
+            }
+            let json =
+                serde_json::to_string(&self.used).context("failed to serialize math cache")?;
+            self.vfs
+                .write(path, json.as_bytes(), false)
+                .with_context(|| format!("failed to write math cache at {path}"))?;
+        }
+        Ok(self.stats)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FragmentCache, MathCache, stable_hash};
+    use crate::{KatexOptions, KatexOutput, KatexOverrides, MemoryFs, RenderMode, Vfs};
+    use camino::Utf8Path;
+
+    #[test]
+    fn hashes() {
+        assert_eq!(stable_hash(&[b"ab", b"c"]), stable_hash(&[b"ab", b"c"]));
+        assert_ne!(stable_hash(&[b"ab", b"c"]), stable_hash(&[b"a", b"bc"]));
+        assert_eq!(stable_hash(&[]).len(), 32);
+    }
+
+    #[test]
+    fn hits_and_pruning() {
+        let vfs = MemoryFs::new();
+        let dir = Utf8Path::new("/cache");
+        let output_dir = Utf8Path::new("/out");
+
+        let mut cache = FragmentCache::new(Some(dir), &vfs).unwrap();
+        assert!(!cache.copy_to("a", &output_dir.join("a.html")).unwrap());
+        cache.insert("a", "<p>a</p>").unwrap();
+        cache.insert("stale", "<p>stale</p>").unwrap();
+        let stats = cache.finish().unwrap();
+        assert_eq!((stats.hits, stats.misses), (0, 1));
+        // Pages inserted without being looked up are removed
+        assert_eq!(vfs.files_in(&dir.join("fragments")).unwrap().len(), 1);
+
+        let mut cache = FragmentCache::new(Some(dir), &vfs).unwrap();
+        assert!(cache.copy_to("a", &output_dir.join("a.html")).unwrap());
+        assert_eq!(
+            vfs.read_to_string(&output_dir.join("a.html")).unwrap(),
+            "<p>a</p>"
+        );
+        let stats = cache.finish().unwrap();
+        assert_eq!((stats.hits, stats.misses), (1, 0));
+        assert_eq!(stats.to_string(), "1 hit(s), 0 miss(es) (100% hit rate)");
+    }
+
+    #[test]
+    fn math() {
+        let vfs = MemoryFs::new();
+        let dir = Utf8Path::new("/cache");
+
+        let mut cache = MathCache::load(Some(dir), &KatexOptions::default(), &vfs).unwrap();
+        let render = |html: &str| {
+            let html = html.to_owned();
+            move || Ok(html)
+        };
+        assert_eq!(
+            cache
+                .get_or_render("x", RenderMode::Inline, render("a"))
+                .unwrap(),
+            "a"
+        );
+        assert_eq!(
+            cache
+                .get_or_render("x", RenderMode::Inline, render("b"))
+                .unwrap(),
+            "a"
+        );
+        assert_eq!(
+            cache
+                .get_or_render("x", RenderMode::Display, render("c"))
+                .unwrap(),
+            "c"
+        );
+        let stats = cache.finish(true).unwrap();
+        assert_eq!((stats.hits, stats.misses), (1, 2));
+
+        let mut cache = MathCache::load(Some(dir), &KatexOptions::default(), &vfs).unwrap();
+        assert_eq!(
+            cache
+                .get_or_render("x", RenderMode::Display, || unreachable!())
+                .unwrap(),
+            "c"
+        );
+        // Unused expressions are kept without pruning
+        cache.finish(false).unwrap();
+
+        let mut cache = MathCache::load(Some(dir), &KatexOptions::default(), &vfs).unwrap();
+        assert_eq!(
+            cache
+                .get_or_render("x", RenderMode::Inline, || unreachable!())
+                .unwrap(),
+            "a"
+        );
+        cache.finish(true).unwrap();
+
+        // Expressions not used by the last build are removed with pruning
+        let mut cache = MathCache::load(Some(dir), &KatexOptions::default(), &vfs).unwrap();
+        assert_eq!(
+            cache
+                .get_or_render("x", RenderMode::Display, render("d"))
+                .unwrap(),
+            "d"
+        );
+        let stats = cache.finish(true).unwrap();
+        assert_eq!((stats.hits, stats.misses), (0, 1));
+
+        // Expressions rendered with other KaTeX settings are not reused
+        let options = KatexOptions {
+            output: KatexOutput::Mathml,
+            ..KatexOptions::default()
+        };
+        let mut cache = MathCache::load(Some(dir), &options, &vfs).unwrap();
+        assert_eq!(
+            cache
+                .get_or_render("x", RenderMode::Display, render("e"))
+                .unwrap(),
+            "e"
+        );
+        let overrides = KatexOverrides {
+            strict: Some(true),
+            ..KatexOverrides::default()
+        };
+        assert_eq!(
+            cache
+                .get_or_render_with_overrides("x", RenderMode::Display, &overrides, render("f"))
+                .unwrap(),
+            "f"
+        );
+    }
+}
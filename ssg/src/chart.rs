@@ -0,0 +1,275 @@
+//! Utility for rendering charts from declarative specifications as static SVG.
+
+use anyhow::{Context, Result, anyhow, bail};
+use plotters::{
+    backend::SVGBackend,
+    chart::ChartBuilder,
+    drawing::IntoDrawingArea,
+    element::{Circle, Rectangle},
+    series::LineSeries,
+    style::{Color, IntoFont, Palette, Palette99, RGBColor, TRANSPARENT},
+};
+use serde::Deserialize;
+use toml_edit::de::from_str as toml_from_str;
+
+// The name of the fenced code block language for chart specifications
+pub const CHART_LANGUAGE: &str = "chart";
+
+const CHART_SIZE: (u32, u32) = (800, 450);
+const AXIS_COLOR: RGBColor = RGBColor(0x6b, 0x6b, 0x6b);
+// The fraction of each label's width taken up by bars
+const BAR_GROUP_WIDTH: f64 = 0.8;
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Debug))]
+pub struct ChartSpec {
+    // The kind of chart to draw
+    #[serde(rename = "type")]
+    kind: ChartKind,
+    // Optional title displayed above the chart
+    #[serde(default)]
+    title: Option<String>,
+    // Labels for the x-axis
+    labels: Vec<String>,
+    // Data series; each series must have one value per label
+    series: Vec<Series>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[serde(rename_all = "lowercase")]
+enum ChartKind {
+    Bar,
+    Line,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(Debug))]
+struct Series {
+    name: String,
+    values: Vec<f64>,
+}
+
+impl ChartSpec {
+    /// Parses a chart specification written in TOML.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the specification is not valid TOML or is missing fields
+    /// - there are no labels or no series
+    /// - a series does not have exactly one value per label
+    /// - a value is not finite
+    pub fn parse(input: &str) -> Result<Self> {
+        let spec: Self = toml_from_str(input).context("failed to parse chart specification")?;
+
+        if spec.labels.is_empty() {
+            bail!("`labels`: chart must have at least one label");
+        }
+        if spec.series.is_empty() {
+            bail!("`series`: chart must have at least one series");
+        }
+        for series in &spec.series {
+            if series.values.len() != spec.labels.len() {
+                bail!(
+                    "`series`: series \"{}\" has {} values, but there are {} labels",
+                    series.name,
+                    series.values.len(),
+                    spec.labels.len()
+                );
+            }
+            if !series.values.iter().all(|value| value.is_finite()) {
+                bail!(
+                    "`series`: series \"{}\" has a non-finite value",
+                    series.name
+                );
+            }
+        }
+
+        Ok(spec)
+    }
+
+    /// Renders the chart as a `<figure>` containing an inline SVG, returned as a string of HTML.
+    ///
+    /// # Errors
+    /// This function returns an error if the chart cannot be drawn.
+    pub fn to_html(&self) -> Result<String> {
+        let mut svg = String::new();
+        self.draw(&mut svg)
+            .map_err(|e| anyhow!("failed to draw chart: {e}"))?;
+
+        Ok(format!(r#"<figure class="__chart">{svg}</figure>"#))
+    }
+
+    fn draw(&self, svg: &mut String) -> Result<(), Box<dyn std::error::Error + '_>> {
+        let root = SVGBackend::with_string(svg, CHART_SIZE).into_drawing_area();
+        root.fill(&TRANSPARENT)?;
+
+        let values = self.series.iter().flat_map(|series| &series.values);
+        // The y-axis always includes zero so bars have a baseline
+        let (min, max) = values.fold((0.0_f64, 0.0_f64), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        });
+        // Leave some headroom above the tallest value, and avoid an empty range
+        let (min, max) = if max <= min {
+            (min, min + 1.0)
+        } else {
+            let margin = (max - min) * 0.05;
+            (if min < 0.0 { min - margin } else { min }, max + margin)
+        };
+
+        let label_count = self.labels.len();
+        #[expect(clippy::cast_precision_loss, reason = "label counts are small")]
+        let x_max = label_count as f64 - 0.5;
+
+        let mut builder = ChartBuilder::on(&root);
+        builder
+            .margin(16)
+            .x_label_area_size(32)
+            .y_label_area_size(56);
+        if let Some(title) = &self.title {
+            builder.caption(title, ("sans-serif", 20).into_font());
+        }
+        let mut chart = builder.build_cartesian_2d(-0.5..x_max, min..max)?;
+
+        chart
+            .configure_mesh()
+            .disable_x_mesh()
+            .x_labels(label_count)
+            .x_label_formatter(&|x| {
+                // Only label integer positions, which correspond to entries of `labels`
+                #[expect(
+                    clippy::cast_possible_truncation,
+                    clippy::cast_sign_loss,
+                    reason = "the value is checked to be a non-negative integer"
+                )]
+                if x.fract() == 0.0 && *x >= 0.0 {
+                    self.labels.get(*x as usize).cloned().unwrap_or_default()
+                } else {
+                    String::new()
+                }
+            })
+            .axis_style(AXIS_COLOR)
+            .y_labels(6)
+            .light_line_style(TRANSPARENT)
+            .bold_line_style(AXIS_COLOR.mix(0.3))
+            .label_style(("sans-serif", 14).into_font().color(&AXIS_COLOR))
+            .draw()?;
+
+        #[expect(clippy::cast_precision_loss, reason = "series counts are small")]
+        let bar_width = BAR_GROUP_WIDTH / self.series.len() as f64;
+
+        for (i, series) in self.series.iter().enumerate() {
+            let color = Palette99::pick(i).to_rgba();
+
+            let drawn = match self.kind {
+                ChartKind::Bar => {
+                    chart.draw_series(series.values.iter().enumerate().map(|(j, &value)| {
+                        #[expect(clippy::cast_precision_loss, reason = "indices are small")]
+                        let left = j as f64 - BAR_GROUP_WIDTH / 2.0 + i as f64 * bar_width;
+                        Rectangle::new([(left, 0.0), (left + bar_width, value)], color.filled())
+                    }))?
+                }
+                ChartKind::Line => {
+                    #[expect(clippy::cast_precision_loss, reason = "indices are small")]
+                    let points = series
+                        .values
+                        .iter()
+                        .enumerate()
+                        .map(|(j, &value)| (j as f64, value));
+                    chart.draw_series(
+                        points
+                            .clone()
+                            .map(|point| Circle::new(point, 3, color.filled())),
+                    )?;
+                    chart.draw_series(LineSeries::new(points, color.stroke_width(2)))?
+                }
+            };
+
+            drawn.label(&series.name).legend(move |(x, y)| {
+                Rectangle::new([(x, y - 5), (x + 10, y + 5)], color.filled())
+            });
+        }
+
+        // A legend is only needed to distinguish between multiple series
+        if self.series.len() > 1 {
+            chart
+                .configure_series_labels()
+                .border_style(AXIS_COLOR)
+                .label_font(("sans-serif", 14).into_font().color(&AXIS_COLOR))
+                .draw()?;
+        }
+
+        root.present()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChartKind, ChartSpec};
+
+    #[test]
+    fn parse() {
+        let spec = ChartSpec::parse(
+            r#"
+type = "bar"
+title = "Fruit"
+labels = ["apples", "pears"]
+
+[[series]]
+name = "2024"
+values = [1, 2.5]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(spec.kind, ChartKind::Bar);
+        assert_eq!(spec.title.as_deref(), Some("Fruit"));
+        assert_eq!(spec.series[0].values, [1.0, 2.5]);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(
+            ChartSpec::parse(
+                "type = \"pie\"\nlabels = [\"a\"]\nseries = [{ name = \"x\", values = [1] }]"
+            )
+            .is_err()
+        );
+        assert!(
+            ChartSpec::parse(
+                "type = \"bar\"\nlabels = []\nseries = [{ name = \"x\", values = [] }]"
+            )
+            .is_err()
+        );
+        assert!(ChartSpec::parse("type = \"bar\"\nlabels = [\"a\"]\nseries = []").is_err());
+        assert!(
+            ChartSpec::parse(
+                "type = \"bar\"\nlabels = [\"a\"]\nseries = [{ name = \"x\", values = [1, 2] }]"
+            )
+            .is_err()
+        );
+        assert!(
+            ChartSpec::parse(
+                "type = \"bar\"\nlabels = [\"a\"]\nseries = [{ name = \"x\", values = [nan] }]"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn render() {
+        for kind in ["bar", "line"] {
+            let spec = ChartSpec::parse(&format!(
+                "type = \"{kind}\"\ntitle = \"<T>\"\nlabels = [\"a\", \"b\"]\nseries = [{{ name = \"x\", values = [-1, 2] }}, {{ name = \"y\", values = [3, 4] }}]"
+            ))
+            .unwrap();
+            let html = spec.to_html().unwrap();
+
+            assert!(html.starts_with(r#"<figure class="__chart"><svg"#));
+            assert!(html.contains("&lt;T&gt;"));
+            assert!(!html.contains("NaN"));
+        }
+    }
+}
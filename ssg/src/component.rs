@@ -0,0 +1,298 @@
+//! Code for expanding reusable HTML components declared in the config file.
+//!
+//! Components with a `{{ children }}` slot wrap Markdown content between marker paragraphs:
+//!
+//! ```markdown
+//! :::callout title="Note"
+//!
+//! Some **Markdown** content.
+//!
+//! :::
+//! ```
+//!
+//! Components without a slot are used like shortcodes (e.g. `{{ badge label="New" }}`).
+
+use crate::shortcode::{BUILTIN_SHORTCODES, Shortcode, is_valid_identifier};
+use anyhow::{Result, anyhow, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// Name of the template placeholder for the content wrapped by a component
+const CHILDREN_SLOT: &str = "children";
+
+#[derive(Deserialize)]
+pub struct Component {
+    // HTML with `{{ prop }}` placeholders, and optionally one `{{ children }}` placeholder
+    template: Box<str>,
+    // Props that must be provided whenever the component is used
+    #[serde(default)]
+    props: Box<[Box<str>]>,
+    // Props that can be omitted; placeholders for omitted props are replaced with nothing
+    #[serde(default)]
+    optional_props: Box<[Box<str>]>,
+}
+
+/// A paragraph that opens or closes a component.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub enum ComponentMarker<'a> {
+    Open(Shortcode<'a>),
+    Close,
+}
+
+impl ComponentMarker<'_> {
+    /// Parses a paragraph of the form `:::name key="value"` or `:::` as a component marker.
+    /// This function returns `Ok(None)` if the paragraph does not start with `:::`.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the marker is not the only line in the paragraph
+    /// - the component name or arguments are invalid
+    pub fn parse(input: &str) -> Result<Option<ComponentMarker<'_>>> {
+        let Some(inner) = input.trim().strip_prefix(":::") else {
+            return Ok(None);
+        };
+
+        if inner.contains('\n') {
+            bail!("component markers must be surrounded by blank lines");
+        }
+
+        if inner.trim().is_empty() {
+            Ok(Some(ComponentMarker::Close))
+        } else {
+            Shortcode::parse_inner(inner).map(|shortcode| Some(ComponentMarker::Open(shortcode)))
+        }
+    }
+}
+
+impl Component {
+    /// Validates the component's name and template.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the name or a prop name is invalid, or the name is used by a built-in shortcode
+    /// - a prop is declared more than once, or is named `children`
+    /// - the template has a placeholder for an undeclared prop, or has multiple `{{ children }}` placeholders
+    pub fn validate(&self, name: &str) -> Result<()> {
+        if !is_valid_identifier(name) {
+            bail!("component name is invalid: \"{name}\"");
+        }
+        if BUILTIN_SHORTCODES.contains(&name) {
+            bail!("component name is used by a built-in shortcode: \"{name}\"");
+        }
+
+        let props: Vec<_> = self.props.iter().chain(&self.optional_props).collect();
+        for (i, prop) in props.iter().enumerate() {
+            if !is_valid_identifier(prop) || ***prop == *CHILDREN_SLOT {
+                bail!("component prop name is invalid: \"{prop}\"");
+            }
+            if props[..i].contains(prop) {
+                bail!("component prop is declared more than once: \"{prop}\"");
+            }
+        }
+
+        let mut slot_count = 0;
+        for placeholder in placeholders(&self.template)? {
+            if placeholder == CHILDREN_SLOT {
+                slot_count += 1;
+            } else if !props.iter().any(|prop| ***prop == *placeholder) {
+                bail!("component template uses an undeclared prop: \"{placeholder}\"");
+            }
+        }
+        if slot_count > 1 {
+            bail!("component template has more than one `{{{{ {CHILDREN_SLOT} }}}}` placeholder");
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the component's template has a `{{ children }}` slot.
+    #[must_use]
+    pub fn has_children(&self) -> bool {
+        placeholders(&self.template).is_ok_and(|placeholders| placeholders.contains(&CHILDREN_SLOT))
+    }
+
+    /// Renders the component with the props provided by a shortcode or marker.
+    /// The output is split into HTML before and after the `{{ children }}` slot;
+    /// if the template has no slot, the HTML after it is empty.
+    ///
+    /// # Errors
+    /// This function returns an error if a required prop is missing or an unknown prop is provided.
+    pub fn render(&self, usage: &Shortcode<'_>) -> Result<(String, String)> {
+        let known: Vec<&str> = self
+            .props
+            .iter()
+            .chain(&self.optional_props)
+            .map(AsRef::as_ref)
+            .collect();
+        usage.expect_args(&known)?;
+        for prop in &self.props {
+            usage.require(prop)?;
+        }
+
+        let mut before = String::with_capacity(self.template.len());
+        let mut after = String::new();
+        let mut output = &mut before;
+        let mut rest = &*self.template;
+
+        while let Some(start) = rest.find("{{") {
+            let len = rest[start..]
+                .find("}}")
+                .ok_or_else(|| anyhow!("component template has an unclosed placeholder"))?;
+            output.push_str(&rest[..start]);
+
+            let placeholder = rest[start + 2..start + len].trim();
+            if placeholder == CHILDREN_SLOT {
+                output = &mut after;
+            } else {
+                output.push_str(&escape_html(usage.get(placeholder).unwrap_or_default()));
+            }
+            rest = &rest[start + len + 2..];
+        }
+        output.push_str(rest);
+
+        Ok((before, after))
+    }
+}
+
+/// Renders a component from the config file's registry, returning its HTML before and after its content.
+/// Components with content must be used with markers, and components without content must be used as shortcodes.
+///
+/// # Errors
+/// This function returns an error if the component doesn't exist, if it is used in the wrong form,
+/// or if its props are invalid.
+pub fn render_component(
+    components: &BTreeMap<Box<str>, Component>,
+    usage: &Shortcode<'_>,
+    has_children: bool,
+) -> Result<(String, String)> {
+    let Some(component) = components.get(usage.name) else {
+        bail!("unknown shortcode or component: {}", usage.name);
+    };
+
+    match (has_children, component.has_children()) {
+        (true, false) => bail!(
+            "component `{}` has no content slot, so it must be used as a shortcode",
+            usage.name
+        ),
+        (false, true) => bail!(
+            "component `{}` has a content slot, so it must be used with `:::` markers",
+            usage.name
+        ),
+        _ => component.render(usage),
+    }
+}
+
+/// Returns the names of all `{{ name }}` placeholders in a template.
+fn placeholders(template: &str) -> Result<Vec<&str>> {
+    let mut names = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let len = rest[start..]
+            .find("}}")
+            .ok_or_else(|| anyhow!("component template has an unclosed placeholder"))?;
+        let name = rest[start + 2..start + len].trim();
+        if !is_valid_identifier(name) {
+            bail!("component template placeholder is invalid: \"{name}\"");
+        }
+        names.push(name);
+        rest = &rest[start + len + 2..];
+    }
+
+    Ok(names)
+}
+
+/// Escapes text for use in HTML content or quoted attribute values.
+pub(crate) fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Component, ComponentMarker};
+    use crate::shortcode::Shortcode;
+
+    fn component(template: &str, props: &[&str], optional_props: &[&str]) -> Component {
+        Component {
+            template: template.into(),
+            props: props.iter().map(|&prop| prop.into()).collect(),
+            optional_props: optional_props.iter().map(|&prop| prop.into()).collect(),
+        }
+    }
+
+    #[test]
+    fn markers() {
+        assert_eq!(ComponentMarker::parse("text").unwrap(), None);
+        assert_eq!(
+            ComponentMarker::parse(":::\n").unwrap(),
+            Some(ComponentMarker::Close)
+        );
+        let Some(ComponentMarker::Open(usage)) =
+            ComponentMarker::parse(":::callout title=\"Hi\"").unwrap()
+        else {
+            panic!("marker should open a component");
+        };
+        assert_eq!(usage.name, "callout");
+        assert_eq!(usage.get("title"), Some("Hi"));
+
+        assert!(ComponentMarker::parse(":::callout\nContent").is_err());
+        assert!(ComponentMarker::parse(":::a/b").is_err());
+    }
+
+    #[test]
+    fn validation() {
+        let valid = component(
+            r#"<aside class="{{ kind }}"><b>{{title}}</b>{{ children }}</aside>"#,
+            &["title"],
+            &["kind"],
+        );
+        assert!(valid.validate("callout").is_ok());
+        assert!(valid.has_children());
+        assert!(valid.validate("table").is_err());
+        assert!(valid.validate("a b").is_err());
+
+        assert!(component("{{ x }}", &[], &[]).validate("a").is_err());
+        assert!(component("{{ x", &["x"], &[]).validate("a").is_err());
+        assert!(
+            component("{{ children }}{{ children }}", &[], &[])
+                .validate("a")
+                .is_err()
+        );
+        assert!(component("", &["x"], &["x"]).validate("a").is_err());
+        assert!(component("", &["children"], &[]).validate("a").is_err());
+        assert!(!component("<hr>", &[], &[]).has_children());
+    }
+
+    #[test]
+    fn render() {
+        let callout = component(
+            r#"<aside class="{{ kind }}"><b>{{title}}</b>{{ children }}</aside>"#,
+            &["title"],
+            &["kind"],
+        );
+
+        let usage = Shortcode::parse_inner(r#"callout title="<Note>""#).unwrap();
+        assert_eq!(
+            callout.render(&usage).unwrap(),
+            (
+                r#"<aside class=""><b>&lt;Note&gt;</b>"#.into(),
+                "</aside>".into()
+            )
+        );
+
+        assert!(
+            callout
+                .render(&Shortcode::parse_inner("callout").unwrap())
+                .is_err()
+        );
+        assert!(
+            callout
+                .render(&Shortcode::parse_inner(r#"callout title="" other="""#).unwrap())
+                .is_err()
+        );
+    }
+}
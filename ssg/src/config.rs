@@ -1,12 +1,30 @@
 //! Code for reading the app config from a TOML file. The config file path is supplied via the command line.
 
-use crate::highlight::THEME_NAMES;
+use crate::{
+    builder::{BuildStamp, KatexCssLoading},
+    component::Component,
+    footnote::FootnoteStyle,
+    frontmatter::License,
+    highlight::{InlineCodeStyle, THEME_NAMES},
+    image::{FallbackFormat, ImageOptions, PlaceholderStyle},
+    latex::KatexOptions,
+    links::LinkCheckPolicy,
+    manifest::ManifestConfig,
+    outline::OutlinePolicy,
+    quotes::QuoteLocale,
+    redirect::{Redirect, RedirectStyle},
+    robots::RobotsConfig,
+    title::DuplicateTitlePolicy,
+    url::AssetUrls,
+    vfs::{RealFs, Vfs},
+};
 use anyhow::{Context, Result, bail};
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use foldhash::{HashSet, HashSetExt};
+use glob::Pattern;
 use same_file::Handle;
-use serde::Deserialize;
-use std::{env::args, fs::read_to_string};
+use serde::{Deserialize, Deserializer};
+use std::{collections::BTreeMap, env::args};
 use toml_edit::de::from_str as toml_from_str;
 
 macro_rules! transform_paths {
@@ -23,69 +41,458 @@ macro_rules! transform_paths {
     };
 }
 
+// Command for checking the frontmatter of every article without building the site
+const FRONTMATTER_CHECK_COMMAND: &str = "frontmatter-check";
+// Command for checking the configuration and the content of every article without writing any output
+const CHECK_COMMAND: &str = "check";
+// Command-line flag for building articles marked as drafts
+const INCLUDE_DRAFTS_FLAG: &str = "--include-drafts";
+// Command-line flag for rebuilding the site whenever its input files change
+const WATCH_FLAG: &str = "--watch";
+// Command-line flag for writing a profile of article build stages, optionally followed by `=<path>`
+const PROFILE_BUILD_FLAG: &str = "--profile-build";
+// Path of the build profile if none is given with the command-line flag
+const DEFAULT_PROFILE_FILE: &str = "build-profile.json";
+
+// The config file is flat, so every on/off setting is its own field
+#[allow(clippy::struct_excessive_bools)]
 #[derive(Deserialize)]
 pub struct Config {
     // Path to directory for generated site output
     pub output_dir: Box<Utf8Path>,
-    // Path to site-wide CSS file
-    pub site_css_file: Box<Utf8Path>,
+    // Paths to site-wide CSS files, bundled in order; a single path can be given as a string
+    #[serde(rename = "site_css_file", deserialize_with = "one_or_more")]
+    pub site_css_files: Box<[Box<Utf8Path>]>,
+    // Path to directory containing the font files used by the site CSS,
+    // which are copied to the output fonts directory
+    #[serde(default)]
+    pub fonts_dir: Option<Box<Utf8Path>>,
+    // Path to directory whose files are copied as is to the output directory (e.g. PDFs or verification files)
+    #[serde(default)]
+    pub static_dir: Option<Box<Utf8Path>>,
     // Path to site-wide head template HTML file
     pub head_template_html_file: Box<Utf8Path>,
     // Path to site-wide body template HTML file
     pub body_template_html_file: Box<Utf8Path>,
+    // Path to directory containing alternate body templates, which articles select with `template` in their frontmatter
+    #[serde(default)]
+    pub templates_dir: Option<Box<Utf8Path>>,
     // List of titles and paths for all webpage fragment files;
     // for non-article pages like the site index and the "about" page
     pub fragments: Box<[Fragment]>,
     // Path to directory containing all articles
     pub articles_dir: Box<Utf8Path>,
-    // Name of theme for code syntax highlighting
-    pub code_theme: Box<str>,
+    // Absolute URL of the site root (e.g. `https://example.com`), for canonical links and other absolute URLs
+    #[serde(default)]
+    pub base_url: Option<Box<str>>,
+    // Absolute URL of another origin (e.g. a CDN) that images and fonts are served from;
+    // assets are served from the site's own host if absent
+    #[serde(default)]
+    pub asset_base_url: Option<Box<str>>,
+    // Name of built-in theme for code syntax highlighting
+    #[serde(default)]
+    pub code_theme: Option<Box<str>>,
+    // Path to `.tmTheme` file for code syntax highlighting; mutually exclusive with `code_theme`
+    #[serde(default)]
+    pub code_theme_file: Option<Box<Utf8Path>>,
+    // Name of built-in theme for code syntax highlighting in dark mode
+    #[serde(default)]
+    pub code_theme_dark: Option<Box<str>>,
+    // Path to `.tmTheme` file for code syntax highlighting in dark mode; mutually exclusive with `code_theme_dark`
+    #[serde(default)]
+    pub code_theme_dark_file: Option<Box<Utf8Path>>,
+    // How inline code segments are styled
+    #[serde(default)]
+    pub inline_code_style: InlineCodeStyle,
+    // Name of theme for inline code segments; `code_theme` is used if absent
+    #[serde(default)]
+    pub inline_code_theme: Option<Box<str>>,
+    // Number of spaces that tabs in the indentation of code blocks are replaced with
+    #[serde(default = "default_code_tab_width")]
+    pub code_tab_width: usize,
+    // Whether to visualize tabs and trailing whitespace in code blocks
+    #[serde(default)]
+    pub show_code_whitespace: bool,
+    // Map of code block language tokens to the names of syntaxes to highlight them with
+    #[serde(default)]
+    pub code_language_aliases: BTreeMap<Box<str>, Box<str>>,
+    // Whether to label code blocks with the name of their language
+    #[serde(default)]
+    pub code_language_labels: bool,
+    // Whether to add a button for copying code to every code block
+    #[serde(default)]
+    pub code_copy_buttons: bool,
+    // Maximum number of entries on the changelog page;
+    // no changelog page is generated if absent
+    #[serde(default)]
+    pub changelog_entries: Option<usize>,
+    // Whether to add a breadcrumb trail to every article page
+    #[serde(default)]
+    pub breadcrumbs: bool,
+    // Minimum age in years for articles to display an age warning;
+    // no age warnings are displayed if absent
+    #[serde(default)]
+    pub age_warning_years: Option<i16>,
+    // License applied to articles that do not specify their own
+    #[serde(default)]
+    pub default_license: Option<License>,
+    // Strategy for loading the KaTeX stylesheet on pages containing math
+    #[serde(default)]
+    pub katex_css_loading: KatexCssLoading,
+    // Browserslist queries selecting the browser versions that the site CSS is compiled for;
+    // a single query can be given as a string
+    #[serde(default = "default_css_targets", deserialize_with = "one_or_more")]
+    pub css_targets: Box<[Box<str>]>,
+    // Whether to inline the site CSS in every page instead of linking to the site CSS file
+    #[serde(default)]
+    pub inline_site_css: bool,
+    // Whether to remove site CSS rules that don't match any generated page from the site CSS file
+    #[serde(default)]
+    pub prune_site_css: bool,
+    // Strings that keep site CSS rules whose selectors contain them when pruning (e.g. classes added by scripts)
+    #[serde(default)]
+    pub prune_site_css_keep: Box<[Box<str>]>,
+    // KaTeX options applied to every math expression
+    #[serde(default)]
+    pub katex: KatexOptions,
+    // What `<build-stamp>` elements in templates are replaced with
+    #[serde(default)]
+    pub build_stamp: BuildStamp,
+    // Whether to build articles marked as drafts; can also be enabled with a command-line flag
+    #[serde(default)]
+    pub include_drafts: bool,
+    // Locale whose quotation marks are used for smart punctuation in articles
+    #[serde(default)]
+    pub quote_locale: QuoteLocale,
+    // Style of footnote labels in articles
+    #[serde(default)]
+    pub footnote_style: FootnoteStyle,
+    // What to do with a level-1 heading at the start of an article that duplicates the article title
+    #[serde(default)]
+    pub duplicate_title: DuplicateTitlePolicy,
+    // How to handle article headings that skip levels or use level 1, which is reserved for the title
+    #[serde(default)]
+    pub heading_outline: OutlinePolicy,
+    // What to do with root-relative links in generated pages that point to files that don't exist
+    #[serde(default)]
+    pub link_check: LinkCheckPolicy,
+    // Heading of a section at the end of every article that collects its footnote definitions;
+    // footnote definitions are left where they appear in articles if absent
+    #[serde(default)]
+    pub footnote_heading: Option<Box<str>>,
+    // Widths in pixels of smaller variants generated for converted images without a fixed display width;
+    // images are only converted at full size if empty
+    #[serde(default)]
+    pub image_widths: Box<[u32]>,
+    // Value of the `sizes` attribute of images with width variants; defaults to `100vw` if absent
+    #[serde(default)]
+    pub image_sizes: Option<Box<str>>,
+    // Format of fallback images saved next to converted AVIF images;
+    // no fallback images are saved if absent
+    #[serde(default)]
+    pub image_fallback: Option<FallbackFormat>,
+    // Maximum dimensions in pixels of converted images; larger images are downscaled to fit
+    #[serde(default)]
+    pub max_image_width: Option<u32>,
+    #[serde(default)]
+    pub max_image_height: Option<u32>,
+    // Whether images with https:// URLs as their source are downloaded and converted at build time
+    #[serde(default)]
+    pub remote_images: bool,
+    // Kind of placeholder shown in place of converted images while they load; none are shown if absent
+    #[serde(default)]
+    pub image_placeholder: Option<PlaceholderStyle>,
+    // Whether to show an excerpt of every article's first paragraph in article lists
+    #[serde(default)]
+    pub archive_excerpts: bool,
+    // Reading speed in words per minute for estimating reading times, which are shown on article pages
+    // and in article lists; no reading times are shown if absent
+    #[serde(default)]
+    pub reading_speed_wpm: Option<u32>,
+    // Whether to generate a JSON index of article contents for client-side search
+    #[serde(default)]
+    pub search_index: bool,
+    // Path to a file for writing a JSON manifest of build metrics;
+    // no manifest is written if absent
+    #[serde(default)]
+    pub build_report_file: Option<Box<Utf8Path>>,
+    // Path to a directory for data persisted between builds, like the dependency graph of the last build;
+    // nothing is persisted if absent
+    #[serde(default)]
+    pub cache_dir: Option<Box<Utf8Path>>,
+    // Whether to sync output files and directories to disk before the build finishes
+    #[serde(default)]
+    pub fsync_output: bool,
+    // Glob patterns for files to ignore when watching for changes, in addition to editor temporary files
+    #[serde(default)]
+    pub watch_ignore: Box<[Box<str>]>,
+    // Registry of HTML components that articles can use, keyed by name
+    #[serde(default)]
+    pub components: BTreeMap<Box<str>, Component>,
+    // Redirects from old URLs of the site, in addition to the aliases of articles
+    #[serde(default)]
+    pub redirects: Box<[Redirect]>,
+    // How redirects are emitted in the output directory
+    #[serde(default)]
+    pub redirect_style: RedirectStyle,
+    // Settings for syndication feeds of articles;
+    // no feeds are generated if absent
+    #[serde(default)]
+    pub feed: Option<FeedConfig>,
+    // Rules for web crawlers written to `robots.txt`;
+    // no robots file is generated if absent
+    #[serde(default)]
+    pub robots: Option<RobotsConfig>,
+    // Settings for the web app manifest, which every page links to;
+    // no manifest is generated if absent
+    #[serde(default)]
+    pub manifest: Option<ManifestConfig>,
+    // Origins and resources that every page hints browsers to connect to or fetch early
+    #[serde(default)]
+    pub resource_hints: ResourceHints,
+    // Path to a file for writing a profile of article build stages; only set with a command-line flag
+    #[serde(skip)]
+    pub profile_file: Option<Box<Utf8Path>>,
+    // Whether the site is checked instead of built, which skips downloading embed thumbnails; only set by `check`
+    #[serde(skip)]
+    pub offline: bool,
 }
 
 #[derive(Deserialize)]
 pub struct Fragment {
     pub title: Box<str>,
     pub path: Box<Utf8Path>,
+    // Whether to render LaTeX math between dollar signs in the fragment, like in articles
+    #[serde(default)]
+    pub math: bool,
+    // Paths to files containing HTML for named slots of the body template, keyed by slot name
+    #[serde(default)]
+    pub slots: BTreeMap<Box<str>, Box<Utf8Path>>,
 }
 
-impl Config {
-    /// Reads a config file from a path provided by command-line arguments.
+#[derive(Deserialize)]
+pub struct FeedConfig {
+    // Absolute URL of the site root, used for links in feeds; defaults to `base_url`
+    #[serde(default)]
+    pub site_url: Box<str>,
+    // Title of the feeds
+    pub title: Box<str>,
+    // Name of the author of all articles
+    pub author: Box<str>,
+    // Whether to generate an RSS 2.0 feed in addition to the Atom feed
+    #[serde(default)]
+    pub rss: bool,
+    // Whether to generate feeds for every tag, containing only the articles with that tag
+    #[serde(default)]
+    pub tags: bool,
+}
+
+/// Resource hints added to the `<head>` of every page.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ResourceHints {
+    // Origins (e.g. `https://tiles.example.com`) to connect to before any resource is requested from them
+    #[serde(default)]
+    pub preconnect: Box<[Box<str>]>,
+    // Origins to resolve the domain names of before any resource is requested from them
+    #[serde(default)]
+    pub dns_prefetch: Box<[Box<str>]>,
+    // URLs of JavaScript modules to fetch and parse before any script imports them
+    #[serde(default)]
+    pub modulepreload: Box<[Box<str>]>,
+}
+
+impl ResourceHints {
+    /// Validates the hints.
     ///
     /// # Errors
     /// This function returns an error if:
-    /// - not enough command-line arguments are provided
+    /// - a `preconnect` or `dns_prefetch` entry is not an HTTP(S) origin (a URL without a path, query, or fragment)
+    /// - a `modulepreload` entry is not a root-relative or absolute HTTP(S) URL
+    /// - an entry contains whitespace
+    fn validate(&self) -> Result<()> {
+        for (key, origin) in self
+            .preconnect
+            .iter()
+            .map(|origin| ("preconnect", origin))
+            .chain(
+                self.dns_prefetch
+                    .iter()
+                    .map(|origin| ("dns_prefetch", origin)),
+            )
+        {
+            let host = ["https://", "http://"]
+                .iter()
+                .find_map(|scheme| origin.strip_prefix(scheme))
+                .map(|rest| rest.strip_suffix('/').unwrap_or(rest));
+            if host.is_none_or(|host| {
+                host.is_empty()
+                    || host.contains(['/', '?', '#'])
+                    || host.contains(char::is_whitespace)
+            }) {
+                bail!(
+                    "`{key}`: must be an HTTP(S) origin like \"https://example.com\" (got \"{origin}\")"
+                );
+            }
+        }
+        for url in &self.modulepreload {
+            let is_root_relative = url.starts_with('/') && !url.starts_with("//");
+            let is_absolute = ["https://", "http://"]
+                .iter()
+                .any(|scheme| url.starts_with(scheme));
+            if !(is_root_relative || is_absolute) || url.contains(char::is_whitespace) {
+                bail!(
+                    "`modulepreload`: must be a root-relative or absolute HTTP(S) URL (got \"{url}\")"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A command for checking the site instead of building it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CheckCommand {
+    /// Check only the frontmatter of every article
+    Frontmatter,
+    /// Check the configuration and the content of every article, without writing any output
+    Site,
+}
+
+impl CheckCommand {
+    /// Returns the command-line argument that runs the check.
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Frontmatter => FRONTMATTER_CHECK_COMMAND,
+            Self::Site => CHECK_COMMAND,
+        }
+    }
+}
+
+/// Options provided as command-line arguments.
+pub struct CliArgs {
+    // Path to the config file
+    pub config_path: Box<Utf8Path>,
+    // Whether to build articles marked as drafts
+    pub include_drafts: bool,
+    // Whether to rebuild the site whenever its input files change
+    pub watch: bool,
+    // Path to a file for writing a profile of article build stages
+    pub profile_file: Option<Box<Utf8Path>>,
+    // Check to run instead of building the site
+    pub check: Option<CheckCommand>,
+}
+
+impl CliArgs {
+    /// Parses command-line arguments, which consist of an optional command, a config file path, and optional flags.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - no config file path is provided
     /// - too many command-line arguments are provided
+    /// - flags for building the site are combined with the `frontmatter-check` or `check` command
+    pub fn from_env() -> Result<Self> {
+        let mut config_path = None;
+        let mut include_drafts = false;
+        let mut watch = false;
+        let mut profile_file = None;
+        let mut check = None;
+
+        for (i, arg) in args().skip(1).enumerate() {
+            if i == 0 && arg == FRONTMATTER_CHECK_COMMAND {
+                check = Some(CheckCommand::Frontmatter);
+            } else if i == 0 && arg == CHECK_COMMAND {
+                check = Some(CheckCommand::Site);
+            } else if arg == INCLUDE_DRAFTS_FLAG {
+                include_drafts = true;
+            } else if arg == WATCH_FLAG {
+                watch = true;
+            } else if let Some(value) = arg.strip_prefix(PROFILE_BUILD_FLAG) {
+                let path = match value.strip_prefix('=') {
+                    Some(path) if !path.is_empty() => path,
+                    None if value.is_empty() => DEFAULT_PROFILE_FILE,
+                    _ => bail!("invalid argument: {arg}"),
+                };
+                profile_file = Some(Utf8Path::new(path).into());
+            } else if config_path.is_none() {
+                config_path = Some(arg);
+            } else {
+                bail!("too many input arguments were provided");
+            }
+        }
+
+        let Some(config_path) = config_path else {
+            bail!("configuration file path was not provided");
+        };
+
+        if let Some(check) = check
+            && (watch || profile_file.is_some())
+        {
+            bail!(
+                "`{}` cannot be combined with {WATCH_FLAG} or {PROFILE_BUILD_FLAG}",
+                check.name()
+            );
+        }
+
+        Ok(Self {
+            config_path: Utf8Path::new(&config_path).into(),
+            include_drafts,
+            watch,
+            profile_file,
+            check,
+        })
+    }
+}
+
+impl Config {
+    /// Reads a config file from the path provided by command-line arguments.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the config file cannot be read or parsed
     /// - a config parameter interpreted as a directory path does not point to a directory
     /// - a config parameter interpreted as a file path does not point to a file
     /// - multiple fragment paths point to the same file
     ///
     /// # Panics
     /// This function panics if the provided config file path has no parent.
-    pub fn from_env() -> Result<Self> {
-        // Get path to config file from command-line arguments
-        let mut args = args().skip(1);
-
-        let Some(config_path) = args.next() else {
-            bail!("configuration file path was not provided");
-        };
+    pub fn from_args(args: &CliArgs) -> Result<Self> {
+        Self::load(args, &RealFs)
+    }
 
-        if args.next().is_some() {
-            bail!("too many input arguments were provided");
-        }
+    /// Like [`Self::from_args()`], but reads the config file and checks input paths within a file system.
+    ///
+    /// # Errors
+    /// This function returns an error under the same conditions as [`Self::from_args()`].
+    ///
+    /// # Panics
+    /// This function panics if the provided config file path has no parent.
+    pub fn load(args: &CliArgs, vfs: &dyn Vfs) -> Result<Self> {
+        let config_path: &Utf8Path = &args.config_path;
 
         let mut config: Self = toml_from_str(
-            &read_to_string(&config_path)
+            &vfs.read_to_string(config_path)
                 .with_context(|| format!("failed to read configuration from {config_path}"))?,
         )
         .context("failed to parse configuration file")?;
 
+        config.include_drafts |= args.include_drafts;
+
+        if let Some(feed) = &mut config.feed
+            && feed.site_url.is_empty()
+            && let Some(base_url) = &config.base_url
+        {
+            feed.site_url.clone_from(base_url);
+        }
+        config.profile_file.clone_from(&args.profile_file);
+        config.offline = args.check.is_some();
+
         // Interpret relative paths in the config as relative to the config file's location
         transform_paths!(
             config,
             &config_path,
             [
                 output_dir,
-                site_css_file,
                 head_template_html_file,
                 body_template_html_file,
                 articles_dir
@@ -94,60 +501,297 @@ impl Config {
 
         for fragment in &mut config.fragments {
             transform_paths!(fragment, &config_path, [path]);
+            for path in fragment.slots.values_mut() {
+                *path = config_path
+                    .parent()
+                    .expect("config file path should have parent")
+                    .join(&**path)
+                    .into();
+            }
+        }
+
+        if let Some(manifest) = &mut config.manifest {
+            for icon in &mut manifest.icons {
+                transform_paths!(icon, &config_path, [path]);
+            }
+        }
+
+        for path in [
+            &mut config.build_report_file,
+            &mut config.cache_dir,
+            &mut config.fonts_dir,
+            &mut config.static_dir,
+            &mut config.templates_dir,
+            &mut config.code_theme_file,
+            &mut config.code_theme_dark_file,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(&mut config.site_css_files)
+        {
+            *path = config_path
+                .parent()
+                .expect("config file path should have parent")
+                .join(&**path)
+                .into();
         }
 
         // Validate config settings
-        config.validate().context("configuration file is invalid")?;
+        config
+            .validate(args.check.is_none(), vfs)
+            .context("configuration file is invalid")?;
 
         Ok(config)
     }
 
-    fn validate(&self) -> Result<()> {
-        if !THEME_NAMES.contains(&self.code_theme) {
-            bail!("`theme`: {} is an invalid theme name", self.code_theme);
-        } else if self.output_dir.is_dir() {
+    /// Returns the settings for converting images in articles.
+    #[must_use]
+    pub fn image_options(&self) -> ImageOptions {
+        ImageOptions {
+            fallback: self.image_fallback,
+            max_width: self.max_image_width,
+            max_height: self.max_image_height,
+            placeholder: self.image_placeholder,
+        }
+    }
+
+    /// Returns the paths of all Markdown files in the articles directory.
+    ///
+    /// # Errors
+    /// This function returns an error if the articles directory cannot be read.
+    pub fn article_paths(&self, vfs: &dyn Vfs) -> Result<Vec<Utf8PathBuf>> {
+        let mut paths = vfs
+            .files_in(&self.articles_dir)
+            .context("failed to access entry in articles directory")?;
+        paths.retain(|path| path.extension() == Some("md"));
+        Ok(paths)
+    }
+
+    /// Returns the generator of URLs for images and fonts, based on the asset base URL.
+    #[must_use]
+    pub fn asset_urls(&self) -> AssetUrls {
+        AssetUrls::new(self.asset_base_url.as_deref())
+    }
+
+    /// Returns the strategy for loading the KaTeX stylesheet on pages containing math,
+    /// or `None` if math is rendered as MathML only, which doesn't need the stylesheet.
+    #[must_use]
+    pub fn katex_css_loading(&self) -> Option<KatexCssLoading> {
+        self.katex
+            .output
+            .needs_stylesheet()
+            .then_some(self.katex_css_loading)
+    }
+
+    /// Validates config settings.
+    /// Checking that the output directory doesn't exist yet can be skipped when the site isn't being built.
+    /// Articles, the site CSS, templates, and fragments are looked up in `vfs`; other paths are always on disk.
+    fn validate(&self, check_output_dir: bool, vfs: &dyn Vfs) -> Result<()> {
+        if let Some(theme) = &self.code_theme
+            && !THEME_NAMES.contains(theme)
+        {
+            bail!("`code_theme`: {theme} is an invalid theme name");
+        } else if self.code_theme.is_some() == self.code_theme_file.is_some() {
+            bail!("exactly one of `code_theme` and `code_theme_file` must be set");
+        } else if let Some(theme) = &self.code_theme_dark
+            && !THEME_NAMES.contains(theme)
+        {
+            bail!("`code_theme_dark`: {theme} is an invalid theme name");
+        } else if self.code_theme_dark.is_some() && self.code_theme_dark_file.is_some() {
+            bail!("at most one of `code_theme_dark` and `code_theme_dark_file` can be set");
+        } else if let Some(theme) = &self.inline_code_theme
+            && !THEME_NAMES.contains(theme)
+        {
+            bail!("`inline_code_theme`: {theme} is an invalid theme name");
+        } else if self.code_tab_width == 0 {
+            bail!("`code_tab_width`: must be greater than 0");
+        } else if self.changelog_entries == Some(0) {
+            bail!("`changelog_entries`: must be greater than 0");
+        } else if self.age_warning_years.is_some_and(|years| years <= 0) {
+            bail!("`age_warning_years`: must be greater than 0");
+        } else if self.reading_speed_wpm == Some(0) {
+            bail!("`reading_speed_wpm`: must be greater than 0");
+        } else if self.image_widths.contains(&0) || !self.image_widths.is_sorted_by(|a, b| a < b) {
+            bail!("`image_widths`: must be positive and in strictly increasing order");
+        } else if self.max_image_width == Some(0) {
+            bail!("`max_image_width`: must be greater than 0");
+        } else if self.max_image_height == Some(0) {
+            bail!("`max_image_height`: must be greater than 0");
+        } else if self
+            .image_sizes
+            .as_ref()
+            .is_some_and(|sizes| sizes.trim().is_empty())
+        {
+            bail!("`image_sizes`: cannot be empty");
+        } else if self
+            .footnote_heading
+            .as_ref()
+            .is_some_and(|heading| heading.trim().is_empty())
+        {
+            bail!("`footnote_heading`: cannot be empty");
+        } else if self.css_targets.is_empty()
+            || self.css_targets.iter().any(|query| query.trim().is_empty())
+        {
+            bail!("`css_targets`: must be a non-empty list of non-empty queries");
+        } else if self.prune_site_css && self.inline_site_css {
+            bail!("`prune_site_css`: cannot be combined with `inline_site_css`");
+        } else if self.prune_site_css_keep.iter().any(|keep| keep.is_empty()) {
+            bail!("`prune_site_css_keep`: cannot contain empty strings");
+        } else if check_output_dir && vfs.is_dir(&self.output_dir) {
             bail!(
                 "`output_dir`: {} already exists as a directory",
                 self.output_dir
             );
-        } else if !self.articles_dir.is_dir() {
+        } else if self
+            .build_report_file
+            .as_ref()
+            .is_some_and(|path| vfs.is_dir(path))
+        {
+            bail!("`build_report_file`: points to an existing directory");
+        } else if self
+            .cache_dir
+            .as_ref()
+            .is_some_and(|path| vfs.is_file(path))
+        {
+            bail!("`cache_dir`: points to an existing file");
+        } else if !vfs.is_dir(&self.articles_dir) {
             bail!(
                 "`articles_dir`: {} could not be opened or does not point to a directory",
                 self.articles_dir
             );
-        } else if !self.site_css_file.is_file() {
-            bail!(
-                "`site_css_file`: {} could not be opened or does not point to a file",
-                self.site_css_file
-            );
-        } else if !self.head_template_html_file.is_file() {
+        } else if self.site_css_files.is_empty() {
+            bail!("`site_css_file`: cannot be an empty list");
+        } else if let Some(path) = self.site_css_files.iter().find(|path| !vfs.is_file(path)) {
+            bail!("`site_css_file`: {path} could not be opened or does not point to a file");
+        } else if let Some(path) = &self.fonts_dir
+            && !vfs.is_dir(path)
+        {
+            bail!("`fonts_dir`: {path} could not be opened or does not point to a directory");
+        } else if let Some(path) = &self.static_dir
+            && !vfs.is_dir(path)
+        {
+            bail!("`static_dir`: {path} could not be opened or does not point to a directory");
+        } else if let Some(path) = &self.templates_dir
+            && !vfs.is_dir(path)
+        {
+            bail!("`templates_dir`: {path} could not be opened or does not point to a directory");
+        } else if let Some(path) = &self.code_theme_file
+            && !vfs.is_file(path)
+        {
+            bail!("`code_theme_file`: {path} could not be opened or does not point to a file");
+        } else if let Some(path) = &self.code_theme_dark_file
+            && !vfs.is_file(path)
+        {
+            bail!("`code_theme_dark_file`: {path} could not be opened or does not point to a file");
+        } else if !vfs.is_file(&self.head_template_html_file) {
             bail!(
                 "`head_template_html_file`: {} could not be opened or does not point to a file",
                 self.head_template_html_file
             );
-        } else if !self.body_template_html_file.is_file() {
+        } else if !vfs.is_file(&self.body_template_html_file) {
             bail!(
                 "`body_template_html_file`: {} could not be opened or does not point to a file",
                 self.body_template_html_file
             );
         }
 
+        if let Some(url) = &self.base_url
+            && !["https://", "http://"]
+                .iter()
+                .any(|scheme| url.starts_with(scheme))
+        {
+            bail!("`base_url`: must be an absolute HTTP(S) URL");
+        } else if let Some(url) = &self.asset_base_url
+            && !["https://", "http://"]
+                .iter()
+                .any(|scheme| url.starts_with(scheme))
+        {
+            bail!("`asset_base_url`: must be an absolute HTTP(S) URL");
+        }
+
+        if let Some(feed) = &self.feed {
+            if !["https://", "http://"]
+                .iter()
+                .any(|scheme| feed.site_url.starts_with(scheme))
+            {
+                bail!("`feed.site_url`: must be an absolute HTTP(S) URL (or set `base_url`)");
+            } else if feed.title.is_empty() {
+                bail!("`feed.title`: must not be empty");
+            } else if feed.author.is_empty() {
+                bail!("`feed.author`: must not be empty");
+            }
+        }
+
+        self.resource_hints
+            .validate()
+            .context("`resource_hints`: hints are invalid")?;
+
+        if let Some(robots) = &self.robots {
+            robots.validate().context("`robots`: rules are invalid")?;
+        }
+
+        if let Some(manifest) = &self.manifest {
+            manifest
+                .validate(vfs)
+                .context("`manifest`: settings are invalid")?;
+        }
+
+        self.katex
+            .validate()
+            .context("`katex`: options are invalid")?;
+
+        if let Some(license) = &self.default_license {
+            license
+                .validate()
+                .context("`default_license`: license is invalid")?;
+        }
+
+        for pattern in &self.watch_ignore {
+            Pattern::new(pattern)
+                .with_context(|| format!("`watch_ignore`: {pattern} is an invalid pattern"))?;
+        }
+
+        for redirect in &self.redirects {
+            redirect.validate().with_context(|| {
+                format!("`redirects`: redirect from {} is invalid", redirect.from)
+            })?;
+        }
+
+        for (name, component) in &self.components {
+            component
+                .validate(name)
+                .with_context(|| format!("`components.{name}`: component is invalid"))?;
+        }
+
         // Validate `fragments` field
+        let mut fragment_handles = HashSet::with_capacity(self.fragments.len());
         let mut fragment_paths = HashSet::with_capacity(self.fragments.len());
 
         for fragment in &self.fragments {
             if fragment.path.file_stem().is_none_or(str::is_empty) {
                 bail!("`fragments`: empty file name found");
-            }
-
-            let handle = Handle::from_path(fragment.path.as_ref()).with_context(|| {
-                format!(
+            } else if !vfs.is_file(&fragment.path) {
+                bail!(
                     "`fragments`: {} could not be opened or does not point to a file",
                     fragment.path
-                )
-            })?;
+                );
+            }
+            for (name, path) in &fragment.slots {
+                if name.is_empty() {
+                    bail!("`fragments`: empty slot name found");
+                } else if !vfs.is_file(path) {
+                    bail!(
+                        "`fragments`: {path} (content of the slot \"{name}\") could not be opened or does not point to a file"
+                    );
+                }
+            }
 
-            if !fragment_paths.insert(handle) {
+            // Files on disk are compared by identity, so different paths to the same file are also caught
+            let is_duplicate = match Handle::from_path(fragment.path.as_ref()) {
+                Ok(handle) => !fragment_handles.insert(handle),
+                Err(_) => !fragment_paths.insert(&fragment.path),
+            };
+            if is_duplicate {
                 bail!("`fragments`: found multiple fragment paths pointing to the same file");
             }
         }
@@ -155,3 +799,147 @@ impl Config {
         Ok(())
     }
 }
+
+const fn default_code_tab_width() -> usize {
+    4
+}
+
+fn default_css_targets() -> Box<[Box<str>]> {
+    Box::new(["defaults".into()])
+}
+
+/// Deserializes either a single value or a list of values.
+fn one_or_more<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<Box<[T]>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMore<T> {
+        One(T),
+        More(Box<[T]>),
+    }
+
+    Ok(match OneOrMore::deserialize(deserializer)? {
+        OneOrMore::One(path) => Box::new([path]),
+        OneOrMore::More(paths) => paths,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CliArgs, Config, ResourceHints};
+    use crate::vfs::MemoryFs;
+    use camino::Utf8Path;
+
+    #[test]
+    fn load_from_memory() {
+        let config_text = r#"
+            output_dir = "out/"
+            site_css_file = "site.css"
+            head_template_html_file = "head.html"
+            body_template_html_file = "body.html"
+            fragments = [{ title = "Welcome", path = "fragments/index.html" }]
+            articles_dir = "articles/"
+            code_theme = "base16-mocha.dark"
+        "#;
+        let vfs = MemoryFs::new()
+            .with_file("/site/config.toml", config_text)
+            .with_file("/site/site.css", "")
+            .with_file("/site/head.html", "")
+            .with_file("/site/body.html", "")
+            .with_file("/site/fragments/index.html", "")
+            .with_file("/site/articles/post.md", "");
+        let args = CliArgs {
+            config_path: Utf8Path::new("/site/config.toml").into(),
+            include_drafts: false,
+            watch: false,
+            profile_file: None,
+            check: None,
+        };
+
+        let config = Config::load(&args, &vfs).unwrap();
+        assert_eq!(&*config.articles_dir, "/site/articles/");
+        assert_eq!(&*config.fragments[0].path, "/site/fragments/index.html");
+        assert_eq!(&*config.site_css_files[0], "/site/site.css");
+        assert_eq!(&*config.css_targets, [Box::from("defaults")]);
+
+        // Browser targets can be one query or a list of queries
+        let vfs = vfs.with_file(
+            "/site/config.toml",
+            format!("css_targets = \"last 2 versions\"\n{config_text}"),
+        );
+        let config = Config::load(&args, &vfs).unwrap();
+        assert_eq!(&*config.css_targets, [Box::from("last 2 versions")]);
+        let vfs = vfs.with_file(
+            "/site/config.toml",
+            format!("css_targets = []\n{config_text}"),
+        );
+        assert!(Config::load(&args, &vfs).is_err());
+
+        // Multiple site CSS files can be listed
+        let vfs = vfs
+            .with_file(
+                "/site/config.toml",
+                config_text.replace(r#""site.css""#, r#"["site.css", "extra.css"]"#),
+            )
+            .with_file("/site/extra.css", "");
+        let config = Config::load(&args, &vfs).unwrap();
+        assert_eq!(&*config.site_css_files[1], "/site/extra.css");
+        let vfs = vfs.with_file(
+            "/site/config.toml",
+            config_text.replace(r#""site.css""#, "[]"),
+        );
+        assert!(Config::load(&args, &vfs).is_err());
+
+        // Input paths are checked within the file system
+        let vfs = vfs.with_file(
+            "/site/config.toml",
+            config_text.replace("site.css", "a.css"),
+        );
+        assert!(Config::load(&args, &vfs).is_err());
+
+        // Feeds link to the site with the base URL unless they set their own
+        let feed_config = |base_url| {
+            format!(
+                "base_url = \"{base_url}\"\n{config_text}\n[feed]\ntitle = \"A\"\nauthor = \"B\"\n"
+            )
+        };
+        let vfs = vfs.with_file("/site/config.toml", feed_config("https://example.com"));
+        let config = Config::load(&args, &vfs).unwrap();
+        assert_eq!(
+            config.feed.unwrap().site_url.as_ref(),
+            "https://example.com"
+        );
+        let vfs = vfs.with_file("/site/config.toml", feed_config("example.com"));
+        assert!(Config::load(&args, &vfs).is_err());
+    }
+
+    #[test]
+    fn resource_hints() {
+        let hints = |preconnect: &str, modulepreload: &str| ResourceHints {
+            preconnect: Box::new([preconnect.into()]),
+            dns_prefetch: Box::new([]),
+            modulepreload: Box::new([modulepreload.into()]),
+        };
+
+        assert!(hints("https://example.com", "/app.js").validate().is_ok());
+        assert!(
+            hints("http://example.com:8080/", "https://example.com/app.mjs")
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            hints("https://example.com/a", "/app.js")
+                .validate()
+                .is_err()
+        );
+        assert!(hints("example.com", "/app.js").validate().is_err());
+        assert!(hints("https://", "/app.js").validate().is_err());
+        assert!(hints("https://example.com", "app.js").validate().is_err());
+        assert!(
+            hints("https://example.com", "//example.com/app.js")
+                .validate()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,124 @@
+//! Code for processing article cover images, which are shown in article lists and in link previews.
+
+#[cfg(feature = "images")]
+use crate::{
+    builder::Thumbnail,
+    image::{
+        ImageOptions, ImageVariants, OUTPUT_IMAGE_EXTENSION, convert_image_variants,
+        validate_image_src,
+    },
+    url::AssetUrls,
+};
+use crate::{config::Config, vfs::Vfs};
+#[cfg(feature = "images")]
+use anyhow::bail;
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+
+// Display width of cover image thumbnails in article lists, in CSS pixels
+#[cfg(feature = "images")]
+const THUMBNAIL_WIDTH: u32 = 96;
+
+// Extensions of cover images that are copied for link previews, in formats that social platforms display
+const SOCIAL_IMAGE_EXTENSIONS: [&str; 5] = ["jpg", "jpeg", "png", "gif", "webp"];
+// File stem of the copy of an article's cover image for link previews
+const SOCIAL_IMAGE_STEM: &str = "__social";
+
+/// Converts an article's cover image to a thumbnail for article lists,
+/// returning the thumbnail along with the total size of its variant files.
+///
+/// # Errors
+/// This function returns an error if the cover image source is invalid, is already an AVIF or SVG image,
+/// or cannot be converted.
+#[cfg(feature = "images")]
+pub fn convert_cover_thumbnail(
+    input_dir: &Utf8Path,
+    output_dir: &Utf8Path,
+    cover: &str,
+    slug: &str,
+    options: ImageOptions,
+    asset_urls: &AssetUrls,
+    vfs: &dyn Vfs,
+) -> Result<(Thumbnail, u64)> {
+    validate_image_src(cover)?;
+    if Utf8Path::new(cover)
+        .extension()
+        .is_some_and(|ext| ext == OUTPUT_IMAGE_EXTENSION || ext == "svg")
+    {
+        bail!("cover images must be in a format that can be converted to AVIF ({cover})");
+    }
+
+    let ImageVariants {
+        src,
+        srcset,
+        dimensions,
+        output_bytes,
+        ..
+    } = convert_image_variants(
+        input_dir,
+        output_dir,
+        cover,
+        THUMBNAIL_WIDTH,
+        // Thumbnails are small enough that placeholders aren't worth their size
+        ImageOptions {
+            placeholder: None,
+            ..options
+        },
+        vfs,
+    )?;
+
+    // Article lists are on other pages, so relative paths need to be made absolute
+    let srcset = srcset
+        .split(", ")
+        .map(|candidate| asset_urls.article_asset(slug, candidate))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Ok((
+        Thumbnail {
+            src: asset_urls.article_asset(slug, &src),
+            srcset,
+            dimensions,
+            fallback: options.fallback,
+        },
+        output_bytes,
+    ))
+}
+
+/// Copies an article's cover image to the article's output directory as is, for link previews on social platforms,
+/// which don't display AVIF images. Returns the URL of the copy, or `None` if the cover image is in a format that
+/// social platforms don't display. The URL is based on the asset base URL or, failing that, the site's base URL,
+/// so it is only root-relative if neither is configured.
+///
+/// # Errors
+/// This function returns an error if the cover image cannot be copied.
+pub fn copy_social_image(
+    input_dir: &Utf8Path,
+    output_dir: &Utf8Path,
+    cover: &str,
+    slug: &str,
+    config: &Config,
+    vfs: &dyn Vfs,
+) -> Result<Option<String>> {
+    let Some(extension) = Utf8Path::new(cover)
+        .extension()
+        .map(str::to_ascii_lowercase)
+        .filter(|ext| SOCIAL_IMAGE_EXTENSIONS.contains(&&**ext))
+    else {
+        return Ok(None);
+    };
+
+    let file_name = format!("{SOCIAL_IMAGE_STEM}.{extension}");
+    let (src, dst) = (input_dir.join(cover), output_dir.join(&file_name));
+    vfs.copy(&src, &dst)
+        .with_context(|| format!("failed to copy {src} to {dst}"))?;
+
+    let asset_urls = config.asset_urls();
+    let url = asset_urls.article_asset(slug, &file_name);
+    Ok(Some(match &config.base_url {
+        Some(base_url) if !asset_urls.is_cross_origin() => {
+            format!("{}{url}", base_url.trim_end_matches('/'))
+        }
+        _ => url,
+    }))
+}
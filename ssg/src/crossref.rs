@@ -0,0 +1,460 @@
+//! Code for numbering labeled figures and code listings in articles and resolving references to them.
+//!
+//! A figure is a paragraph containing only an image followed by a label, like `![Alt](a.png){#fig:id}`.
+//! A listing is a fenced code block with a label after its language, like ```` ```rust {#lst:id} ````.
+//! Labels can include a quoted caption (e.g. `{#lst:id "Reading a file"}`).
+//! Prose can reference labeled items with `[@fig:id]` and `[@lst:id]`.
+
+use crate::builder::{create_el, create_el_with_attrs, create_text, tree_to_html};
+use anyhow::{Result, anyhow, bail};
+use ego_tree::{Tree, tree};
+use foldhash::{HashMap, HashMapExt};
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use serde::Deserialize;
+
+const FIGURE_CLASS: &str = "__figure";
+const LISTING_CLASS: &str = "__listing";
+const INDEX_CLASS: &str = "__figure-index";
+const REFERENCE_CLASS: &str = "__crossref";
+
+/// Position of the index of figures and listings within an article.
+#[derive(Deserialize, Clone, Copy)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[serde(rename_all = "lowercase")]
+pub enum IndexPosition {
+    Top,
+    Bottom,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+enum LabelKind {
+    Figure,
+    Listing,
+}
+
+impl LabelKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Figure => "Figure",
+            Self::Listing => "Listing",
+        }
+    }
+}
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+struct Label<'a> {
+    kind: LabelKind,
+    id: &'a str,
+    caption: Option<&'a str>,
+}
+
+/// A numbered figure or listing.
+pub struct LabeledItem {
+    kind: LabelKind,
+    id: Box<str>,
+    number: usize,
+    caption: Box<str>,
+}
+
+impl LabeledItem {
+    /// Returns the opening tag of the `<figure>` element wrapping the item, as a string of HTML.
+    #[must_use]
+    pub fn opening_html(&self) -> String {
+        let class = match self.kind {
+            LabelKind::Figure => FIGURE_CLASS,
+            LabelKind::Listing => LISTING_CLASS,
+        };
+        // IDs only contain characters that don't need escaping (checked in `parse_label()`)
+        format!(r#"<figure id="{}" class="{class}">"#, self.id)
+    }
+
+    /// Returns the `<figcaption>` element for the item, as a string of HTML.
+    #[must_use]
+    pub fn caption_html(&self) -> String {
+        tree_to_html(tree! {
+            create_el("figcaption") => { create_text(&self.display_text()) }
+        })
+    }
+
+    fn title(&self) -> String {
+        format!("{} {}", self.kind.name(), self.number)
+    }
+
+    fn display_text(&self) -> String {
+        if self.caption.is_empty() {
+            self.title()
+        } else {
+            format!("{}: {}", self.title(), self.caption)
+        }
+    }
+}
+
+pub struct CrossReferences {
+    items: Vec<LabeledItem>,
+    // Maps the source offset of each labeled paragraph or code block to its index in `items`
+    offsets: HashMap<usize, usize>,
+}
+
+impl CrossReferences {
+    /// Collects and numbers all labeled figures and listings in an article, in order of appearance.
+    /// Figures and listings are numbered separately.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - a label is malformed or has the wrong kind (e.g. a `fig:` label on a code block)
+    /// - multiple items have the same label
+    pub fn collect(markdown: &str, options: Options) -> Result<Self> {
+        let mut items: Vec<LabeledItem> = Vec::new();
+        let mut offsets = HashMap::new();
+
+        // State for the current paragraph, if it might be a figure
+        let mut figure: Option<(usize, Label<'_>)> = None;
+        let mut image_title = CowStr::Borrowed("");
+        let mut alt_text = String::new();
+        let mut trailing_text = String::new();
+        let mut is_in_image = false;
+        let mut is_first_in_paragraph = false;
+
+        let mut add_item = |offset, label: Label<'_>, default_caption: &str| -> Result<()> {
+            if items.iter().any(|item| *item.id == *label.id) {
+                bail!("found duplicate label: {}", label.id);
+            }
+            let number = items.iter().filter(|item| item.kind == label.kind).count() + 1;
+            offsets.insert(offset, items.len());
+            items.push(LabeledItem {
+                kind: label.kind,
+                id: label.id.into(),
+                number,
+                caption: label.caption.unwrap_or(default_caption).into(),
+            });
+            Ok(())
+        };
+
+        for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+            let was_first_in_paragraph = is_first_in_paragraph;
+            is_first_in_paragraph = false;
+
+            match event {
+                Event::Start(Tag::Paragraph) => {
+                    let source = markdown[range.clone()].trim_end();
+                    figure = match source.rfind("{#") {
+                        Some(index) if source.starts_with("![") && source.ends_with('}') => {
+                            parse_label(&source[index..])?.map(|label| (range.start, label))
+                        }
+                        _ => None,
+                    };
+                    alt_text.clear();
+                    trailing_text.clear();
+                    is_first_in_paragraph = true;
+                }
+                // A figure paragraph must start with an image
+                Event::Start(Tag::Image { title, .. }) if was_first_in_paragraph => {
+                    image_title = title;
+                    is_in_image = true;
+                }
+                Event::End(TagEnd::Image) if is_in_image => is_in_image = false,
+                Event::Text(text) | Event::Code(text) if is_in_image => alt_text.push_str(&text),
+                Event::Text(text) if figure.is_some() => trailing_text.push_str(&text),
+                Event::SoftBreak if figure.is_some() => {}
+                Event::End(TagEnd::Paragraph) => {
+                    // The image must only be followed by the label
+                    let trailing_text = trailing_text.trim();
+                    if let Some((offset, label)) = figure.take()
+                        && trailing_text.starts_with("{#")
+                        && trailing_text.ends_with('}')
+                    {
+                        if label.kind != LabelKind::Figure {
+                            bail!("label on an image must start with `fig:` ({})", label.id);
+                        }
+                        let caption = if image_title.is_empty() {
+                            alt_text.trim()
+                        } else {
+                            &image_title
+                        };
+                        add_item(offset, label, caption)?;
+                    }
+                }
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                    if let Some(index) = info.find("{#")
+                        && let Some(label) = parse_label(info[index..].trim_end())?
+                    {
+                        if label.kind != LabelKind::Listing {
+                            bail!(
+                                "label on a code block must start with `lst:` ({})",
+                                label.id
+                            );
+                        }
+                        add_item(range.start, label, "")?;
+                    }
+                }
+                // Anything else in a paragraph means it isn't a figure
+                _ if !is_in_image => figure = None,
+                _ => {}
+            }
+        }
+
+        Ok(Self { items, offsets })
+    }
+
+    /// Returns the IDs of all labeled figures and listings.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.items.iter().map(|item| &*item.id)
+    }
+
+    /// Returns the labeled figure or listing whose paragraph or code block starts at the source offset.
+    #[must_use]
+    pub fn at(&self, offset: usize) -> Option<&LabeledItem> {
+        self.offsets.get(&offset).map(|&index| &self.items[index])
+    }
+
+    /// Replaces references like `[@fig:id]` in text with links to the referenced items.
+    /// This function returns `Ok(None)` if the text contains no references.
+    ///
+    /// # Errors
+    /// This function returns an error if a reference does not correspond to any label.
+    pub fn resolve_references<'a>(&self, text: &str) -> Result<Option<Vec<Event<'a>>>> {
+        if !text.contains("[@") {
+            return Ok(None);
+        }
+
+        let mut events = Vec::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find("[@") {
+            let Some(len) = rest[start..].find(']') else {
+                break;
+            };
+            let id = &rest[start + 2..start + len];
+
+            if !(id.starts_with("fig:") || id.starts_with("lst:")) {
+                // Not a reference, so keep the text as-is
+                events.push(Event::Text(rest[..start + 2].to_owned().into()));
+                rest = &rest[start + 2..];
+                continue;
+            }
+
+            let item = self
+                .items
+                .iter()
+                .find(|item| *item.id == *id)
+                .ok_or_else(|| anyhow!("found a reference to an unknown label: {id}"))?;
+
+            if start > 0 {
+                events.push(Event::Text(rest[..start].to_owned().into()));
+            }
+            let href = format!("#{}", item.id);
+            events.push(Event::InlineHtml(
+                tree_to_html(tree! {
+                    create_el_with_attrs("a", &[("href", &href), ("class", REFERENCE_CLASS)]) => {
+                        create_text(&item.title())
+                    }
+                })
+                .into(),
+            ));
+            rest = &rest[start + len + 1..];
+        }
+
+        if !rest.is_empty() {
+            events.push(Event::Text(rest.to_owned().into()));
+        }
+
+        Ok(Some(events))
+    }
+
+    /// Returns an index linking to every figure and listing, as a string of HTML.
+    /// This function returns `None` if there are no labeled items.
+    #[must_use]
+    pub fn index_html(&self) -> Option<String> {
+        if self.items.is_empty() {
+            return None;
+        }
+
+        let mut html = Tree::new(create_el_with_attrs(
+            "nav",
+            &[
+                ("class", INDEX_CLASS),
+                ("aria-label", "Figures and listings"),
+            ],
+        ));
+
+        for (kind, heading) in [
+            (LabelKind::Figure, "Figures"),
+            (LabelKind::Listing, "Listings"),
+        ] {
+            let mut items = self
+                .items
+                .iter()
+                .filter(|item| item.kind == kind)
+                .peekable();
+            if items.peek().is_none() {
+                continue;
+            }
+
+            let mut root = html.root_mut();
+            root.append_subtree(tree! {
+                create_el("h2") => { create_text(heading) }
+            });
+            let mut list = root.append(create_el("ol"));
+            for item in items {
+                let href = format!("#{}", item.id);
+                list.append_subtree(tree! {
+                    create_el("li") => {
+                        create_el_with_attrs("a", &[("href", &href)]) => { create_text(&item.display_text()) }
+                    }
+                });
+            }
+        }
+
+        Some(tree_to_html(html))
+    }
+}
+
+/// Removes a label (e.g. `{#lst:id}`) from a code block's info string, leaving the language.
+#[must_use]
+pub fn strip_label(info: &str) -> &str {
+    info.find("{#").map_or(info, |index| &info[..index]).trim()
+}
+
+/// Parses a label of the form `{#kind:id}` or `{#kind:id "caption"}`.
+/// This function returns `Ok(None)` if the input is not a label of a known kind.
+fn parse_label(input: &str) -> Result<Option<Label<'_>>> {
+    let Some(inner) = input
+        .strip_prefix("{#")
+        .and_then(|rest| rest.strip_suffix('}'))
+    else {
+        return Ok(None);
+    };
+
+    let (id, caption) = match inner.split_once(char::is_whitespace) {
+        Some((id, caption)) => {
+            let caption = caption
+                .trim()
+                .strip_prefix('"')
+                .and_then(|caption| caption.strip_suffix('"'))
+                .ok_or_else(|| anyhow!("label caption must be quoted ({id})"))?;
+            (id, Some(caption))
+        }
+        None => (inner, None),
+    };
+
+    let kind = if id.starts_with("fig:") {
+        LabelKind::Figure
+    } else if id.starts_with("lst:") {
+        LabelKind::Listing
+    } else {
+        return Ok(None);
+    };
+
+    let name = &id[4..];
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+    {
+        bail!("label name is invalid: \"{id}\"");
+    }
+
+    Ok(Some(Label { kind, id, caption }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CrossReferences, Label, LabelKind, LabeledItem, parse_label, strip_label};
+    use pulldown_cmark::{Event, Options};
+
+    #[test]
+    fn labels() {
+        assert_eq!(
+            parse_label("{#fig:a-b_1}").unwrap(),
+            Some(Label {
+                kind: LabelKind::Figure,
+                id: "fig:a-b_1",
+                caption: None
+            })
+        );
+        assert_eq!(
+            parse_label(r#"{#lst:x "Some code"}"#).unwrap(),
+            Some(Label {
+                kind: LabelKind::Listing,
+                id: "lst:x",
+                caption: Some("Some code")
+            })
+        );
+        assert_eq!(parse_label("{#tbl:x}").unwrap(), None);
+        assert_eq!(parse_label("{fig:x}").unwrap(), None);
+        assert!(parse_label("{#fig:}").is_err());
+        assert!(parse_label("{#fig:a.b}").is_err());
+        assert!(parse_label("{#fig:a caption}").is_err());
+
+        assert_eq!(strip_label("rust {#lst:x}"), "rust");
+        assert_eq!(strip_label("rust"), "rust");
+    }
+
+    #[test]
+    fn numbering() {
+        let markdown = r#"
+![First](a.png){#fig:a}
+
+```rust {#lst:a "Code"}
+```
+
+![Second](b.png "Title") {#fig:b}
+
+![Not a figure](c.png) text {#fig:c}
+
+See [@fig:b], [@lst:a], and [@other].
+"#;
+        let refs = CrossReferences::collect(markdown, Options::empty()).unwrap();
+
+        let captions: Vec<_> = refs.items.iter().map(LabeledItem::display_text).collect();
+        assert_eq!(
+            captions,
+            ["Figure 1: First", "Listing 1: Code", "Figure 2: Title"]
+        );
+        assert_eq!(
+            refs.at(1).unwrap().opening_html(),
+            r#"<figure id="fig:a" class="__figure">"#
+        );
+
+        let events = refs
+            .resolve_references("See [@fig:b], [@lst:a], and [@other].")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            events,
+            [
+                Event::Text("See ".into()),
+                Event::InlineHtml(r##"<a href="#fig:b" class="__crossref">Figure 2</a>"##.into()),
+                Event::Text(", ".into()),
+                Event::InlineHtml(r##"<a href="#lst:a" class="__crossref">Listing 1</a>"##.into()),
+                Event::Text(", and [@".into()),
+                Event::Text("other].".into()),
+            ]
+        );
+        assert!(refs.resolve_references("[@fig:c]").is_err());
+        assert_eq!(refs.resolve_references("no references").unwrap(), None);
+
+        let index = refs.index_html().unwrap();
+        assert!(
+            index
+                .contains(r##"<h2>Figures</h2><ol><li><a href="#fig:a">Figure 1: First</a></li>"##)
+        );
+        assert!(index.contains(
+            r##"<h2>Listings</h2><ol><li><a href="#lst:a">Listing 1: Code</a></li></ol>"##
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(CrossReferences::collect("![a](a.png){#lst:a}", Options::empty()).is_err());
+        assert!(CrossReferences::collect("```rust {#fig:a}\n```", Options::empty()).is_err());
+        assert!(
+            CrossReferences::collect(
+                "![a](a.png){#fig:a}\n\n![b](b.png){#fig:a}",
+                Options::empty()
+            )
+            .is_err()
+        );
+    }
+}
@@ -1,6 +1,9 @@
-//! Code for CSS minification and font dependency analysis.
+//! Code for Sass compilation, CSS minification and pruning, and font dependency analysis.
 
-use anyhow::{Context, Result};
+use crate::{builder::Font, url::AssetUrls, vfs::Vfs};
+use anyhow::{Context, Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
+use common::{OUTPUT_FONTS_DIR, OUTPUT_FONTS_DIR_ABSOLUTE};
 use lightningcss::{
     error::Error,
     printer::PrinterOptions,
@@ -10,39 +13,174 @@ use lightningcss::{
     },
     stylesheet::{MinifyOptions, ParserFlags, ParserOptions, StyleSheet},
     targets::{Browsers, Features, Targets},
-    traits::IntoOwned,
-    values::string::CowArcStr,
+    traits::ToCss,
 };
-use std::{collections::HashSet, hint::unreachable_unchecked};
+use scraper::{Html, Selector};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashSet,
+    fmt::{self, Debug, Formatter},
+    hint::unreachable_unchecked,
+    io,
+    path::Path,
+};
+
+// Extensions of stylesheets that are compiled from Sass (SCSS or indented syntax) to CSS
+const SASS_EXTENSIONS: [&str; 2] = ["sass", "scss"];
+// Pseudo-classes that depend on user interaction, which are ignored when matching selectors against pages
+const DYNAMIC_PSEUDO_CLASSES: [&str; 7] = [
+    "active",
+    "focus",
+    "focus-visible",
+    "focus-within",
+    "hover",
+    "target",
+    "visited",
+];
+// Pseudo-elements that can also be written with a single colon
+const LEGACY_PSEUDO_ELEMENTS: [&str; 4] = ["after", "before", "first-letter", "first-line"];
+// Classes of elements that scripts add to pages, which never appear in generated HTML
+const SCRIPT_CLASSES: [&str; 1] = ["__code-copy-button"];
+
+/// Reads a site stylesheet, returning its CSS and the paths of the files it was read from.
+/// Sass files (`.scss` or `.sass`) are compiled to CSS, resolving their imports (e.g. `@use "partials/colors"`)
+/// relative to the importing file, so the imported files are returned too.
+///
+/// # Errors
+/// This function returns an error if:
+/// - the file or one of its imports cannot be read
+/// - the Sass file cannot be compiled
+pub fn read_css_file(path: &Utf8Path, vfs: &dyn Vfs) -> Result<(String, Vec<Utf8PathBuf>)> {
+    if !path
+        .extension()
+        .is_some_and(|extension| SASS_EXTENSIONS.contains(&&*extension.to_lowercase()))
+    {
+        let css = vfs
+            .read_to_string(path)
+            .with_context(|| format!("failed to read CSS file at {path}"))?;
+        return Ok((css, vec![path.to_path_buf()]));
+    }
+
+    let fs = SassFs {
+        vfs,
+        read_files: RefCell::default(),
+    };
+    let css = grass::from_path(path, &grass::Options::default().fs(&fs))
+        .map_err(|error| anyhow!("{error}"))
+        .with_context(|| format!("failed to compile Sass file at {path}"))?;
+    Ok((css, fs.read_files.into_inner()))
+}
+
+/// A file system for the Sass compiler, which reads files from a `Vfs` and records the files it reads.
+struct SassFs<'a> {
+    vfs: &'a dyn Vfs,
+    read_files: RefCell<Vec<Utf8PathBuf>>,
+}
+
+impl Debug for SassFs<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SassFs")
+            .field("read_files", &self.read_files)
+            .finish_non_exhaustive()
+    }
+}
+
+impl grass::Fs for SassFs<'_> {
+    fn is_dir(&self, path: &Path) -> bool {
+        Utf8Path::from_path(path).is_some_and(|path| self.vfs.is_dir(path))
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        Utf8Path::from_path(path).is_some_and(|path| self.vfs.is_file(path))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let path = Utf8Path::from_path(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "path is not valid UTF-8")
+        })?;
+        let contents = self.vfs.read_to_string(path)?;
+        self.read_files.borrow_mut().push(path.to_path_buf());
+        Ok(contents.into_bytes())
+    }
+}
 
 /// Parses the input string as CSS. This function returns:
 /// - two minified CSS strings (one contains only the `@font-face` rules; one contains everything else)
 /// - a list of font dependencies (highest-priority sources only)
+/// - a list of the URLs of all font sources, as written in the input
 ///
-/// Output CSS is compatible with a set of "reasonable" target browser versions.
+/// Output CSS is compatible with the target browser versions selected by a list of
+/// [browserslist queries](https://browsersl.ist) (e.g. `["defaults"]` for a set of "reasonable" versions).
+/// Root-relative font URLs are rewritten to be served from the asset base URL, if one is configured.
 ///
 /// # Errors
 /// This function returns an error if:
+/// - the browserslist queries are invalid or don't select any browser versions
 /// - the input string cannot be successfully parsed as CSS
 /// - parsed stylesheet cannot be serialized to a string
 /// - parsed stylesheet cannot be minified for the target browser versions
+pub fn transform_css(
+    source: &str,
+    asset_urls: &AssetUrls,
+    browser_queries: &[impl AsRef<str>],
+) -> Result<CssOutput> {
+    let targets = browser_targets(browser_queries)?;
+    let (css, font_rules) = transform_stylesheet(source, targets)?;
+    finish_css_output(css, font_rules, asset_urls, targets)
+}
+
+/// Parses each of the input files as CSS, then bundles them in order into the same output as `transform_css()`.
+/// `@font-face` rules are extracted from every file, and identical rules in multiple files are only kept once,
+/// so fonts are only declared and preloaded once.
 ///
-/// # Panics
-/// This function panics if:
-/// - querying for the default set of target browser versions returns an error
-/// - the default set of target browser versions does not exist
-pub fn transform_css(source: &str) -> Result<CssOutput> {
-    // Determine target browser versions for stylesheet compilation
-    let targets = Targets {
-        browsers: Some(
-            Browsers::from_browserslist(["defaults"])
-                .expect("query for browserslist defaults should succeed")
-                .expect("browser targets should exist"),
-        ),
+/// # Errors
+/// This function returns an error if:
+/// - the browserslist queries are invalid or don't select any browser versions
+/// - one of the input strings cannot be successfully parsed as CSS
+/// - parsed stylesheets cannot be serialized to strings
+/// - parsed stylesheets cannot be minified for the target browser versions
+pub fn transform_css_files(
+    sources: &[(&Utf8Path, &str)],
+    asset_urls: &AssetUrls,
+    browser_queries: &[impl AsRef<str>],
+) -> Result<CssOutput> {
+    let targets = browser_targets(browser_queries)?;
+    let mut css = String::new();
+    let mut font_rules = Vec::new();
+    let mut font_rule_texts = HashSet::new();
+    for &(path, source) in sources {
+        let (file_css, file_font_rules) = transform_stylesheet(source, targets)
+            .with_context(|| format!("failed to transform CSS file at {path}"))?;
+        css.push_str(&file_css);
+        for rule in file_font_rules {
+            // Rules are compared without their source locations, which differ between files
+            let text = rule
+                .to_css_string(PrinterOptions::default())
+                .context("failed to serialize font CSS")?;
+            if font_rule_texts.insert(text) {
+                font_rules.push(rule);
+            }
+        }
+    }
+    finish_css_output(css, font_rules, asset_urls, targets)
+}
+
+/// Returns the target browser versions for stylesheet compilation, as selected by a list of browserslist queries.
+fn browser_targets(queries: &[impl AsRef<str>]) -> Result<Targets> {
+    let browsers = Browsers::from_browserslist(queries.iter().map(AsRef::as_ref))
+        .context("failed to resolve browserslist queries")?
+        .context("browserslist queries don't select any browser versions")?;
+    Ok(Targets {
+        browsers: Some(browsers),
         include: Features::empty(),
         exclude: Features::empty(),
-    };
+    })
+}
 
+/// Parses and minifies the input string as CSS, returning the minified CSS without `@font-face` rules
+/// and the extracted `@font-face` rules.
+fn transform_stylesheet(source: &str, targets: Targets) -> Result<(String, Vec<CssRule<'_>>)> {
     // Parse input as CSS
     let mut stylesheet = StyleSheet::parse(source, const { parser_options() })
         .map_err(Error::into_owned)
@@ -57,18 +195,52 @@ pub fn transform_css(source: &str) -> Result<CssOutput> {
         .context("failed to minify CSS")?;
 
     // Extract `@font-face` rules from the stylesheet
-    let font_rules: Vec<_> = stylesheet
+    let font_rules = stylesheet
         .rules
         .0
         .extract_if(.., |rule| matches!(rule, CssRule::FontFace(_)))
         .collect();
 
-    // Find the highest-priority source for each font in the stylesheet
+    let css = serialize_stylesheet(&stylesheet, targets).context("failed to serialize CSS")?;
+    Ok((css, font_rules))
+}
+
+/// Finds the font dependencies of `@font-face` rules and serializes the rules, after rewriting their URLs
+/// to be served from the asset base URL.
+fn finish_css_output(
+    css: String,
+    mut font_rules: Vec<CssRule<'_>>,
+    asset_urls: &AssetUrls,
+    targets: Targets,
+) -> Result<CssOutput> {
+    let font_urls = font_source_urls(&font_rules);
+
+    // Serve fonts from the asset base URL
+    for rule in &mut font_rules {
+        let CssRule::FontFace(font_rule) = rule else {
+            continue;
+        };
+        for property in &mut font_rule.properties {
+            let FontFaceProperty::Source(sources) = property else {
+                continue;
+            };
+            for source in sources {
+                if let Source::Url(url_src) = source
+                    && let Cow::Owned(url) = asset_urls.rewrite_root_relative(&url_src.url.url)
+                {
+                    url_src.url.url = url.into();
+                }
+            }
+        }
+    }
+
+    // Find the highest-priority source for each font in the stylesheet, preloading each file once
+    let mut top_font_paths = HashSet::new();
     let top_fonts = font_rules
         .iter()
         .flat_map(|rule| match rule {
             CssRule::FontFace(font_rule) => font_rule.properties.clone(),
-            // SAFETY: `rule` is guaranteed to match `CssRule::FontFace(_)` because of the earlier `Vec::extract_if()` call
+            // SAFETY: `rule` is guaranteed to match `CssRule::FontFace(_)` because of the `Vec::extract_if()` call in `transform_stylesheet()`
             _ => unsafe { unreachable_unchecked() },
         })
         .filter_map(|property| match property {
@@ -81,7 +253,7 @@ pub fn transform_css(source: &str) -> Result<CssOutput> {
             Source::Local(_) => None,
         })
         .map(|src| Font {
-            path: src.url.url.into_owned(),
+            path: src.url.url.as_ref().into(),
             mime: src.format.and_then(|format| match format {
                 FontFormat::WOFF2 => Some("font/woff2"),
                 FontFormat::WOFF => Some("font/woff"),
@@ -91,11 +263,9 @@ pub fn transform_css(source: &str) -> Result<CssOutput> {
                 _ => None,
             }),
         })
+        .filter(|font| top_font_paths.insert(font.path.clone()))
         .collect();
 
-    // Serialize stylesheets to strings
-    let css = serialize_stylesheet(&stylesheet, targets).context("failed to serialize CSS")?;
-
     let font_stylesheet = StyleSheet::new(
         Vec::new(),
         CssRuleList(font_rules),
@@ -108,9 +278,235 @@ pub fn transform_css(source: &str) -> Result<CssOutput> {
         css,
         font_css,
         top_fonts,
+        font_urls,
     })
 }
 
+/// Removes the style rules of transformed site CSS whose selectors don't match an element of any page,
+/// returning the pruned CSS and the number of removed rules. Selectors that don't match are removed from the
+/// selector lists of rules that are kept, and conditional rules (e.g. `@media`) left empty are removed too.
+///
+/// Selectors are matched while ignoring pseudo-elements and pseudo-classes that depend on user interaction
+/// (e.g. `a:hover::after` is matched as `a`). Rules are kept if one of their selectors:
+/// - contains one of the `keep` strings or the class of an element added by a script
+/// - cannot be matched against static HTML (e.g. `:checked` or nested selectors with `&`)
+///
+/// # Errors
+/// This function returns an error if:
+/// - the input string cannot be successfully parsed as CSS
+/// - pruned stylesheet cannot be serialized to a string
+pub fn prune_css(source: &str, pages: &[Html], keep: &[Box<str>]) -> Result<(String, usize)> {
+    let mut stylesheet = StyleSheet::parse(source, const { parser_options() })
+        .map_err(Error::into_owned)
+        .context("failed to parse input as valid CSS")?;
+
+    let is_used = |selector: &str| {
+        if keep
+            .iter()
+            .map(AsRef::as_ref)
+            .chain(SCRIPT_CLASSES)
+            .any(|keep| selector.contains(keep))
+        {
+            return true;
+        }
+        Selector::parse(&static_selector(selector)).map_or(true, |selector| {
+            pages
+                .iter()
+                .any(|page| page.select(&selector).next().is_some())
+        })
+    };
+    let removed = prune_rules(&mut stylesheet.rules, &is_used);
+
+    // The CSS was already compiled for the target browser versions
+    let css = serialize_stylesheet(&stylesheet, Targets::default())
+        .context("failed to serialize pruned CSS")?;
+    Ok((css, removed))
+}
+
+/// Removes unused selectors and rules from a list of rules, returning the number of removed rules.
+/// Rules nested in style rules are kept or removed with their parent rule.
+fn prune_rules(rules: &mut CssRuleList<'_>, is_used: &impl Fn(&str) -> bool) -> usize {
+    let mut removed = 0;
+    rules.0.retain_mut(|rule| {
+        let keep = match rule {
+            CssRule::Style(style_rule) => {
+                style_rule.selectors.0.retain(|selector| {
+                    selector
+                        .to_css_string(PrinterOptions::default())
+                        .map_or(true, |selector| is_used(&selector))
+                });
+                !style_rule.selectors.0.is_empty()
+            }
+            CssRule::Media(media_rule) => {
+                removed += prune_rules(&mut media_rule.rules, is_used);
+                !media_rule.rules.0.is_empty()
+            }
+            CssRule::Supports(supports_rule) => {
+                removed += prune_rules(&mut supports_rule.rules, is_used);
+                !supports_rule.rules.0.is_empty()
+            }
+            CssRule::Container(container_rule) => {
+                removed += prune_rules(&mut container_rule.rules, is_used);
+                !container_rule.rules.0.is_empty()
+            }
+            _ => true,
+        };
+        if !keep {
+            removed += 1;
+        }
+        keep
+    });
+    removed
+}
+
+/// Rewrites a selector so it can be matched against static HTML, by removing its pseudo-elements and
+/// pseudo-classes that depend on user interaction. Compound selectors that would be left empty become `*`.
+/// Only top-level pseudo-classes are removed, so e.g. `:not(:hover)` is left as is.
+fn static_selector(selector: &str) -> String {
+    let mut output = String::with_capacity(selector.len());
+    let mut chars = selector.chars().peekable();
+    let mut depth = 0_usize;
+    let mut quote = None;
+
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            output.push(c);
+            output.extend(chars.next());
+            continue;
+        }
+        if let Some(open_quote) = quote {
+            output.push(c);
+            if c == open_quote {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ':' if depth == 0 => {
+                let is_element = chars.next_if_eq(&':').is_some();
+                let mut name = String::new();
+                while let Some(c) =
+                    chars.next_if(|&c| c.is_alphanumeric() || matches!(c, '-' | '_'))
+                {
+                    name.push(c);
+                }
+                let arguments = if chars.next_if_eq(&'(').is_some() {
+                    pseudo_arguments(&mut chars)
+                } else {
+                    String::new()
+                };
+
+                let lowercase_name = name.to_lowercase();
+                if is_element
+                    || DYNAMIC_PSEUDO_CLASSES.contains(&&*lowercase_name)
+                    || LEGACY_PSEUDO_ELEMENTS.contains(&&*lowercase_name)
+                {
+                    if output.is_empty() || output.ends_with([' ', '>', '+', '~']) {
+                        output.push('*');
+                    }
+                } else {
+                    output.push(':');
+                    output.push_str(&name);
+                    output.push_str(&arguments);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        output.push(c);
+    }
+    output
+}
+
+/// Consumes the arguments of a functional pseudo-class or pseudo-element after its opening parenthesis,
+/// returning them with their parentheses.
+fn pseudo_arguments(chars: &mut impl Iterator<Item = char>) -> String {
+    let mut arguments = String::from("(");
+    let mut depth = 1_usize;
+    let mut quote = None;
+    while let Some(c) = chars.next() {
+        arguments.push(c);
+        if c == '\\' {
+            arguments.extend(chars.next());
+        } else if let Some(open_quote) = quote {
+            if c == open_quote {
+                quote = None;
+            }
+        } else if matches!(c, '"' | '\'') {
+            quote = Some(c);
+        } else if c == '(' {
+            depth += 1;
+        } else if c == ')' {
+            depth -= 1;
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+    arguments
+}
+
+/// Parses the input string as CSS, returning the URLs of all sources in its `@font-face` rules.
+///
+/// # Errors
+/// This function returns an error if the input string cannot be successfully parsed as CSS.
+pub(crate) fn font_urls(source: &str) -> Result<Vec<Box<str>>> {
+    let stylesheet = StyleSheet::parse(source, const { parser_options() })
+        .map_err(Error::into_owned)
+        .context("failed to parse input as valid CSS")?;
+    Ok(font_source_urls(&stylesheet.rules.0))
+}
+
+/// Returns the URLs of font sources that don't resolve to a file in the output fonts directory.
+/// URLs with a scheme (e.g. `https:` or `data:`) and protocol-relative URLs point elsewhere, so they are not checked;
+/// any other URL must be root-relative and name a file in the fonts directory (e.g. `/fonts/a.woff2`).
+pub(crate) fn missing_font_files<'a>(
+    urls: impl IntoIterator<Item = &'a str>,
+    output_dir: &Utf8Path,
+    vfs: &dyn Vfs,
+) -> Vec<&'a str> {
+    let fonts_dir = output_dir.join(OUTPUT_FONTS_DIR);
+    urls.into_iter()
+        .filter(|url| {
+            let has_scheme = url.split_once(':').is_some_and(|(scheme, _)| {
+                !scheme.is_empty()
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+            });
+            !has_scheme && !url.starts_with("//")
+        })
+        .filter(|url| {
+            url.strip_prefix(OUTPUT_FONTS_DIR_ABSOLUTE)
+                .is_none_or(|name| name.is_empty() || !vfs.is_file(&fonts_dir.join(name)))
+        })
+        .collect()
+}
+
+/// Returns the URLs of all sources in the `@font-face` rules of a list of rules.
+fn font_source_urls(rules: &[CssRule<'_>]) -> Vec<Box<str>> {
+    rules
+        .iter()
+        .filter_map(|rule| match rule {
+            CssRule::FontFace(font_rule) => Some(&font_rule.properties),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|property| match property {
+            FontFaceProperty::Source(sources) => Some(sources),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|src| match src {
+            Source::Url(url_src) => Some((*url_src.url.url).into()),
+            Source::Local(_) => None,
+        })
+        .collect()
+}
+
 const fn parser_options<'o, 'i>() -> ParserOptions<'o, 'i> {
     ParserOptions {
         // The source file path will be included higher in the error chain
@@ -143,42 +539,64 @@ pub struct CssOutput {
     pub css: String,
     pub font_css: String,
     pub top_fonts: Vec<Font>,
-}
-
-#[cfg_attr(test, derive(Debug, PartialEq))]
-pub struct Font {
-    pub(crate) path: CowArcStr<'static>,
-    pub(crate) mime: Option<&'static str>,
+    pub font_urls: Vec<Box<str>>,
 }
 
 #[cfg(test)]
 mod test {
-    use super::{CssOutput, Font, transform_css};
+    use super::{
+        CssOutput, font_urls, missing_font_files, prune_css, read_css_file, static_selector,
+        transform_css, transform_css_files,
+    };
+    use crate::{builder::Font, url::AssetUrls, vfs::MemoryFs};
+    use camino::{Utf8Path, Utf8PathBuf};
+    use scraper::Html;
+
+    const BROWSERS: [&str; 1] = ["defaults"];
 
     #[test]
     fn no_fonts() {
         assert_eq!(
-            transform_css("p { font-size: 1em }").expect("CSS transformation should succeed"),
+            transform_css("p { font-size: 1em }", &AssetUrls::default(), &BROWSERS)
+                .expect("CSS transformation should succeed"),
             CssOutput {
                 css: "p{font-size:1em}".into(),
                 font_css: String::new(),
-                top_fonts: vec![]
+                top_fonts: vec![],
+                font_urls: vec![]
             }
         );
     }
 
+    #[test]
+    fn invalid_browser_queries() {
+        assert!(
+            transform_css(
+                "p { color: red }",
+                &AssetUrls::default(),
+                &["invalid query"]
+            )
+            .is_err()
+        );
+    }
+
     #[test]
     fn one_font() {
         assert_eq!(
-            transform_css("@font-face { src: url('foo.bin') format('woff2'); }")
-                .expect("CSS transformation should succeed"),
+            transform_css(
+                "@font-face { src: url('foo.bin') format('woff2'); }",
+                &AssetUrls::default(),
+                &BROWSERS
+            )
+            .expect("CSS transformation should succeed"),
             CssOutput {
                 css: String::new(),
                 font_css: "@font-face{src:url(foo.bin)format(\"woff2\")}".into(),
                 top_fonts: vec![Font {
                     path: "foo.bin".into(),
                     mime: Some("font/woff2")
-                }]
+                }],
+                font_urls: vec!["foo.bin".into()]
             }
         );
     }
@@ -186,7 +604,7 @@ mod test {
     #[test]
     fn multiple_fonts() {
         assert_eq!(
-            transform_css("@font-face { src: url('foo.bin') format('woff'), url('bar.bin') format('ttf'); } @font-face { src: url('baz.bin'); }")
+            transform_css("@font-face { src: url('foo.bin') format('woff'), url('bar.bin') format('ttf'); } @font-face { src: url('baz.bin'); }", &AssetUrls::default(), &BROWSERS)
                 .expect("CSS transformation should succeed"),
             CssOutput {
                 css: String::new(),
@@ -197,8 +615,156 @@ mod test {
                 }, Font {
                     path: "baz.bin".into(),
                     mime: None
-                }]
+                }],
+                font_urls: vec!["foo.bin".into(), "bar.bin".into(), "baz.bin".into()]
+            }
+        );
+    }
+
+    #[test]
+    fn asset_base_url() {
+        assert_eq!(
+            transform_css(
+                "@font-face { src: url('/fonts/foo.woff2') format('woff2'), url('bar.woff'); } p { background: url('/a.png') }",
+                &AssetUrls::new(Some("https://cdn.example.com")),
+                &BROWSERS
+            )
+            .expect("CSS transformation should succeed"),
+            CssOutput {
+                css: "p{background:url(/a.png)}".into(),
+                font_css: "@font-face{src:url(https://cdn.example.com/fonts/foo.woff2)format(\"woff2\"),url(bar.woff)}".into(),
+                top_fonts: vec![Font {
+                    path: "https://cdn.example.com/fonts/foo.woff2".into(),
+                    mime: Some("font/woff2")
+                }],
+                font_urls: vec!["/fonts/foo.woff2".into(), "bar.woff".into()]
+            }
+        );
+    }
+
+    #[test]
+    fn sass() {
+        let vfs = MemoryFs::new()
+            .with_file(
+                "/site/site.scss",
+                "@use \"partials/colors\";\n.a { color: colors.$accent; .b { margin: 0 } }",
+            )
+            .with_file("/site/partials/_colors.scss", "$accent: red;")
+            .with_file("/site/plain.css", "p { color: red }");
+
+        let (css, inputs) = read_css_file(Utf8Path::new("/site/site.scss"), &vfs).unwrap();
+        assert_eq!(
+            transform_css(&css, &AssetUrls::default(), &BROWSERS)
+                .unwrap()
+                .css,
+            ".a{color:red}.a .b{margin:0}"
+        );
+        assert_eq!(
+            inputs,
+            [
+                Utf8PathBuf::from("/site/site.scss"),
+                Utf8PathBuf::from("/site/partials/_colors.scss")
+            ]
+        );
+
+        let (css, inputs) = read_css_file(Utf8Path::new("/site/plain.css"), &vfs).unwrap();
+        assert_eq!(css, "p { color: red }");
+        assert_eq!(inputs, [Utf8PathBuf::from("/site/plain.css")]);
+
+        let vfs = vfs.with_file("/site/site.scss", "@use \"missing\";");
+        assert!(read_css_file(Utf8Path::new("/site/site.scss"), &vfs).is_err());
+    }
+
+    #[test]
+    fn multiple_files() {
+        let font = "@font-face { font-family: A; src: url('/fonts/a.woff2') format('woff2') }";
+        let output = transform_css_files(
+            &[
+                (
+                    Utf8Path::new("a.css"),
+                    &format!("{font} p {{ color: red }}"),
+                ),
+                (
+                    Utf8Path::new("b.css"),
+                    &format!("{font} a {{ color: blue }}"),
+                ),
+            ],
+            &AssetUrls::default(),
+            &BROWSERS,
+        )
+        .expect("CSS transformation should succeed");
+        assert_eq!(
+            output,
+            CssOutput {
+                css: "p{color:red}a{color:#00f}".into(),
+                font_css: "@font-face{font-family:A;src:url(/fonts/a.woff2)format(\"woff2\")}"
+                    .into(),
+                top_fonts: vec![Font {
+                    path: "/fonts/a.woff2".into(),
+                    mime: Some("font/woff2")
+                }],
+                font_urls: vec!["/fonts/a.woff2".into()]
             }
         );
+        assert!(
+            transform_css_files(
+                &[(Utf8Path::new("a.css"), "p[ { color: red }")],
+                &AssetUrls::default(),
+                &BROWSERS
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn prune() {
+        let pages = [
+            Html::parse_document(r#"<p class="a">A</p>"#),
+            Html::parse_document(
+                r#"<ul><li><a href="/">B</a></li></ul><pre data-copy-code></pre>"#,
+            ),
+        ];
+        let css = ".a,.b{color:red}.c{color:red}a:hover::after{content:\"\"}@media (width>=40em){.c{color:red}}\
+            @media print{li>a{color:red}}:checked{color:red}.__code-copy-button,.keep-me{color:red}";
+        assert_eq!(
+            prune_css(css, &pages, &["keep-me".into()]).expect("CSS pruning should succeed"),
+            (
+                ".a{color:red}a:hover:after{content:\"\"}@media print{li>a{color:red}}:checked{color:red}\
+                .__code-copy-button,.keep-me{color:red}"
+                    .into(),
+                3
+            )
+        );
+    }
+
+    #[test]
+    fn static_selectors() {
+        assert_eq!(static_selector("a:hover::after"), "a");
+        assert_eq!(static_selector(":focus-visible"), "*");
+        assert_eq!(static_selector("ul>:hover"), "ul>*");
+        assert_eq!(static_selector("p:first-child:before"), "p:first-child");
+        assert_eq!(static_selector("::part(label):hover"), "*");
+        assert_eq!(static_selector("a:not(:hover)"), "a:not(:hover)");
+        assert_eq!(
+            static_selector(r#"[title=":hover"]"#),
+            r#"[title=":hover"]"#
+        );
+        assert_eq!(static_selector(r".a\:hover"), r".a\:hover");
+    }
+
+    #[test]
+    fn missing_fonts() {
+        let vfs = MemoryFs::new().with_file("/out/fonts/a.woff2", "");
+
+        let urls = font_urls(
+            "@font-face { src: url('/fonts/a.woff2'), url('/fonts/b.woff2'), url('/font/a.woff2'), url('a.woff2'), local(A) } \
+            @font-face { src: url('https://example.com/c.woff2'), url('//example.com/d.woff2'), url('data:font/woff2;base64,AA') }",
+        )
+        .unwrap();
+        assert_eq!(urls.len(), 7);
+        assert_eq!(
+            missing_font_files(urls.iter().map(AsRef::as_ref), Utf8Path::new("/out"), &vfs),
+            ["/fonts/b.woff2", "/font/a.woff2", "a.woff2"]
+        );
     }
 }
@@ -0,0 +1,169 @@
+//! Code for tracking which input files every output file of a site is generated from.
+
+use crate::vfs::Vfs;
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::ErrorKind,
+};
+
+/// A mapping from output files to the input files they depend on.
+///
+/// Input paths are stored in canonical form, so they can be compared with paths reported by file system watchers.
+#[derive(Serialize, Deserialize, Default)]
+pub struct DependencyGraph {
+    // Input files of every output file, keyed by output path relative to the output directory
+    outputs: BTreeMap<Box<str>, BTreeSet<Utf8PathBuf>>,
+}
+
+impl DependencyGraph {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that an output file depends on the given input files.
+    pub fn add<P: AsRef<Utf8Path>>(&mut self, output: &str, inputs: impl IntoIterator<Item = P>) {
+        let dependencies = self.outputs.entry(output.into()).or_default();
+
+        for input in inputs {
+            let input = input.as_ref();
+            // Inputs that cannot be resolved are kept as-is, since they could still be created later
+            dependencies.insert(
+                input
+                    .canonicalize_utf8()
+                    .unwrap_or_else(|_| input.to_path_buf()),
+            );
+        }
+    }
+
+    /// Checks if an output file (relative to the output directory) is in the graph.
+    #[must_use]
+    pub fn contains_output(&self, output: &str) -> bool {
+        self.outputs.contains_key(output)
+    }
+
+    /// Checks if any output file depends on the given input file.
+    #[must_use]
+    pub fn contains_input(&self, input: &Utf8Path) -> bool {
+        self.outputs
+            .values()
+            .any(|dependencies| dependencies.contains(input))
+    }
+
+    /// Returns every input file that any output file depends on.
+    #[must_use]
+    pub fn inputs(&self) -> BTreeSet<&Utf8Path> {
+        self.outputs.values().flatten().map(AsRef::as_ref).collect()
+    }
+
+    /// Returns the outputs that depend on any of the given (canonical) input paths.
+    #[must_use]
+    pub fn affected_outputs<'a>(
+        &self,
+        changed: impl IntoIterator<Item = &'a Utf8Path>,
+    ) -> BTreeSet<&str> {
+        let changed: BTreeSet<_> = changed.into_iter().collect();
+
+        self.outputs
+            .iter()
+            .filter(|(_, dependencies)| {
+                dependencies
+                    .iter()
+                    .any(|input| changed.contains(input.as_path()))
+            })
+            .map(|(output, _)| &**output)
+            .collect()
+    }
+
+    /// Returns the outputs of a previous build that this graph no longer has (e.g. pages of deleted articles).
+    #[must_use]
+    pub fn removed_outputs<'a>(&self, previous: &'a Self) -> Vec<&'a str> {
+        previous
+            .outputs
+            .keys()
+            .filter(|output| !self.outputs.contains_key(*output))
+            .map(AsRef::as_ref)
+            .collect()
+    }
+
+    /// Reads a dependency graph persisted by a previous build.
+    /// This function returns `Ok(None)` if no graph has been persisted at the path.
+    ///
+    /// # Errors
+    /// This function returns an error if the file exists but cannot be read or parsed.
+    pub fn load(path: &Utf8Path, vfs: &dyn Vfs) -> Result<Option<Self>> {
+        match vfs.read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .map(Some)
+                .with_context(|| format!("failed to parse dependency graph at {path}")),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("failed to read dependency graph at {path}")),
+        }
+    }
+
+    /// Serializes the graph as JSON.
+    ///
+    /// # Errors
+    /// This function returns an error if the graph cannot be serialized.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("failed to serialize dependency graph")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DependencyGraph;
+    use camino::Utf8Path;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn affected_outputs() {
+        let mut graph = DependencyGraph::new();
+        graph.add("index.html", ["/site/index.html", "/site/body.html"]);
+        graph.add(
+            "writing/a/index.html",
+            [
+                "/site/articles/a/a.md",
+                "/site/articles/a/cat.png",
+                "/site/body.html",
+            ],
+        );
+        graph.add("writing/index.html", ["/site/articles/a/a.md"]);
+
+        let affected =
+            |paths: &[&str]| graph.affected_outputs(paths.iter().map(|&path| Utf8Path::new(path)));
+        assert_eq!(
+            affected(&["/site/body.html"]),
+            BTreeSet::from(["index.html", "writing/a/index.html"])
+        );
+        assert_eq!(
+            affected(&["/site/articles/a/a.md"]),
+            BTreeSet::from(["writing/a/index.html", "writing/index.html"])
+        );
+        assert_eq!(
+            affected(&["/site/articles/a/cat.png"]),
+            BTreeSet::from(["writing/a/index.html"])
+        );
+        assert!(affected(&["/site/site.css"]).is_empty());
+
+        assert!(graph.contains_output("writing/index.html"));
+        assert!(!graph.contains_output("writing/"));
+        assert!(graph.contains_input(Utf8Path::new("/site/articles/a/cat.png")));
+        assert!(!graph.contains_input(Utf8Path::new("/site/articles/b/b.md")));
+        assert_eq!(graph.inputs().len(), 4);
+
+        let mut rebuilt = DependencyGraph::new();
+        rebuilt.add("index.html", ["/site/index.html", "/site/body.html"]);
+        rebuilt.add("writing/b/index.html", ["/site/articles/b/b.md"]);
+        assert_eq!(
+            rebuilt.removed_outputs(&graph),
+            ["writing/a/index.html", "writing/index.html"]
+        );
+
+        let reloaded: DependencyGraph = serde_json::from_str(&graph.to_json().unwrap()).unwrap();
+        assert_eq!(reloaded.outputs, graph.outputs);
+    }
+}
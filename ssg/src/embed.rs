@@ -0,0 +1,374 @@
+//! Utility for rendering privacy-preserving previews of third-party embeds.
+//! Instead of `<iframe>`s, embeds are rendered as static previews linking to the original content.
+
+use crate::{
+    builder::{create_el, create_el_with_attrs, create_text, tree_to_html},
+    cache::stable_hash,
+    image::{OUTPUT_IMAGE_EXTENSION, convert_image_bytes},
+    shortcode::Shortcode,
+    url::AssetUrls,
+    vfs::Vfs,
+};
+use anyhow::{Context, Result, bail};
+use camino::Utf8Path;
+use ego_tree::{Tree, tree};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use std::{sync::OnceLock, time::Duration};
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+// Subdirectory of the cache directory containing downloaded video thumbnails
+const THUMBNAILS_DIR: &str = "embeds";
+
+/// A third-party embed, rendered as a static preview instead of an `<iframe>`.
+pub enum Embed<'a> {
+    YouTube { id: &'a str, title: Option<&'a str> },
+    Vimeo { id: &'a str, title: Option<&'a str> },
+    Tweet { url: &'a str, text: Option<&'a str> },
+}
+
+impl<'a> Embed<'a> {
+    /// Interprets a shortcode as an embed.
+    /// This function returns `Ok(None)` if the shortcode name does not correspond to an embed kind.
+    /// Supported shortcodes are:
+    /// - `{{ youtube id="..." title="..." }}`
+    /// - `{{ vimeo id="..." title="..." }}`
+    /// - `{{ tweet url="..." text="..." }}`
+    ///
+    /// # Errors
+    /// This function returns an error if the shortcode has missing, unknown, or invalid arguments.
+    pub fn from_shortcode(shortcode: &Shortcode<'a>) -> Result<Option<Self>> {
+        let embed = match shortcode.name {
+            "youtube" => {
+                shortcode.expect_args(&["id", "title"])?;
+                let id = shortcode.require("id")?;
+                if id.is_empty()
+                    || !id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+                {
+                    bail!("YouTube video ID is invalid: \"{id}\"");
+                }
+                Self::YouTube {
+                    id,
+                    title: shortcode.get("title"),
+                }
+            }
+            "vimeo" => {
+                shortcode.expect_args(&["id", "title"])?;
+                let id = shortcode.require("id")?;
+                if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+                    bail!("Vimeo video ID is invalid: \"{id}\"");
+                }
+                Self::Vimeo {
+                    id,
+                    title: shortcode.get("title"),
+                }
+            }
+            "tweet" => {
+                shortcode.expect_args(&["url", "text"])?;
+                let url = shortcode.require("url")?;
+                if !["https://twitter.com/", "https://x.com/"]
+                    .iter()
+                    .any(|prefix| url.starts_with(prefix))
+                {
+                    bail!("tweet URL must start with https://twitter.com/ or https://x.com/");
+                }
+                Self::Tweet {
+                    url,
+                    text: shortcode.get("text"),
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(embed))
+    }
+
+    /// Consumes the embed, returning a static preview as a string of HTML for the page of the article with `slug`.
+    /// Video thumbnails are converted to AVIF and saved to `output_dir`. Thumbnails are downloaded
+    /// unless they are stored in `cache_dir`, where downloaded thumbnails are stored for later builds.
+    /// Without `download`, thumbnails are never downloaded, and previews of videos without a stored thumbnail
+    /// have no image (e.g. when checking the site).
+    ///
+    /// # Errors
+    /// This function returns an error if a thumbnail cannot be downloaded, converted, or saved.
+    pub fn into_html(
+        self,
+        output_dir: &Utf8Path,
+        slug: &str,
+        asset_urls: &AssetUrls,
+        cache_dir: Option<&Utf8Path>,
+        download: bool,
+        vfs: &dyn Vfs,
+    ) -> Result<String> {
+        let (kind, id, href, title, label) = match self {
+            Self::YouTube { id, title } => (
+                "youtube",
+                id,
+                format!("https://www.youtube.com/watch?v={id}"),
+                title,
+                "Watch on YouTube",
+            ),
+            Self::Vimeo { id, title } => (
+                "vimeo",
+                id,
+                format!("https://vimeo.com/{id}"),
+                title,
+                "Watch on Vimeo",
+            ),
+            Self::Tweet { url, text } => {
+                let mut html = Tree::new(create_el_with_attrs(
+                    "blockquote",
+                    &[("class", "__embed __embed-tweet")],
+                ));
+                let mut root = html.root_mut();
+                if let Some(text) = text {
+                    root.append_subtree(tree! {
+                        create_el("p") => { create_text(text) }
+                    });
+                }
+                root.append_subtree(tree! {
+                    create_el_with_attrs("a", &[("href", url)]) => { create_text("View post on X") }
+                });
+                return Ok(tree_to_html(html));
+            }
+        };
+
+        let class = format!("__embed __embed-{kind}");
+        let mut html = Tree::new(create_el_with_attrs("figure", &[("class", &class)]));
+        let mut root = html.root_mut();
+        let mut link = root.append(create_el_with_attrs("a", &[("href", &href)]));
+
+        let thumbnail_url = || match kind {
+            "vimeo" => {
+                fetch_vimeo_thumbnail_url(id).context("failed to find Vimeo video thumbnail")
+            }
+            _ => Ok(format!("https://i.ytimg.com/vi/{id}/hqdefault.jpg")),
+        };
+        if let Some(thumbnail) = load_thumbnail(&href, thumbnail_url, cache_dir, download, vfs)? {
+            let file_name = format!("embed-{kind}-{id}.{OUTPUT_IMAGE_EXTENSION}");
+            let dimensions = convert_image_bytes(&thumbnail, &output_dir.join(&file_name), vfs)
+                .context("failed to process thumbnail")?;
+            let (width, height) = (dimensions.width.to_string(), dimensions.height.to_string());
+            let src = asset_urls.article_local_asset(slug, &file_name);
+            link.append(create_el_with_attrs(
+                "img",
+                &[
+                    ("src", &src),
+                    ("alt", title.unwrap_or("Video thumbnail")),
+                    ("decoding", "async"),
+                    ("loading", "lazy"),
+                    ("width", &width),
+                    ("height", &height),
+                ],
+            ));
+        }
+        link.append_subtree(tree! {
+            create_el_with_attrs("span", &[("class", "__embed-label")]) => { create_text(label) }
+        });
+
+        Ok(tree_to_html(html))
+    }
+}
+
+/// Returns the thumbnail of the video at `video_url` stored in `cache_dir`, or if there is none,
+/// downloads it from the URL returned by `thumbnail_url` and stores it in `cache_dir`.
+/// Thumbnails are keyed by the video URL, so finding the thumbnail URL (which may need a request) is skipped too.
+/// This function returns `Ok(None)` if the thumbnail is not stored and `download` is `false`.
+fn load_thumbnail(
+    video_url: &str,
+    thumbnail_url: impl FnOnce() -> Result<String>,
+    cache_dir: Option<&Utf8Path>,
+    download: bool,
+    vfs: &dyn Vfs,
+) -> Result<Option<Vec<u8>>> {
+    let cache_path = cache_dir.map(|cache_dir| {
+        cache_dir
+            .join(THUMBNAILS_DIR)
+            .join(stable_hash(&[video_url.as_bytes()]))
+    });
+    if let Some(path) = &cache_path
+        && vfs.is_file(path)
+    {
+        return vfs
+            .read(path)
+            .map(Some)
+            .with_context(|| format!("failed to read cached thumbnail at {path}"));
+    }
+    if !download {
+        return Ok(None);
+    }
+
+    let thumbnail_url = thumbnail_url()?;
+    let thumbnail = fetch(&thumbnail_url)
+        .with_context(|| format!("failed to download thumbnail from {thumbnail_url}"))?;
+    if let Some(path) = &cache_path {
+        if let Some(parent) = path.parent() {
+            vfs.create_dir_all(parent).with_context(|| {
+                format!("failed to create thumbnail cache directory at {parent}")
+            })?;
+        }
+        vfs.write(path, &thumbnail, false)
+            .with_context(|| format!("failed to cache thumbnail at {path}"))?;
+    }
+    Ok(Some(thumbnail))
+}
+
+/// Returns the shared HTTP client for downloading embed thumbnails and remote images.
+///
+/// # Panics
+/// This function panics if the client cannot be constructed (e.g. if the TLS backend fails to initialize).
+fn client() -> &'static Client {
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .https_only(true)
+            .timeout(Duration::from_secs(15))
+            .use_rustls_tls()
+            .build()
+            .expect("HTTP client construction should succeed")
+    })
+}
+
+/// Downloads the contents at `url` over HTTPS.
+///
+/// # Errors
+/// This function returns an error if the request fails or the response has an error status.
+pub(crate) fn fetch(url: &str) -> Result<Vec<u8>> {
+    Ok(client()
+        .get(url)
+        .send()?
+        .error_for_status()?
+        .bytes()?
+        .to_vec())
+}
+
+/// Looks up the thumbnail URL of the Vimeo video with `id` with Vimeo's oEmbed API.
+fn fetch_vimeo_thumbnail_url(id: &str) -> Result<String> {
+    #[derive(Deserialize)]
+    struct OEmbedResponse {
+        thumbnail_url: String,
+    }
+
+    // https://developer.vimeo.com/api/oembed/videos
+    let response: OEmbedResponse = client()
+        .get("https://vimeo.com/api/oembed.json")
+        .query(&[("url", format!("https://vimeo.com/{id}"))])
+        .send()?
+        .error_for_status()?
+        .json()?;
+
+    Ok(response.thumbnail_url)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Embed, THUMBNAILS_DIR};
+    use crate::{
+        cache::stable_hash,
+        shortcode::Shortcode,
+        url::AssetUrls,
+        vfs::{MemoryFs, Vfs},
+    };
+    use camino::Utf8Path;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    #[test]
+    fn from_shortcode() {
+        let embed = |input| Embed::from_shortcode(&Shortcode::parse(input).unwrap().unwrap());
+
+        assert!(matches!(
+            embed(r#"{{ youtube id="dQw4w9WgXcQ" title="A" }}"#),
+            Ok(Some(Embed::YouTube {
+                id: "dQw4w9WgXcQ",
+                title: Some("A")
+            }))
+        ));
+        assert!(matches!(
+            embed(r#"{{ vimeo id="76979871" }}"#),
+            Ok(Some(Embed::Vimeo {
+                id: "76979871",
+                title: None
+            }))
+        ));
+        assert!(matches!(
+            embed(r#"{{ tweet url="https://x.com/a/status/1" }}"#),
+            Ok(Some(Embed::Tweet {
+                url: "https://x.com/a/status/1",
+                text: None
+            }))
+        ));
+        assert!(matches!(embed(r#"{{ video src="a.mp4" }}"#), Ok(None)));
+
+        // Missing arguments
+        assert!(embed("{{ youtube }}").is_err());
+        assert!(embed(r#"{{ tweet text="A" }}"#).is_err());
+        // Unknown arguments
+        assert!(embed(r#"{{ youtube id="a" autoplay="true" }}"#).is_err());
+        assert!(embed(r#"{{ vimeo id="1" url="https://vimeo.com/1" }}"#).is_err());
+        // Invalid arguments
+        assert!(embed(r#"{{ youtube id="" }}"#).is_err());
+        assert!(embed(r#"{{ youtube id="a/b" }}"#).is_err());
+        assert!(embed(r#"{{ vimeo id="abc" }}"#).is_err());
+        assert!(embed(r#"{{ tweet url="https://example.com/a" }}"#).is_err());
+    }
+
+    #[test]
+    fn html() {
+        let mut png = Cursor::new(Vec::new());
+        RgbImage::from_pixel(4, 3, Rgb([255, 0, 0]))
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        let cached_thumbnail = |video_url: &str| {
+            format!(
+                "/cache/{THUMBNAILS_DIR}/{}",
+                stable_hash(&[video_url.as_bytes()])
+            )
+        };
+        let vfs = MemoryFs::new()
+            .with_file(
+                cached_thumbnail("https://www.youtube.com/watch?v=a"),
+                png.get_ref().clone(),
+            )
+            .with_file(cached_thumbnail("https://vimeo.com/1"), png.into_inner());
+        // Without downloads, only cached thumbnails are used
+        let html = |input| {
+            Embed::from_shortcode(&Shortcode::parse(input).unwrap().unwrap())
+                .unwrap()
+                .unwrap()
+                .into_html(
+                    Utf8Path::new("/out"),
+                    "post",
+                    &AssetUrls::default(),
+                    Some(Utf8Path::new("/cache")),
+                    false,
+                    &vfs,
+                )
+                .unwrap()
+        };
+
+        assert_eq!(
+            html(r#"{{ youtube id="a" title="A & B" }}"#),
+            r#"<figure class="__embed __embed-youtube"><a href="https://www.youtube.com/watch?v=a"><img src="embed-youtube-a.avif" alt="A &amp; B" decoding="async" loading="lazy" width="4" height="3"><span class="__embed-label">Watch on YouTube</span></a></figure>"#
+        );
+        assert!(vfs.is_file(Utf8Path::new("/out/embed-youtube-a.avif")));
+        assert_eq!(
+            html(r#"{{ vimeo id="1" }}"#),
+            r#"<figure class="__embed __embed-vimeo"><a href="https://vimeo.com/1"><img src="embed-vimeo-1.avif" alt="Video thumbnail" decoding="async" loading="lazy" width="4" height="3"><span class="__embed-label">Watch on Vimeo</span></a></figure>"#
+        );
+        assert_eq!(
+            html(r#"{{ youtube id="b" }}"#),
+            r#"<figure class="__embed __embed-youtube"><a href="https://www.youtube.com/watch?v=b"><span class="__embed-label">Watch on YouTube</span></a></figure>"#
+        );
+        assert_eq!(
+            html(r#"{{ tweet url="https://x.com/a/status/1" text="A & B" }}"#),
+            r#"<blockquote class="__embed __embed-tweet"><p>A &amp; B</p><a href="https://x.com/a/status/1">View post on X</a></blockquote>"#
+        );
+        assert_eq!(
+            html(r#"{{ tweet url="https://twitter.com/a/status/1" }}"#),
+            r#"<blockquote class="__embed __embed-tweet"><a href="https://twitter.com/a/status/1">View post on X</a></blockquote>"#
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! Code for numbering equations in articles' display math and resolving references to them.
+//!
+//! Every row of an `equation`, `align`, or `gather` environment is numbered in order of appearance,
+//! unless it contains `\nonumber` or `\notag`, or sets its own number with `\tag`.
+//! Rows can be labeled with `\label{eq:id}`, and math can reference labeled rows with `\eqref{eq:id}`.
+//! KaTeX supports neither labels nor references, so environments are rewritten into their unnumbered variants
+//! (e.g. `align*`) with an explicit `\tag` for every numbered row.
+
+use crate::builder::{create_el_with_attrs, create_text, tree_to_html};
+use anyhow::{Result, anyhow, bail};
+use ego_tree::tree;
+use foldhash::{HashMap, HashMapExt};
+use pulldown_cmark::{Event, Options, Parser};
+use std::{borrow::Cow, fmt::Write};
+
+// Environments whose rows are numbered
+const NUMBERED_ENVIRONMENTS: [&str; 3] = ["equation", "align", "gather"];
+// Environments with only one row, even if their contents contain line breaks (e.g. in a nested `split`)
+const SINGLE_ROW_ENVIRONMENTS: [&str; 1] = ["equation"];
+
+const REFERENCE_CLASS: &str = "__eqref";
+
+/// A display math expression containing numbered environments.
+pub struct NumberedMath {
+    // Source with environments rewritten into their unnumbered variants with explicit tags
+    src: String,
+    // Labels of rows within the expression
+    labels: Vec<Box<str>>,
+}
+
+impl NumberedMath {
+    /// Returns the source of the expression to render, with an explicit `\tag` for every numbered row.
+    #[must_use]
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// Returns empty elements with the IDs of the expression's labels, to be placed before the rendered expression
+    /// so references can link to it.
+    #[must_use]
+    pub fn anchors_html(&self) -> String {
+        // Labels only contain characters that don't need escaping (checked in `validate_label()`)
+        self.labels.iter().fold(String::new(), |mut html, label| {
+            write!(html, r#"<span id="{label}"></span>"#)
+                .expect("writing to a string should succeed");
+            html
+        })
+    }
+}
+
+pub struct Equations {
+    // Expressions containing numbered environments, keyed by their source offset
+    expressions: HashMap<usize, NumberedMath>,
+    // Number of every labeled row
+    numbers: HashMap<Box<str>, Box<str>>,
+}
+
+impl Equations {
+    /// Numbers the rows of all numbered environments in the display math of an article, in order of appearance.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - a label is malformed, or labels a row without a number
+    /// - multiple rows have the same label
+    /// - a numbered environment is not closed
+    pub fn collect(markdown: &str, options: Options) -> Result<Self> {
+        let mut expressions = HashMap::new();
+        let mut numbers = HashMap::new();
+        let mut count = 0;
+
+        for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+            let Event::DisplayMath(src) = event else {
+                continue;
+            };
+            let mut labels = Vec::new();
+            let Some(src) = number_environments(&src, &mut count, &mut labels)? else {
+                continue;
+            };
+
+            for (label, number) in &labels {
+                if numbers.insert(label.clone(), number.clone()).is_some() {
+                    bail!("found duplicate equation label: {label}");
+                }
+            }
+            expressions.insert(
+                range.start,
+                NumberedMath {
+                    src,
+                    labels: labels.into_iter().map(|(label, _)| label).collect(),
+                },
+            );
+        }
+
+        Ok(Self {
+            expressions,
+            numbers,
+        })
+    }
+
+    /// Returns the display math expression starting at the source offset, if it contains numbered environments.
+    #[must_use]
+    pub fn at(&self, offset: usize) -> Option<&NumberedMath> {
+        self.expressions.get(&offset)
+    }
+
+    /// Replaces references like `\eqref{eq:id}` in math with the numbers of the referenced rows.
+    ///
+    /// # Errors
+    /// This function returns an error if a reference does not correspond to any label.
+    pub fn resolve_references<'a>(&self, src: &'a str) -> Result<Cow<'a, str>> {
+        if !src.contains("\\eqref{") {
+            return Ok(Cow::Borrowed(src));
+        }
+
+        let mut resolved = String::with_capacity(src.len());
+        let mut rest = src;
+        while let Some(start) = rest.find("\\eqref{") {
+            let label_start = start + "\\eqref{".len();
+            let len = rest[label_start..]
+                .find('}')
+                .ok_or_else(|| anyhow!("equation reference is not closed"))?;
+            let number = self.number(&rest[label_start..label_start + len])?;
+            write!(resolved, "{}\\textup{{({number})}}", &rest[..start])
+                .expect("writing to a string should succeed");
+            rest = &rest[label_start + len + 1..];
+        }
+        resolved.push_str(rest);
+
+        Ok(Cow::Owned(resolved))
+    }
+
+    /// Returns a link to the referenced row, as a string of HTML,
+    /// if an inline math expression consists of a single reference (e.g. `$\eqref{eq:id}$`).
+    ///
+    /// # Errors
+    /// This function returns an error if the reference does not correspond to any label.
+    pub fn reference_html(&self, src: &str) -> Result<Option<String>> {
+        let Some(label) = src
+            .trim()
+            .strip_prefix("\\eqref{")
+            .and_then(|rest| rest.strip_suffix('}'))
+            .filter(|label| !label.contains('}'))
+        else {
+            return Ok(None);
+        };
+
+        let href = format!("#{label}");
+        let text = format!("({})", self.number(label)?);
+        Ok(Some(tree_to_html(tree! {
+            create_el_with_attrs("a", &[("href", &href), ("class", REFERENCE_CLASS)]) => {
+                create_text(&text)
+            }
+        })))
+    }
+
+    fn number(&self, label: &str) -> Result<&str> {
+        self.numbers
+            .get(label)
+            .map(AsRef::as_ref)
+            .ok_or_else(|| anyhow!("found a reference to an unknown equation label: {label}"))
+    }
+}
+
+/// Rewrites the numbered environments in a display math expression, numbering rows after the first `count` rows
+/// and collecting the labels of rows with their numbers.
+/// This function returns `Ok(None)` if the expression contains no numbered environments.
+fn number_environments(
+    src: &str,
+    count: &mut usize,
+    labels: &mut Vec<(Box<str>, Box<str>)>,
+) -> Result<Option<String>> {
+    let mut rewritten = String::with_capacity(src.len());
+    let mut rest = src;
+    let mut has_environments = false;
+
+    while let Some((start, environment)) = NUMBERED_ENVIRONMENTS
+        .iter()
+        .filter_map(|&environment| {
+            rest.find(&format!("\\begin{{{environment}}}"))
+                .map(|start| (start, environment))
+        })
+        .min()
+    {
+        has_environments = true;
+        let opening = format!("\\begin{{{environment}}}");
+        let closing = format!("\\end{{{environment}}}");
+        let body_start = start + opening.len();
+        let body_len = rest[body_start..]
+            .find(&closing)
+            .ok_or_else(|| anyhow!("`{environment}` environment is not closed"))?;
+        let body = &rest[body_start..body_start + body_len];
+
+        let rows = if SINGLE_ROW_ENVIRONMENTS.contains(&environment) {
+            vec![body]
+        } else {
+            split_rows(body)
+        };
+        let rows = rows
+            .into_iter()
+            .map(|row| number_row(row, count, labels))
+            .collect::<Result<Vec<_>>>()?;
+
+        write!(
+            rewritten,
+            "{}\\begin{{{environment}*}}{}\\end{{{environment}*}}",
+            &rest[..start],
+            rows.join("\\\\")
+        )
+        .expect("writing to a string should succeed");
+        rest = &rest[body_start + body_len + closing.len()..];
+    }
+
+    if !has_environments {
+        return Ok(None);
+    }
+    rewritten.push_str(rest);
+    Ok(Some(rewritten))
+}
+
+/// Splits the body of an environment into rows at line breaks (`\\`) that are not within groups or environments.
+fn split_rows(body: &str) -> Vec<&str> {
+    let bytes = body.as_bytes();
+    let mut rows = Vec::new();
+    let mut depth = 0_isize;
+    let mut row_start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if bytes[i..].starts_with(b"\\\\") => {
+                if depth == 0 {
+                    rows.push(&body[row_start..i]);
+                    row_start = i + 2;
+                }
+                i += 2;
+                continue;
+            }
+            b'\\' => {
+                if bytes[i..].starts_with(b"\\begin{") {
+                    depth += 1;
+                } else if bytes[i..].starts_with(b"\\end{") {
+                    depth -= 1;
+                }
+                // Skip the escaped character (e.g. in `\{`)
+                i += 2;
+                continue;
+            }
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    rows.push(&body[row_start..]);
+    rows
+}
+
+/// Removes labels and `\nonumber` from a row and adds a `\tag` if the row is numbered.
+fn number_row(
+    row: &str,
+    count: &mut usize,
+    labels: &mut Vec<(Box<str>, Box<str>)>,
+) -> Result<String> {
+    let mut row = row.to_owned();
+
+    let mut row_labels = Vec::new();
+    while let Some(start) = row.find("\\label{") {
+        let label_start = start + "\\label{".len();
+        let len = row[label_start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("equation label is not closed"))?;
+        let label = &row[label_start..label_start + len];
+        validate_label(label)?;
+        row_labels.push(Box::<str>::from(label));
+        row.replace_range(start..=label_start + len, "");
+    }
+
+    let is_unnumbered = row.contains("\\nonumber") || row.contains("\\notag");
+    let row = row.replace("\\nonumber", "").replace("\\notag", "");
+
+    // Rows with their own tag are referenced by the tag's contents
+    let own_tag = row
+        .find("\\tag")
+        .and_then(|start| row[start..].split_once('{'))
+        .and_then(|(_, rest)| rest.split_once('}'))
+        .map(|(tag, _)| Box::<str>::from(tag));
+    let has_own_tag = own_tag.is_some();
+    let number = if has_own_tag {
+        own_tag
+    } else if is_unnumbered || row.trim().is_empty() {
+        None
+    } else {
+        *count += 1;
+        Some(count.to_string().into())
+    };
+
+    match &number {
+        Some(number) => labels.extend(row_labels.into_iter().map(|label| (label, number.clone()))),
+        None if !row_labels.is_empty() => {
+            bail!(
+                "equation label is on a row without a number: {}",
+                row_labels[0]
+            );
+        }
+        None => {}
+    }
+
+    Ok(if number.is_some() && !has_own_tag {
+        format!("{row}\\tag{{{count}}}")
+    } else {
+        row
+    })
+}
+
+/// Checks that an equation label has the form `eq:id`, where `id` only contains ASCII alphanumerics, `-`, and `_`.
+fn validate_label(label: &str) -> Result<()> {
+    match label.strip_prefix("eq:") {
+        Some(name)
+            if !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_')) =>
+        {
+            Ok(())
+        }
+        _ => bail!("equation label is invalid: \"{label}\""),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Equations, split_rows};
+    use pulldown_cmark::Options;
+
+    fn collect(markdown: &str) -> Equations {
+        Equations::collect(markdown, Options::ENABLE_MATH).unwrap()
+    }
+
+    #[test]
+    fn rows() {
+        assert_eq!(split_rows("a \\\\ b"), ["a ", " b"]);
+        assert_eq!(
+            split_rows("a \\\\ \\begin{matrix} 1 \\\\ 2 \\end{matrix} \\\\ \\{ b"),
+            ["a ", " \\begin{matrix} 1 \\\\ 2 \\end{matrix} ", " \\{ b"]
+        );
+        assert_eq!(split_rows("\\text{{a \\\\ b}}"), ["\\text{{a \\\\ b}}"]);
+    }
+
+    #[test]
+    fn numbering() {
+        let markdown = "$$\\begin{equation} E = mc^2 \\label{eq:energy} \\end{equation}$$\n\n\
+            $$x = 1$$\n\n\
+            $$[leqno] \\begin{align} a &= b \\label{eq:a} \\\\ c &= d \\nonumber \\\\ e &= f \\tag{*} \\label{eq:star} \\\\ g &= h \\end{align}$$\n\n\
+            See $\\eqref{eq:a}$ and $\\eqref{eq:energy} + \\eqref{eq:star}$.";
+        let equations = collect(markdown);
+
+        let first = equations.at(0).unwrap();
+        assert_eq!(
+            first.src(),
+            "\\begin{equation*} E = mc^2  \\tag{1}\\end{equation*}"
+        );
+        assert_eq!(first.anchors_html(), "<span id=\"eq:energy\"></span>");
+
+        let offset = markdown.find("$$[leqno]").unwrap();
+        let second = equations.at(offset).unwrap();
+        assert_eq!(
+            second.src(),
+            "[leqno] \\begin{align*} a &= b  \\tag{2}\\\\ c &= d  \\\\ e &= f \\tag{*}  \\\\ g &= h \\tag{3}\\end{align*}"
+        );
+        assert!(equations.at(markdown.find("$$x").unwrap()).is_none());
+
+        assert_eq!(
+            equations.reference_html("\\eqref{eq:a}").unwrap().unwrap(),
+            "<a href=\"#eq:a\" class=\"__eqref\">(2)</a>"
+        );
+        assert_eq!(
+            equations
+                .resolve_references("\\eqref{eq:energy} + \\eqref{eq:star}")
+                .unwrap(),
+            "\\textup{(1)} + \\textup{(*)}"
+        );
+        assert_eq!(equations.resolve_references("x").unwrap(), "x");
+        assert!(equations.reference_html("x").unwrap().is_none());
+        assert!(equations.reference_html("\\eqref{eq:b}").is_err());
+    }
+
+    #[cfg(feature = "math")]
+    #[test]
+    fn rendering() {
+        use crate::{LatexConverter, MathOptions, RenderMode};
+
+        // Rewritten expressions can be rendered by KaTeX, with every row tagged
+        let equations = collect("$$[leqno] \\begin{align} a \\\\ b \\\\ c \\end{align}$$");
+        let converter = LatexConverter::new().unwrap();
+        let (options, latex) = MathOptions::parse(equations.at(0).unwrap().src());
+        assert!(
+            converter
+                .latex_to_html(latex, RenderMode::Display, options)
+                .unwrap()
+                .contains("<span class=\"mord\">3</span>")
+        );
+    }
+
+    #[test]
+    fn invalid_labels() {
+        for markdown in [
+            "$$\\begin{align} a \\label{a} \\end{align}$$",
+            "$$\\begin{align} a \\nonumber \\label{eq:a} \\end{align}$$",
+            "$$\\begin{equation} a \\label{eq:a} \\end{equation}$$ $$\\begin{equation} b \\label{eq:a} \\end{equation}$$",
+            "$$\\begin{align} a$$",
+        ] {
+            assert!(Equations::collect(markdown, Options::ENABLE_MATH).is_err());
+        }
+    }
+}
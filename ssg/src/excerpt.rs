@@ -0,0 +1,103 @@
+//! Code for taking excerpts for article lists from the text of articles.
+
+use crate::toc::TOC_MARKER;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+// Maximum number of words in an article excerpt taken from its first paragraph
+const EXCERPT_WORDS: usize = 30;
+// HTML comment on its own line that ends an article's excerpt
+const EXCERPT_MARKER: &str = "<!-- more -->";
+
+/// Returns the plain text of the top-level paragraphs before an article's `<!-- more -->` marker,
+/// or if the article has no marker, of its first paragraph, truncated to a limited number of words.
+/// Paragraphs that are replaced while building the article (e.g. shortcodes) are skipped.
+#[must_use]
+pub fn article_excerpt(markdown: &str, options: Options) -> Option<String> {
+    // Nesting depth of the current element, for only considering top-level paragraphs
+    let mut depth = 0_usize;
+    // Text of the top-level paragraphs so far, without empty ones
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut excerpt: Option<String> = None;
+    // Image alt text is not part of the paragraph's text
+    let mut is_in_image = false;
+
+    for (event, offset) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Paragraph) if depth == 0 => {
+                let source = markdown[offset].trim();
+                if source != TOC_MARKER && !source.starts_with("{{") && !source.starts_with(":::") {
+                    excerpt = Some(String::new());
+                }
+                depth += 1;
+            }
+            Event::End(TagEnd::Paragraph) if depth == 1 => {
+                paragraphs.extend(excerpt.take().filter(|text| !text.trim().is_empty()));
+                depth -= 1;
+            }
+            Event::Html(html) if depth == 1 && html.trim() == EXCERPT_MARKER => {
+                let excerpt = paragraphs.join(" ");
+                return (!excerpt.is_empty())
+                    .then(|| excerpt.split_whitespace().collect::<Vec<_>>().join(" "));
+            }
+            Event::Start(Tag::Image { .. }) => {
+                is_in_image = true;
+                depth += 1;
+            }
+            Event::End(TagEnd::Image) => {
+                is_in_image = false;
+                depth -= 1;
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            Event::Text(text) | Event::Code(text) | Event::InlineMath(text)
+                if !is_in_image && let Some(excerpt) = &mut excerpt =>
+            {
+                excerpt.push_str(&text);
+            }
+            Event::SoftBreak | Event::HardBreak if let Some(excerpt) = &mut excerpt => {
+                excerpt.push(' ');
+            }
+            _ => {}
+        }
+    }
+
+    let excerpt = paragraphs.into_iter().next()?;
+    let mut words = excerpt.split_whitespace();
+    let mut output = words
+        .by_ref()
+        .take(EXCERPT_WORDS)
+        .collect::<Vec<_>>()
+        .join(" ");
+    if words.next().is_some() {
+        output.push('…');
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::article_excerpt;
+    use pulldown_cmark::Options;
+
+    #[test]
+    fn excerpts() {
+        let excerpt = |markdown: &str| article_excerpt(markdown, Options::empty());
+
+        // Without a marker, the first paragraph is used, skipping shortcodes and image alt text
+        assert_eq!(
+            excerpt("# A\n\n{{ video src=\"a.mp4\" }}\n\n![Alt](a.png) `B`\nC\n\nD").as_deref(),
+            Some("B C")
+        );
+        assert_eq!(
+            excerpt(&"word ".repeat(31)).as_deref(),
+            Some(format!("{}…", ["word"; 30].join(" ")).as_str())
+        );
+        assert_eq!(excerpt("# A"), None);
+
+        // With a marker, every paragraph before it is used without truncation
+        assert_eq!(
+            excerpt(&format!("A\n\n{}\n\n<!-- more -->\n\nC", "B ".repeat(30))).as_deref(),
+            Some(format!("A {}", ["B"; 30].join(" ")).as_str())
+        );
+    }
+}
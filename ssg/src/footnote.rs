@@ -0,0 +1,226 @@
+//! Code for rendering footnote references and definitions in articles with configurable labels.
+
+use crate::component::escape_html;
+use foldhash::{HashMap, HashMapExt};
+use pulldown_cmark::{CowStr, Event, Tag, TagEnd};
+use serde::Deserialize;
+use std::iter::repeat_n;
+
+const FOOTNOTES_CLASS: &str = "__footnotes";
+
+// Letters used for alphabetic labels
+const LETTERS: &[u8; 26] = b"abcdefghijklmnopqrstuvwxyz";
+
+// Symbols used for labels in order; later footnotes repeat them (e.g. `**`, `††`)
+const FOOTNOTE_SYMBOLS: [char; 6] = ['*', '†', '‡', '§', '‖', '¶'];
+
+/// Style of the labels of footnotes, which are numbered in order of their first reference.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FootnoteStyle {
+    /// 1, 2, 3, ...
+    #[default]
+    Numeric,
+    /// a, b, ..., z, aa, ab, ...
+    Alphabetic,
+    /// *, †, ‡, §, ‖, ¶, **, ††, ...
+    Symbol,
+}
+
+impl FootnoteStyle {
+    /// Returns the label of the footnote with the given number, starting from 1.
+    #[must_use]
+    pub fn label(self, number: usize) -> String {
+        debug_assert!(number > 0, "footnote numbers should start from 1");
+
+        match self {
+            Self::Numeric => number.to_string(),
+            Self::Alphabetic => {
+                // Bijective base-26, like spreadsheet columns
+                let mut label = Vec::new();
+                let mut n = number;
+                while n > 0 {
+                    n -= 1;
+                    label.push(char::from(LETTERS[n % LETTERS.len()]));
+                    n /= LETTERS.len();
+                }
+                label.iter().rev().collect()
+            }
+            Self::Symbol => {
+                let symbol = FOOTNOTE_SYMBOLS[(number - 1) % FOOTNOTE_SYMBOLS.len()];
+                let count = (number - 1) / FOOTNOTE_SYMBOLS.len() + 1;
+                repeat_n(symbol, count).collect()
+            }
+        }
+    }
+}
+
+/// Renders the footnotes of an article.
+/// Footnote references are rendered as they are parsed, while definitions are rendered afterward,
+/// since a definition's label depends on when its footnote is first referenced.
+pub struct Footnotes {
+    style: FootnoteStyle,
+    // Number of every referenced footnote, keyed by ID
+    numbers: HashMap<Box<str>, usize>,
+}
+
+impl Footnotes {
+    #[must_use]
+    pub fn new(style: FootnoteStyle) -> Self {
+        Self {
+            style,
+            numbers: HashMap::new(),
+        }
+    }
+
+    /// Returns the HTML of a reference to a footnote, numbering the footnote if it has not been referenced before.
+    pub fn reference_html(&mut self, id: &str) -> String {
+        let next_number = self.numbers.len() + 1;
+        let number = *self.numbers.entry(id.into()).or_insert(next_number);
+        let id = escape_html(&anchor_id(id));
+
+        format!(
+            r##"<sup class="footnote-reference"><a href="#{id}">{}</a></sup>"##,
+            self.style.label(number)
+        )
+    }
+
+    /// Returns the anchor IDs of the definitions of every referenced footnote.
+    pub fn anchor_ids(&self) -> impl Iterator<Item = String> {
+        self.numbers.keys().map(|id| anchor_id(id))
+    }
+
+    /// Replaces the footnote definitions in a list of article events with their HTML.
+    /// If `heading` is given, the definitions are moved to a section at the end of the article
+    /// with that heading, in order of their labels. Otherwise, they are left in place.
+    ///
+    /// Every definition should be referenced at least once before this function is called.
+    pub fn render_definitions(&self, events: &mut Vec<Event<'_>>, heading: Option<&str>) {
+        let mut output = Vec::with_capacity(events.len());
+        let mut definitions = Vec::new();
+        // Number and events of the definition being moved to the footnotes section
+        let mut active_definition: Option<(usize, Vec<Event<'_>>)> = None;
+
+        for event in events.drain(..) {
+            match event {
+                Event::Start(Tag::FootnoteDefinition(id)) => {
+                    let number = self.numbers.get(&*id).copied().unwrap_or_default();
+                    let html = Event::InlineHtml(
+                        format!(
+                            r#"<div class="footnote-definition" id="{}"><sup class="footnote-definition-label">{}</sup>"#,
+                            escape_html(&anchor_id(&id)),
+                            self.style.label(number.max(1))
+                        )
+                        .into(),
+                    );
+
+                    if heading.is_some() {
+                        active_definition = Some((number, vec![html]));
+                    } else {
+                        output.push(html);
+                    }
+                }
+                Event::End(TagEnd::FootnoteDefinition) => {
+                    let html = Event::InlineHtml(CowStr::Borrowed("</div>\n"));
+                    match active_definition.take() {
+                        Some((number, mut definition)) => {
+                            definition.push(html);
+                            definitions.push((number, definition));
+                        }
+                        None => output.push(html),
+                    }
+                }
+                event => match &mut active_definition {
+                    Some((_, definition)) => definition.push(event),
+                    None => output.push(event),
+                },
+            }
+        }
+
+        if let Some(heading) = heading
+            && !definitions.is_empty()
+        {
+            definitions.sort_by_key(|(number, _)| *number);
+
+            output.push(Event::InlineHtml(
+                format!(
+                    r#"<section class="{FOOTNOTES_CLASS}" role="doc-endnotes"><h2>{}</h2>"#,
+                    escape_html(heading)
+                )
+                .into(),
+            ));
+            output.extend(definitions.into_iter().flat_map(|(_, events)| events));
+            output.push(Event::InlineHtml(CowStr::Borrowed("</section>")));
+        }
+
+        *events = output;
+    }
+}
+
+/// Returns the anchor ID of a footnote definition.
+/// IDs are derived from the footnote's name rather than its number, so they don't change when footnotes are added,
+/// and they are prefixed so they don't conflict with heading IDs.
+fn anchor_id(id: &str) -> String {
+    format!("fn-{id}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FootnoteStyle, Footnotes};
+    use pulldown_cmark::{Event, Options, Parser, html::push_html};
+
+    #[test]
+    fn labels() {
+        let labels = |style: FootnoteStyle, numbers: &[usize]| -> Vec<String> {
+            numbers.iter().map(|&number| style.label(number)).collect()
+        };
+
+        assert_eq!(labels(FootnoteStyle::Numeric, &[1, 12]), ["1", "12"]);
+        assert_eq!(
+            labels(FootnoteStyle::Alphabetic, &[1, 26, 27, 52, 703]),
+            ["a", "z", "aa", "az", "aaa"]
+        );
+        assert_eq!(
+            labels(FootnoteStyle::Symbol, &[1, 2, 3, 6, 7, 9]),
+            ["*", "†", "‡", "¶", "**", "‡‡"]
+        );
+    }
+
+    #[test]
+    fn render() {
+        fn render(heading: Option<&str>) -> String {
+            let markdown = "[^b]: B\n\nText[^a] and[^b].\n\n[^a]: A\n";
+            let mut footnotes = Footnotes::new(FootnoteStyle::Alphabetic);
+            let mut events: Vec<_> = Parser::new_ext(markdown, Options::ENABLE_FOOTNOTES)
+                .map(|event| match event {
+                    Event::FootnoteReference(id) => {
+                        Event::InlineHtml(footnotes.reference_html(&id).into())
+                    }
+                    event => event,
+                })
+                .collect();
+            footnotes.render_definitions(&mut events, heading);
+
+            let mut html = String::new();
+            push_html(&mut html, events.into_iter());
+            html
+        }
+
+        assert_eq!(
+            render(None),
+            "<div class=\"footnote-definition\" id=\"fn-b\"><sup class=\"footnote-definition-label\">b</sup>\n<p>B</p>\n</div>\n\
+            <p>Text<sup class=\"footnote-reference\"><a href=\"#fn-a\">a</a></sup> and\
+            <sup class=\"footnote-reference\"><a href=\"#fn-b\">b</a></sup>.</p>\n\
+            <div class=\"footnote-definition\" id=\"fn-a\"><sup class=\"footnote-definition-label\">a</sup>\n<p>A</p>\n</div>\n"
+        );
+        assert_eq!(
+            render(Some("Notes & sources")),
+            "<p>Text<sup class=\"footnote-reference\"><a href=\"#fn-a\">a</a></sup> and\
+            <sup class=\"footnote-reference\"><a href=\"#fn-b\">b</a></sup>.</p>\n\
+            <section class=\"__footnotes\" role=\"doc-endnotes\"><h2>Notes &amp; sources</h2>\
+            <div class=\"footnote-definition\" id=\"fn-a\"><sup class=\"footnote-definition-label\">a</sup>\n<p>A</p>\n</div>\n\
+            <div class=\"footnote-definition\" id=\"fn-b\"><sup class=\"footnote-definition-label\">b</sup>\n<p>B</p>\n</div>\n\
+            </section>"
+        );
+    }
+}
@@ -1,14 +1,65 @@
 //! Code for parsing YAML-style frontmatter from articles.
 
+use crate::{
+    builder::validate_head_html, config::Config, crossref::IndexPosition, latex::KatexOverrides,
+    local::validate_local_src, normalize::normalize_text, quotes::QuoteLocale,
+    redirect::validate_redirect_path, tags_dir_name, vfs::Vfs,
+};
 use aho_corasick::AhoCorasick;
 use anyhow::{Context, Result, anyhow, bail};
+use camino::Utf8Path;
+use foldhash::{HashMap, HashMapExt};
 use gray_matter::{Matter, engine::YAML};
 use jiff::civil::Date;
+use pulldown_cmark::Options;
 use serde::Deserialize;
-use std::sync::OnceLock;
+use serde_json::Value;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, hash_map::Entry},
+    sync::OnceLock,
+};
 
 static SLUG_MATCHER: OnceLock<AhoCorasick> = OnceLock::new();
 
+// Names of all frontmatter fields, which should be kept in sync with `Frontmatter`
+const FIELDS: [&str; 21] = [
+    "title",
+    "slug",
+    "created",
+    "updated",
+    "license",
+    "figure_index",
+    "quote_locale",
+    "tags",
+    "draft",
+    "noindex",
+    "head",
+    "toc",
+    "cover",
+    "description",
+    "excerpt",
+    "author",
+    "template",
+    "slots",
+    "disable_markdown",
+    "aliases",
+    "katex",
+];
+
+// Plain scalars that YAML 1.1 parses as Booleans but YAML 1.2 parses as strings, so their meaning depends on the parser
+const AMBIGUOUS_BOOLEANS: [&str; 6] = ["yes", "no", "on", "off", "y", "n"];
+
+// Options for parsing the Markdown of every article, unless disabled in its frontmatter
+const MARKDOWN_OPTIONS: Options = Options::ENABLE_TABLES
+    .union(Options::ENABLE_FOOTNOTES)
+    .union(Options::ENABLE_STRIKETHROUGH)
+    .union(Options::ENABLE_TASKLISTS)
+    .union(Options::ENABLE_WIKILINKS)
+    .union(Options::ENABLE_SMART_PUNCTUATION)
+    .union(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS)
+    .union(Options::ENABLE_MATH);
+
 #[cfg_attr(test, derive(Debug, PartialEq))]
 #[derive(Deserialize)]
 pub struct Frontmatter {
@@ -17,6 +68,130 @@ pub struct Frontmatter {
     pub created: Date,
     #[serde(default)]
     pub updated: Option<Date>,
+    #[serde(default)]
+    pub license: Option<License>,
+    #[serde(default)]
+    pub figure_index: Option<IndexPosition>,
+    #[serde(default)]
+    pub quote_locale: Option<QuoteLocale>,
+    #[serde(default)]
+    pub tags: Vec<Box<str>>,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub noindex: bool,
+    #[serde(default)]
+    pub head: Option<Box<str>>,
+    #[serde(default)]
+    pub toc: bool,
+    #[serde(default)]
+    pub cover: Option<Box<str>>,
+    #[serde(default)]
+    pub description: Option<Box<str>>,
+    #[serde(default)]
+    pub excerpt: Option<Box<str>>,
+    #[serde(default)]
+    pub author: Option<Box<str>>,
+    #[serde(default)]
+    pub template: Option<Box<str>>,
+    #[serde(default)]
+    pub slots: BTreeMap<Box<str>, Box<str>>,
+    #[serde(default)]
+    pub disable_markdown: Vec<MarkdownExtension>,
+    #[serde(default)]
+    pub aliases: Vec<Box<str>>,
+    #[serde(default)]
+    pub katex: KatexOverrides,
+}
+
+/// A Markdown extension that is enabled for every article unless disabled in its frontmatter.
+#[cfg_attr(test, derive(Debug))]
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkdownExtension {
+    /// GitHub-style tables
+    Tables,
+    /// Footnote references and definitions
+    Footnotes,
+    /// `~~struck through~~` text
+    Strikethrough,
+    /// List items starting with `[ ]` or `[x]`, rendered as checkboxes
+    Tasklists,
+    /// `[[Article Title]]` links to other articles
+    Wikilinks,
+    /// Curly quotes, dashes, and ellipses in place of straight quotes, hyphens, and periods
+    SmartPunctuation,
+    /// Math expressions between dollar signs
+    Math,
+}
+
+impl MarkdownExtension {
+    const fn option(self) -> Options {
+        match self {
+            Self::Tables => Options::ENABLE_TABLES,
+            Self::Footnotes => Options::ENABLE_FOOTNOTES,
+            Self::Strikethrough => Options::ENABLE_STRIKETHROUGH,
+            Self::Tasklists => Options::ENABLE_TASKLISTS,
+            Self::Wikilinks => Options::ENABLE_WIKILINKS,
+            Self::SmartPunctuation => Options::ENABLE_SMART_PUNCTUATION,
+            Self::Math => Options::ENABLE_MATH,
+        }
+    }
+}
+
+/// A content license, specified either as an SPDX identifier or free-form name (e.g. `"CC-BY-4.0"`)
+/// or as a table with a name and URL.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum License {
+    Name(Box<str>),
+    Link { name: Box<str>, url: Box<str> },
+}
+
+impl License {
+    /// Returns the display name of the license.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Name(name) | Self::Link { name, .. } => name,
+        }
+    }
+
+    /// Returns a URL for the license text, if one is known.
+    /// Names that look like SPDX identifiers link to the SPDX license list.
+    #[must_use]
+    pub fn url(&self) -> Option<Cow<'_, str>> {
+        match self {
+            Self::Name(name) => is_spdx_id(name)
+                .then(|| Cow::Owned(format!("https://spdx.org/licenses/{name}.html"))),
+            Self::Link { url, .. } => Some(Cow::Borrowed(url)),
+        }
+    }
+
+    /// Validates the license.
+    ///
+    /// # Errors
+    /// This function returns an error if the license name or URL is empty.
+    pub fn validate(&self) -> Result<()> {
+        if self.name().trim().is_empty() {
+            bail!("license name cannot be empty");
+        }
+        if let Self::Link { url, .. } = self
+            && url.trim().is_empty()
+        {
+            bail!("license URL cannot be empty");
+        }
+        Ok(())
+    }
+}
+
+/// Returns `true` if the input looks like an SPDX license identifier (e.g. `MIT`, `CC-BY-SA-4.0`).
+fn is_spdx_id(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '+'))
 }
 
 impl Frontmatter {
@@ -26,11 +201,20 @@ impl Frontmatter {
     /// This function returns an error if:
     /// - no frontmatter is found in the text
     /// - frontmatter cannot be parsed due to invalid syntax, missing fields, invalid field values, etc.
+    /// - frontmatter relies on YAML behavior that is easy to trip over (see [`check_yaml()`])
     /// - the parsed last-updated date is before the parsed creation date
+    /// - the description, the excerpt, or the author is empty
+    /// - the template name contains characters other than ASCII letters, digits, hyphens, and underscores
+    /// - a slot name is empty, or a slot's content path is not a normalized relative path
+    /// - the head HTML is invalid or contains elements that don't belong in `<head>`
+    /// - an alias is not a valid path to redirect from
+    /// - a KaTeX macro name is invalid
     ///
     /// # Panics
     /// This function panics if the string matcher for detecting invalid slug characters cannot be constructed.
     pub fn from_text(input: &str) -> Result<Self> {
+        check_yaml(input).context("article frontmatter is invalid")?;
+
         let matter: Frontmatter = Matter::<YAML>::new()
             .parse(input)
             .context("failed to parse article frontmatter")?
@@ -50,15 +234,302 @@ impl Frontmatter {
         if matter.updated.is_some_and(|date| date < matter.created) {
             bail!("last-updated date precedes creation date of article");
         }
+        if matter
+            .description
+            .as_deref()
+            .is_some_and(|description| description.trim().is_empty())
+        {
+            bail!("article description cannot be empty");
+        }
+        if matter
+            .excerpt
+            .as_deref()
+            .is_some_and(|excerpt| excerpt.trim().is_empty())
+        {
+            bail!("article excerpt cannot be empty");
+        }
+        if matter
+            .author
+            .as_deref()
+            .is_some_and(|author| author.trim().is_empty())
+        {
+            bail!("article author cannot be empty");
+        }
+        if let Some(template) = &matter.template
+            && (template.is_empty()
+                || !template
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        {
+            // Template names are file stems, so they can't refer to files outside the templates directory
+            bail!(
+                "article template can only contain ASCII letters, digits, hyphens, and underscores (got \"{template}\")"
+            );
+        }
+        if let Some(license) = &matter.license {
+            license.validate().context("article license is invalid")?;
+        }
+        for (i, tag) in matter.tags.iter().enumerate() {
+            // Tags are used in URLs, so they are restricted to characters that don't need escaping
+            if tag.is_empty()
+                || !tag
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            {
+                bail!(
+                    "article tag can only contain lowercase ASCII letters, digits, and hyphens (got \"{tag}\")"
+                );
+            }
+            if matter.tags[..i].contains(tag) {
+                bail!("found duplicate article tag: {tag}");
+            }
+        }
+        if let Some(head) = &matter.head {
+            validate_head_html(head).context("article head HTML is invalid")?;
+        }
+        for (name, path) in &matter.slots {
+            if name.is_empty() {
+                bail!("article slot name cannot be empty");
+            }
+            validate_local_src(path, &format!("content of the article slot \"{name}\""))?;
+        }
+        for alias in &matter.aliases {
+            validate_redirect_path(alias).context("article alias is invalid")?;
+        }
+        matter
+            .katex
+            .validate()
+            .context("article KaTeX settings are invalid")?;
 
         Ok(matter)
     }
+
+    /// Returns the options for parsing the article's Markdown, without the extensions it disables.
+    #[must_use]
+    pub fn markdown_options(&self) -> Options {
+        self.disable_markdown
+            .iter()
+            .fold(MARKDOWN_OPTIONS, |options, extension| {
+                options.difference(extension.option())
+            })
+    }
+
+    /// Returns the keys in the frontmatter of an article that are not frontmatter fields, in sorted order.
+    /// Unknown keys are ignored when building the site, so they are usually misspelled field names.
+    ///
+    /// # Errors
+    /// This function returns an error if no frontmatter is found in the text or it is not a YAML mapping.
+    pub fn unknown_keys(input: &str) -> Result<Vec<String>> {
+        let matter: BTreeMap<String, Value> = Matter::<YAML>::new()
+            .parse(input)
+            .context("failed to parse article frontmatter")?
+            .data
+            .ok_or_else(|| anyhow!("article frontmatter not found"))?;
+
+        Ok(matter
+            .into_keys()
+            .filter(|key| !FIELDS.contains(&key.as_str()))
+            .collect())
+    }
+}
+
+/// Checks YAML-style frontmatter for behavior that YAML parsers accept without complaint but is rarely intended.
+/// Line numbers in errors are relative to the start of the article.
+///
+/// # Errors
+/// This function returns an error if:
+/// - a line is indented with tabs
+/// - a mapping has duplicate keys
+/// - a key has no value, which parses as null
+/// - a value is an unquoted `yes`, `no`, `on`, `off`, `y`, or `n`, which only some parsers treat as Booleans
+fn check_yaml(input: &str) -> Result<()> {
+    let mut lines = input.lines().enumerate();
+    if lines
+        .next()
+        .is_none_or(|(_, line)| line.trim_end() != "---")
+    {
+        // Missing frontmatter is reported by the parser
+        return Ok(());
+    }
+    let lines: Vec<_> = lines
+        .take_while(|(_, line)| !matches!(line.trim_end(), "---" | "..."))
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .collect();
+
+    // Keys of every mapping containing the current line, with their indentation and line numbers
+    let mut scopes: Vec<(usize, BTreeMap<&str, usize>)> = Vec::new();
+    // Indentation of the key of the current block scalar (e.g. `key: |`), whose lines are plain text
+    let mut block_scalar_indent = None;
+
+    for (i, &(line_number, line)) in lines.iter().enumerate() {
+        let content = line.trim_start_matches([' ', '\t']);
+        let indent = line.len() - content.len();
+        if block_scalar_indent.is_some_and(|block_indent| indent > block_indent) {
+            continue;
+        }
+        block_scalar_indent = None;
+        if line[..indent].contains('\t') {
+            bail!("line {line_number}: tabs cannot be used for indentation");
+        }
+
+        // List items can contain mappings, which start a new scope
+        let (indent, content) = match content.strip_prefix("- ") {
+            Some(item) => {
+                let item_content = item.trim_start();
+                let item_indent = line.len() - item_content.len();
+                scopes.retain(|(scope_indent, _)| *scope_indent < item_indent);
+                scopes.push((item_indent, BTreeMap::new()));
+                (item_indent, item_content)
+            }
+            None => (indent, content),
+        };
+
+        let (key, value) = match content.split_once(": ") {
+            Some((key, value)) => (Some(key), value),
+            None => match content.strip_suffix(':') {
+                Some(key) => (Some(key), ""),
+                None => (None, content),
+            },
+        };
+        // Comments can follow values after whitespace
+        let value = value
+            .split_once(" #")
+            .map_or(value, |(value, _)| value)
+            .trim();
+
+        if let Some(key) = key.filter(|key| !key.starts_with(['"', '\'', '[', '{'])) {
+            let key = key.trim_end();
+            scopes.retain(|(scope_indent, _)| *scope_indent <= indent);
+            if scopes
+                .last()
+                .is_none_or(|(scope_indent, _)| *scope_indent < indent)
+            {
+                scopes.push((indent, BTreeMap::new()));
+            }
+            let (_, keys) = scopes.last_mut().expect("scope should exist");
+            if let Some(first_line_number) = keys.insert(key, line_number) {
+                bail!(
+                    "line {line_number}: found duplicate key `{key}` (first set on line {first_line_number})"
+                );
+            }
+
+            if value.is_empty() {
+                // Nested mappings and lists start on the next line, indented or as list items
+                let has_nested_value = lines.get(i + 1).is_some_and(|(_, next_line)| {
+                    let next_content = next_line.trim_start_matches([' ', '\t']);
+                    let next_indent = next_line.len() - next_content.len();
+                    next_indent > indent || (next_indent == indent && next_content.starts_with('-'))
+                });
+                if !has_nested_value {
+                    bail!("line {line_number}: key `{key}` has no value");
+                }
+            } else if value.starts_with(['|', '>']) {
+                block_scalar_indent = Some(indent);
+            }
+        }
+
+        if AMBIGUOUS_BOOLEANS
+            .iter()
+            .any(|ambiguous| value.eq_ignore_ascii_case(ambiguous))
+        {
+            bail!(
+                "line {line_number}: `{value}` is ambiguous; use `true` or `false` for a Boolean, or quote it for a string"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the frontmatter of every article, adding every problem found to `problems`.
+/// Dates after `today` are reported as future dates.
+/// This function returns the number of articles checked.
+///
+/// # Errors
+/// This function returns an error if the articles directory cannot be read.
+pub fn frontmatter_problems(
+    config: &Config,
+    vfs: &dyn Vfs,
+    today: Date,
+    problems: &mut Vec<String>,
+) -> Result<usize> {
+    let paths = config.article_paths(vfs)?;
+
+    // Path of the article using each slug
+    let mut article_slugs: HashMap<String, &Utf8Path> = HashMap::new();
+    let mut has_tags = false;
+
+    for path in &paths {
+        let mut report = |problem: String| problems.push(format!("{path}: {problem}"));
+
+        let text = match vfs.read_to_string(path) {
+            Ok(text) => normalize_text(text).0,
+            Err(e) => {
+                report(format!("failed to read article file: {e}"));
+                continue;
+            }
+        };
+
+        // Parsing errors are reported below
+        for key in Frontmatter::unknown_keys(&text).unwrap_or_default() {
+            report(format!("unknown frontmatter key: {key}"));
+        }
+
+        let frontmatter = match Frontmatter::from_text(&text) {
+            Ok(frontmatter) => frontmatter,
+            Err(e) => {
+                report(format!("{e:#}"));
+                continue;
+            }
+        };
+
+        for (field, date) in [
+            ("created", Some(frontmatter.created)),
+            ("updated", frontmatter.updated),
+        ] {
+            if let Some(date) = date
+                && date > today
+            {
+                report(format!("`{field}` date {date} is in the future"));
+            }
+        }
+
+        // Drafts don't need unique slugs unless they are being built
+        if frontmatter.draft && !config.include_drafts {
+            continue;
+        }
+
+        has_tags |= !frontmatter.tags.is_empty();
+        match article_slugs.entry(frontmatter.slug.clone()) {
+            Entry::Occupied(entry) => report(format!(
+                "duplicate article slug \"{}\" (also used by {})",
+                entry.key(),
+                entry.get()
+            )),
+            Entry::Vacant(entry) => {
+                entry.insert(path);
+            }
+        }
+    }
+
+    let tags_dir_name = tags_dir_name();
+    if has_tags && let Some(path) = article_slugs.get(tags_dir_name) {
+        problems.push(format!(
+            "{path}: article slug \"{tags_dir_name}\" is reserved for tag pages when articles have tags"
+        ));
+    }
+
+    Ok(paths.len())
 }
 
 #[cfg(test)]
 mod test {
-    use super::Frontmatter;
+    use super::{Frontmatter, License, MARKDOWN_OPTIONS, MarkdownExtension, check_yaml};
+    use crate::latex::KatexOverrides;
     use jiff::civil::date;
+    use pulldown_cmark::Options;
+    use std::collections::BTreeMap;
 
     /// Utility function for asserting failure to parse the input text as frontmatter
     fn assert_parse_err(input: &str) {
@@ -125,6 +596,23 @@ mod test {
                 slug: "def".into(),
                 created: date(2000, 1, 1),
                 updated: None,
+                license: None,
+                figure_index: None,
+                quote_locale: None,
+                tags: Vec::new(),
+                draft: false,
+                noindex: false,
+                head: None,
+                toc: false,
+                cover: None,
+                description: None,
+                excerpt: None,
+                author: None,
+                template: None,
+                slots: BTreeMap::new(),
+                disable_markdown: Vec::new(),
+                aliases: Vec::new(),
+                katex: KatexOverrides::default(),
             },
         );
     }
@@ -142,6 +630,23 @@ mod test {
                 slug: "def".into(),
                 created: date(2000, 1, 1),
                 updated: Some(date(2000, 1, 1)),
+                license: None,
+                figure_index: None,
+                quote_locale: None,
+                tags: Vec::new(),
+                draft: false,
+                noindex: false,
+                head: None,
+                toc: false,
+                cover: None,
+                description: None,
+                excerpt: None,
+                author: None,
+                template: None,
+                slots: BTreeMap::new(),
+                disable_markdown: Vec::new(),
+                aliases: Vec::new(),
+                katex: KatexOverrides::default(),
             },
         );
         assert_parse_eq(
@@ -151,6 +656,23 @@ mod test {
                 slug: "def".into(),
                 created: date(2000, 1, 1),
                 updated: Some(date(2000, 1, 2)),
+                license: None,
+                figure_index: None,
+                quote_locale: None,
+                tags: Vec::new(),
+                draft: false,
+                noindex: false,
+                head: None,
+                toc: false,
+                cover: None,
+                description: None,
+                excerpt: None,
+                author: None,
+                template: None,
+                slots: BTreeMap::new(),
+                disable_markdown: Vec::new(),
+                aliases: Vec::new(),
+                katex: KatexOverrides::default(),
             },
         );
     }
@@ -174,7 +696,333 @@ mod test {
                 slug: "def".into(),
                 created: date(2000, 1, 1),
                 updated: Some(date(2000, 1, 1)),
+                license: None,
+                figure_index: None,
+                quote_locale: None,
+                tags: Vec::new(),
+                draft: false,
+                noindex: false,
+                head: None,
+                toc: false,
+                cover: None,
+                description: None,
+                excerpt: None,
+                author: None,
+                template: None,
+                slots: BTreeMap::new(),
+                disable_markdown: Vec::new(),
+                aliases: Vec::new(),
+                katex: KatexOverrides::default(),
+            },
+        );
+    }
+
+    #[test]
+    fn license() {
+        assert_parse_eq(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nlicense: CC-BY-4.0\n---",
+            Frontmatter {
+                title: "abc".into(),
+                slug: "def".into(),
+                created: date(2000, 1, 1),
+                updated: None,
+                license: Some(License::Name("CC-BY-4.0".into())),
+                figure_index: None,
+                quote_locale: None,
+                tags: Vec::new(),
+                draft: false,
+                noindex: false,
+                head: None,
+                toc: false,
+                cover: None,
+                description: None,
+                excerpt: None,
+                author: None,
+                template: None,
+                slots: BTreeMap::new(),
+                disable_markdown: Vec::new(),
+                aliases: Vec::new(),
+                katex: KatexOverrides::default(),
+            },
+        );
+        assert_parse_eq(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nlicense:\n  name: My license\n  url: https://example.com\n---",
+            Frontmatter {
+                title: "abc".into(),
+                slug: "def".into(),
+                created: date(2000, 1, 1),
+                updated: None,
+                license: Some(License::Link {
+                    name: "My license".into(),
+                    url: "https://example.com".into(),
+                }),
+                figure_index: None,
+                quote_locale: None,
+                tags: Vec::new(),
+                draft: false,
+                noindex: false,
+                head: None,
+                toc: false,
+                cover: None,
+                description: None,
+                excerpt: None,
+                author: None,
+                template: None,
+                slots: BTreeMap::new(),
+                disable_markdown: Vec::new(),
+                aliases: Vec::new(),
+                katex: KatexOverrides::default(),
+            },
+        );
+
+        // Parsing should fail if the license name or URL is empty
+        assert_parse_err("---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nlicense: \"\"\n---");
+        assert_parse_err(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nlicense:\n  name: abc\n  url: \"\"\n---",
+        );
+    }
+
+    #[test]
+    fn tags() {
+        assert_parse_eq(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ntags: [rust, web-dev, v2]\n---",
+            Frontmatter {
+                title: "abc".into(),
+                slug: "def".into(),
+                created: date(2000, 1, 1),
+                updated: None,
+                license: None,
+                figure_index: None,
+                quote_locale: None,
+                tags: vec!["rust".into(), "web-dev".into(), "v2".into()],
+                draft: false,
+                noindex: false,
+                head: None,
+                toc: false,
+                cover: None,
+                description: None,
+                excerpt: None,
+                author: None,
+                template: None,
+                slots: BTreeMap::new(),
+                disable_markdown: Vec::new(),
+                aliases: Vec::new(),
+                katex: KatexOverrides::default(),
             },
         );
+
+        // Parsing should fail if a tag is empty, contains disallowed characters, or is repeated
+        assert_parse_err("---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ntags: [\"\"]\n---");
+        assert_parse_err("---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ntags: [Rust]\n---");
+        assert_parse_err("---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ntags: [a/b]\n---");
+        assert_parse_err("---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ntags: [a, a]\n---");
+    }
+
+    #[test]
+    fn draft() {
+        assert!(
+            Frontmatter::from_text(
+                "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ndraft: true\n---"
+            )
+            .unwrap()
+            .draft
+        );
+    }
+
+    #[test]
+    fn disable_markdown() {
+        let frontmatter = Frontmatter::from_text(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ndisable_markdown: [smart_punctuation, math]\n---",
+        )
+        .unwrap();
+        assert_eq!(
+            frontmatter.disable_markdown,
+            [MarkdownExtension::SmartPunctuation, MarkdownExtension::Math]
+        );
+        let options = frontmatter.markdown_options();
+        assert!(!options.contains(Options::ENABLE_SMART_PUNCTUATION));
+        assert!(!options.contains(Options::ENABLE_MATH));
+        assert!(options.contains(Options::ENABLE_TABLES));
+        assert!(options.contains(Options::ENABLE_TASKLISTS));
+
+        assert_eq!(
+            Frontmatter::from_text("---\ntitle: abc\nslug: def\ncreated: 2000-01-01\n---")
+                .unwrap()
+                .markdown_options(),
+            MARKDOWN_OPTIONS
+        );
+        assert_parse_err(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ndisable_markdown: [yaml]\n---",
+        );
+    }
+
+    #[test]
+    fn description() {
+        let frontmatter = Frontmatter::from_text(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ndescription: A short summary.\n---",
+        )
+        .unwrap();
+        assert_eq!(frontmatter.description.as_deref(), Some("A short summary."));
+        assert_parse_err(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ndescription: \" \"\n---",
+        );
+        assert_parse_err("---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nexcerpt: \"\"\n---");
+    }
+
+    #[test]
+    fn template() {
+        let frontmatter = Frontmatter::from_text(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ntemplate: wide_photo-2\n---",
+        )
+        .unwrap();
+        assert_eq!(frontmatter.template.as_deref(), Some("wide_photo-2"));
+        assert_parse_err("---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ntemplate: ../body\n---");
+        assert_parse_err("---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ntemplate: \"\"\n---");
+    }
+
+    #[test]
+    fn head() {
+        let frontmatter = Frontmatter::from_text(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nhead: <meta name=\"a\" content=\"b\">\n---",
+        )
+        .unwrap();
+        assert_eq!(
+            frontmatter.head.as_deref(),
+            Some(r#"<meta name="a" content="b">"#)
+        );
+        assert_parse_err("---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nhead: <div></div>\n---");
+    }
+
+    #[test]
+    fn slots() {
+        let frontmatter = Frontmatter::from_text(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nslots: {sidebar: parts/side.html}\n---",
+        )
+        .unwrap();
+        assert_eq!(
+            frontmatter.slots,
+            BTreeMap::from([("sidebar".into(), "parts/side.html".into())])
+        );
+        assert_parse_err(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nslots: {sidebar: ../side.html}\n---",
+        );
+        assert_parse_err(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nslots: {\"\": a.html}\n---",
+        );
+    }
+
+    #[test]
+    fn aliases() {
+        let frontmatter = Frontmatter::from_text(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\naliases: [/blog/def/, /old.html]\n---",
+        )
+        .unwrap();
+        assert_eq!(
+            frontmatter.aliases,
+            [Box::from("/blog/def/"), Box::from("/old.html")]
+        );
+        assert_parse_err(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\naliases: [blog/def/]\n---",
+        );
+        assert_parse_err(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\naliases: [/blog/*]\n---",
+        );
+    }
+
+    #[test]
+    fn katex() {
+        let frontmatter = Frontmatter::from_text(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nkatex:\n  strict: true\n  fleqn: true\n  macros:\n    \\RR: \\mathbb{R}\n    \\,: \\;\n---",
+        )
+        .unwrap();
+        assert_eq!(frontmatter.katex.strict, Some(true));
+        assert!(frontmatter.katex.fleqn && !frontmatter.katex.leqno);
+        assert_eq!(
+            frontmatter.katex.macros.get("\\RR").map(AsRef::as_ref),
+            Some("\\mathbb{R}")
+        );
+        assert_eq!(frontmatter.katex.macros.len(), 2);
+        assert_parse_err(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nkatex:\n  macros:\n    RR: \\mathbb{R}\n---",
+        );
+        assert_parse_err(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nkatex:\n  macros:\n    \\R1: \\mathbb{R}\n---",
+        );
+        assert_parse_err(
+            "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\nkatex:\n  output: html\n---",
+        );
+    }
+
+    #[test]
+    fn license_url() {
+        assert_eq!(
+            License::Name("MIT".into()).url().as_deref(),
+            Some("https://spdx.org/licenses/MIT.html")
+        );
+        assert_eq!(License::Name("All rights reserved".into()).url(), None);
+        assert_eq!(
+            License::Link {
+                name: "abc".into(),
+                url: "https://example.com".into()
+            }
+            .url()
+            .as_deref(),
+            Some("https://example.com")
+        );
+    }
+
+    #[test]
+    fn yaml_quirks() {
+        let error = |input: &str| {
+            check_yaml(input)
+                .expect_err("checking should fail")
+                .to_string()
+        };
+
+        assert_eq!(
+            error("---\ntitle: abc\nslug: def\ntitle: ghi\n---"),
+            "line 4: found duplicate key `title` (first set on line 2)"
+        );
+        assert_eq!(
+            error("---\ntitle: abc\nlicense:\n\tname: MIT\n---"),
+            "line 4: tabs cannot be used for indentation"
+        );
+        assert_eq!(
+            error("---\ntitle: abc\ndraft: no # not yet\n---"),
+            "line 3: `no` is ambiguous; use `true` or `false` for a Boolean, or quote it for a string"
+        );
+        assert_eq!(
+            error("---\ntitle: abc\nupdated:\nslug: def\n---"),
+            "line 3: key `updated` has no value"
+        );
+        assert!(error("---\nlicense:\n  name: a\n  name: b\n---").starts_with("line 4:"));
+        assert!(error("---\ntags:\n  - On\n---").starts_with("line 3:"));
+        assert_parse_err("---\ntitle: abc\nslug: def\nslug: ghi\ncreated: 2000-01-01\n---");
+
+        // Keys can repeat in different mappings, and block scalars and quoted values are not checked
+        assert!(
+            check_yaml(
+                "---\ntitle: \"no\"\nlicense:\n  name: a\n  url: b\ntags:\n- a\n- b\n\
+                items:\n  - name: a\n  - name: b\ndescription: |\n  a: b\n  a: b\n\tno\n---\nname: a"
+            )
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn unknown_keys() {
+        assert_eq!(
+            Frontmatter::unknown_keys(
+                "---\ntitle: abc\nslug: def\ncreated: 2000-01-01\ntags: [a]\ntag: b\ndrat: true\n---"
+            )
+            .unwrap(),
+            ["drat", "tag"]
+        );
+        assert!(
+            Frontmatter::unknown_keys("---\ntitle: abc\ntoc: true\ncover: a.png\n---")
+                .unwrap()
+                .is_empty()
+        );
+        assert!(Frontmatter::unknown_keys("abc123").is_err());
     }
 }
@@ -0,0 +1,418 @@
+//! Utility for rendering GPS tracks (from GPX or GeoJSON files) as static SVG maps.
+
+use crate::{
+    local::{copy_local_file, validate_local_src},
+    shortcode::Shortcode,
+    url::AssetUrls,
+    vfs::Vfs,
+};
+use anyhow::{Context, Result, anyhow, bail};
+use camino::Utf8Path;
+use roxmltree::Document;
+use serde_json::Value;
+use std::{f64::consts::FRAC_PI_4, fmt::Write};
+
+// Width of the SVG coordinate system; the height depends on the track's aspect ratio
+const MAP_WIDTH: f64 = 800.0;
+const MAP_MIN_HEIGHT: f64 = 200.0;
+const MAP_MAX_HEIGHT: f64 = 800.0;
+const MAP_PADDING: f64 = 16.0;
+
+pub struct TrackMap<'a> {
+    src: &'a str,
+    title: Option<&'a str>,
+}
+
+impl<'a> TrackMap<'a> {
+    /// Interprets a shortcode of the form `{{ map src="..." title="..." }}` as a track map.
+    /// This function returns `Ok(None)` if the shortcode is not named `map`.
+    ///
+    /// # Errors
+    /// This function returns an error if the shortcode has missing, unknown, or invalid arguments.
+    pub fn from_shortcode(shortcode: &Shortcode<'a>) -> Result<Option<Self>> {
+        if shortcode.name != "map" {
+            return Ok(None);
+        }
+
+        shortcode.expect_args(&["src", "title"])?;
+        let src = shortcode.require("src")?;
+        validate_local_src(src, "track source")?;
+
+        Ok(Some(Self {
+            src,
+            title: shortcode.get("title"),
+        }))
+    }
+
+    /// Consumes the map, returning a `<figure>` with an inline SVG map as a string of HTML for the page of the article with `slug`.
+    /// The track file is copied from `input_dir` to `output_dir` so it can be downloaded.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the track file does not have a `.gpx`, `.geojson`, or `.json` extension
+    /// - the track file cannot be read, parsed, or copied
+    /// - the track contains no points
+    pub fn into_html(
+        self,
+        input_dir: &Utf8Path,
+        output_dir: &Utf8Path,
+        slug: &str,
+        asset_urls: &AssetUrls,
+        vfs: &dyn Vfs,
+    ) -> Result<String> {
+        let input_path = input_dir.join(self.src);
+        let text = vfs
+            .read_to_string(&input_path)
+            .with_context(|| format!("failed to read track file at {input_path}"))?;
+
+        let track = match input_path.extension() {
+            Some("gpx") => Track::from_gpx(&text),
+            Some("geojson" | "json") => Track::from_geojson(&text),
+            _ => bail!("track file must have a .gpx, .geojson, or .json extension"),
+        }
+        .with_context(|| format!("failed to parse track file at {input_path}"))?;
+
+        copy_local_file(self.src, input_dir, output_dir, vfs)?;
+
+        let mut html = String::from(r#"<figure class="__map">"#);
+        html.push_str(&track.to_svg(self.title.unwrap_or("Map of track"))?);
+        write!(
+            html,
+            r#"<figcaption><a href="{}" download>Download track</a></figcaption></figure>"#,
+            escape_attr(&asset_urls.article_local_asset(slug, self.src))
+        )
+        .expect("writing to a string should succeed");
+
+        Ok(html)
+    }
+}
+
+#[derive(Clone, Copy)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+struct Point {
+    lat: f64,
+    lon: f64,
+}
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+struct Track {
+    segments: Vec<Vec<Point>>,
+}
+
+impl Track {
+    /// Parses track segments (`<trkseg>`) and routes (`<rte>`) from a GPX document.
+    fn from_gpx(text: &str) -> Result<Self> {
+        let document = Document::parse(text).context("failed to parse GPX as valid XML")?;
+
+        let segments = document
+            .descendants()
+            .filter(|node| matches!(node.tag_name().name(), "trkseg" | "rte"))
+            .map(|segment| {
+                segment
+                    .children()
+                    .filter(|node| matches!(node.tag_name().name(), "trkpt" | "rtept"))
+                    .map(|point| {
+                        let coord = |name| {
+                            point
+                                .attribute(name)
+                                .ok_or_else(|| anyhow!("GPX point is missing `{name}` attribute"))?
+                                .parse::<f64>()
+                                .with_context(|| {
+                                    format!("GPX point has invalid `{name}` attribute")
+                                })
+                        };
+                        Ok(Point {
+                            lat: coord("lat")?,
+                            lon: coord("lon")?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::new(segments)
+    }
+
+    /// Parses `LineString` and `MultiLineString` geometries from a GeoJSON document.
+    fn from_geojson(text: &str) -> Result<Self> {
+        fn collect(value: &Value, segments: &mut Vec<Vec<Point>>) -> Result<()> {
+            match value.get("type").and_then(Value::as_str) {
+                Some("FeatureCollection") => {
+                    for feature in value
+                        .get("features")
+                        .and_then(Value::as_array)
+                        .ok_or_else(|| anyhow!("feature collection has no `features` array"))?
+                    {
+                        collect(feature, segments)?;
+                    }
+                }
+                Some("Feature") => {
+                    if let Some(geometry) = value.get("geometry") {
+                        collect(geometry, segments)?;
+                    }
+                }
+                Some("GeometryCollection") => {
+                    for geometry in value
+                        .get("geometries")
+                        .and_then(Value::as_array)
+                        .ok_or_else(|| anyhow!("geometry collection has no `geometries` array"))?
+                    {
+                        collect(geometry, segments)?;
+                    }
+                }
+                Some("LineString") => {
+                    segments.push(parse_line(value.get("coordinates"))?);
+                }
+                Some("MultiLineString") => {
+                    for line in value
+                        .get("coordinates")
+                        .and_then(Value::as_array)
+                        .ok_or_else(|| anyhow!("MultiLineString has no `coordinates` array"))?
+                    {
+                        segments.push(parse_line(Some(line))?);
+                    }
+                }
+                // Other geometries (e.g. points and polygons) are not part of a track
+                _ => {}
+            }
+            Ok(())
+        }
+
+        fn parse_line(coordinates: Option<&Value>) -> Result<Vec<Point>> {
+            coordinates
+                .and_then(Value::as_array)
+                .ok_or_else(|| anyhow!("LineString has no `coordinates` array"))?
+                .iter()
+                .map(|position| {
+                    // GeoJSON positions are ordered as [longitude, latitude]
+                    match position.as_array().map(Vec::as_slice) {
+                        Some([lon, lat, ..]) => Ok(Point {
+                            lat: lat
+                                .as_f64()
+                                .ok_or_else(|| anyhow!("latitude is not a number"))?,
+                            lon: lon
+                                .as_f64()
+                                .ok_or_else(|| anyhow!("longitude is not a number"))?,
+                        }),
+                        _ => bail!("position is not an array of at least two numbers"),
+                    }
+                })
+                .collect()
+        }
+
+        let value: Value = serde_json::from_str(text).context("failed to parse GeoJSON")?;
+        let mut segments = Vec::new();
+        collect(&value, &mut segments)?;
+
+        Self::new(segments)
+    }
+
+    fn new(mut segments: Vec<Vec<Point>>) -> Result<Self> {
+        segments.retain(|segment| !segment.is_empty());
+
+        if segments.is_empty() {
+            bail!("track contains no points");
+        }
+
+        for point in segments.iter().flatten() {
+            if !(-90.0..=90.0).contains(&point.lat) || !(-180.0..=180.0).contains(&point.lon) {
+                bail!(
+                    "track contains out-of-range coordinates ({}, {})",
+                    point.lat,
+                    point.lon
+                );
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Renders the track as an SVG element, projected with the Web Mercator projection.
+    /// The first and last points of the track are marked with circles.
+    fn to_svg(&self, title: &str) -> Result<String> {
+        // Project all points to planar coordinates (y increases downward, like SVG)
+        let projected: Vec<Vec<(f64, f64)>> = self
+            .segments
+            .iter()
+            .map(|segment| {
+                segment
+                    .iter()
+                    .map(|point| {
+                        let x = point.lon.to_radians();
+                        // Clamp latitudes to the valid range of Web Mercator
+                        let lat = point.lat.clamp(-85.051_128, 85.051_128).to_radians();
+                        let y = -(FRAC_PI_4 + lat / 2.0).tan().ln();
+                        (x, y)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let (min_x, max_x, min_y, max_y) = projected.iter().flatten().fold(
+            (
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            |(min_x, max_x, min_y, max_y), &(x, y)| {
+                (min_x.min(x), max_x.max(x), min_y.min(y), max_y.max(y))
+            },
+        );
+
+        // Fit the track inside the map while preserving its aspect ratio
+        let (span_x, span_y) = (max_x - min_x, max_y - min_y);
+        let inner_width = MAP_WIDTH - 2.0 * MAP_PADDING;
+        let height = if span_x > 0.0 {
+            (inner_width * span_y / span_x + 2.0 * MAP_PADDING)
+                .clamp(MAP_MIN_HEIGHT, MAP_MAX_HEIGHT)
+        } else {
+            MAP_MAX_HEIGHT
+        };
+        let inner_height = height - 2.0 * MAP_PADDING;
+
+        let scale = match (span_x > 0.0, span_y > 0.0) {
+            (true, true) => (inner_width / span_x).min(inner_height / span_y),
+            (true, false) => inner_width / span_x,
+            (false, true) => inner_height / span_y,
+            (false, false) => 0.0,
+        };
+        let offset_x = (MAP_WIDTH - span_x * scale) / 2.0;
+        let offset_y = (height - span_y * scale) / 2.0;
+        let to_map = |(x, y): (f64, f64)| {
+            (
+                offset_x + (x - min_x) * scale,
+                offset_y + (y - min_y) * scale,
+            )
+        };
+
+        let mut svg = String::new();
+        write!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {MAP_WIDTH} {height:.0}" role="img" aria-label="{}">"#,
+            escape_attr(title)
+        )?;
+
+        for segment in &projected {
+            svg.push_str(r#"<polyline class="__map-track" fill="none" stroke="currentColor" stroke-width="3" stroke-linejoin="round" stroke-linecap="round" points=""#);
+            for (i, &point) in segment.iter().enumerate() {
+                let (x, y) = to_map(point);
+                if i > 0 {
+                    svg.push(' ');
+                }
+                write!(svg, "{x:.1},{y:.1}")?;
+            }
+            svg.push_str(r#""></polyline>"#);
+        }
+
+        let markers = [
+            ("__map-start", projected.first().and_then(|s| s.first())),
+            ("__map-end", projected.last().and_then(|s| s.last())),
+        ];
+        for (class, point) in markers {
+            let (x, y) = to_map(*point.expect("track should contain at least one point"));
+            write!(
+                svg,
+                r#"<circle class="{class}" cx="{x:.1}" cy="{y:.1}" r="6"></circle>"#
+            )?;
+        }
+
+        svg.push_str("</svg>");
+
+        Ok(svg)
+    }
+}
+
+/// Escapes a string for use within a double-quoted HTML attribute value.
+fn escape_attr(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Point, Track};
+
+    #[test]
+    fn gpx() {
+        let track = Track::from_gpx(
+            r#"<?xml version="1.0"?>
+<gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+  <trk><trkseg>
+    <trkpt lat="1.5" lon="2.5"><ele>10</ele></trkpt>
+    <trkpt lat="3" lon="4"></trkpt>
+  </trkseg></trk>
+</gpx>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            track.segments,
+            vec![vec![
+                Point { lat: 1.5, lon: 2.5 },
+                Point { lat: 3.0, lon: 4.0 }
+            ]]
+        );
+    }
+
+    #[test]
+    fn invalid_gpx() {
+        assert!(Track::from_gpx("<gpx>").is_err());
+        assert!(Track::from_gpx("<gpx></gpx>").is_err());
+        assert!(
+            Track::from_gpx(r#"<gpx><trk><trkseg><trkpt lat="1"/></trkseg></trk></gpx>"#).is_err()
+        );
+        assert!(
+            Track::from_gpx(r#"<gpx><trk><trkseg><trkpt lat="100" lon="0"/></trkseg></trk></gpx>"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn geojson() {
+        let track = Track::from_geojson(
+            r#"{"type": "FeatureCollection", "features": [
+                {"type": "Feature", "geometry": {"type": "LineString", "coordinates": [[2.5, 1.5], [4, 3, 100]]}},
+                {"type": "Feature", "geometry": {"type": "Point", "coordinates": [0, 0]}},
+                {"type": "Feature", "geometry": {"type": "MultiLineString", "coordinates": [[[5, 6]]]}}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            track.segments,
+            vec![
+                vec![Point { lat: 1.5, lon: 2.5 }, Point { lat: 3.0, lon: 4.0 }],
+                vec![Point { lat: 6.0, lon: 5.0 }]
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_geojson() {
+        assert!(Track::from_geojson("{").is_err());
+        assert!(Track::from_geojson(r#"{"type": "Point", "coordinates": [0, 0]}"#).is_err());
+        assert!(Track::from_geojson(r#"{"type": "LineString", "coordinates": [[0]]}"#).is_err());
+    }
+
+    #[test]
+    fn svg() {
+        let track = Track::from_geojson(
+            r#"{"type": "LineString", "coordinates": [[0, 0], [1, 1], [2, 0]]}"#,
+        )
+        .unwrap();
+        let svg = track.to_svg("A \"title\"").unwrap();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"aria-label="A &quot;title&quot;""#));
+        assert_eq!(svg.matches("<polyline").count(), 1);
+        assert_eq!(svg.matches("<circle").count(), 2);
+
+        // A single point should not cause division by zero
+        let track =
+            Track::from_geojson(r#"{"type": "LineString", "coordinates": [[0, 0]]}"#).unwrap();
+        assert!(!track.to_svg("abc").unwrap().contains("NaN"));
+    }
+}
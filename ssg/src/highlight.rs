@@ -1,16 +1,33 @@
 //! Utility for highlighting code in articles by converting Markdown code blocks to styled HTML.
 
-use anyhow::{Result, anyhow};
+#[cfg(feature = "highlight")]
+use crate::{
+    ansi::{ANSI_LANGUAGE, AnsiRenderer},
+    component::escape_html,
+    config::Config,
+    vfs::Vfs,
+};
+use anyhow::{Context, Result, anyhow, bail};
+#[cfg(feature = "highlight")]
+use camino::Utf8Path;
+#[cfg(feature = "highlight")]
+use foldhash::{HashMap, HashMapExt};
 use phf::{Set, phf_set};
-use std::borrow::Cow;
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+#[cfg(feature = "highlight")]
+use std::{fmt::Write, io::Cursor, ops::Range};
+#[cfg(feature = "highlight")]
 use syntect::{
-    easy::HighlightLines,
-    highlighting::{FontStyle, Style, Theme, ThemeSet, ThemeSettings},
+    highlighting::{
+        Color, FontStyle, HighlightIterator, HighlightState, Highlighter, Style, Theme, ThemeSet,
+        ThemeSettings,
+    },
     html::{
         IncludeBackground, append_highlighted_html_for_styled_line, start_highlighted_html_snippet,
         styled_line_to_highlighted_html,
     },
-    parsing::SyntaxSet,
+    parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet},
     util::LinesWithEndings,
 };
 
@@ -26,11 +43,204 @@ pub(crate) const THEME_NAMES: Set<&str> = phf_set! {
     "Solarized (light)",
 };
 
+// Class of elements wrapping lines that are highlighted with `hl_lines`
+#[cfg(feature = "highlight")]
+const HIGHLIGHTED_LINE_CLASS: &str = "__highlighted-line";
+// Classes of elements wrapping labeled or copyable code blocks and containing their labels
+#[cfg(feature = "highlight")]
+const CODE_BLOCK_CLASS: &str = "__code-block";
+#[cfg(feature = "highlight")]
+const LANGUAGE_LABEL_CLASS: &str = "__code-language";
+// Attribute of elements wrapping code blocks that get a copy button
+#[cfg(feature = "highlight")]
+const COPY_CODE_ATTRIBUTE: &str = "data-copy-code";
+// Class of elements containing visualized whitespace
+#[cfg(feature = "highlight")]
+const WHITESPACE_CLASS: &str = "__whitespace";
+// Prefix of languages for diffs of code in another language (e.g. `diff-rust`)
+#[cfg(feature = "highlight")]
+const DIFF_LANGUAGE_PREFIX: &str = "diff-";
+// Classes and translucent background colors of added and removed lines in diffs, which suit light and dark themes
+#[cfg(feature = "highlight")]
+const DIFF_ADDED_HTML: &str =
+    "<span class=\"__diff-added\" style=\"display:block;background-color:#2ea04333;\">";
+#[cfg(feature = "highlight")]
+const DIFF_REMOVED_HTML: &str =
+    "<span class=\"__diff-removed\" style=\"display:block;background-color:#f8514933;\">";
+
+/// Language and options of a fenced code block, parsed from its info string (e.g. `rust hl_lines=1,3-5`).
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CodeBlockInfo {
+    pub language: Option<String>,
+    /// Ranges of 1-indexed line numbers to highlight
+    pub highlighted_lines: Vec<RangeInclusive<usize>>,
+    /// Number of spaces that tabs in indentation are replaced with; the site-wide setting is used if absent
+    pub tab_width: Option<usize>,
+    /// Whether to visualize tabs and trailing whitespace; the site-wide setting is used if absent
+    pub show_whitespace: Option<bool>,
+}
+
+impl CodeBlockInfo {
+    /// Parses the info string of a fenced code block.
+    /// The first token is the language; the remaining tokens are `key=value` attributes,
+    /// separated by whitespace or commas (e.g. `go,tabwidth=8`). Supported attributes:
+    /// - `hl_lines`: comma-separated list of line numbers and ranges to highlight
+    /// - `tabwidth`: number of spaces that tabs in indentation are replaced with
+    /// - `show_whitespace`: `true` or `false`
+    ///
+    /// # Errors
+    /// This function returns an error if an attribute is unknown or has an invalid value.
+    pub fn parse(info: &str) -> Result<Self> {
+        let mut segments = info
+            .split(|c: char| c.is_ascii_whitespace() || c == ',')
+            .filter(|segment| !segment.is_empty())
+            .peekable();
+        let mut output = Self {
+            language: segments
+                .next_if(|segment| !segment.contains('='))
+                .map(ToOwned::to_owned),
+            ..Self::default()
+        };
+
+        // Segments without `=` continue the list of values of the preceding attribute
+        let mut attributes: Vec<(&str, Vec<&str>)> = Vec::new();
+        for segment in segments {
+            match segment.split_once('=') {
+                Some((key, value)) => attributes.push((key, vec![value])),
+                None => match attributes.last_mut() {
+                    Some((_, values)) => values.push(segment),
+                    None => {
+                        bail!("code block attribute \"{segment}\" should have the form `key=value`")
+                    }
+                },
+            }
+        }
+
+        for (key, values) in attributes {
+            let single_value = || match values.as_slice() {
+                [value] => Ok(*value),
+                _ => Err(anyhow!(
+                    "code block attribute \"{key}\" should have a single value"
+                )),
+            };
+            match key {
+                "hl_lines" => {
+                    for range in values {
+                        output.highlighted_lines.push(
+                            parse_line_range(range)
+                                .with_context(|| format!("invalid line range \"{range}\""))?,
+                        );
+                    }
+                }
+                "tabwidth" => {
+                    let value = single_value()?;
+                    match value.parse() {
+                        Ok(width) if width > 0 => output.tab_width = Some(width),
+                        _ => bail!("tab width should be a positive integer, not \"{value}\""),
+                    }
+                }
+                "show_whitespace" => {
+                    let value = single_value()?;
+                    output.show_whitespace = Some(value.parse().with_context(|| {
+                        format!("`show_whitespace` should be `true` or `false`, not \"{value}\"")
+                    })?);
+                }
+                _ => bail!("unknown code block attribute \"{key}\""),
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+/// Returns the parts of the styled regions of a highlighted line that fall within a byte range of the line.
+#[cfg(feature = "highlight")]
+fn slice_regions<'a>(regions: &[(Style, &'a str)], range: Range<usize>) -> Vec<(Style, &'a str)> {
+    let mut output = Vec::with_capacity(regions.len());
+    let mut start = 0;
+    for &(style, text) in regions {
+        let end = start + text.len();
+        let (slice_start, slice_end) = (range.start.max(start), range.end.min(end));
+        if slice_start < slice_end {
+            output.push((style, &text[slice_start - start..slice_end - start]));
+        }
+        start = end;
+    }
+    output
+}
+
+/// Appends whitespace to `output`, with tabs shown as arrows followed by spaces up to the tab width.
+/// If `mark_spaces` is true, spaces are shown as middle dots.
+#[cfg(feature = "highlight")]
+fn push_whitespace(
+    output: &mut String,
+    opening_html: &str,
+    whitespace: &str,
+    tab_width: usize,
+    mark_spaces: bool,
+) {
+    if !whitespace.contains('\t') && (!mark_spaces || whitespace.is_empty()) {
+        output.push_str(whitespace);
+        return;
+    }
+    output.push_str(opening_html);
+    for c in whitespace.chars() {
+        match c {
+            '\t' => {
+                output.push('→');
+                output.push_str(&" ".repeat(tab_width - 1));
+            }
+            ' ' if mark_spaces => output.push('·'),
+            c => output.push(c),
+        }
+    }
+    output.push_str("</span>");
+}
+
+/// Parses a 1-indexed line number (e.g. `3`) or inclusive range of line numbers (e.g. `3-5`).
+fn parse_line_range(range: &str) -> Result<RangeInclusive<usize>> {
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let start: usize = start.parse()?;
+    let end: usize = end.parse()?;
+    if start == 0 {
+        bail!("line numbers start at 1");
+    }
+    if start > end {
+        bail!("range should not end before it starts");
+    }
+    Ok(start..=end)
+}
+
+/// How inline code segments are styled.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum InlineCodeStyle {
+    /// Text and background colors of the theme
+    #[default]
+    Background,
+    /// Text color of the theme, without a background
+    Foreground,
+    /// No inline styles, leaving styling to the site CSS
+    Plain,
+}
+
+#[cfg(feature = "highlight")]
 pub struct SyntaxHighlighter {
     syntaxes: SyntaxSet,
     theme: Theme,
+    // Theme used instead of `theme` when the page is displayed in dark mode
+    dark_theme: Option<Theme>,
+    inline_style: InlineCodeStyle,
+    // Theme for inline code segments, if different from the theme for code blocks
+    inline_theme: Option<Theme>,
+    // Syntax names for language tokens that don't match a syntax on their own (e.g. `jsx` -> "JavaScript")
+    language_aliases: HashMap<Box<str>, Box<str>>,
+    // Defaults for code blocks that don't set `tabwidth` or `show_whitespace`
+    tab_width: usize,
+    show_whitespace: bool,
 }
 
+#[cfg(feature = "highlight")]
 impl SyntaxHighlighter {
     /// Initializes a utility to add syntax highlighting to code.
     /// Hightlighting styles are based on the input theme.
@@ -40,65 +250,306 @@ impl SyntaxHighlighter {
     /// This function panics if the default theme set of `syntect` does not contain the input theme.
     #[must_use]
     pub fn new(theme: &str) -> Self {
-        let syntaxes = SyntaxSet::load_defaults_newlines();
+        Self::with_theme(load_theme(theme))
+    }
 
-        // To obtain an owned `Theme`, we call `BTreeMap::remove()` instead of `BTreeMap::get()`.
-        // This is fine because we do not need the entire `ThemeSet` after this.
-        // (If we did, we could just call `ThemeSet::load_defaults()` again.)
-        let Some(theme) = ThemeSet::load_defaults().themes.remove(theme) else {
-            panic!("default theme set should include \"{theme}\"");
+    /// Initializes a utility to add syntax highlighting to code, set up with the code themes,
+    /// inline code style, whitespace settings, and language aliases in the config file.
+    ///
+    /// # Errors
+    /// This function returns an error if no code theme is configured, a theme file can't be loaded,
+    /// or a language alias is invalid.
+    pub fn from_config(config: &Config, vfs: &dyn Vfs) -> Result<Self> {
+        let syntax_highlighter = match (&config.code_theme_file, &config.code_theme) {
+            (Some(path), _) => Self::from_theme_file(path, vfs)?,
+            (None, Some(theme)) => Self::new(theme),
+            (None, None) => bail!("no code theme is configured"),
         };
+        match (&config.code_theme_dark_file, &config.code_theme_dark) {
+            (Some(path), _) => syntax_highlighter.with_dark_theme_file(path, vfs)?,
+            (None, Some(theme)) => syntax_highlighter.with_dark_theme(theme),
+            (None, None) => syntax_highlighter,
+        }
+        .with_inline_code(
+            config.inline_code_style,
+            config.inline_code_theme.as_deref(),
+        )
+        .with_whitespace(config.code_tab_width, config.show_code_whitespace)
+        .with_language_aliases(
+            config
+                .code_language_aliases
+                .iter()
+                .map(|(token, name)| (&**token, &**name)),
+        )
+        .context("invalid `code_language_aliases`")
+    }
+
+    /// Initializes a utility to add syntax highlighting to code, with styles from a `.tmTheme` file.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the file can't be read or isn't a valid `.tmTheme` file
+    /// - the theme doesn't contain default text and background colors
+    pub fn from_theme_file(path: &Utf8Path, vfs: &dyn Vfs) -> Result<Self> {
+        Ok(Self::with_theme(load_theme_file(path, vfs)?))
+    }
+
+    fn with_theme(theme: Theme) -> Self {
+        let syntaxes = SyntaxSet::load_defaults_newlines();
+
+        Self {
+            syntaxes,
+            theme,
+            dark_theme: None,
+            inline_style: InlineCodeStyle::default(),
+            inline_theme: None,
+            language_aliases: HashMap::new(),
+            tab_width: 4,
+            show_whitespace: false,
+        }
+    }
+
+    /// Sets a theme to use instead of the main theme when the page is displayed in dark mode.
+    /// Colors switch between the themes with the CSS `light-dark()` function,
+    /// which follows the page's `color-scheme`.
+    ///
+    /// # Panics
+    /// This function panics if the default theme set of `syntect` does not contain the input theme.
+    #[must_use]
+    pub fn with_dark_theme(mut self, theme: &str) -> Self {
+        self.dark_theme = Some(load_theme(theme));
+        self
+    }
+
+    /// Sets a theme from a `.tmTheme` file to use instead of the main theme when the page is displayed in dark mode.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the file can't be read or isn't a valid `.tmTheme` file
+    /// - the theme doesn't contain default text and background colors
+    pub fn with_dark_theme_file(mut self, path: &Utf8Path, vfs: &dyn Vfs) -> Result<Self> {
+        self.dark_theme = Some(load_theme_file(path, vfs)?);
+        Ok(self)
+    }
+
+    /// Maps language tokens to the names of syntaxes (e.g. `jsx` to "JavaScript").
+    /// Aliases take precedence over the syntaxes found for tokens by default.
+    ///
+    /// # Errors
+    /// This function returns an error if no syntax has the name an alias maps to.
+    pub fn with_language_aliases<'a>(
+        mut self,
+        aliases: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<Self> {
+        for (token, name) in aliases {
+            if self.syntaxes.find_syntax_by_name(name).is_none() {
+                bail!("no syntax named \"{name}\" exists for the language alias \"{token}\"");
+            }
+            self.language_aliases.insert(token.into(), name.into());
+        }
+        Ok(self)
+    }
 
-        Self { syntaxes, theme }
+    /// Sets the default tab width and whitespace visualization of code blocks.
+    #[must_use]
+    pub const fn with_whitespace(mut self, tab_width: usize, show_whitespace: bool) -> Self {
+        self.tab_width = tab_width;
+        self.show_whitespace = show_whitespace;
+        self
+    }
+
+    /// Sets how inline code segments are styled, optionally with a different theme than code blocks.
+    ///
+    /// # Panics
+    /// This function panics if the default theme set of `syntect` does not contain the input theme.
+    #[must_use]
+    pub fn with_inline_code(mut self, style: InlineCodeStyle, theme: Option<&str>) -> Self {
+        self.inline_style = style;
+        self.inline_theme = theme.map(load_theme);
+        self
     }
 
     /// Adds syntax highlighting to a code block, outputting HTML with inline styles.
     /// If no language is provided, the input string is highlighted as plaintext.
+    /// Highlighted lines are wrapped in a block with the theme's line highlight color.
+    /// Tabs in indentation are replaced with spaces, and whitespace is visualized if enabled.
+    /// Code blocks in the `ansi` language are terminal transcripts, which are styled by their ANSI escape sequences
+    /// instead of a syntax (without tab replacement or whitespace visualization).
+    /// Code blocks in a `diff-` language (e.g. `diff-rust`) are diffs: lines starting with `+` or `-` get
+    /// added or removed backgrounds, and the rest of every line is highlighted in the language after the prefix.
     ///
     /// # Errors
     /// This function returns an error if:
     /// - no syntax can be found for the provided language
+    /// - a highlighted line range extends past the end of the code block
     /// - `syntect` fails to highlight the provided text
-    pub fn highlight_block(&self, text: &str, language: Option<&str>) -> Result<String> {
-        // Find language syntax
-        let syntax = match language {
-            Some(lang) if !lang.is_empty() => {
-                self.syntaxes.find_syntax_by_token(lang).ok_or_else(|| {
-                    anyhow!("no syntax could be found for the provided language \"{lang}\"")
-                })?
-            }
-            _ => self.syntaxes.find_syntax_plain_text(),
+    pub fn highlight_block(&self, text: &str, info: &CodeBlockInfo) -> Result<String> {
+        let mut ansi_renderer =
+            (info.language.as_deref() == Some(ANSI_LANGUAGE)).then(AnsiRenderer::default);
+        let diff_language = info
+            .language
+            .as_deref()
+            .and_then(|language| language.strip_prefix(DIFF_LANGUAGE_PREFIX));
+        let syntax = if ansi_renderer.is_some() {
+            self.syntaxes.find_syntax_plain_text()
+        } else {
+            self.find_syntax(diff_language.or(info.language.as_deref()))?
         };
 
-        // Highlight line by line
-        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        let num_lines = LinesWithEndings::from(text).count();
+        if let Some(range) = info
+            .highlighted_lines
+            .iter()
+            .find(|range| *range.end() > num_lines)
+        {
+            bail!(
+                "highlighted line range {}-{} extends past the end of the code block ({num_lines} lines)",
+                range.start(),
+                range.end(),
+            );
+        }
+        let dark_settings = self.dark_theme.as_ref().map(|theme| &theme.settings);
+        let highlighted_line_html = match self.theme.settings.line_highlight {
+            Some(color) => format!(
+                "<span class=\"{HIGHLIGHTED_LINE_CLASS}\" style=\"display:block;background-color:{};\">",
+                dual_css_color(
+                    color,
+                    dark_settings.and_then(|settings| settings.line_highlight)
+                ),
+            ),
+            None => format!("<span class=\"{HIGHLIGHTED_LINE_CLASS}\" style=\"display:block;\">"),
+        };
+
+        let tab_width = info.tab_width.unwrap_or(self.tab_width);
+        let show_whitespace = info.show_whitespace.unwrap_or(self.show_whitespace);
+        let whitespace_html = match self.theme.settings.guide {
+            Some(color) => format!(
+                "<span class=\"{WHITESPACE_CLASS}\" style=\"color:{};\">",
+                dual_css_color(color, dark_settings.and_then(|settings| settings.guide)),
+            ),
+            None => format!("<span class=\"{WHITESPACE_CLASS}\">"),
+        };
+
+        // Highlight line by line; lines are parsed once and styled with each theme
+        let mut parse_state = ParseState::new(syntax);
+        let highlighter = Highlighter::new(&self.theme);
+        let mut highlight_state = HighlightState::new(&highlighter, ScopeStack::new());
+        let dark_highlighter = self.dark_theme.as_ref().map(Highlighter::new);
+        let mut dark_highlight_state = dark_highlighter
+            .as_ref()
+            .map(|highlighter| HighlightState::new(highlighter, ScopeStack::new()));
         let (mut output, background) = start_highlighted_html_snippet(&self.theme);
+        let dark_background = dark_settings.and_then(|settings| settings.background);
+        if let Some(dark_background) = dark_background {
+            output = format!(
+                "<pre style=\"background-color:{};\">\n",
+                dual_css_color(background, Some(dark_background)),
+            );
+        }
+        // Unstyled text in terminal transcripts has the theme's text color
+        if ansi_renderer.is_some()
+            && let Some(foreground) = self.theme.settings.foreground
+        {
+            output = format!(
+                "<pre style=\"background-color:{};color:{};\">\n",
+                dual_css_color(background, dark_background),
+                dual_css_color(
+                    foreground,
+                    dark_settings.and_then(|settings| settings.foreground)
+                ),
+            );
+        }
 
-        for line in LinesWithEndings::from(text) {
-            // Replace starting tabs with spaces (1 tab = 4 spaces)
-            let num_starting_whitespace_bytes: usize = line
-                .chars()
-                .take_while(|c| c.is_whitespace())
-                .map(char::len_utf8)
-                .sum();
-
-            let line = if num_starting_whitespace_bytes > 0 {
-                let (whitespace, remaining) = line.split_at(num_starting_whitespace_bytes);
-                let mut line = whitespace.replace('\t', "    ");
-                line.reserve_exact(remaining.len());
-                line.push_str(remaining);
-                Cow::Owned(line)
+        for (i, line) in LinesWithEndings::from(text).enumerate() {
+            let is_highlighted = info
+                .highlighted_lines
+                .iter()
+                .any(|range| range.contains(&(i + 1)));
+            if is_highlighted {
+                output.push_str(&highlighted_line_html);
+            }
+
+            if let Some(ansi_renderer) = &mut ansi_renderer {
+                ansi_renderer.push_line(&mut output, line);
+                if is_highlighted {
+                    output.push_str("</span>");
+                }
+                continue;
+            }
+
+            // Diff markers are kept as unstyled text, and the rest of the line is highlighted as code
+            let mut line = line;
+            let mut is_changed_line = false;
+            if diff_language.is_some()
+                && let Some(marker @ ('+' | '-' | ' ')) = line.chars().next()
+            {
+                match marker {
+                    '+' => output.push_str(DIFF_ADDED_HTML),
+                    '-' => output.push_str(DIFF_REMOVED_HTML),
+                    _ => {}
+                }
+                is_changed_line = marker != ' ';
+                output.push(marker);
+                line = &line[1..];
+            }
+
+            let content = line.trim_end_matches(['\n', '\r']);
+            let trimmed = content.trim_start();
+            // Whitespace-only lines consist entirely of trailing whitespace
+            let (indent, trimmed) = if trimmed.trim_end().is_empty() {
+                ("", content)
             } else {
-                Cow::Borrowed(line)
+                content.split_at(content.len() - trimmed.len())
             };
+            let (code, trailing) = trimmed.split_at(trimmed.trim_end().len());
+
+            // Replace tabs in indentation with spaces
+            let expanded_indent = indent.replace('\t', &" ".repeat(tab_width));
+            let expanded_line = format!("{expanded_indent}{trimmed}{}", &line[content.len()..]);
 
             // Highlight line
-            let regions = highlighter.highlight_line(&line, &self.syntaxes)?;
-            append_highlighted_html_for_styled_line(
-                &regions,
-                IncludeBackground::IfDifferent(background),
-                &mut output,
-            )?;
+            let ops = parse_state.parse_line(&expanded_line, &self.syntaxes)?;
+            let regions: Vec<_> =
+                HighlightIterator::new(&mut highlight_state, &ops, &expanded_line, &highlighter)
+                    .collect();
+            let dark_regions: Option<Vec<_>> = dark_highlighter
+                .as_ref()
+                .zip(dark_highlight_state.as_mut())
+                .map(|(highlighter, state)| {
+                    HighlightIterator::new(state, &ops, &expanded_line, highlighter).collect()
+                });
+
+            // Indentation and trailing whitespace are replaced with visible markers if enabled,
+            // so only the code in between is styled by the theme
+            let styled_range = if show_whitespace {
+                push_whitespace(&mut output, &whitespace_html, indent, tab_width, false);
+                expanded_indent.len()..expanded_indent.len() + code.len()
+            } else {
+                0..expanded_line.len()
+            };
+            let regions = slice_regions(&regions, styled_range.clone());
+            match (dark_regions, dark_background) {
+                (Some(dark_regions), Some(dark_background)) => push_dual_regions(
+                    &mut output,
+                    &merge_regions(&regions, &slice_regions(&dark_regions, styled_range)),
+                    (background, dark_background),
+                ),
+                _ => append_highlighted_html_for_styled_line(
+                    &regions,
+                    IncludeBackground::IfDifferent(background),
+                    &mut output,
+                )?,
+            }
+            if show_whitespace {
+                push_whitespace(&mut output, &whitespace_html, trailing, tab_width, true);
+                output.push_str(&line[content.len()..]);
+            }
+            if is_changed_line {
+                output.push_str("</span>");
+            }
+            if is_highlighted {
+                output.push_str("</span>");
+            }
         }
 
         // Add closing tag; the opening tag was added in `start_highlighted_html_snippet()`
@@ -110,7 +561,70 @@ impl SyntaxHighlighter {
         Ok(output)
     }
 
-    /// Adds plaintext highlighting to an inline code segment, outputting HTML with inline styles.
+    /// Returns HTML for the start of a wrapper around a code block, or `None` if the code block needs no wrapper.
+    /// With `label`, the wrapper contains a label with the name of the code block's syntax (e.g. "Rust" for `rs`),
+    /// unless no language is provided. With `copy_button`, the wrapper has a `data-copy-code` attribute,
+    /// which the page's script uses to add a button for copying the code.
+    /// The wrapper should be closed with `</div>` after the code block.
+    ///
+    /// # Errors
+    /// This function returns an error if no syntax can be found for the provided language.
+    pub fn code_block_opening_html(
+        &self,
+        language: Option<&str>,
+        label: bool,
+        copy_button: bool,
+    ) -> Result<Option<String>> {
+        let name = match language {
+            Some(ANSI_LANGUAGE) => "Terminal".into(),
+            Some(language) if let Some(language) = language.strip_prefix(DIFF_LANGUAGE_PREFIX) => {
+                format!("{} diff", self.find_syntax(Some(language))?.name)
+            }
+            language => self.find_syntax(language)?.name.clone(),
+        };
+        let label = label && name != self.syntaxes.find_syntax_plain_text().name;
+        if !label && !copy_button {
+            return Ok(None);
+        }
+
+        let mut output = format!("<div class=\"{CODE_BLOCK_CLASS}\"");
+        if copy_button {
+            output.push(' ');
+            output.push_str(COPY_CODE_ATTRIBUTE);
+        }
+        output.push('>');
+        if label {
+            write!(
+                output,
+                "<span class=\"{LANGUAGE_LABEL_CLASS}\">{}</span>",
+                escape_html(&name)
+            )
+            .expect("writing to a string should succeed");
+        }
+        Ok(Some(output))
+    }
+
+    /// Finds the syntax for a language alias, token, or file extension,
+    /// defaulting to plaintext if none is provided.
+    fn find_syntax(&self, language: Option<&str>) -> Result<&SyntaxReference> {
+        match language {
+            Some(lang)
+                if let Some(name) = self.language_aliases.get(lang)
+                    && let Some(syntax) = self.syntaxes.find_syntax_by_name(name) =>
+            {
+                Ok(syntax)
+            }
+            Some(lang) if !lang.is_empty() => {
+                self.syntaxes.find_syntax_by_token(lang).ok_or_else(|| {
+                    anyhow!("no syntax could be found for the provided language \"{lang}\"")
+                })
+            }
+            _ => Ok(self.syntaxes.find_syntax_plain_text()),
+        }
+    }
+
+    /// Adds plaintext highlighting to an inline code segment, outputting HTML with inline styles
+    /// according to the configured inline code style.
     ///
     /// # Errors
     /// This function returns an error if `syntect` fails to highlight the provided text.
@@ -118,18 +632,45 @@ impl SyntaxHighlighter {
     /// # Panics
     /// This function panics if the selected theme does not contain default text and background colors.
     pub fn highlight_segment(&self, text: &str) -> Result<String> {
+        let include_background = match self.inline_style {
+            InlineCodeStyle::Background => IncludeBackground::Yes,
+            InlineCodeStyle::Foreground => IncludeBackground::No,
+            InlineCodeStyle::Plain => return Ok(format!("<code>{}</code>", escape_html(text))),
+        };
+
+        let theme = self.inline_theme.as_ref().unwrap_or(&self.theme);
         let ThemeSettings {
             foreground: Some(foreground),
             background: Some(background),
             ..
-        } = self.theme.settings
+        } = theme.settings
         else {
             panic!(
                 "\"{}\" should contain default text and background colors",
-                self.theme.name.as_deref().unwrap_or("selected theme"),
+                theme.name.as_deref().unwrap_or("selected theme"),
             );
         };
 
+        // In dark mode, inline code uses the dark theme's colors
+        if let Some(ThemeSettings {
+            foreground: Some(dark_foreground),
+            background: Some(dark_background),
+            ..
+        }) = self.dark_theme.as_ref().map(|theme| &theme.settings)
+        {
+            let mut style = String::new();
+            if let IncludeBackground::Yes = include_background {
+                let background = dual_css_color(background, Some(*dark_background));
+                write!(style, "background-color:{background};")?;
+            }
+            let foreground = dual_css_color(foreground, Some(*dark_foreground));
+            write!(style, "color:{foreground};")?;
+            return Ok(format!(
+                "<code><span style=\"{style}\">{}</span></code>",
+                escape_html(text),
+            ));
+        }
+
         let style = Style {
             foreground,
             background,
@@ -138,15 +679,134 @@ impl SyntaxHighlighter {
 
         Ok(format!(
             "<code>{}</code>",
-            styled_line_to_highlighted_html(&[(style, text)], IncludeBackground::Yes)?
+            styled_line_to_highlighted_html(&[(style, text)], include_background)?
         ))
     }
 }
 
-#[cfg(test)]
+/// Loads a theme from a `.tmTheme` file, checking that it contains default text and background colors.
+#[cfg(feature = "highlight")]
+fn load_theme_file(path: &Utf8Path, vfs: &dyn Vfs) -> Result<Theme> {
+    let bytes = vfs
+        .read(path)
+        .with_context(|| format!("failed to read highlighting theme at {path}"))?;
+    let theme = ThemeSet::load_from_reader(&mut Cursor::new(bytes))
+        .with_context(|| format!("failed to load highlighting theme from {path}"))?;
+    if theme.settings.foreground.is_none() || theme.settings.background.is_none() {
+        bail!("highlighting theme at {path} should contain default text and background colors");
+    }
+    Ok(theme)
+}
+
+/// Returns a CSS color value, with an alpha channel if the color isn't opaque.
+#[cfg(feature = "highlight")]
+fn css_color(color: Color) -> String {
+    let Color { r, g, b, a } = color;
+    if a == 0xFF {
+        format!("#{r:02x}{g:02x}{b:02x}")
+    } else {
+        format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+    }
+}
+
+/// Returns a CSS color value that switches to a different color in dark mode, if one is provided.
+#[cfg(feature = "highlight")]
+fn dual_css_color(light: Color, dark: Option<Color>) -> String {
+    match dark {
+        Some(dark) if dark != light => {
+            format!("light-dark({},{})", css_color(light), css_color(dark))
+        }
+        _ => css_color(light),
+    }
+}
+
+/// Combines the styled regions of a line highlighted with a light theme and a dark theme,
+/// splitting regions so that each has a single style in both themes.
+#[cfg(feature = "highlight")]
+fn merge_regions<'a>(
+    light: &[(Style, &'a str)],
+    dark: &[(Style, &'a str)],
+) -> Vec<(Style, Style, &'a str)> {
+    let mut output = Vec::with_capacity(light.len().max(dark.len()));
+    let (mut light_iter, mut dark_iter) = (light.iter().copied(), dark.iter().copied());
+    let (mut light_region, mut dark_region) = (light_iter.next(), dark_iter.next());
+    while let (Some((light_style, light_text)), Some((dark_style, dark_text))) =
+        (light_region, dark_region)
+    {
+        let len = light_text.len().min(dark_text.len());
+        if len > 0 {
+            output.push((light_style, dark_style, &light_text[..len]));
+        }
+        light_region = if len == light_text.len() {
+            light_iter.next()
+        } else {
+            Some((light_style, &light_text[len..]))
+        };
+        dark_region = if len == dark_text.len() {
+            dark_iter.next()
+        } else {
+            Some((dark_style, &dark_text[len..]))
+        };
+    }
+    output
+}
+
+/// Appends HTML for regions styled with both a light and a dark theme to `output`.
+/// Backgrounds are only included if they differ from the code block's background in either theme.
+#[cfg(feature = "highlight")]
+fn push_dual_regions(
+    output: &mut String,
+    regions: &[(Style, Style, &str)],
+    (background, dark_background): (Color, Color),
+) {
+    for &(light, dark, text) in regions {
+        output.push_str("<span style=\"");
+        if light.background != background || dark.background != dark_background {
+            output.push_str("background-color:");
+            output.push_str(&dual_css_color(light.background, Some(dark.background)));
+            output.push(';');
+        }
+        if light.font_style.contains(FontStyle::UNDERLINE) {
+            output.push_str("text-decoration:underline;");
+        }
+        if light.font_style.contains(FontStyle::BOLD) {
+            output.push_str("font-weight:bold;");
+        }
+        if light.font_style.contains(FontStyle::ITALIC) {
+            output.push_str("font-style:italic;");
+        }
+        output.push_str("color:");
+        output.push_str(&dual_css_color(light.foreground, Some(dark.foreground)));
+        output.push_str(";\">");
+        output.push_str(&escape_html(text));
+        output.push_str("</span>");
+    }
+}
+
+/// Loads a theme from the default theme set of `syntect`.
+///
+/// # Panics
+/// This function panics if the default theme set does not contain the input theme.
+#[cfg(feature = "highlight")]
+fn load_theme(theme: &str) -> Theme {
+    // To obtain an owned `Theme`, we call `BTreeMap::remove()` instead of `BTreeMap::get()`.
+    // This is fine because we do not need the entire `ThemeSet` after this.
+    // (If we did, we could just call `ThemeSet::load_defaults()` again.)
+    let Some(owned) = ThemeSet::load_defaults().themes.remove(theme) else {
+        panic!("default theme set should include \"{theme}\"");
+    };
+    owned
+}
+
+#[cfg(all(test, feature = "highlight"))]
 mod test {
-    use super::{SyntaxHighlighter, THEME_NAMES};
+    use super::{
+        CodeBlockInfo, HIGHLIGHTED_LINE_CLASS, InlineCodeStyle, SyntaxHighlighter, THEME_NAMES,
+        WHITESPACE_CLASS,
+    };
+    use crate::vfs::MemoryFs;
     use anyhow::Result;
+    use camino::Utf8Path;
 
     #[test]
     fn plaintext() -> Result<()> {
@@ -154,7 +814,7 @@ mod test {
             let highlighter = SyntaxHighlighter::new(theme);
 
             highlighter.highlight_segment("abc123")?;
-            highlighter.highlight_block("abc123", None)?;
+            highlighter.highlight_block("abc123", &CodeBlockInfo::default())?;
         }
         Ok(())
     }
@@ -162,7 +822,8 @@ mod test {
     #[test]
     fn extension_based_syntax_detection() -> Result<()> {
         for theme in &THEME_NAMES {
-            SyntaxHighlighter::new(theme).highlight_block("const FOO: usize = 42;", Some("rs"))?;
+            SyntaxHighlighter::new(theme)
+                .highlight_block("const FOO: usize = 42;", &CodeBlockInfo::parse("rs")?)?;
         }
         Ok(())
     }
@@ -171,7 +832,7 @@ mod test {
     fn name_based_syntax_detection() -> Result<()> {
         for theme in &THEME_NAMES {
             SyntaxHighlighter::new(theme)
-                .highlight_block("const FOO: usize = 42;", Some("rust"))?;
+                .highlight_block("const FOO: usize = 42;", &CodeBlockInfo::parse("rust")?)?;
         }
         Ok(())
     }
@@ -180,7 +841,7 @@ mod test {
     fn invalid_syntax() -> Result<()> {
         for theme in &THEME_NAMES {
             SyntaxHighlighter::new(theme)
-                .highlight_block("constant foo u0 = \"abc", Some("rust"))?;
+                .highlight_block("constant foo u0 = \"abc", &CodeBlockInfo::parse("rust")?)?;
         }
         Ok(())
     }
@@ -190,7 +851,7 @@ mod test {
         for theme in &THEME_NAMES {
             assert!(
                 SyntaxHighlighter::new(theme)
-                    .highlight_block("abc", Some("klingon"))
+                    .highlight_block("abc", &CodeBlockInfo::parse("klingon").unwrap())
                     .is_err(),
                 "syntax detection for non-existent language should fail"
             );
@@ -220,11 +881,313 @@ abc
             let highlighter = SyntaxHighlighter::new(theme);
 
             assert_eq!(
-                highlighter.highlight_block(TEXT_SPACES, None)?,
-                highlighter.highlight_block(TEXT_TABS, None)?,
+                highlighter.highlight_block(TEXT_SPACES, &CodeBlockInfo::default())?,
+                highlighter.highlight_block(TEXT_TABS, &CodeBlockInfo::default())?,
             );
         }
 
         Ok(())
     }
+
+    #[test]
+    fn highlighted_lines() -> Result<()> {
+        const TEXT: &str = "let a = 1;\nlet b = 2;\nlet c = 3;\nlet d = 4;\n";
+
+        assert_eq!(
+            CodeBlockInfo::parse("rust hl_lines=1,3-4")?,
+            CodeBlockInfo {
+                language: Some("rust".into()),
+                highlighted_lines: vec![1..=1, 3..=4],
+                ..CodeBlockInfo::default()
+            }
+        );
+        assert_eq!(CodeBlockInfo::parse("")?, CodeBlockInfo::default());
+        assert!(CodeBlockInfo::parse("rust hl_lines").is_err());
+        assert!(CodeBlockInfo::parse("rust hl_lines=0").is_err());
+        assert!(CodeBlockInfo::parse("rust hl_lines=4-3").is_err());
+        assert!(CodeBlockInfo::parse("rust hl_lines=a").is_err());
+        assert!(CodeBlockInfo::parse("rust linenos=1").is_err());
+
+        for theme in &THEME_NAMES {
+            let highlighter = SyntaxHighlighter::new(theme);
+            let html =
+                highlighter.highlight_block(TEXT, &CodeBlockInfo::parse("rust hl_lines=1,3-4")?)?;
+            assert_eq!(html.matches(HIGHLIGHTED_LINE_CLASS).count(), 3);
+            assert!(
+                highlighter
+                    .highlight_block(TEXT, &CodeBlockInfo::parse("rust hl_lines=4-5")?)
+                    .is_err()
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn inline_code_styles() -> Result<()> {
+        let highlighter = SyntaxHighlighter::new("base16-ocean.dark");
+        assert!(
+            highlighter
+                .highlight_segment("a")?
+                .contains("background-color")
+        );
+
+        let highlighter = SyntaxHighlighter::new("base16-ocean.dark")
+            .with_inline_code(InlineCodeStyle::Foreground, Some("InspiredGitHub"));
+        let html = highlighter.highlight_segment("a")?;
+        assert!(html.contains("color:") && !html.contains("background-color"));
+        assert_ne!(
+            html,
+            SyntaxHighlighter::new("base16-ocean.dark")
+                .with_inline_code(InlineCodeStyle::Foreground, None)
+                .highlight_segment("a")?
+        );
+
+        let highlighter = SyntaxHighlighter::new("base16-ocean.dark")
+            .with_inline_code(InlineCodeStyle::Plain, None);
+        assert_eq!(highlighter.highlight_segment("a<b")?, "<code>a&lt;b</code>");
+
+        Ok(())
+    }
+
+    #[test]
+    fn whitespace() -> Result<()> {
+        assert_eq!(
+            CodeBlockInfo::parse("go,tabwidth=8,hl_lines=1,3 show_whitespace=true")?,
+            CodeBlockInfo {
+                language: Some("go".into()),
+                highlighted_lines: vec![1..=1, 3..=3],
+                tab_width: Some(8),
+                show_whitespace: Some(true),
+            }
+        );
+        assert!(CodeBlockInfo::parse("go,tabwidth=0").is_err());
+        assert!(CodeBlockInfo::parse("go,tabwidth=2,4").is_err());
+        assert!(CodeBlockInfo::parse("go show_whitespace=yes").is_err());
+
+        let highlighter = SyntaxHighlighter::new("base16-ocean.dark").with_whitespace(2, false);
+        assert_eq!(
+            highlighter.highlight_block("\tabc\n", &CodeBlockInfo::default())?,
+            highlighter.highlight_block("  abc\n", &CodeBlockInfo::default())?,
+        );
+        assert_eq!(
+            highlighter.highlight_block("\tabc\n", &CodeBlockInfo::parse("tabwidth=3")?)?,
+            highlighter.highlight_block("   abc\n", &CodeBlockInfo::default())?,
+        );
+
+        let html = highlighter.highlight_block(
+            "\tabc \t\n  \nabc\n",
+            &CodeBlockInfo::parse("show_whitespace=true")?,
+        )?;
+        assert_eq!(html.matches(WHITESPACE_CLASS).count(), 3);
+        assert!(html.contains(">→ </span>"));
+        assert!(html.contains("·→ </span>\n"));
+        assert!(html.contains("··</span>\n"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn ansi_block() -> Result<()> {
+        let highlighter = SyntaxHighlighter::new("InspiredGitHub");
+        assert_eq!(
+            highlighter.highlight_block(
+                "$ make\n\u{1b}[31merror\u{1b}[0m\n",
+                &CodeBlockInfo::parse("ansi hl_lines=2")?
+            )?,
+            "<pre style=\"background-color:#ffffff;color:#323232;\">\n$ make\n\
+            <span class=\"__highlighted-line\" style=\"display:block;background-color:#f5f5f5;\">\
+            <span style=\"color:#cd0000;\">error</span>\n</span></pre>"
+        );
+        assert_eq!(
+            highlighter
+                .code_block_opening_html(Some("ansi"), true, false)?
+                .as_deref(),
+            Some("<div class=\"__code-block\"><span class=\"__code-language\">Terminal</span>"),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn diff_block() -> Result<()> {
+        let highlighter = SyntaxHighlighter::new("InspiredGitHub");
+        let html = highlighter.highlight_block(
+            " let a = 1;\n-let b = 2;\n+let b = 3;\n",
+            &CodeBlockInfo::parse("diff-rust")?,
+        )?;
+        // Lines are highlighted as Rust, without the markers
+        assert_eq!(
+            html.matches(">let</span>").count(),
+            3,
+            "unexpected highlighting in {html}"
+        );
+        assert!(html.contains("\n <span"));
+        assert!(html.contains(
+            "<span class=\"__diff-removed\" style=\"display:block;background-color:#f8514933;\">-<span"
+        ));
+        assert!(html.contains(
+            "<span class=\"__diff-added\" style=\"display:block;background-color:#2ea04333;\">+<span"
+        ));
+        assert!(
+            highlighter
+                .highlight_block("+a", &CodeBlockInfo::parse("diff-klingon")?)
+                .is_err()
+        );
+        assert_eq!(
+            highlighter
+                .code_block_opening_html(Some("diff-rs"), true, false)?
+                .as_deref(),
+            Some("<div class=\"__code-block\"><span class=\"__code-language\">Rust diff</span>"),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn language_labels() -> Result<()> {
+        let highlighter = SyntaxHighlighter::new("base16-ocean.dark");
+        assert_eq!(
+            highlighter
+                .code_block_opening_html(Some("rs"), true, false)?
+                .as_deref(),
+            Some("<div class=\"__code-block\"><span class=\"__code-language\">Rust</span>"),
+        );
+        assert_eq!(
+            highlighter.code_block_opening_html(None, true, false)?,
+            None
+        );
+        assert_eq!(
+            highlighter.code_block_opening_html(Some("txt"), true, false)?,
+            None
+        );
+        assert_eq!(
+            highlighter.code_block_opening_html(Some("rs"), false, false)?,
+            None
+        );
+        assert!(
+            highlighter
+                .code_block_opening_html(Some("klingon"), true, false)
+                .is_err()
+        );
+        assert_eq!(
+            highlighter
+                .code_block_opening_html(Some("rs"), true, true)?
+                .as_deref(),
+            Some(
+                "<div class=\"__code-block\" data-copy-code><span class=\"__code-language\">Rust</span>"
+            ),
+        );
+        assert_eq!(
+            highlighter
+                .code_block_opening_html(None, true, true)?
+                .as_deref(),
+            Some("<div class=\"__code-block\" data-copy-code>"),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn theme_file() -> Result<()> {
+        const THEME: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>name</key>
+    <string>Custom</string>
+    <key>settings</key>
+    <array>
+        <dict>
+            <key>settings</key>
+            <dict>
+                <key>background</key>
+                <string>#123456</string>
+                <key>foreground</key>
+                <string>#abcdef</string>
+            </dict>
+        </dict>
+        <dict>
+            <key>scope</key>
+            <string>keyword</string>
+            <key>settings</key>
+            <dict>
+                <key>foreground</key>
+                <string>#ff0000</string>
+            </dict>
+        </dict>
+    </array>
+</dict>
+</plist>
+"#;
+
+        let path = Utf8Path::new("/site/custom.tmTheme");
+        let vfs = MemoryFs::new().with_file(path, THEME);
+
+        let highlighter = SyntaxHighlighter::from_theme_file(path, &vfs)?;
+        let html = highlighter.highlight_block("if true {}", &CodeBlockInfo::parse("rs")?)?;
+        assert!(html.contains("background-color:#123456"));
+        assert!(html.contains("color:#ff0000"));
+
+        let vfs = MemoryFs::new().with_file(path, "not a theme");
+        assert!(SyntaxHighlighter::from_theme_file(path, &vfs).is_err());
+        assert!(
+            SyntaxHighlighter::from_theme_file(Utf8Path::new("/site/missing.tmTheme"), &vfs)
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn language_aliases() -> Result<()> {
+        let highlighter = SyntaxHighlighter::new("base16-ocean.dark");
+        assert!(
+            highlighter
+                .highlight_block("<div />", &CodeBlockInfo::parse("jsx")?)
+                .is_err()
+        );
+
+        let highlighter = highlighter.with_language_aliases([("jsx", "JavaScript")])?;
+        highlighter.highlight_block("<div />", &CodeBlockInfo::parse("jsx")?)?;
+        assert_eq!(
+            highlighter
+                .code_block_opening_html(Some("jsx"), true, false)?
+                .as_deref(),
+            Some("<div class=\"__code-block\"><span class=\"__code-language\">JavaScript</span>"),
+        );
+
+        assert!(
+            SyntaxHighlighter::new("base16-ocean.dark")
+                .with_language_aliases([("x", "Klingon")])
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn dark_theme() -> Result<()> {
+        const TEXT: &str = "fn main() {\n    let x = \"a\";\n}\n";
+
+        let light = SyntaxHighlighter::new("InspiredGitHub");
+        let dual = SyntaxHighlighter::new("InspiredGitHub").with_dark_theme("base16-ocean.dark");
+        let info = CodeBlockInfo::parse("rs hl_lines=2")?;
+        let light_html = light.highlight_block(TEXT, &info)?;
+        let dual_html = dual.highlight_block(TEXT, &info)?;
+        assert!(!light_html.contains("light-dark("));
+        assert!(
+            dual_html.starts_with("<pre style=\"background-color:light-dark(#ffffff,#2b303b);\">")
+        );
+        // Both versions contain the same text
+        let strip_tags = |html: &str| {
+            html.split('<')
+                .map(|part| part.split_once('>').map_or(part, |(_, text)| text))
+                .collect::<String>()
+        };
+        assert_eq!(strip_tags(&light_html), strip_tags(&dual_html));
+
+        assert!(dual.highlight_segment("a")?.contains("color:light-dark("));
+
+        Ok(())
+    }
 }
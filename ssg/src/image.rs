@@ -1,25 +1,232 @@
 //! Utility for converting images in articles to AVIF.
 
-use crate::builder::create_img_html;
-use anyhow::{Context, Result, bail};
-use camino::{Utf8Component, Utf8Path};
-use image::{GenericImageView, ImageEncoder, ImageReader, codecs::avif::AvifEncoder};
-use pulldown_cmark::CowStr;
-use std::{fs::File, io::BufWriter, ops::Range};
+use crate::{
+    builder::{create_el, create_el_with_attrs, create_picture, create_text, tree_to_html},
+    local::validate_local_src,
+    vfs::Vfs,
+};
+#[cfg(feature = "images")]
+use crate::{cache::stable_hash, embed::fetch};
+use anyhow::{Context, Result, anyhow, bail};
+#[cfg(feature = "images")]
+use base64::{Engine, engine::general_purpose::STANDARD as BASE64};
+use camino::Utf8Path;
+use ego_tree::{Tree, tree};
+use foldhash::{HashSet, HashSetExt};
+#[cfg(feature = "images")]
+use image::{
+    DynamicImage, ExtendedColorType, GenericImageView, ImageEncoder,
+    codecs::{avif::AvifEncoder, jpeg::JpegEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    load_from_memory,
+};
+use pulldown_cmark::{CowStr, Event, Options, Parser, Tag, TagEnd};
+use scraper::node::Node;
+use serde::Deserialize;
+use std::ops::Range;
+#[cfg(feature = "images")]
+use std::{borrow::Cow, io::Cursor};
 
 pub const OUTPUT_IMAGE_EXTENSION: &str = "avif";
 
 // In debug builds, we use the fastest encoding speed for the fastest site build times.
 // In release builds, we use the slowest encoding speed for the best compression.
-#[cfg(debug_assertions)]
+#[cfg(all(feature = "images", debug_assertions))]
 const ENCODER_SPEED: u8 = 10;
-#[cfg(not(debug_assertions))]
+#[cfg(all(feature = "images", not(debug_assertions)))]
 const ENCODER_SPEED: u8 = 1;
 
+// Pixel densities of variants generated for images with a fixed display width
+#[cfg(feature = "images")]
+const PIXEL_DENSITIES: [u32; 2] = [1, 2];
+
+// Value of the `sizes` attribute for images with width variants if none is configured
+pub const DEFAULT_IMAGE_SIZES: &str = "100vw";
+
+const OUTPUT_IMAGE_MIME_TYPE: &str = "image/avif";
+
+const CAPTIONED_IMAGE_CLASS: &str = "__captioned-image";
+
+// Quality of lossy image encoding, from 1 to 100
+#[cfg(feature = "images")]
+const ENCODER_QUALITY: u8 = 80;
+
+// Width in pixels of blurred placeholder images; browsers smoothly upscale them to the displayed size
+#[cfg(feature = "images")]
+const PLACEHOLDER_WIDTH: u32 = 16;
+
+// Standard deviation of the Gaussian blur applied to placeholder images, in placeholder pixels
+#[cfg(feature = "images")]
+const PLACEHOLDER_BLUR_SIGMA: f32 = 1.0;
+
+/// Kind of placeholder shown in place of a converted image while it loads.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaceholderStyle {
+    /// A tiny blurred version of the image, inlined as a data URI
+    Blur,
+    /// The average color of the image
+    Color,
+}
+
+#[cfg(feature = "images")]
+impl PlaceholderStyle {
+    /// Returns a CSS `background` value previewing `image` in this style.
+    fn create(self, image: &DynamicImage) -> Result<String> {
+        match self {
+            Self::Blur => {
+                let preview = image
+                    .resize(PLACEHOLDER_WIDTH, u32::MAX, FilterType::Triangle)
+                    .blur(PLACEHOLDER_BLUR_SIGMA)
+                    .to_rgba8();
+                let mut bytes = Vec::new();
+                WebPEncoder::new_lossless(Cursor::new(&mut bytes))
+                    .write_image(
+                        &preview,
+                        preview.width(),
+                        preview.height(),
+                        ExtendedColorType::Rgba8,
+                    )
+                    .context("failed to encode placeholder image")?;
+                Ok(format!(
+                    "url(data:image/webp;base64,{}) center/cover",
+                    BASE64.encode(bytes)
+                ))
+            }
+            Self::Color => {
+                // Resizing to a single pixel averages the colors of the whole image
+                let [r, g, b] = image
+                    .resize_exact(1, 1, FilterType::Triangle)
+                    .to_rgb8()
+                    .get_pixel(0, 0)
+                    .0;
+                Ok(format!("#{r:02x}{g:02x}{b:02x}"))
+            }
+        }
+    }
+}
+
+/// Format of fallback images saved next to converted AVIF images, for browsers without AVIF support.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum FallbackFormat {
+    /// Lossless WebP, which preserves transparency
+    Webp,
+    /// JPEG, which is supported by every browser but discards transparency
+    Jpeg,
+}
+
+impl FallbackFormat {
+    #[must_use]
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Webp => "webp",
+            Self::Jpeg => "jpg",
+        }
+    }
+
+    fn mime_type(self) -> &'static str {
+        match self {
+            Self::Webp => "image/webp",
+            Self::Jpeg => "image/jpeg",
+        }
+    }
+
+    /// Returns the path of the fallback image saved next to the AVIF image at `path`.
+    #[must_use]
+    pub fn fallback_path(self, path: &str) -> String {
+        Utf8Path::new(path)
+            .with_extension(self.extension())
+            .into_string()
+    }
+
+    /// Returns a `srcset` attribute value listing the fallback images of the AVIF images in `srcset`.
+    #[must_use]
+    pub fn fallback_srcset(self, srcset: &str) -> String {
+        srcset
+            .split(", ")
+            .map(|candidate| match candidate.split_once(' ') {
+                Some((path, descriptor)) => format!("{} {descriptor}", self.fallback_path(path)),
+                None => self.fallback_path(candidate),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Returns a `<picture>` element containing `<source>` elements for an AVIF image and its fallback image,
+    /// followed by an `<img>` element for the fallback image with the given attributes.
+    /// `src` and `srcset` refer to the AVIF image.
+    pub(crate) fn picture(
+        self,
+        src: &str,
+        srcset: Option<&str>,
+        sizes: Option<&str>,
+        img_attrs: &[(&str, &str)],
+    ) -> Tree<Node> {
+        let srcset = srcset.unwrap_or(src);
+        let fallback_srcset = self.fallback_srcset(srcset);
+        let fallback_src = self.fallback_path(src);
+
+        let source_attrs = |mime_type, srcset| {
+            let mut attrs = vec![("type", mime_type), ("srcset", srcset)];
+            if let Some(sizes) = sizes {
+                attrs.push(("sizes", sizes));
+            }
+            attrs
+        };
+
+        let mut attrs = Vec::with_capacity(img_attrs.len() + 1);
+        attrs.push(("src", fallback_src.as_str()));
+        attrs.extend_from_slice(img_attrs);
+
+        create_picture(
+            &[
+                source_attrs(OUTPUT_IMAGE_MIME_TYPE, srcset),
+                source_attrs(self.mime_type(), &fallback_srcset),
+            ],
+            &attrs,
+        )
+    }
+
+    #[cfg(feature = "images")]
+    fn encode(self, image: &DynamicImage, output_path: &Utf8Path, vfs: &dyn Vfs) -> Result<()> {
+        let mut bytes = Vec::new();
+
+        match self {
+            Self::Webp => {
+                let image = image.to_rgba8();
+                WebPEncoder::new_lossless(&mut bytes).write_image(
+                    &image,
+                    image.width(),
+                    image.height(),
+                    ExtendedColorType::Rgba8,
+                )
+            }
+            Self::Jpeg => {
+                let image = image.to_rgb8();
+                JpegEncoder::new_with_quality(&mut bytes, ENCODER_QUALITY).write_image(
+                    &image,
+                    image.width(),
+                    image.height(),
+                    ExtendedColorType::Rgb8,
+                )
+            }
+        }
+        .with_context(|| format!("failed to encode image for {output_path}"))?;
+
+        vfs.write(output_path, &bytes, false)
+            .with_context(|| format!("failed to write image to {output_path}"))
+    }
+}
+
 pub struct ActiveImageState<'a> {
     nesting_level: usize,
     url: CowStr<'a>,
     dimensions: Option<Dimensions>,
+    srcset: Option<Box<str>>,
+    sizes: Option<Box<str>>,
+    fallback: Option<FallbackFormat>,
+    placeholder: Option<Box<str>>,
     title: CowStr<'a>,
     id: CowStr<'a>,
     alt_text_range: Range<usize>,
@@ -31,10 +238,14 @@ impl<'a> ActiveImageState<'a> {
     const INITIAL_END_INDEX: usize = usize::MIN;
 
     /// Creates a context for tracking the character range of an image's alt text within a Markdown source.
+    /// If `fallback` is given, the image is an AVIF image with fallback images in that format.
     #[must_use]
     pub fn new(
         url: CowStr<'a>,
         dimensions: Option<Dimensions>,
+        srcset: Option<Box<str>>,
+        sizes: Option<Box<str>>,
+        fallback: Option<FallbackFormat>,
         title: CowStr<'a>,
         id: CowStr<'a>,
     ) -> Self {
@@ -42,6 +253,10 @@ impl<'a> ActiveImageState<'a> {
             nesting_level: Self::INITIAL_NESTING_LEVEL,
             url,
             dimensions,
+            srcset,
+            sizes,
+            fallback,
+            placeholder: None,
             title,
             id,
             alt_text_range: Range {
@@ -51,6 +266,13 @@ impl<'a> ActiveImageState<'a> {
         }
     }
 
+    /// Sets a CSS `background` value shown behind the image while it loads.
+    #[must_use]
+    pub fn with_placeholder(mut self, placeholder: Option<Box<str>>) -> Self {
+        self.placeholder = placeholder;
+        self
+    }
+
     /// Increments the nesting level.
     /// This is used when the start of an image element is encountered within the context.
     pub fn nest(&mut self) {
@@ -84,28 +306,51 @@ impl<'a> ActiveImageState<'a> {
         }
     }
 
-    /// Consumes the context, returning a complete `<img>` element as a string of HTML.
-    /// The input Markdown source is used for retrieving the image's alt text.
+    /// Returns the image's alt text from the input Markdown source, which is empty if the image has no alt text.
     #[must_use]
-    pub fn into_html(self, markdown_source: &str) -> String {
-        debug_assert_eq!(self.nesting_level, Self::INITIAL_NESTING_LEVEL - 1);
-
-        let alt_text = if self.alt_text_range.start == Self::INITIAL_START_INDEX
+    pub fn alt_text<'m>(&self, markdown_source: &'m str) -> &'m str {
+        if self.alt_text_range.start == Self::INITIAL_START_INDEX
             || self.alt_text_range.end == Self::INITIAL_END_INDEX
         {
             // self.update_alt_text_range() was never called, so the image has no alt text
             ""
         } else {
-            &markdown_source[self.alt_text_range]
-        };
+            &markdown_source[self.alt_text_range.clone()]
+        }
+    }
+
+    /// Consumes the context, returning a complete `<img>` element as a string of HTML.
+    /// Images with fallbacks are wrapped in a `<picture>` element instead.
+    /// If the image is `standalone` (i.e. alone in its paragraph) and has a title,
+    /// it is wrapped in a `<figure>` element with the title as its caption.
+    /// Otherwise, the title is set as the image's `title` attribute.
+    /// The input Markdown source is used for retrieving the image's alt text.
+    #[must_use]
+    pub fn into_html(self, markdown_source: &str, standalone: bool) -> String {
+        debug_assert_eq!(self.nesting_level, Self::INITIAL_NESTING_LEVEL - 1);
 
+        let alt_text = self.alt_text(markdown_source);
+
+        let style = self
+            .placeholder
+            .as_deref()
+            .map(|placeholder| format!("background:{placeholder}"));
         let dimension_strs = self
             .dimensions
             .map(|Dimensions { width, height }| (width.to_string(), height.to_string()));
 
         // Build image HTML representation
-        let mut attrs = Vec::with_capacity(8);
-        attrs.push(("src", self.url.as_ref()));
+        let mut attrs = Vec::with_capacity(11);
+        // With a fallback, the sources are listed by `<source>` elements instead
+        if self.fallback.is_none() {
+            attrs.push(("src", self.url.as_ref()));
+            if let Some(srcset) = &self.srcset {
+                attrs.push(("srcset", srcset));
+            }
+            if let Some(sizes) = &self.sizes {
+                attrs.push(("sizes", sizes));
+            }
+        }
         attrs.push(("alt", alt_text));
         // Asynchronous image decoding improves the rendering performance of other elements.
         // https://www.tunetheweb.com/blog/what-does-the-image-decoding-attribute-actually-do/
@@ -116,15 +361,84 @@ impl<'a> ActiveImageState<'a> {
             attrs.push(("width", width_str));
             attrs.push(("height", height_str));
         }
-        if !self.title.is_empty() {
+        let is_captioned = standalone && !self.title.is_empty();
+        if !self.title.is_empty() && !is_captioned {
             attrs.push(("title", &self.title));
         }
         if !self.id.is_empty() {
             attrs.push(("id", &self.id));
         }
+        if let Some(style) = &style {
+            attrs.push(("style", style));
+        }
+
+        let image = match self.fallback {
+            Some(fallback) => fallback.picture(
+                &self.url,
+                self.srcset.as_deref(),
+                self.sizes.as_deref(),
+                &attrs,
+            ),
+            None => Tree::new(create_el_with_attrs("img", &attrs)),
+        };
+
+        if !is_captioned {
+            return tree_to_html(image);
+        }
+
+        let mut figure = Tree::new(create_el_with_attrs(
+            "figure",
+            &[("class", CAPTIONED_IMAGE_CLASS)],
+        ));
+        figure.root_mut().append_subtree(image);
+        figure.root_mut().append_subtree(tree! {
+            create_el("figcaption") => { create_text(&self.title) }
+        });
+        tree_to_html(figure)
+    }
+}
+
+/// Returns the source offsets of the paragraphs in `markdown` that only contain a single image with a title.
+/// These paragraphs are replaced by a `<figure>` element captioned with the image's title.
+#[must_use]
+pub fn captioned_image_paragraphs(markdown: &str, options: Options) -> HashSet<usize> {
+    let mut offsets = HashSet::new();
+    // Source offset of the current paragraph, if its contents so far are a single image with a title
+    let mut paragraph_start = None;
+    // Nesting level of images within the current paragraph, since alt text can contain images
+    let mut image_level = 0_usize;
+    let mut image_count = 0_usize;
 
-        create_img_html(&attrs)
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Paragraph) => {
+                paragraph_start = Some(range.start);
+                image_count = 0;
+            }
+            Event::End(TagEnd::Paragraph) => {
+                if let Some(start) = paragraph_start.take()
+                    && image_count == 1
+                {
+                    offsets.insert(start);
+                }
+            }
+            Event::Start(Tag::Image { title, .. }) => {
+                if image_level == 0 {
+                    image_count += 1;
+                    if title.is_empty() {
+                        paragraph_start = None;
+                    }
+                }
+                image_level += 1;
+            }
+            Event::End(TagEnd::Image) => image_level -= 1,
+            // Anything besides the image means the paragraph isn't replaced
+            _ if image_level == 0 => paragraph_start = None,
+            _ => {}
+        }
     }
+
+    offsets
 }
 
 /// Validates the input image source.
@@ -132,64 +446,698 @@ impl<'a> ActiveImageState<'a> {
 /// # Errors
 /// This function returns an error if:
 /// - the input source is an empty string
+/// - the input source is a URL
 /// - the input source is not a relative path
 /// - the input source is a path with parent-referencing components ("..")
 pub fn validate_image_src(url: &str) -> Result<()> {
     if url.is_empty() {
         bail!("no source provided for image");
     }
+    if url.contains("://") {
+        bail!(
+            "image source is a URL ({url}); only https:// URLs are supported, with `remote_images` enabled"
+        );
+    }
+
+    validate_local_src(url, "image source")
+}
 
-    let url = Utf8Path::new(url);
+/// Returns a description of why an image's alt text is unlikely to describe the image to readers who cannot see it,
+/// if it is empty, equal to the image's file name (e.g. `diagram.png`),
+/// or generic text that image files are often named after (e.g. `image1` or `Screenshot 2024-01-01 at 12.00.00`).
+#[must_use]
+pub fn alt_text_problem(alt_text: &str, src: &str) -> Option<&'static str> {
+    const GENERIC_ALT_TEXTS: [&str; 12] = [
+        "alt",
+        "dsc",
+        "figure",
+        "graphic",
+        "image",
+        "img",
+        "photo",
+        "pic",
+        "picture",
+        "screenshot",
+        "screenshotat",
+        "untitled",
+    ];
 
-    if !url.is_relative()
-        || url
-            .components()
-            .any(|part| matches!(part, Utf8Component::ParentDir | Utf8Component::Normal("..")))
-    {
-        bail!("image source is not a normalized relative file path ({url})");
+    let alt_text = alt_text.trim();
+    let file_name = src
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .unwrap_or(src);
+    let file_stem = file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _)| stem);
+    // Digits, spaces, and punctuation are ignored, so numbered names and timestamps are also generic
+    let letters: String = alt_text
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    if alt_text.is_empty() {
+        Some("has no alt text")
+    } else if alt_text.eq_ignore_ascii_case(file_name) || alt_text.eq_ignore_ascii_case(file_stem) {
+        Some("has its file name as alt text")
+    } else if GENERIC_ALT_TEXTS.contains(&&*letters) {
+        Some("has generic alt text")
+    } else {
+        None
     }
+}
 
-    Ok(())
+/// Returns a Boolean indicating if an image source is a URL of an image to download.
+#[must_use]
+pub fn is_remote_image_src(url: &str) -> bool {
+    url.starts_with("https://")
 }
 
-/// Converts the image at the input path to AVIF and saves it to an output path.
-/// This function outputs a (width, height) tuple of the image's dimensions.
+/// Splits an image source into a file path and an optional display width in CSS pixels.
+/// The display width is specified with a `?width=<pixels>` suffix (e.g. `icon.png?width=64`).
+///
+/// # Errors
+/// This function returns an error if the source has a suffix
+/// that is not of the form `?width=<pixels>`, where `<pixels>` is a positive integer.
+pub fn parse_image_src(url: &str) -> Result<(&str, Option<u32>)> {
+    let Some((path, query)) = url.split_once('?') else {
+        return Ok((url, None));
+    };
+
+    let width = query
+        .strip_prefix("width=")
+        .ok_or_else(|| anyhow!("image source has an unknown directive (?{query})"))?;
+
+    match width.parse() {
+        Ok(0) | Err(_) => bail!("image display width must be a positive integer (got {width})"),
+        Ok(width) => Ok((path, Some(width))),
+    }
+}
+
+/// Converts the image at the input path to AVIF and saves it to an output path,
+/// along with a fallback image if one is configured in `options`.
+/// This function outputs the image's dimensions after any downscaling,
+/// plus a CSS `background` value for a placeholder if one is configured in `options`.
 ///
 /// # Errors
 /// This function returns an error if:
 /// - the file at the input image path cannot be opened or read from
 /// - the file at the output file path cannot be created or written to
+#[cfg(feature = "images")]
 pub fn convert_image(
     input_article_dir: &Utf8Path,
     output_article_dir: &Utf8Path,
     image_path: &str,
-) -> Result<Dimensions> {
+    options: ImageOptions,
+    vfs: &dyn Vfs,
+) -> Result<(Dimensions, Option<String>)> {
     let input_path = input_article_dir.join(image_path);
     let output_path = output_article_dir
         .join(image_path)
         .with_extension(OUTPUT_IMAGE_EXTENSION);
 
-    let image = ImageReader::open(&input_path)
-        .with_context(|| format!("failed to open file at {input_path}"))?
-        .decode()
-        .with_context(|| format!("failed to read image from {input_path}"))?;
+    let image = decode_image(&input_path, options, vfs)?;
+
+    Ok((
+        encode_image(&image, &output_path, options.fallback, vfs)?,
+        create_placeholder(&image, options)?,
+    ))
+}
+
+/// Converts the image at the input path to AVIF variants for displaying at a fixed width,
+/// one for each supported pixel density (e.g. 1x and 2x), and saves them next to each other.
+/// Variants are not generated for densities that would require upscaling the image.
+/// Every variant has a fallback image if one is configured in `options`.
+/// A placeholder is generated from the full-size image if one is configured in `options`.
+///
+/// # Errors
+/// This function returns an error if:
+/// - the file at the input image path cannot be opened or read from
+/// - a file at an output file path cannot be created or written to
+#[cfg(feature = "images")]
+pub fn convert_image_variants(
+    input_article_dir: &Utf8Path,
+    output_article_dir: &Utf8Path,
+    image_path: &str,
+    display_width: u32,
+    options: ImageOptions,
+    vfs: &dyn Vfs,
+) -> Result<ImageVariants> {
+    let ImageOptions { fallback, .. } = options;
+    let input_path = input_article_dir.join(image_path);
+
+    let image = decode_image(&input_path, options, vfs)?;
 
     let (width, height) = image.dimensions();
+    let display_height =
+        u32::try_from((u64::from(height) * u64::from(display_width) / u64::from(width)).max(1))
+            .context("image display height is too large")?;
+
+    let stem = Utf8Path::new(image_path).with_extension("");
+    let variant_path = |density| {
+        if density == 1 {
+            format!("{stem}-{display_width}px.{OUTPUT_IMAGE_EXTENSION}")
+        } else {
+            format!("{stem}-{display_width}px@{density}x.{OUTPUT_IMAGE_EXTENSION}")
+        }
+    };
+
+    let mut srcset = Vec::with_capacity(PIXEL_DENSITIES.len());
+    let mut output_bytes = 0;
+
+    for density in PIXEL_DENSITIES {
+        let target_width = display_width.saturating_mul(density);
+
+        // Upscaling doesn't add detail, so higher densities are only generated from larger images
+        if density > 1 && target_width > width {
+            break;
+        }
+
+        let variant = if target_width < width {
+            Cow::Owned(image.resize(target_width, u32::MAX, FilterType::Lanczos3))
+        } else {
+            Cow::Borrowed(&image)
+        };
+
+        let variant_path = variant_path(density);
+        let output_path = output_article_dir.join(&variant_path);
+
+        encode_image(&variant, &output_path, fallback, vfs)?;
+        output_bytes += output_size(&output_path, fallback, vfs)?;
+
+        srcset.push(format!("{variant_path} {density}x"));
+    }
+
+    Ok(ImageVariants {
+        src: variant_path(1),
+        srcset: srcset.join(", "),
+        dimensions: Dimensions {
+            width: display_width,
+            height: display_height,
+        },
+        placeholder: create_placeholder(&image, options)?,
+        output_bytes,
+    })
+}
+
+/// Converts the image at the input path to AVIF at its full size, plus smaller variants at each of the given widths,
+/// and saves them next to each other. Variants are not generated for widths that would require upscaling the image.
+/// The variants are listed with width descriptors in the output `srcset` value.
+/// Every variant has a fallback image if one is configured in `options`.
+/// A placeholder is generated from the full-size image if one is configured in `options`.
+///
+/// # Errors
+/// This function returns an error if:
+/// - the file at the input image path cannot be opened or read from
+/// - a file at an output file path cannot be created or written to
+#[cfg(feature = "images")]
+pub fn convert_image_width_variants(
+    input_article_dir: &Utf8Path,
+    output_article_dir: &Utf8Path,
+    image_path: &str,
+    widths: &[u32],
+    options: ImageOptions,
+    vfs: &dyn Vfs,
+) -> Result<ImageVariants> {
+    let ImageOptions { fallback, .. } = options;
+    let input_path = input_article_dir.join(image_path);
+
+    let image = decode_image(&input_path, options, vfs)?;
+
+    let src = Utf8Path::new(image_path)
+        .with_extension(OUTPUT_IMAGE_EXTENSION)
+        .into_string();
+    let output_path = output_article_dir.join(&src);
+    let dimensions = encode_image(&image, &output_path, fallback, vfs)?;
+    let mut output_bytes = output_size(&output_path, fallback, vfs)?;
+
+    let stem = Utf8Path::new(image_path).with_extension("");
+    let mut srcset = Vec::with_capacity(widths.len() + 1);
+
+    for &width in widths.iter().filter(|&&width| width < dimensions.width) {
+        let variant_path = format!("{stem}-{width}w.{OUTPUT_IMAGE_EXTENSION}");
+        let output_path = output_article_dir.join(&variant_path);
+
+        encode_image(
+            &image.resize(width, u32::MAX, FilterType::Lanczos3),
+            &output_path,
+            fallback,
+            vfs,
+        )?;
+        output_bytes += output_size(&output_path, fallback, vfs)?;
+
+        srcset.push(format!("{variant_path} {width}w"));
+    }
+    srcset.push(format!("{src} {}w", dimensions.width));
+
+    Ok(ImageVariants {
+        src,
+        srcset: srcset.join(", "),
+        dimensions,
+        placeholder: create_placeholder(&image, options)?,
+        output_bytes,
+    })
+}
 
-    let writer = BufWriter::new(
-        File::create(&output_path)
-            .with_context(|| format!("failed to create file at {output_path}"))?,
+/// Downloads the image at `url`, converts it to AVIF, and saves it to the output directory
+/// under a name derived from the URL, along with a fallback image if one is configured in `options`.
+/// This function outputs the name of the output file, the image's dimensions after any downscaling,
+/// and a CSS `background` value for a placeholder if one is configured in `options`.
+///
+/// # Errors
+/// This function returns an error if:
+/// - the image cannot be downloaded or decoded
+/// - the file at the output file path cannot be created or written to
+#[cfg(feature = "images")]
+pub fn convert_remote_image(
+    url: &str,
+    output_article_dir: &Utf8Path,
+    options: ImageOptions,
+    vfs: &dyn Vfs,
+) -> Result<(String, Dimensions, Option<String>)> {
+    let bytes = fetch(url).with_context(|| format!("failed to download image from {url}"))?;
+    let image = fit_image(
+        load_from_memory(&bytes).with_context(|| format!("failed to read image from {url}"))?,
+        options,
+    );
+
+    // Hashing the URL gives every remote image a stable file name, even if the URL has no usable file name
+    let src = format!(
+        "remote-{}.{OUTPUT_IMAGE_EXTENSION}",
+        &stable_hash(&[url.as_bytes()])[..16]
     );
+    let dimensions = encode_image(
+        &image,
+        &output_article_dir.join(&src),
+        options.fallback,
+        vfs,
+    )?;
+
+    Ok((src, dimensions, create_placeholder(&image, options)?))
+}
+
+/// Reads the image at the input path, downscaling it to fit within the maximum dimensions in `options`.
+#[cfg(feature = "images")]
+fn decode_image(
+    input_path: &Utf8Path,
+    options: ImageOptions,
+    vfs: &dyn Vfs,
+) -> Result<DynamicImage> {
+    let bytes = vfs
+        .read(input_path)
+        .with_context(|| format!("failed to open file at {input_path}"))?;
+    let image = load_from_memory(&bytes)
+        .with_context(|| format!("failed to read image from {input_path}"))?;
+
+    Ok(fit_image(image, options))
+}
+
+/// Downscales an image to fit within the maximum dimensions in `options`.
+#[cfg(feature = "images")]
+fn fit_image(image: DynamicImage, options: ImageOptions) -> DynamicImage {
+    let max_width = options.max_width.unwrap_or(u32::MAX);
+    let max_height = options.max_height.unwrap_or(u32::MAX);
+
+    // Resizing preserves the aspect ratio, fitting the image within the given dimensions
+    if image.width() > max_width || image.height() > max_height {
+        image.resize(max_width, max_height, FilterType::Lanczos3)
+    } else {
+        image
+    }
+}
+
+/// Returns a CSS `background` value for a placeholder of `image`, if a placeholder style is configured in `options`.
+#[cfg(feature = "images")]
+fn create_placeholder(image: &DynamicImage, options: ImageOptions) -> Result<Option<String>> {
+    options
+        .placeholder
+        .map(|style| style.create(image))
+        .transpose()
+}
+
+/// Converts the encoded image in `bytes` to AVIF and saves it to `output_path`.
+/// This function outputs the image's dimensions.
+///
+/// # Errors
+/// This function returns an error if:
+/// - the input bytes cannot be decoded as an image
+/// - the file at the output file path cannot be created or written to
+#[cfg(feature = "images")]
+pub(crate) fn convert_image_bytes(
+    bytes: &[u8],
+    output_path: &Utf8Path,
+    vfs: &dyn Vfs,
+) -> Result<Dimensions> {
+    let image = load_from_memory(bytes).context("failed to read image from memory")?;
+    encode_image(&image, output_path, None, vfs)
+}
+
+/// Returns the total size of the AVIF image at `output_path` and its fallback image, if any.
+///
+/// # Errors
+/// This function returns an error if the metadata of an image file cannot be read.
+pub fn output_size(
+    output_path: &Utf8Path,
+    fallback: Option<FallbackFormat>,
+    vfs: &dyn Vfs,
+) -> Result<u64> {
+    let fallback_path = fallback.map(|fallback| output_path.with_extension(fallback.extension()));
 
-    AvifEncoder::new_with_speed_quality(writer, ENCODER_SPEED, 80)
+    [output_path]
+        .into_iter()
+        .chain(fallback_path.as_deref())
+        .map(|path| {
+            vfs.file_size(path)
+                .with_context(|| format!("failed to read metadata of {path}"))
+        })
+        .sum()
+}
+
+/// Encodes an image as AVIF to `output_path`, plus a fallback image next to it in the `fallback` format if given.
+#[cfg(feature = "images")]
+fn encode_image(
+    image: &DynamicImage,
+    output_path: &Utf8Path,
+    fallback: Option<FallbackFormat>,
+    vfs: &dyn Vfs,
+) -> Result<Dimensions> {
+    let (width, height) = image.dimensions();
+
+    if let Some(fallback) = fallback {
+        fallback.encode(
+            image,
+            &output_path.with_extension(fallback.extension()),
+            vfs,
+        )?;
+    }
+
+    let mut bytes = Vec::new();
+    AvifEncoder::new_with_speed_quality(&mut bytes, ENCODER_SPEED, ENCODER_QUALITY)
         .write_image(image.as_bytes(), width, height, image.color().into())
+        .with_context(|| format!("failed to encode image for {output_path}"))?;
+    vfs.write(output_path, &bytes, false)
         .with_context(|| format!("failed to write image to {output_path}"))?;
 
     Ok(Dimensions { width, height })
 }
 
+/// Settings for converting images in articles.
+#[derive(Clone, Copy, Default)]
+pub struct ImageOptions {
+    // Format of fallback images saved next to converted images, if any
+    pub fallback: Option<FallbackFormat>,
+    // Maximum width in pixels of converted images; larger images are downscaled before conversion
+    pub max_width: Option<u32>,
+    // Maximum height in pixels of converted images; larger images are downscaled before conversion
+    pub max_height: Option<u32>,
+    // Kind of placeholder shown while converted images load, if any
+    pub placeholder: Option<PlaceholderStyle>,
+}
+
+/// Output of converting an image to multiple pixel density or width variants.
+pub struct ImageVariants {
+    // Relative path of the 1x or full-size variant
+    pub src: String,
+    // Value for the `srcset` attribute listing all variants
+    pub srcset: String,
+    // Display dimensions of the image in CSS pixels
+    pub dimensions: Dimensions,
+    // CSS `background` value for a placeholder shown while the image loads, if configured
+    pub placeholder: Option<String>,
+    // Total size of all variant files
+    pub output_bytes: u64,
+}
+
 #[derive(Clone, Copy)]
 pub struct Dimensions {
-    width: u32,
-    height: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+#[cfg(all(test, feature = "images"))]
+mod test {
+    use super::{
+        ActiveImageState, Dimensions, FallbackFormat, ImageOptions, PlaceholderStyle,
+        alt_text_problem, captioned_image_paragraphs, convert_image, convert_image_variants,
+        convert_image_width_variants, is_remote_image_src, parse_image_src, validate_image_src,
+    };
+    use crate::vfs::RealFs;
+    use camino::Utf8PathBuf;
+    use image::{Rgb, RgbImage};
+    use pulldown_cmark::Options;
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+    };
+
+    #[test]
+    fn image_src_display_width() {
+        assert_eq!(parse_image_src("a.png").unwrap(), ("a.png", None));
+        assert_eq!(
+            parse_image_src("a/b.png?width=64").unwrap(),
+            ("a/b.png", Some(64))
+        );
+        assert!(parse_image_src("a.png?width=0").is_err());
+        assert!(parse_image_src("a.png?width=").is_err());
+        assert!(parse_image_src("a.png?height=64").is_err());
+    }
+
+    #[test]
+    fn alt_text_problems() {
+        assert_eq!(alt_text_problem(" ", "a.png"), Some("has no alt text"));
+        assert_eq!(
+            alt_text_problem("Diagram.PNG", "img/diagram.png"),
+            Some("has its file name as alt text")
+        );
+        assert_eq!(
+            alt_text_problem("diagram", "img/diagram.png?width=64"),
+            Some("has its file name as alt text")
+        );
+        for alt_text in [
+            "image1",
+            "IMG_0042",
+            "Screenshot 2024-01-01 at 12.00.00",
+            "photo (2)",
+        ] {
+            assert_eq!(
+                alt_text_problem(alt_text, "a.png"),
+                Some("has generic alt text")
+            );
+        }
+        assert_eq!(
+            alt_text_problem("A diagram of the pipeline", "diagram.png"),
+            None
+        );
+        assert_eq!(
+            alt_text_problem("Screenshot of the settings page", "a.png"),
+            None
+        );
+    }
+
+    #[test]
+    fn remote_image_src() {
+        assert!(is_remote_image_src("https://example.com/a.png"));
+        assert!(!is_remote_image_src("http://example.com/a.png"));
+        assert!(!is_remote_image_src("a/https://b.png"));
+        assert!(validate_image_src("http://example.com/a.png").is_err());
+        assert!(validate_image_src("https://example.com/a.png").is_err());
+        assert!(validate_image_src("a/b.png").is_ok());
+    }
+
+    #[test]
+    fn width_variants() {
+        let dir = Utf8PathBuf::try_from(temp_dir())
+            .unwrap()
+            .join(format!("ssg-image-test-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        RgbImage::new(64, 32).save(dir.join("a.png")).unwrap();
+
+        let variants = convert_image_width_variants(
+            &dir,
+            &dir,
+            "a.png",
+            &[16, 32, 64, 128],
+            ImageOptions::default(),
+            &RealFs,
+        )
+        .unwrap();
+        assert_eq!(variants.src, "a.avif");
+        assert_eq!(
+            variants.srcset,
+            "a-16w.avif 16w, a-32w.avif 32w, a.avif 64w"
+        );
+        assert_eq!(
+            (variants.dimensions.width, variants.dimensions.height),
+            (64, 32)
+        );
+        assert!(dir.join("a-16w.avif").is_file());
+        assert!(!dir.join("a-64w.avif").exists());
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn captioned_images() {
+        let markdown = "![a](a.png \"A\")\n\n![b](b.png)\n\nText ![c](c.png \"C\")\n\n\
+            ![d ![e](e.png)](d.png \"D\")\n\n![f](f.png \"F\") ![g](g.png \"G\")\n\n> ![h](h.png \"H\")";
+        let mut offsets: Vec<_> = captioned_image_paragraphs(markdown, Options::empty())
+            .into_iter()
+            .collect();
+        offsets.sort_unstable();
+        assert_eq!(offsets, [0, 52, 116]);
+
+        let image = |title: &'static str, standalone| {
+            let mut state = ActiveImageState::new(
+                "a.avif".into(),
+                None,
+                None,
+                None,
+                None,
+                title.into(),
+                "".into(),
+            );
+            state.update_alt_text_range(2..3);
+            state.unnest();
+            state.into_html("![a](a.png)", standalone)
+        };
+        assert_eq!(
+            image("A & B", true),
+            "<figure class=\"__captioned-image\"><img src=\"a.avif\" alt=\"a\" decoding=\"async\" loading=\"lazy\">\
+            <figcaption>A &amp; B</figcaption></figure>"
+        );
+        assert_eq!(
+            image("A", false),
+            "<img src=\"a.avif\" alt=\"a\" decoding=\"async\" loading=\"lazy\" title=\"A\">"
+        );
+        assert_eq!(
+            image("", true),
+            "<img src=\"a.avif\" alt=\"a\" decoding=\"async\" loading=\"lazy\">"
+        );
+    }
+
+    #[test]
+    fn max_dimensions() {
+        let dir = Utf8PathBuf::try_from(temp_dir())
+            .unwrap()
+            .join(format!("ssg-image-max-test-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        RgbImage::new(64, 32).save(dir.join("a.png")).unwrap();
+
+        let dimensions = |max_width, max_height| {
+            let options = ImageOptions {
+                max_width,
+                max_height,
+                ..ImageOptions::default()
+            };
+            let (dimensions, _) = convert_image(&dir, &dir, "a.png", options, &RealFs).unwrap();
+            (dimensions.width, dimensions.height)
+        };
+        assert_eq!(dimensions(None, None), (64, 32));
+        assert_eq!(dimensions(Some(128), Some(128)), (64, 32));
+        assert_eq!(dimensions(Some(16), None), (16, 8));
+        assert_eq!(dimensions(Some(16), Some(4)), (8, 4));
+
+        let options = ImageOptions {
+            max_width: Some(24),
+            ..ImageOptions::default()
+        };
+        let variants =
+            convert_image_width_variants(&dir, &dir, "a.png", &[16, 32], options, &RealFs).unwrap();
+        assert_eq!(variants.srcset, "a-16w.avif 16w, a.avif 24w");
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn fallback() {
+        let dir = Utf8PathBuf::try_from(temp_dir())
+            .unwrap()
+            .join(format!("ssg-image-fallback-test-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        RgbImage::new(64, 32).save(dir.join("a.png")).unwrap();
+
+        let options = ImageOptions {
+            fallback: Some(FallbackFormat::Jpeg),
+            ..ImageOptions::default()
+        };
+        let variants = convert_image_variants(&dir, &dir, "a.png", 32, options, &RealFs).unwrap();
+        assert_eq!(variants.srcset, "a-32px.avif 1x, a-32px@2x.avif 2x");
+        assert_eq!(
+            FallbackFormat::Jpeg.fallback_srcset(&variants.srcset),
+            "a-32px.jpg 1x, a-32px@2x.jpg 2x"
+        );
+        assert!(dir.join("a-32px.jpg").is_file());
+        assert!(dir.join("a-32px@2x.jpg").is_file());
+
+        let mut state = ActiveImageState::new(
+            "a.avif".into(),
+            Some(Dimensions {
+                width: 64,
+                height: 32,
+            }),
+            None,
+            None,
+            Some(FallbackFormat::Webp),
+            "".into(),
+            "".into(),
+        );
+        state.unnest();
+        let html = state.into_html("", false);
+        assert_eq!(
+            html,
+            "<picture><source type=\"image/avif\" srcset=\"a.avif\"><source type=\"image/webp\" srcset=\"a.webp\">\
+            <img src=\"a.webp\" alt=\"\" decoding=\"async\" loading=\"lazy\" width=\"64\" height=\"32\"></picture>"
+        );
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn placeholders() {
+        let dir = Utf8PathBuf::try_from(temp_dir())
+            .unwrap()
+            .join(format!("ssg-image-placeholder-test-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        RgbImage::from_pixel(64, 32, Rgb([255, 128, 0]))
+            .save(dir.join("a.png"))
+            .unwrap();
+
+        let placeholder = |placeholder| {
+            let options = ImageOptions {
+                placeholder,
+                ..ImageOptions::default()
+            };
+            convert_image(&dir, &dir, "a.png", options, &RealFs)
+                .unwrap()
+                .1
+        };
+        assert_eq!(placeholder(None), None);
+        assert_eq!(
+            placeholder(Some(PlaceholderStyle::Color)).as_deref(),
+            Some("#ff8000")
+        );
+        let blur = placeholder(Some(PlaceholderStyle::Blur)).unwrap();
+        assert!(blur.starts_with("url(data:image/webp;base64,"));
+        assert!(blur.ends_with(") center/cover"));
+
+        let mut state = ActiveImageState::new(
+            "a.avif".into(),
+            None,
+            None,
+            None,
+            None,
+            "".into(),
+            "".into(),
+        )
+        .with_placeholder(Some("#ff8000".into()));
+        state.unnest();
+        assert_eq!(
+            state.into_html("", false),
+            "<img src=\"a.avif\" alt=\"\" decoding=\"async\" loading=\"lazy\" style=\"background:#ff8000\">"
+        );
+
+        remove_dir_all(dir).unwrap();
+    }
 }
@@ -1,12 +1,318 @@
 //! Utility for converting math markup in articles from LaTeX to HTML.
 
-use anyhow::{Context as _, Error, Result};
+#[cfg(feature = "math")]
+use crate::{cache::MathCache, component::escape_html};
+#[cfg(feature = "math")]
+use anyhow::{Context as _, Error};
+use anyhow::{Result, bail};
+#[cfg(feature = "math")]
 use rquickjs::{Context, Exception, Function, Object, Runtime};
+use serde::Deserialize;
+use std::{borrow::Cow, collections::BTreeMap};
 
+#[cfg(feature = "math")]
 const KATEX_SRC: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../katex/katex.js"));
+pub(crate) const KATEX_VERSION: &str =
+    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../katex/version.txt"));
 
+// The name of the fenced code block language for pre-rendered math (e.g. hand-written MathML),
+// which is inserted into pages as is instead of being converted by KaTeX
+pub const RAW_MATH_LANGUAGE: &str = "math-html";
+
+const RAW_MATH_CLASS: &str = "__raw-math";
+
+/// Returns the opening tag of the element wrapping a block of pre-rendered math.
+#[must_use]
+pub fn raw_math_opening_html() -> String {
+    format!("<div class=\"{RAW_MATH_CLASS}\">")
+}
+
+// Elements whose contents are never searched for math in HTML
+const NON_MATH_ELEMENTS: [&str; 6] = ["script", "style", "pre", "code", "textarea", "math"];
+
+/// Replaces math expressions in the text of an HTML document with their rendered HTML.
+/// As in Markdown, display expressions are surrounded by double dollar signs (`$$`), and inline expressions by single
+/// dollar signs (`$`), where the opening `$` cannot be followed by whitespace and the closing `$` cannot be preceded by
+/// whitespace or followed by a digit, so prices like `$5 and $10` are left alone. `\$` is a literal dollar sign.
+///
+/// Expressions cannot contain tags, and character references in them (e.g. `&lt;`) are decoded before rendering.
+/// Comments and the contents of `<script>`, `<style>`, `<pre>`, `<code>`, `<textarea>`, and `<math>` elements
+/// are left as is.
+///
+/// # Errors
+/// This function returns an error if `render` fails for an expression.
+pub fn render_html_math(
+    html: &str,
+    mut render: impl FnMut(&str, RenderMode) -> Result<String>,
+) -> Result<String> {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(index) = rest.find(['<', '\\', '$']) {
+        output.push_str(&rest[..index]);
+        rest = &rest[index..];
+
+        if rest.starts_with('<') {
+            let len = markup_len(rest);
+            output.push_str(&rest[..len]);
+            rest = &rest[len..];
+        } else if let Some(after) = rest.strip_prefix("\\$") {
+            output.push('$');
+            rest = after;
+        } else if rest.starts_with('\\') {
+            output.push('\\');
+            rest = &rest[1..];
+        } else if let Some((src, len)) = display_math(rest) {
+            output.push_str(&render(&decode_html(src), RenderMode::Display)?);
+            rest = &rest[len..];
+        } else if let Some((src, len)) = inline_math(rest) {
+            output.push_str(&render(&decode_html(src), RenderMode::Inline)?);
+            rest = &rest[len..];
+        } else {
+            // A lone `$$` is skipped as a whole, so its second `$` doesn't open an inline expression
+            let len = if rest.starts_with("$$") { 2 } else { 1 };
+            output.push_str(&rest[..len]);
+            rest = &rest[len..];
+        }
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+/// Returns the length of the markup starting at a `<`: a comment, a tag,
+/// or an element whose contents are never searched for math, including its contents and closing tag.
+fn markup_len(html: &str) -> usize {
+    let end_of = |pattern: &str, from: usize| {
+        html[from..]
+            .find(pattern)
+            .map_or(html.len(), |index| from + index + pattern.len())
+    };
+
+    if html.starts_with("<!--") {
+        return end_of("-->", 4);
+    }
+    let tag_len = end_of(">", 1);
+
+    let name_len = html[1..]
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(html.len() - 1);
+    let name = html[1..=name_len].to_ascii_lowercase();
+    if NON_MATH_ELEMENTS.contains(&name.as_str()) && !html[..tag_len].ends_with("/>") {
+        // Closing tags are matched case-insensitively
+        let closing_tag = format!("</{name}");
+        let closing_start = html[tag_len..]
+            .to_ascii_lowercase()
+            .find(&closing_tag)
+            .map_or(html.len(), |index| tag_len + index);
+        end_of(">", closing_start)
+    } else {
+        tag_len
+    }
+}
+
+/// Returns the source and total length of a display math expression (e.g. `$$x$$`) at the start of `html`.
+fn display_math(html: &str) -> Option<(&str, usize)> {
+    let after = html.strip_prefix("$$")?;
+    let len = after.find("$$")?;
+    let src = &after[..len];
+    (!src.trim().is_empty() && !src.contains('<')).then_some((src, len + 4))
+}
+
+/// Returns the source and total length of an inline math expression (e.g. `$x$`) at the start of `html`.
+fn inline_math(html: &str) -> Option<(&str, usize)> {
+    let after = html.strip_prefix('$')?;
+    if after.starts_with(char::is_whitespace) || after.starts_with('$') {
+        return None;
+    }
+
+    let mut search_start = 0;
+    loop {
+        let len = search_start + after[search_start..].find(['$', '<'])?;
+        if after[len..].starts_with('<') {
+            return None;
+        }
+        let src = &after[..len];
+        let is_closing = !src.ends_with(char::is_whitespace)
+            && !src.ends_with('\\')
+            && !after[len + 1..].starts_with(|c: char| c.is_ascii_digit());
+        if is_closing {
+            return Some((src, len + 2));
+        }
+        search_start = len + 1;
+    }
+}
+
+/// Decodes the character references that HTML text can use for characters in LaTeX.
+fn decode_html(src: &str) -> Cow<'_, str> {
+    if !src.contains('&') {
+        return Cow::Borrowed(src);
+    }
+    Cow::Owned(
+        src.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&#x27;", "'")
+            .replace("&nbsp;", "\u{a0}")
+            .replace("&amp;", "&"),
+    )
+}
+
+// Prefix of the messages of errors that KaTeX raises for invalid LaTeX
+#[cfg(feature = "math")]
+const PARSE_ERROR_PREFIX: &str = "KaTeX parse error: ";
+
+const MATH_ERROR_CLASS: &str = "katex-error";
+
+/// Returns the error text that invalid LaTeX is rendered as with the `render_errors` option,
+/// in the same markup as KaTeX's (so its stylesheet applies), with the error message as the title.
+#[cfg(feature = "math")]
+fn math_error_html(src: &str, message: &str) -> String {
+    format!(
+        "<span class=\"{MATH_ERROR_CLASS}\" title=\"{}\" style=\"color:#cc0000\">{}</span>",
+        escape_html(message),
+        escape_html(src)
+    )
+}
+
+/// Returns the message of a LaTeX error that was rendered as error text (with the `render_errors` option),
+/// if the rendered HTML of a math expression contains one.
+#[must_use]
+pub fn math_error(html: &str) -> Option<Cow<'_, str>> {
+    let error = &html[html.find(&format!("class=\"{MATH_ERROR_CLASS}\""))?..];
+    let title = &error[error.find("title=\"")? + "title=\"".len()..];
+    let message = &title[..title.find('"')?];
+    Some(decode_html(message))
+}
+
+#[cfg(feature = "math")]
 pub struct LatexConverter {
     context: Context,
+    options: KatexOptions,
+}
+
+/// Site-wide KaTeX options, applied to every math expression.
+/// Options that are absent keep KaTeX's defaults (see https://katex.org/docs/options).
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct KatexOptions {
+    // Whether LaTeX that KaTeX accepts but that isn't standard LaTeX (e.g. Unicode text in math mode) is an error
+    pub strict: bool,
+    // Whether commands that can add links or arbitrary HTML (e.g. `\href` and `\htmlClass`) are allowed
+    pub trust: bool,
+    // Markup that math is rendered as
+    pub output: KatexOutput,
+    // Minimum thickness of fraction lines and other rules, in ems
+    pub min_rule_thickness: Option<f64>,
+    // Whether `\color` works like `\textcolor`, as in old versions of MathJax
+    pub color_is_text_color: bool,
+    // Maximum size of user-specified sizes (e.g. in `\rule{500em}{500em}`), in ems
+    pub max_size: Option<f64>,
+    // Maximum number of macro expansions in an expression
+    pub max_expand: Option<u32>,
+    // Whether invalid LaTeX is rendered as error text instead of failing the build
+    pub render_errors: bool,
+}
+
+impl KatexOptions {
+    /// Checks that the numeric options are in range.
+    ///
+    /// # Errors
+    /// This function returns an error if an option is negative, zero where that is not allowed, or not finite.
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self
+            .min_rule_thickness
+            .is_some_and(|thickness| !thickness.is_finite() || thickness < 0.0)
+        {
+            bail!("`min_rule_thickness`: must be a non-negative number");
+        } else if self
+            .max_size
+            .is_some_and(|size| size.is_nan() || size <= 0.0)
+        {
+            bail!("`max_size`: must be greater than 0");
+        }
+        Ok(())
+    }
+
+    /// Returns a string that differs between options that can render the same expression differently.
+    pub(crate) fn fingerprint(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// KaTeX settings that an article can set in its frontmatter, which take precedence over the site's for its math.
+#[derive(Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct KatexOverrides {
+    // Overrides the site's `strict` option
+    pub strict: Option<bool>,
+    // Whether every display math expression is aligned left instead of centered
+    pub fleqn: bool,
+    // Whether every equation tag is placed on the left instead of the right
+    pub leqno: bool,
+    // Macros available in every expression, mapping names (e.g. `\RR`) to their expansions (e.g. `\mathbb{R}`)
+    pub macros: BTreeMap<Box<str>, Box<str>>,
+}
+
+impl KatexOverrides {
+    /// Checks that the macro names are valid.
+    ///
+    /// # Errors
+    /// This function returns an error if a macro name is not a backslash followed by either letters or one other character.
+    pub(crate) fn validate(&self) -> Result<()> {
+        for name in self.macros.keys() {
+            let Some(command) = name.strip_prefix('\\') else {
+                bail!("macro name must start with a backslash (got \"{name}\")");
+            };
+            if command.is_empty()
+                || !(command.chars().count() == 1
+                    || command.chars().all(|c| c.is_ascii_alphabetic()))
+            {
+                bail!(
+                    "macro name must be a backslash followed by letters or a single other character (got \"{name}\")"
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a string that differs between overrides that can render the same expression differently.
+    pub(crate) fn fingerprint(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+/// Markup that KaTeX renders math as.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum KatexOutput {
+    /// HTML for display, and MathML for accessibility.
+    #[default]
+    HtmlAndMathml,
+    /// Only HTML, which is not accessible to screen readers.
+    Html,
+    /// Only MathML, which doesn't need the KaTeX stylesheet, but looks different in each browser.
+    Mathml,
+}
+
+impl KatexOutput {
+    /// Returns a Boolean indicating if rendered math needs the KaTeX stylesheet and fonts to display correctly.
+    #[must_use]
+    pub const fn needs_stylesheet(self) -> bool {
+        !matches!(self, Self::Mathml)
+    }
+
+    /// Returns the value of KaTeX's `output` option.
+    #[cfg(feature = "math")]
+    const fn as_katex_str(self) -> &'static str {
+        match self {
+            Self::HtmlAndMathml => "htmlAndMathml",
+            Self::Html => "html",
+            Self::Mathml => "mathml",
+        }
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -15,6 +321,47 @@ pub enum RenderMode {
     Display,
 }
 
+/// KaTeX options that can be overridden for individual display math expressions.
+/// Every option is disabled by default, matching KaTeX's defaults.
+#[cfg(feature = "math")]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct MathOptions {
+    // Whether equation tags are placed on the left instead of the right
+    leqno: bool,
+    // Whether display math is aligned left instead of centered
+    fleqn: bool,
+}
+
+#[cfg(feature = "math")]
+impl MathOptions {
+    /// Splits a list of options in square brackets (e.g. `[leqno, fleqn]`) from the start of a math expression,
+    /// returning the options and the rest of the expression.
+    /// If the expression doesn't start with a list of known option names (e.g. `[a, b]`),
+    /// the brackets are considered part of the expression, and default options are returned.
+    #[must_use]
+    pub fn parse(src: &str) -> (Self, &str) {
+        let Some((list, rest)) = src
+            .trim_start()
+            .strip_prefix('[')
+            .and_then(|src| src.split_once(']'))
+        else {
+            return (Self::default(), src);
+        };
+
+        let mut options = Self::default();
+        for name in list.split(',').map(str::trim) {
+            match name {
+                "leqno" => options.leqno = true,
+                "fleqn" => options.fleqn = true,
+                _ => return (Self::default(), src),
+            }
+        }
+
+        (options, rest)
+    }
+}
+
+#[cfg(feature = "math")]
 impl LatexConverter {
     /// Initializes a utility to convert LaTeX source code into HTML.
     /// The current implementation works by running the KaTeX library in a QuickJS runtime via the `rquickjs` crate.
@@ -43,10 +390,20 @@ impl LatexConverter {
             })
             .context("failed to initialize `katex`")?;
 
-        Ok(Self { context })
+        Ok(Self {
+            context,
+            options: KatexOptions::default(),
+        })
+    }
+
+    /// Sets the KaTeX options applied to every expression.
+    #[must_use]
+    pub fn with_options(mut self, options: KatexOptions) -> Self {
+        self.options = options;
+        self
     }
 
-    /// Converts a string of LaTeX into a string of HTML.
+    /// Converts a string of LaTeX into a string of HTML, applying `options` in display mode.
     /// The output HTML uses CSS classes from KaTeX.
     /// The CSS file that comes with KaTeX distributions contains rules for these classes;
     /// it should be used for math to display properly.
@@ -56,7 +413,49 @@ impl LatexConverter {
     /// - the rendering settings cannot be initialized
     /// - the `katex.renderToString()` function cannot be found
     /// - the `katex.renderToString()` function fails to run (e.g. due to invalid LaTeX)
-    pub fn latex_to_html(&self, src: &str, mode: RenderMode) -> Result<String> {
+    pub fn latex_to_html(
+        &self,
+        src: &str,
+        mode: RenderMode,
+        options: MathOptions,
+    ) -> Result<String> {
+        self.latex_to_html_with_overrides(src, mode, options, &KatexOverrides::default())
+    }
+
+    /// Converts a math expression in an article into a string of HTML like [`Self::latex_to_html_with_overrides()`],
+    /// unless `cache` already has its HTML. Display math can start with a list of options (see [`MathOptions::parse()`]),
+    /// which are part of the cache key along with the rest of the source.
+    ///
+    /// # Errors
+    /// This function returns an error if the expression is not cached and cannot be converted.
+    pub fn render_cached(
+        &self,
+        src: &str,
+        mode: RenderMode,
+        overrides: &KatexOverrides,
+        cache: &mut MathCache<'_>,
+    ) -> Result<String> {
+        cache.get_or_render_with_overrides(src, mode, overrides, || {
+            let (options, latex) = match mode {
+                RenderMode::Inline => (MathOptions::default(), src),
+                RenderMode::Display => MathOptions::parse(src),
+            };
+            self.latex_to_html_with_overrides(latex, mode, options, overrides)
+        })
+    }
+
+    /// Converts a string of LaTeX into a string of HTML like [`Self::latex_to_html()`],
+    /// with an article's KaTeX settings taking precedence over the site's.
+    ///
+    /// # Errors
+    /// This function returns an error in the same cases as [`Self::latex_to_html()`].
+    pub fn latex_to_html_with_overrides(
+        &self,
+        src: &str,
+        mode: RenderMode,
+        options: MathOptions,
+        overrides: &KatexOverrides,
+    ) -> Result<String> {
         self.context.with(|ctx| {
             // `katex.renderToString()` accepts an object of options.
             // The `displayMode` option controls whether the input string will be rendered in display or inline mode.
@@ -72,6 +471,48 @@ impl LatexConverter {
                     },
                 )
                 .context("failed to initialize `katex` settings")?;
+            settings
+                .set("leqno", options.leqno || overrides.leqno)
+                .context("failed to initialize `katex` settings")?;
+            settings
+                .set("fleqn", options.fleqn || overrides.fleqn)
+                .context("failed to initialize `katex` settings")?;
+
+            let KatexOptions {
+                strict,
+                trust,
+                output,
+                min_rule_thickness,
+                color_is_text_color,
+                max_size,
+                max_expand,
+                render_errors,
+            } = self.options;
+            (|| {
+                settings.set("strict", overrides.strict.unwrap_or(strict))?;
+                settings.set("trust", trust)?;
+                settings.set("output", output.as_katex_str())?;
+                settings.set("colorIsTextColor", color_is_text_color)?;
+                if let Some(thickness) = min_rule_thickness {
+                    settings.set("minRuleThickness", thickness)?;
+                }
+                if let Some(size) = max_size {
+                    settings.set("maxSize", size)?;
+                }
+                if let Some(expand) = max_expand {
+                    settings.set("maxExpand", expand)?;
+                }
+                if !overrides.macros.is_empty() {
+                    // KaTeX adds macros defined with `\gdef` to this object, so each expression gets a new one
+                    let macros = Object::new(ctx.clone())?;
+                    for (name, expansion) in &overrides.macros {
+                        macros.set(&**name, &**expansion)?;
+                    }
+                    settings.set("macros", macros)?;
+                }
+                rquickjs::Result::Ok(())
+            })()
+            .context("failed to initialize `katex` settings")?;
 
             // To call `katex.renderToString()`, we have to get the function from global context.
             ctx.globals()
@@ -80,29 +521,44 @@ impl LatexConverter {
                 .get::<_, Function<'_>>("renderToString")
                 .context("failed to find the function `katex.renderToString()`")?
                 .call((src, settings))
-                .map_err(|e| {
+                .or_else(|e| {
+                    let msg = ctx.catch().as_exception().and_then(Exception::message);
+                    // KaTeX's own `throwOnError: false` renders some errors (e.g. undefined commands) as colored text
+                    // that can't be told apart from valid math, so errors are rendered here instead
+                    if render_errors
+                        && let Some(msg) = msg
+                            .as_deref()
+                            .filter(|msg| msg.starts_with(PARSE_ERROR_PREFIX))
+                    {
+                        return Ok(math_error_html(src, msg));
+                    }
                     let mut err = Error::new(e);
                     // Add exceptions raised by QuickJS to the error chain
-                    if let Some(msg) = ctx.catch().as_exception().and_then(Exception::message) {
+                    if let Some(msg) = msg {
                         err = err.context(msg);
                     }
-                    err.context("failed to run `katex.renderToString()`")
+                    Err(err.context("failed to run `katex.renderToString()`"))
                 })
         })
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "math"))]
 mod test {
-    use super::{LatexConverter, RenderMode};
+    use super::{
+        KatexOptions, KatexOutput, KatexOverrides, LatexConverter, MathOptions, RenderMode,
+        math_error, render_html_math,
+    };
     use anyhow::Result;
 
     #[test]
     fn inline_display_comparison() -> Result<()> {
         let converter = LatexConverter::new()?;
 
-        let inline_html = converter.latex_to_html("2x+3y=4z", RenderMode::Inline)?;
-        let display_html = converter.latex_to_html("2x+3y=4z", RenderMode::Display)?;
+        let inline_html =
+            converter.latex_to_html("2x+3y=4z", RenderMode::Inline, MathOptions::default())?;
+        let display_html =
+            converter.latex_to_html("2x+3y=4z", RenderMode::Display, MathOptions::default())?;
 
         assert_ne!(
             inline_html, display_html,
@@ -117,7 +573,7 @@ mod test {
         assert!(
             LatexConverter::new()
                 .expect("engine initialization should succeed")
-                .latex_to_html("\\frac{", RenderMode::Inline)
+                .latex_to_html("\\frac{", RenderMode::Inline, MathOptions::default())
                 .is_err(),
             "conversion should fail on invalid LaTeX"
         );
@@ -128,7 +584,7 @@ mod test {
         let converter = LatexConverter::new()?;
 
         // Surprisingly, this is enough to exhaust the JavaScript runtime's default stack size of 256 KiB
-        converter.latex_to_html("\\frac{1}{2}", RenderMode::Inline)?;
+        converter.latex_to_html("\\frac{1}{2}", RenderMode::Inline, MathOptions::default())?;
 
         converter.latex_to_html(
             r"\begin{align}
@@ -156,13 +612,208 @@ f'(x)
 &= x\tan^{-1}x-\tfrac{1}{2}\ln(x^2+1)+C
 \end{align}",
             RenderMode::Display,
+            MathOptions::default(),
         )?;
 
         // Even further nesting causes the test thread's stack to overflow
         converter.latex_to_html(
             &format!("{}2{}", "\\frac{1}{".repeat(10), "}".repeat(10)),
             RenderMode::Inline,
+            MathOptions::default(),
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn math_options() -> Result<()> {
+        let leqno = MathOptions {
+            leqno: true,
+            ..MathOptions::default()
+        };
+        assert_eq!(MathOptions::parse("[leqno] x"), (leqno, " x"));
+        assert_eq!(
+            MathOptions::parse(" [ fleqn , leqno ]x"),
+            (
+                MathOptions {
+                    leqno: true,
+                    fleqn: true
+                },
+                "x"
+            )
+        );
+        assert_eq!(
+            MathOptions::parse("[a, b]"),
+            (MathOptions::default(), "[a, b]")
+        );
+        assert_eq!(
+            MathOptions::parse("[leqno"),
+            (MathOptions::default(), "[leqno")
+        );
+        assert_eq!(MathOptions::parse("x"), (MathOptions::default(), "x"));
+
+        let converter = LatexConverter::new()?;
+        let html = converter.latex_to_html("x \\tag{1}", RenderMode::Display, leqno)?;
+        assert!(html.contains("katex-display leqno"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn katex_options() -> Result<()> {
+        let default = LatexConverter::new()?;
+        assert!(
+            default
+                .latex_to_html("é", RenderMode::Inline, MathOptions::default())
+                .is_ok()
+        );
+        assert!(
+            default
+                .latex_to_html(
+                    "\\href{https://example.com}{x}",
+                    RenderMode::Inline,
+                    MathOptions::default()
+                )?
+                .contains("\\href")
+        );
+
+        let options = KatexOptions {
+            strict: true,
+            trust: true,
+            output: KatexOutput::Mathml,
+            ..KatexOptions::default()
+        };
+        let converter = LatexConverter::new()?.with_options(options);
+        assert!(
+            converter
+                .latex_to_html("é", RenderMode::Inline, MathOptions::default())
+                .is_err()
+        );
+        let html = converter.latex_to_html(
+            "\\href{https://example.com}{x}",
+            RenderMode::Inline,
+            MathOptions::default(),
+        )?;
+        assert!(html.contains("href=\"https://example.com\""));
+        assert!(!html.contains("katex-html"));
+
+        assert_ne!(options.fingerprint(), KatexOptions::default().fingerprint());
+
+        assert!(
+            KatexOptions {
+                min_rule_thickness: Some(-1.0),
+                ..KatexOptions::default()
+            }
+            .validate()
+            .is_err()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn render_errors() -> Result<()> {
+        // Without the option, invalid LaTeX fails the conversion
+        assert!(
+            LatexConverter::new()?
+                .latex_to_html("\\frac{a}", RenderMode::Inline, MathOptions::default())
+                .is_err()
+        );
+
+        let lenient = LatexConverter::new()?.with_options(KatexOptions {
+            render_errors: true,
+            ..KatexOptions::default()
+        });
+        let html =
+            lenient.latex_to_html("\\frac{a}", RenderMode::Inline, MathOptions::default())?;
+        assert!(math_error(&html).is_some_and(|error| {
+            error.starts_with(
+                "KaTeX parse error: Unexpected end of input in a macro argument, expected '}'",
+            )
+        }));
+        let html = lenient.latex_to_html(
+            "a < \\undefined",
+            RenderMode::Display,
+            MathOptions::default(),
         )?;
+        assert!(html.contains(">a &lt; \\undefined</span>"));
+        assert!(math_error(&html).is_some_and(|error| {
+            error.starts_with("KaTeX parse error: Undefined control sequence: \\undefined")
+        }));
+        assert!(
+            math_error(&lenient.latex_to_html("x", RenderMode::Inline, MathOptions::default())?)
+                .is_none()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn katex_overrides() -> Result<()> {
+        let converter = LatexConverter::new()?;
+        let overrides = KatexOverrides {
+            strict: Some(true),
+            fleqn: true,
+            leqno: false,
+            macros: [("\\RR".into(), "\\mathbb{R}".into())].into(),
+        };
+        let render = |src: &str, overrides: &KatexOverrides| {
+            converter.latex_to_html_with_overrides(
+                src,
+                RenderMode::Display,
+                MathOptions::default(),
+                overrides,
+            )
+        };
+
+        assert!(
+            render("x \\in \\RR", &overrides)?.contains("<span class=\"mord mathbb\">R</span>")
+        );
+        assert!(render("x \\in \\RR", &KatexOverrides::default()).is_err());
+        assert!(render("x", &overrides)?.contains("katex-display fleqn"));
+        assert!(render("é", &overrides).is_err());
+        assert!(render("é", &KatexOverrides::default()).is_ok());
+
+        assert!(overrides.validate().is_ok());
+        for name in ["RR", "\\R1", "\\"] {
+            let overrides = KatexOverrides {
+                macros: [(name.into(), "x".into())].into(),
+                ..KatexOverrides::default()
+            };
+            assert!(overrides.validate().is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn html_math() -> Result<()> {
+        let render = |html| {
+            render_html_math(html, |src, mode| {
+                Ok(match mode {
+                    RenderMode::Inline => format!("[{src}]"),
+                    RenderMode::Display => format!("[[{src}]]"),
+                })
+            })
+        };
+
+        assert_eq!(
+            render("<p>Let $x &lt; 1$. Then $$x^2 &lt; 1$$.</p>")?,
+            "<p>Let [x < 1]. Then [[x^2 < 1]].</p>"
+        );
+        assert_eq!(
+            render("<p>It costs $5 and $10, or \\$2 a $day $ $</p>")?,
+            "<p>It costs $5 and $10, or $2 a $day $ $</p>"
+        );
+        assert_eq!(
+            render("<p title=\"$a$\">$a$ <!-- $b$ --></p><PRE>$c$</pre><code>$d$</code>$$")?,
+            "<p title=\"$a$\">[a] <!-- $b$ --></p><PRE>$c$</pre><code>$d$</code>$$"
+        );
+        assert_eq!(render("<p>$a <em>b</em>$</p>")?, "<p>$a <em>b</em>$</p>");
+        assert_eq!(render(r"$\$$ and $\{a\}$")?, r"[\$] and [\{a\}]");
+
+        // Errors from rendering are returned
+        assert!(render_html_math("$a$", |_, _| Err(anyhow::anyhow!("invalid"))).is_err());
 
         Ok(())
     }
@@ -1,46 +1,369 @@
+#[cfg(feature = "highlight")]
+mod ansi;
+#[cfg(feature = "images")]
+mod article_image;
+mod audio;
 mod builder;
+mod cache;
+#[cfg(feature = "charts")]
+mod chart;
+mod component;
 mod config;
+mod cover;
+mod crossref;
+#[cfg(feature = "css")]
 mod css;
+mod depgraph;
+#[cfg(feature = "images")]
+mod embed;
+mod equation;
+mod excerpt;
+mod footnote;
 mod frontmatter;
+#[cfg(feature = "maps")]
+mod geo;
 mod highlight;
 mod image;
 mod latex;
+mod lines;
+mod links;
+mod local;
+mod manifest;
+mod normalize;
+mod outline;
+mod partial;
+mod profile;
+mod quotes;
+mod redirect;
+mod report;
+mod robots;
+mod search;
+mod shortcode;
+#[cfg(feature = "svg")]
+mod svg;
+mod table;
+mod title;
+mod toc;
+mod url;
+mod vfs;
+mod video;
+mod watch;
+mod wikilink;
 
-pub use builder::{ArchiveBuilder, PageBuilder, PageKind};
-pub use config::{Config, Fragment};
-pub use css::{CssOutput, Font, transform_css};
-pub use frontmatter::Frontmatter;
+#[cfg(feature = "images")]
+pub use article_image::ArticleImages;
+pub use audio::Audio;
+pub use builder::{
+    ArchiveBuilder, BuildStamp, ChangelogBuilder, FeedBuilder, Font, KatexCssLoading, PageBuilder,
+    PageKind, Thumbnail,
+};
+pub use cache::{CacheStats, FragmentCache, MathCache};
+#[cfg(feature = "charts")]
+pub use chart::{CHART_LANGUAGE, ChartSpec};
+pub use component::{Component, ComponentMarker, render_component};
+pub use config::{CheckCommand, CliArgs, Config, FeedConfig, Fragment};
+#[cfg(feature = "images")]
+pub use cover::convert_cover_thumbnail;
+pub use cover::copy_social_image;
+pub use crossref::{CrossReferences, IndexPosition, LabeledItem, strip_label};
+#[cfg(feature = "css")]
+pub use css::{CssOutput, prune_css, read_css_file, transform_css, transform_css_files};
+pub use depgraph::DependencyGraph;
+#[cfg(feature = "images")]
+pub use embed::Embed;
+pub use equation::{Equations, NumberedMath};
+pub use excerpt::article_excerpt;
+pub use footnote::{FootnoteStyle, Footnotes};
+pub use frontmatter::{Frontmatter, License, MarkdownExtension, frontmatter_problems};
+#[cfg(feature = "maps")]
+pub use geo::TrackMap;
+#[cfg(feature = "highlight")]
 pub use highlight::SyntaxHighlighter;
-pub use image::{ActiveImageState, OUTPUT_IMAGE_EXTENSION, convert_image, validate_image_src};
-pub use latex::{LatexConverter, RenderMode};
+pub use highlight::{CodeBlockInfo, InlineCodeStyle};
+pub use image::{
+    ActiveImageState, DEFAULT_IMAGE_SIZES, Dimensions, FallbackFormat, ImageOptions, ImageVariants,
+    OUTPUT_IMAGE_EXTENSION, PlaceholderStyle, alt_text_problem, captioned_image_paragraphs,
+    is_remote_image_src, output_size, parse_image_src, validate_image_src,
+};
+#[cfg(feature = "images")]
+pub use image::{
+    convert_image, convert_image_variants, convert_image_width_variants, convert_remote_image,
+};
+pub use latex::{
+    KatexOptions, KatexOutput, KatexOverrides, RAW_MATH_LANGUAGE, RenderMode, math_error,
+    raw_math_opening_html, render_html_math,
+};
+#[cfg(feature = "math")]
+pub use latex::{LatexConverter, MathOptions};
+pub use lines::LineIndex;
+pub use links::{BrokenLink, LinkCheckPolicy, find_broken_links};
+pub use manifest::{ManifestConfig, ManifestIcon, OUTPUT_MANIFEST_FILE};
+pub use normalize::{TextFixes, normalize_text};
+pub use outline::{HeadingOutline, OutlinePolicy};
+pub use partial::Template;
+pub use profile::{ArticleProfile, BuildProfile, Stage};
+pub use quotes::{QuoteLocale, QuoteTransformer};
+pub use redirect::{OUTPUT_REDIRECTS_FILE, Redirect, RedirectStyle, redirects_file};
+pub use report::{ArticleMetrics, BuildReport};
+pub use robots::{OUTPUT_ROBOTS_FILE, RobotsConfig};
+pub use search::SearchIndex;
+pub use shortcode::{Shortcode, render_shortcode};
+#[cfg(feature = "svg")]
+pub use svg::minify_svg;
+pub use table::DataTable;
+pub use title::{DuplicateTitlePolicy, demote_heading, duplicate_title_range};
+pub use toc::{TOC_MARKER, TableOfContents, heading_anchor_html};
+pub use url::{AssetUrls, article_url, page_url, tag_url};
+pub use vfs::{MemoryFs, OverlayFs, RealFs, Vfs};
+pub use video::Video;
+pub use watch::{ChangeSet, SiteWatcher};
+pub use wikilink::WikiLinks;
 
 pub use common::OUTPUT_FONTS_DIR;
 
 pub const OUTPUT_CSS_DIR: &str = "stylesheets/";
 pub const OUTPUT_SITE_CSS_FILE: &str = "stylesheets/site.css";
 const OUTPUT_SITE_CSS_FILE_ABSOLUTE: &str = "/stylesheets/site.css";
-const OUTPUT_KATEX_CSS_FILE: &str = "stylesheets/katex.css";
+pub(crate) const OUTPUT_KATEX_CSS_FILE: &str = "stylesheets/katex.css";
 pub const OUTPUT_CONTENT_DIR: &str = "writing/";
+pub const OUTPUT_CHANGELOG_DIR: &str = "changelog/";
+pub const OUTPUT_ICONS_DIR: &str = "icons/";
+pub const OUTPUT_TAGS_DIR: &str = "writing/tags/";
+pub const OUTPUT_ATOM_FEED_FILE: &str = "writing/feed.xml";
+pub const OUTPUT_RSS_FEED_FILE: &str = "writing/rss.xml";
+pub const OUTPUT_SEARCH_INDEX_FILE: &str = "writing/search-index.json";
+// Names of the feed files generated in the output directory of every tag page
+pub const TAG_ATOM_FEED_FILE_NAME: &str = "feed.xml";
+pub const TAG_RSS_FEED_FILE_NAME: &str = "rss.xml";
+pub const CACHE_DEPENDENCIES_FILE: &str = "dependencies.json";
 
+/// Returns the name of the tag pages directory within the articles output directory,
+/// which no article slug can be equal to when articles have tags.
+///
+/// # Panics
+/// This function panics if the tags directory is not within the articles directory.
+#[must_use]
+pub fn tags_dir_name() -> &'static str {
+    OUTPUT_TAGS_DIR
+        .strip_prefix(OUTPUT_CONTENT_DIR)
+        .expect("tags directory should be within articles directory")
+        .trim_end_matches('/')
+}
+
+#[cfg(any(feature = "css", feature = "math"))]
 const KATEX_CSS: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/../katex/katex.css"));
+#[cfg(feature = "math")]
 const KATEX_FONTS: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/../katex/fonts/");
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use camino::Utf8Path;
+#[cfg(feature = "math")]
 use include_dir::{Dir, include_dir};
-use std::fs::write;
 
 /// Saves the KaTeX CSS and font files for math markup to the output directory.
 ///
 /// # Errors
 /// This function returns an error if files cannot be written to the destination.
-pub fn save_math_assets(output_dir: &Utf8Path) -> Result<()> {
-    write(output_dir.join(OUTPUT_KATEX_CSS_FILE), KATEX_CSS)
-        .context("failed to write KaTeX CSS to output destination")?;
+#[cfg(feature = "math")]
+pub fn save_math_assets(output_dir: &Utf8Path, vfs: &dyn Vfs) -> Result<()> {
+    vfs.write(
+        &output_dir.join(OUTPUT_KATEX_CSS_FILE),
+        KATEX_CSS.as_bytes(),
+        false,
+    )
+    .context("failed to write KaTeX CSS to output destination")?;
 
-    KATEX_FONTS
-        .extract(output_dir.join(OUTPUT_FONTS_DIR))
+    let output_fonts_dir = output_dir.join(OUTPUT_FONTS_DIR);
+    vfs.create_dir_all(&output_fonts_dir)
         .context("failed to write KaTeX fonts to output destination")?;
+    for file in KATEX_FONTS.files() {
+        let Some(path) = Utf8Path::from_path(file.path()) else {
+            bail!(
+                "KaTeX font file name {} is not UTF-8",
+                file.path().display()
+            );
+        };
+        vfs.write(&output_fonts_dir.join(path), file.contents(), false)
+            .context("failed to write KaTeX fonts to output destination")?;
+    }
 
     Ok(())
 }
+
+/// Copies the site's font files from a directory to the output fonts directory.
+/// Subdirectories are not copied.
+///
+/// # Errors
+/// This function returns an error if the directory cannot be read or a file cannot be copied.
+pub fn save_site_fonts(fonts_dir: &Utf8Path, output_dir: &Utf8Path, vfs: &dyn Vfs) -> Result<()> {
+    let output_fonts_dir = output_dir.join(OUTPUT_FONTS_DIR);
+    vfs.create_dir_all(&output_fonts_dir)
+        .with_context(|| format!("failed to create directory at {output_fonts_dir}"))?;
+    for path in vfs
+        .files_in(fonts_dir)
+        .with_context(|| format!("failed to read {fonts_dir}"))?
+    {
+        let Some(file_name) = path.file_name() else {
+            continue;
+        };
+        if path.parent() != Some(fonts_dir) {
+            continue;
+        }
+        vfs.copy(&path, &output_fonts_dir.join(file_name))
+            .with_context(|| format!("failed to copy font file at {path}"))?;
+    }
+    Ok(())
+}
+
+/// Copies every file within a directory and its subdirectories to the same relative path in the output directory,
+/// recording every copied file in `dependencies` and returning the number of copied files.
+/// Hidden files and directories (e.g. `.well-known/`) are copied too.
+///
+/// # Errors
+/// This function returns an error if:
+/// - the directory or one of its subdirectories cannot be read
+/// - the output path of a file is an output in `dependencies` (e.g. a generated page),
+///   or a file already exists there (e.g. a converted image or a font file, which `dependencies` doesn't track)
+/// - a file cannot be copied
+pub fn save_static_files(
+    static_dir: &Utf8Path,
+    output_dir: &Utf8Path,
+    dependencies: &mut DependencyGraph,
+    vfs: &dyn Vfs,
+) -> Result<usize> {
+    let files = vfs.files_in(static_dir)?;
+    for path in &files {
+        let relative_path = path
+            .strip_prefix(static_dir)
+            .with_context(|| format!("static file at {path} is outside {static_dir}"))?;
+        let output_path = output_dir.join(relative_path);
+        if dependencies.contains_output(relative_path.as_str())
+            || vfs.is_file(&output_path)
+            || vfs.is_dir(&output_path)
+        {
+            bail!("static file at {path} conflicts with a file generated at {output_path}");
+        }
+        if let Some(parent) = output_path.parent() {
+            vfs.create_dir_all(parent)
+                .with_context(|| format!("failed to create directory at {parent}"))?;
+        }
+        vfs.copy(path, &output_path)
+            .with_context(|| format!("failed to copy static file at {path}"))?;
+        dependencies.add(relative_path.as_str(), [path]);
+    }
+    Ok(files.len())
+}
+
+/// Checks that every font file referenced by `@font-face` rules in the site CSS
+/// and (with `check_katex_fonts`) in the KaTeX CSS exists in the output fonts directory.
+///
+/// # Errors
+/// This function returns an error listing the URLs of the font files that don't exist.
+///
+/// # Panics
+/// This function panics if the bundled KaTeX CSS cannot be parsed.
+#[cfg(feature = "css")]
+pub fn check_font_files(
+    output_dir: &Utf8Path,
+    site_font_urls: &[Box<str>],
+    check_katex_fonts: bool,
+    vfs: &dyn Vfs,
+) -> Result<()> {
+    let missing_site_fonts =
+        css::missing_font_files(site_font_urls.iter().map(AsRef::as_ref), output_dir, vfs);
+    if !missing_site_fonts.is_empty() {
+        bail!(
+            "font file(s) referenced by the site CSS do not exist in the output fonts directory: {}",
+            missing_site_fonts.join(", ")
+        );
+    }
+    if !check_katex_fonts {
+        return Ok(());
+    }
+
+    let katex_font_urls = css::font_urls(KATEX_CSS).expect("KaTeX CSS should be valid");
+    let missing_katex_fonts =
+        css::missing_font_files(katex_font_urls.iter().map(AsRef::as_ref), output_dir, vfs);
+    if !missing_katex_fonts.is_empty() {
+        bail!(
+            "font file(s) referenced by the KaTeX CSS do not exist in the output fonts directory: {}",
+            missing_katex_fonts.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::save_static_files;
+    use crate::{
+        depgraph::DependencyGraph,
+        vfs::{MemoryFs, Vfs},
+    };
+    use camino::Utf8Path;
+
+    #[test]
+    fn static_files() {
+        let static_dir = Utf8Path::new("/site/static");
+        let output_dir = Utf8Path::new("/out");
+        let vfs = MemoryFs::new()
+            .with_file("/site/static/favicon.ico", "icon")
+            .with_file("/site/static/files/docs/resume.pdf", "resume")
+            .with_file("/site/static/.well-known/keybase.txt", "keybase")
+            .with_file("/out/index.html", "generated")
+            .with_file("/out/writing/index.html", "generated");
+
+        // Nested and hidden directories keep their relative paths, alongside generated files
+        let mut dependencies = DependencyGraph::new();
+        assert_eq!(
+            save_static_files(static_dir, output_dir, &mut dependencies, &vfs).unwrap(),
+            3
+        );
+        // Copied files are outputs that depend on their static file
+        assert!(dependencies.contains_output("files/docs/resume.pdf"));
+        assert!(dependencies.contains_output(".well-known/keybase.txt"));
+        assert!(dependencies.contains_input(Utf8Path::new("/site/static/files/docs/resume.pdf")));
+        assert_eq!(
+            vfs.files_in(output_dir).unwrap(),
+            [
+                "/out/.well-known/keybase.txt",
+                "/out/favicon.ico",
+                "/out/files/docs/resume.pdf",
+                "/out/index.html",
+                "/out/writing/index.html"
+            ]
+        );
+        assert_eq!(
+            vfs.file(Utf8Path::new("/out/files/docs/resume.pdf"))
+                .unwrap(),
+            b"resume"
+        );
+
+        // Static files can't replace generated files or directories
+        for path in ["/site/static/writing/index.html", "/site/static/writing"] {
+            let vfs = MemoryFs::new()
+                .with_file(path, "static")
+                .with_file("/out/writing/index.html", "generated");
+            let output_path =
+                output_dir.join(Utf8Path::new(path).strip_prefix(static_dir).unwrap());
+            assert_eq!(
+                save_static_files(static_dir, output_dir, &mut DependencyGraph::new(), &vfs)
+                    .unwrap_err()
+                    .to_string(),
+                format!("static file at {path} conflicts with a file generated at {output_path}")
+            );
+            assert_eq!(
+                vfs.read_to_string(Utf8Path::new("/out/writing/index.html"))
+                    .unwrap(),
+                "generated"
+            );
+        }
+
+        // Outputs in the dependency graph conflict too, even if no file exists at their path
+        let mut dependencies = DependencyGraph::new();
+        dependencies.add::<&Utf8Path>("robots.txt", []);
+        let vfs = MemoryFs::new().with_file("/site/static/robots.txt", "static");
+        assert!(save_static_files(static_dir, output_dir, &mut dependencies, &vfs).is_err());
+        assert!(!vfs.is_file(Utf8Path::new("/out/robots.txt")));
+    }
+}
@@ -0,0 +1,47 @@
+//! Code for mapping byte offsets in Markdown files to line numbers for diagnostics.
+
+/// The byte offsets where each line of a text starts, for looking up line numbers without rescanning the text.
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    /// Indexes the line starts of `text`.
+    #[must_use]
+    pub fn new(text: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(offset, _)| offset + 1))
+            .collect();
+        Self { line_starts }
+    }
+
+    /// Returns the 1-based number of the line containing the byte at `offset`.
+    #[must_use]
+    pub fn line(&self, offset: usize) -> usize {
+        // The first line starts at offset 0, so the partition point is always at least 1
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LineIndex;
+
+    #[test]
+    fn lines() {
+        let text = "first\nsecond\n\nfourth";
+        let lines = LineIndex::new(text);
+        assert_eq!(lines.line(0), 1);
+        assert_eq!(lines.line(5), 1);
+        assert_eq!(lines.line(6), 2);
+        assert_eq!(lines.line(13), 3);
+        assert_eq!(lines.line(14), 4);
+        assert_eq!(lines.line(text.len()), 4);
+
+        for offset in 0..=text.len() {
+            assert_eq!(lines.line(offset), text[..offset].matches('\n').count() + 1);
+        }
+
+        assert_eq!(LineIndex::new("").line(0), 1);
+    }
+}
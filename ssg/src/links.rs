@@ -0,0 +1,188 @@
+//! Code for checking that links within generated pages point to files that exist in the output directory.
+//!
+//! Only root-relative URLs (e.g. `/writing/post/`) are checked, since they are the ones the site itself serves.
+
+use crate::{redirect::page_path, vfs::Vfs};
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use scraper::Html;
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+    hash::BuildHasher,
+};
+
+// Attributes of elements that link to or load URLs
+const URL_ATTRIBUTES: [&str; 3] = ["href", "src", "poster"];
+
+/// What to do with links within generated pages that point to files that don't exist.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkCheckPolicy {
+    /// Don't check links
+    #[default]
+    Off,
+    /// Print a warning for every broken link
+    Warn,
+    /// Fail the build, listing every broken link
+    Error,
+}
+
+/// A root-relative URL within a generated page that doesn't point to a file in the output directory.
+pub struct BrokenLink {
+    // Path of the page relative to the output directory
+    pub page: Box<str>,
+    pub url: Box<str>,
+}
+
+impl Display for BrokenLink {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: broken link to {}", self.page, self.url)
+    }
+}
+
+/// Checks every root-relative URL within the HTML files of the output directory. A URL is valid if it points to a file,
+/// to a directory with an `index.html` file, or to a path with a redirect, given as the redirect's page path
+/// (see [`crate::Redirect::page_path()`]).
+///
+/// # Errors
+/// This function returns an error if the output directory or one of its HTML files cannot be read.
+pub fn find_broken_links<S: BuildHasher>(
+    output_dir: &Utf8Path,
+    vfs: &dyn Vfs,
+    redirect_pages: &HashSet<String, S>,
+) -> Result<Vec<BrokenLink>> {
+    let mut broken_links = Vec::new();
+    for path in vfs.files_in(output_dir)? {
+        if path.extension() != Some("html") {
+            continue;
+        }
+        let html = vfs
+            .read_to_string(&path)
+            .with_context(|| format!("failed to read generated page at {path}"))?;
+        let page = path.strip_prefix(output_dir).unwrap_or(&path);
+
+        for url in root_relative_urls(&html) {
+            let target = decode_path(target_path(&url));
+            let exists = |path: &str| {
+                let path = output_dir.join(path);
+                vfs.is_file(&path)
+            };
+            let target_page = page_path(&target);
+            if !(exists(target.trim_start_matches('/'))
+                || exists(&target_page)
+                || redirect_pages.contains(&target_page))
+            {
+                broken_links.push(BrokenLink {
+                    page: page.as_str().into(),
+                    url: url.into(),
+                });
+            }
+        }
+    }
+    Ok(broken_links)
+}
+
+/// Returns the root-relative URLs (without duplicates, in order of appearance)
+/// that an HTML document links to or loads resources from.
+fn root_relative_urls(html: &str) -> Vec<String> {
+    let html = Html::parse_document(html);
+    let mut urls = Vec::new();
+    for node in html.tree.nodes() {
+        let Some(el) = node.value().as_element() else {
+            continue;
+        };
+        let srcset_urls = el.attr("srcset").into_iter().flat_map(|srcset| {
+            srcset
+                .split(',')
+                .filter_map(|candidate| candidate.split_whitespace().next())
+        });
+        for url in URL_ATTRIBUTES
+            .iter()
+            .filter_map(|&attr| el.attr(attr))
+            .chain(srcset_urls)
+        {
+            if url.starts_with('/') && !url.starts_with("//") && !urls.iter().any(|u| u == url) {
+                urls.push(url.to_owned());
+            }
+        }
+    }
+    urls
+}
+
+/// Returns the path of a URL without its query string or fragment.
+fn target_path(url: &str) -> &str {
+    url.find(['?', '#']).map_or(url, |end| &url[..end])
+}
+
+/// Decodes percent-encoded bytes in a URL path, leaving invalid escapes as they are.
+fn decode_path(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(byte) = path
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_path, find_broken_links, root_relative_urls, target_path};
+    use crate::vfs::MemoryFs;
+    use camino::Utf8Path;
+    use foldhash::{HashSet, HashSetExt};
+
+    #[test]
+    fn urls() {
+        assert_eq!(
+            root_relative_urls(
+                r##"<a href="/a/">A</a><a href="https://example.com/">B</a><a href="//example.com/">C</a>
+                <a href="#top">D</a><img src="/b.avif" srcset="/b-400.avif 400w, /b.avif 800w"><a href="/a/">A</a>"##
+            ),
+            ["/a/", "/b.avif", "/b-400.avif"]
+        );
+        assert_eq!(target_path("/a/?b=c#d"), "/a/");
+        assert_eq!(decode_path("/caf%C3%A9/%zz"), "/café/%zz");
+    }
+
+    #[test]
+    fn broken_links() {
+        let vfs = MemoryFs::new()
+            .with_file(
+                "/out/index.html",
+                r#"<a href="/writing/">A</a><a href="/writing/post">B</a><a href="/feed.xml">C</a>
+                <a href="/old/#x">D</a><a href="/missing/">E</a><img src="/writing/post/a%20b.avif">"#,
+            )
+            .with_file("/out/writing/index.html", "")
+            .with_file("/out/writing/post/index.html", r#"<a href="/nope.png">F</a>"#)
+            .with_file("/out/writing/post/a b.avif", "")
+            .with_file("/out/feed.xml", "");
+        let mut redirect_pages = HashSet::new();
+        redirect_pages.insert("old/index.html".to_owned());
+
+        let broken_links: Vec<_> = find_broken_links(Utf8Path::new("/out"), &vfs, &redirect_pages)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            broken_links,
+            [
+                "index.html: broken link to /missing/",
+                "writing/post/index.html: broken link to /nope.png"
+            ]
+        );
+    }
+}
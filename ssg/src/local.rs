@@ -0,0 +1,86 @@
+//! Utilities for files that articles reference by paths relative to their own directory (e.g. media and data files).
+
+use crate::vfs::Vfs;
+use anyhow::{Context, Result, bail};
+use camino::{Utf8Component, Utf8Path};
+
+/// Validates the path of an article-local file. `description` names the path in error messages (e.g. "video source").
+///
+/// # Errors
+/// This function returns an error if the path is empty, is not relative, or has parent-referencing components ("..").
+pub(crate) fn validate_local_src(src: &str, description: &str) -> Result<()> {
+    let path = Utf8Path::new(src);
+    if src.is_empty()
+        || !path.is_relative()
+        || path
+            .components()
+            .any(|part| matches!(part, Utf8Component::ParentDir | Utf8Component::Normal("..")))
+    {
+        bail!("{description} is not a normalized relative file path ({src})");
+    }
+    Ok(())
+}
+
+/// Copies the article-local file at `src` from `input_dir` to the same relative path in `output_dir`,
+/// creating any missing parent directories.
+///
+/// # Errors
+/// This function returns an error if a directory cannot be created or the file cannot be copied.
+pub(crate) fn copy_local_file(
+    src: &str,
+    input_dir: &Utf8Path,
+    output_dir: &Utf8Path,
+    vfs: &dyn Vfs,
+) -> Result<()> {
+    let input_path = input_dir.join(src);
+    let output_path = output_dir.join(src);
+    if let Some(parent) = output_path.parent() {
+        vfs.create_dir_all(parent)
+            .with_context(|| format!("failed to create directory at {parent}"))?;
+    }
+    vfs.copy(&input_path, &output_path)
+        .with_context(|| format!("failed to copy file from {input_path} to {output_path}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{copy_local_file, validate_local_src};
+    use crate::vfs::MemoryFs;
+    use camino::Utf8Path;
+
+    #[test]
+    fn local_src() {
+        assert!(validate_local_src("a.mp4", "video source").is_ok());
+        assert!(validate_local_src("clips/a.mp4", "video source").is_ok());
+        assert!(validate_local_src("", "video source").is_err());
+        assert!(validate_local_src("/a.mp4", "video source").is_err());
+        assert!(validate_local_src("../a.mp4", "video source").is_err());
+        assert_eq!(
+            validate_local_src("clips/../../a.mp4", "video source")
+                .unwrap_err()
+                .to_string(),
+            "video source is not a normalized relative file path (clips/../../a.mp4)"
+        );
+    }
+
+    #[test]
+    fn copy_file() {
+        let vfs = MemoryFs::new().with_file("/in/clips/a.mp4", "video");
+
+        copy_local_file(
+            "clips/a.mp4",
+            Utf8Path::new("/in"),
+            Utf8Path::new("/out"),
+            &vfs,
+        )
+        .unwrap();
+        assert_eq!(
+            vfs.file(Utf8Path::new("/out/clips/a.mp4")).unwrap(),
+            b"video"
+        );
+        assert!(
+            copy_local_file("b.mp4", Utf8Path::new("/in"), Utf8Path::new("/out"), &vfs).is_err()
+        );
+    }
+}
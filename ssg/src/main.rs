@@ -1,71 +1,517 @@
 use anyhow::{Context, Result, anyhow, bail};
 use camino::{Utf8Path, Utf8PathBuf};
 use foldhash::{HashMap, HashMapExt, HashSet, HashSetExt};
-use glob::glob;
+use jiff::{Timestamp, tz::TimeZone};
 use pulldown_cmark::{
-    CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd, TextMergeWithOffset,
+    CodeBlockKind, CowStr, Event, LinkType, Parser, Tag, TagEnd, TextMergeWithOffset,
     html::push_html,
 };
-use same_file::Handle;
+use scraper::Html;
 use ssg::{
-    ActiveImageState, ArchiveBuilder, Config, CssOutput, Frontmatter, LatexConverter,
-    OUTPUT_CONTENT_DIR, OUTPUT_CSS_DIR, OUTPUT_FONTS_DIR, OUTPUT_IMAGE_EXTENSION,
-    OUTPUT_SITE_CSS_FILE, PageBuilder, PageKind, RenderMode, SyntaxHighlighter, convert_image,
-    save_math_assets, transform_css, validate_image_src,
+    ActiveImageState, ArchiveBuilder, ArticleImages, ArticleMetrics, ArticleProfile, BuildProfile,
+    BuildReport, CACHE_DEPENDENCIES_FILE, CHART_LANGUAGE, ChangelogBuilder, ChartSpec,
+    CheckCommand, CliArgs, CodeBlockInfo, ComponentMarker, Config, CrossReferences, CssOutput,
+    DependencyGraph, DuplicateTitlePolicy, Equations, FeedBuilder, Footnotes, FragmentCache,
+    Frontmatter, HeadingOutline, IndexPosition, KatexOverrides, LabeledItem, LatexConverter,
+    LineIndex, LinkCheckPolicy, MathCache, NumberedMath, OUTPUT_ATOM_FEED_FILE,
+    OUTPUT_CHANGELOG_DIR, OUTPUT_CONTENT_DIR, OUTPUT_CSS_DIR, OUTPUT_FONTS_DIR,
+    OUTPUT_REDIRECTS_FILE, OUTPUT_ROBOTS_FILE, OUTPUT_RSS_FEED_FILE, OUTPUT_SEARCH_INDEX_FILE,
+    OUTPUT_SITE_CSS_FILE, OUTPUT_TAGS_DIR, OverlayFs, PageBuilder, PageKind, QuoteTransformer,
+    RAW_MATH_LANGUAGE, RealFs, Redirect, RedirectStyle, RenderMode, SearchIndex, Shortcode,
+    SiteWatcher, Stage, SyntaxHighlighter, TAG_ATOM_FEED_FILE_NAME, TAG_RSS_FEED_FILE_NAME,
+    TOC_MARKER, TableOfContents, Template, Thumbnail, Vfs, WikiLinks, alt_text_problem,
+    article_excerpt, article_url, captioned_image_paragraphs, check_font_files,
+    convert_cover_thumbnail, copy_social_image, demote_heading, duplicate_title_range,
+    find_broken_links, frontmatter_problems, heading_anchor_html, math_error, normalize_text,
+    page_url, prune_css, raw_math_opening_html, read_css_file, redirects_file, render_component,
+    render_html_math, render_shortcode, save_math_assets, save_site_fonts, save_static_files,
+    strip_label, tags_dir_name, transform_css, transform_css_files,
 };
 use std::{
-    collections::hash_map::Entry,
-    fs::{copy, create_dir, create_dir_all, read_to_string, write},
+    borrow::Cow,
+    collections::BTreeSet,
+    env::var,
+    panic::resume_unwind,
+    thread::{Scope, ScopedJoinHandle, scope},
 };
 
 fn main() -> Result<()> {
-    let config = Config::from_env().context("failed to read configuration file")?;
+    let args = CliArgs::from_env()?;
+    let config = Config::from_args(&args).context("failed to read configuration file")?;
 
+    match args.check {
+        Some(CheckCommand::Frontmatter) => check_frontmatter(&config, &RealFs, build_time()?),
+        Some(CheckCommand::Site) => check_site(&config, &RealFs, build_time()?),
+        None if args.watch => watch_site(&args, config),
+        None => build_site(&config, &RealFs, None, build_time()?).map(drop),
+    }
+}
+
+/// Builds the site, then rebuilds it whenever its input files change, until the process is stopped.
+/// Rebuilds reuse the output of articles that are not affected by the changes.
+/// Every rebuild is a separate build, so it gets its own build time.
+fn watch_site(args: &CliArgs, mut config: Config) -> Result<()> {
+    // Dependency graph of the last successful build, for determining which outputs changes affect
+    let mut dependencies = match &config.cache_dir {
+        Some(cache_dir) => {
+            DependencyGraph::load(&cache_dir.join(CACHE_DEPENDENCIES_FILE), &RealFs)?
+        }
+        None => None,
+    };
+    // Articles built by the last build, if it was successful
+    let mut articles;
+    let mut previous_build = None;
+
+    loop {
+        // Start watching before building so changes made during the build are not missed
+        let mut watcher = SiteWatcher::new(&config, &args.config_path)
+            .context("failed to watch site input files")?;
+
+        match build_time().and_then(|now| build_site(&config, &RealFs, previous_build.take(), now))
+        {
+            Ok(build) => {
+                // Outputs of the previous build are never carried over unless they are regenerated or reused,
+                // so outputs that the new build didn't produce are already gone
+                if let Some(previous) = &dependencies {
+                    let removed = build.dependencies.removed_outputs(previous);
+                    if !removed.is_empty() {
+                        println!("Removed stale outputs: {}", removed.join(", "));
+                    }
+                }
+                dependencies = Some(build.dependencies);
+                articles = Some(build.articles);
+            }
+            Err(e) => {
+                eprintln!("Error: {e:?}");
+                articles = None;
+            }
+        }
+        // Watch inputs that are only known after building, like partials included by templates
+        if let Some(graph) = &dependencies {
+            watcher
+                .watch_dependencies(graph)
+                .context("failed to watch site input files")?;
+        }
+        println!("Watching for changes...");
+
+        config = loop {
+            let changes = watcher.wait_for_changes()?;
+            println!("{changes}");
+
+            let affected_outputs = dependencies
+                .as_ref()
+                .and_then(|graph| changes.affected_outputs(graph))
+                .map(|outputs| outputs.into_iter().map(Box::from).collect::<BTreeSet<_>>());
+            match &affected_outputs {
+                // Changes to files that no output depends on (e.g. unused images) don't require a rebuild
+                Some(outputs) if outputs.is_empty() => {
+                    println!("No outputs are affected");
+                    continue;
+                }
+                Some(outputs) => {
+                    let outputs: Vec<_> = outputs.iter().map(AsRef::as_ref).collect();
+                    println!("Affected outputs: {}", outputs.join(", "));
+                }
+                None => println!("Affected outputs: all"),
+            }
+
+            // The output directory must not exist when the config is read,
+            // so it is moved aside if the next build can reuse its contents
+            let previous_output_dir = previous_output_dir(&config.output_dir);
+            if RealFs.is_dir(&previous_output_dir) {
+                RealFs
+                    .remove_dir_all(&previous_output_dir)
+                    .with_context(|| {
+                        format!("failed to remove directory at {previous_output_dir}")
+                    })?;
+            }
+            if RealFs.is_dir(&config.output_dir) {
+                if affected_outputs.is_some() && articles.is_some() {
+                    RealFs
+                        .rename(&config.output_dir, &previous_output_dir)
+                        .with_context(|| {
+                            format!("failed to move output directory to {previous_output_dir}")
+                        })?;
+                } else {
+                    RealFs.remove_dir_all(&config.output_dir).with_context(|| {
+                        format!("failed to remove output directory at {}", config.output_dir)
+                    })?;
+                }
+            }
+
+            match Config::from_args(args) {
+                Ok(config) => {
+                    previous_build = affected_outputs.zip(articles.take()).map(
+                        |(affected_outputs, articles)| PreviousBuild {
+                            output_dir: previous_output_dir,
+                            affected_outputs,
+                            articles,
+                            reused_count: 0,
+                        },
+                    );
+                    break config;
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Error: {:?}",
+                        e.context("failed to read configuration file")
+                    );
+                    articles = None;
+                }
+            }
+        };
+    }
+}
+
+/// Normalizes the text of an input file, printing a warning if anything had to be fixed.
+fn normalize_input(text: String, path: &Utf8Path) -> String {
+    let (text, fixes) = normalize_text(text);
+    if !fixes.is_empty() {
+        eprintln!("Warning: {path}: {fixes}");
+    }
+    text
+}
+
+/// Returns the path that the output directory is moved to while the site is rebuilt in watch mode.
+fn previous_output_dir(output_dir: &Utf8Path) -> Utf8PathBuf {
+    let name = output_dir.file_name().unwrap_or("output");
+    output_dir.with_file_name(format!("{name}.previous"))
+}
+
+/// Builds the entire site into the output directory.
+/// Articles whose output is not affected by changes since a previous build are moved from that build's output.
+/// Every input is read from `vfs` and every generated file is written to it.
+/// Anything that depends on the current time (e.g. build stamps and age warnings) uses `now`.
+fn build_site(
+    config: &Config,
+    vfs: &dyn Vfs,
+    previous_build: Option<PreviousBuild>,
+    now: Timestamp,
+) -> Result<SiteBuild> {
+    // Setup threads borrow the file system, so they can't outlive the build
+    scope(|scope| build_site_in_scope(scope, config, vfs, previous_build, now))
+}
+
+/// Builds the site like `build_site()`, running setup work on threads of `scope`.
+fn build_site_in_scope<'scope, 'env>(
+    scope: &'scope Scope<'scope, 'env>,
+    config: &'env Config,
+    vfs: &'env dyn Vfs,
+    mut previous_build: Option<PreviousBuild>,
+    now: Timestamp,
+) -> Result<SiteBuild> {
     // Create output directories
-    create_dir_all(config.output_dir.as_ref()).context("failed to create output directory")?;
-    create_dir(config.output_dir.join(OUTPUT_CSS_DIR))
-        .context("failed to create output CSS directory")?;
-    create_dir(config.output_dir.join(OUTPUT_FONTS_DIR))
-        .context("failed to create output fonts directory")?;
-    create_dir(config.output_dir.join(OUTPUT_CONTENT_DIR))
-        .context("failed to create output articles directory")?;
+    for dir in [OUTPUT_CSS_DIR, OUTPUT_FONTS_DIR, OUTPUT_CONTENT_DIR] {
+        let dir = config.output_dir.join(dir);
+        vfs.create_dir_all(&dir)
+            .with_context(|| format!("failed to create output directory at {dir}"))?;
+    }
+
+    // Run independent setup work on other threads, so it overlaps with processing templates, fragments, and articles:
+    // - extracting math assets and copying site fonts
+    // - minifying the site CSS
+    // - initializing the syntax highlighter and the LaTeX converter, which parse large amounts of data
+    let math_assets_thread = {
+        let output_dir = config.output_dir.clone();
+        let fonts_dir = config.fonts_dir.clone();
+        // Math rendered as MathML only is displayed by browsers without KaTeX's stylesheet and fonts
+        let needs_katex_css = config.katex.output.needs_stylesheet();
+        scope.spawn(move || -> Result<()> {
+            if needs_katex_css {
+                save_math_assets(&output_dir, vfs)?;
+            }
+            if let Some(fonts_dir) = fonts_dir {
+                save_site_fonts(&fonts_dir, &output_dir, vfs)?;
+            }
+            Ok(())
+        })
+    };
+    // Read the site CSS files, compiling Sass files to CSS and keeping track of the files they import
+    let mut site_css = Vec::with_capacity(config.site_css_files.len());
+    let mut site_css_inputs = Vec::new();
+    for path in &config.site_css_files {
+        let (css, inputs) = read_css_file(path, vfs).context("failed to read site CSS")?;
+        site_css.push((path.clone(), css));
+        site_css_inputs.extend(inputs);
+    }
+    let css_thread = {
+        let asset_urls = config.asset_urls();
+        let css_targets = config.css_targets.clone();
+        scope.spawn(move || {
+            let sources: Vec<_> = site_css
+                .iter()
+                .map(|(path, css)| (&**path, css.as_str()))
+                .collect();
+            transform_css_files(&sources, &asset_urls, &css_targets)
+                .context("failed to minify site CSS")
+        })
+    };
+    let converters_thread = scope.spawn(move || {
+        let syntax_highlighter = SyntaxHighlighter::from_config(config, vfs)?;
+        LatexConverter::new()
+            .context("failed to initialize LaTeX-to-HTML converter")
+            .map(|latex_converter| {
+                (
+                    syntax_highlighter,
+                    latex_converter.with_options(config.katex),
+                )
+            })
+    });
+
+    // Track the input files of every output file
+    let mut dependencies = DependencyGraph::new();
+    // Get site HTML templates
+    let head_template_text = vfs
+        .read_to_string(&config.head_template_html_file)
+        .context("failed to read head HTML template file")?;
+    let body_template_text = vfs
+        .read_to_string(&config.body_template_html_file)
+        .context("failed to read body HTML template file")?;
 
     // Process site CSS file
     let CssOutput {
         css,
         font_css,
         top_fonts,
-    } = read_to_string(config.site_css_file.as_ref())
-        .context("failed to read site CSS file")
-        .and_then(|css| transform_css(&css).context("failed to minify site CSS"))?;
+        font_urls,
+    } = join_thread(css_thread)?;
 
-    write(config.output_dir.join(OUTPUT_SITE_CSS_FILE), css)
-        .context("failed to write site CSS to output destination")?;
-
-    save_math_assets(&config.output_dir)
-        .context("failed to write math CSS to output destination")?;
+    // Inlined CSS delays the first render of every page if it doesn't fit in the first round trip of a connection
+    if config.inline_site_css && css.len() > INLINE_CSS_WARNING_SIZE {
+        eprintln!(
+            "Warning: site CSS is {} KiB, so inlining it in every page may slow down page loads",
+            css.len() / 1024
+        );
+    }
+    let site_css_path = config.output_dir.join(OUTPUT_SITE_CSS_FILE);
+    vfs.write(&site_css_path, css.as_bytes(), false)
+        .with_context(|| format!("failed to write site CSS to {site_css_path}"))?;
+    dependencies.add(OUTPUT_SITE_CSS_FILE, &site_css_inputs);
 
-    // Get site HTML templates
-    let head_template_text = read_to_string(config.head_template_html_file.as_ref())
-        .context("failed to read head HTML template file")?;
-    let body_template_text = read_to_string(config.body_template_html_file.as_ref())
-        .context("failed to read body HTML template file")?;
+    // Create a page builder with a body template, set up the same way for the site template and article templates
+    let create_page_builder = |body_template: Template<'_>| -> Result<PageBuilder> {
+        let mut page_builder = PageBuilder::new(
+            Template {
+                text: &head_template_text,
+                path: &config.head_template_html_file,
+                vfs,
+            },
+            body_template,
+            &top_fonts,
+            &font_css,
+        )?;
+        // The time of the build is fixed for the whole build
+        page_builder.configure(config, &css, now);
+        Ok(page_builder)
+    };
 
     // Create page builder (template for every page)
-    let page_builder = PageBuilder::new(
-        &head_template_text,
-        &body_template_text,
-        &top_fonts,
-        &font_css,
-    )
+    let page_builder = create_page_builder(Template {
+        text: &body_template_text,
+        path: &config.body_template_html_file,
+        vfs,
+    })
     .context("failed to process HTML templates")?;
 
     let mut fragment_stems = HashSet::new();
 
+    // Reserve the changelog page's output path
+    if config.changelog_entries.is_some() {
+        let changelog_stem = OUTPUT_CHANGELOG_DIR.trim_end_matches('/');
+        fragment_stems.insert(changelog_stem.to_owned());
+    }
+
+    // Every page depends on the site CSS files and their imports (which determine its font loading),
+    // the templates, and their partials
+    let page_inputs: Vec<_> = site_css_inputs
+        .iter()
+        .map(AsRef::as_ref)
+        .chain([
+            &*config.head_template_html_file,
+            &*config.body_template_html_file,
+        ])
+        .chain(page_builder.partials())
+        .collect();
+
+    let mut article_slugs = HashSet::new();
+
+    // Read all articles and their metadata before building any article pages
+    let mut articles = Vec::new();
+
+    for entry_path in config.article_paths(vfs)? {
+        let Some((text, frontmatter)) = (|| {
+            let text = vfs
+                .read_to_string(&entry_path)
+                .context("failed to read article file")?;
+            let text = normalize_input(text, &entry_path);
+
+            let frontmatter =
+                Frontmatter::from_text(&text).context("failed to read article frontmatter")?;
+
+            // Skip drafts unless they are being previewed
+            if frontmatter.draft && !config.include_drafts {
+                return Ok(None);
+            }
+
+            if config.duplicate_title == DuplicateTitlePolicy::Warn
+                && duplicate_title_range(
+                    &text,
+                    &frontmatter.title,
+                    frontmatter.markdown_options(),
+                ).is_some()
+            {
+                eprintln!(
+                    "Warning: {entry_path}: article body starts with a heading that duplicates the title"
+                );
+            }
+
+            // Check for article slug collisions to ensure every article has a unique output directory
+            if !article_slugs.insert(frontmatter.slug.clone()) {
+                bail!("duplicate article slug found: {}", frontmatter.slug);
+            }
+
+            Ok(Some((text, frontmatter)))
+        })()
+        .with_context(|| format!("failed to process article at {entry_path}"))?
+        else {
+            continue;
+        };
+
+        articles.push(Article {
+            path: entry_path,
+            text,
+            frontmatter,
+        });
+    }
+
+    // Check for collisions between article output directories and the tag pages directory
+    let tags_dir_name = tags_dir_name();
+    if articles
+        .iter()
+        .any(|article| !article.frontmatter.tags.is_empty())
+        && article_slugs.contains(tags_dir_name)
+    {
+        bail!("article slug \"{tags_dir_name}\" is reserved for tag pages when articles have tags");
+    }
+
+    // Pages listing articles and feeds depend on every article
+    let article_paths: Vec<_> = articles
+        .iter()
+        .map(|article| article.path.clone())
+        .collect();
+
+    // Old URLs of articles redirect to the articles
+    let alias_redirects: Vec<_> = articles
+        .iter()
+        .flat_map(|article| {
+            let url = article_url(&article.frontmatter.slug);
+            article.frontmatter.aliases.iter().map(move |alias| {
+                (
+                    Redirect::permanent(alias, url.clone()),
+                    article.path.clone(),
+                )
+            })
+        })
+        .collect();
+
+    // Build a page linking to all articles
+    let mut archive_builder = ArchiveBuilder::new();
+
+    // Build a page listing recent article creations and updates
+    let mut changelog_builder = ChangelogBuilder::new();
+
+    // Build syndication feeds containing all articles
+    let mut feed_builder = FeedBuilder::new();
+
+    // Build an index of article contents for client-side search
+    let mut search_index = SearchIndex::new();
+
+    // Collect metrics about the content of every article
+    let mut build_report = BuildReport::new(config.katex_css_loading());
+
+    let (syntax_highlighter, latex_converter) = join_thread(converters_thread)?;
+
+    // Wiki-style links between articles refer to their titles
+    let mut wiki_links = WikiLinks::new();
+    for article in &articles {
+        wiki_links.add_article(
+            &article.frontmatter.title,
+            &article.frontmatter.slug,
+            &article.path,
+        );
+    }
+
+    // Create a page builder for every alternate body template used by articles
+    let mut article_templates = HashMap::new();
+    for article in &articles {
+        let Some(name) = &article.frontmatter.template else {
+            continue;
+        };
+        if article_templates.contains_key(name) {
+            continue;
+        }
+
+        let path = config
+            .templates_dir
+            .as_deref()
+            .with_context(|| {
+                format!(
+                    "failed to process article at {}: article uses template \"{name}\", but `templates_dir` is not set",
+                    article.path
+                )
+            })?
+            .join(format!("{name}.html"));
+        let text = vfs.read_to_string(&path).with_context(|| {
+            format!(
+                "failed to read template \"{name}\" at {path} for article at {}",
+                article.path
+            )
+        })?;
+        let page_builder = create_page_builder(Template {
+            text: &text,
+            path: &path,
+            vfs,
+        })
+        .with_context(|| format!("failed to process HTML template at {path}"))?;
+
+        // Articles with the template also depend on it and its partials, in addition to the site templates
+        let inputs = [path.clone()]
+            .into_iter()
+            .chain(page_builder.partials().map(Utf8Path::to_path_buf))
+            .collect();
+        article_templates.insert(
+            name.clone(),
+            ArticleTemplate {
+                page_builder,
+                inputs,
+            },
+        );
+    }
+
+    let context = ArticleContext {
+        config,
+        syntax_highlighter: &syntax_highlighter,
+        latex_converter: &latex_converter,
+        page_builder: &page_builder,
+        article_templates: &article_templates,
+        wiki_links: &wiki_links,
+        vfs,
+    };
+
+    // Time the stages of building every article, if a profile was requested
+    let mut build_profile = BuildProfile::new();
+
+    // Reuse math rendered by previous builds
+    let mut math_cache = MathCache::load(config.cache_dir.as_deref(), &config.katex, vfs)?;
+
+    // Reuse fragment pages from previous builds if neither the fragment nor the page template has changed
+    let mut fragment_cache = FragmentCache::new(config.cache_dir.as_deref(), vfs)?;
+    let builder_fingerprint = page_builder.fingerprint();
+
     // Process all fragment files
-    for fragment in config.fragments {
+    for fragment in &config.fragments {
         let stem = fragment.path.file_stem().expect(
-            "fragment path should include file name if validation in `Config::from_env()` was successful"
+            "fragment path should include file name if validation in `Config::from_args()` was successful"
         );
 
         (|| {
@@ -74,106 +520,305 @@ fn main() -> Result<()> {
                 bail!("duplicate fragment slug found: {stem}");
             }
 
-            let fragment_text =
-                read_to_string(fragment.path.as_ref()).context("failed to read fragment file")?;
-            let html = page_builder
-                .build_page(&fragment.title, &fragment_text, PageKind::Fragment)
-                .context("failed to parse fragment as valid HTML")?;
+            let fragment_text = vfs
+                .read_to_string(&fragment.path)
+                .context("failed to read fragment file")?;
+            let mut fragment_text = normalize_input(fragment_text, &fragment.path);
+            if fragment.math {
+                fragment_text = render_html_math(&fragment_text, |src, mode| {
+                    latex_converter.render_cached(
+                        src,
+                        mode,
+                        &KatexOverrides::default(),
+                        &mut math_cache,
+                    )
+                })
+                .context("failed to convert LaTeX to HTML")?;
+                if let Some(error) = math_error(&fragment_text) {
+                    eprintln!("Warning: {}: invalid math: {error}", fragment.path);
+                }
+            }
 
-            let output_path = if stem == "index" {
-                config.output_dir.join("index.html")
+            let (output_file, url) = if stem == "index" {
+                ("index.html".into(), page_url(""))
             } else {
                 let dir = config.output_dir.join(stem);
-                create_dir(&dir).with_context(|| format!("failed to create directory at {dir}"))?;
-                dir.join("index.html")
+                vfs.create_dir_all(&dir)
+                    .with_context(|| format!("failed to create directory at {dir}"))?;
+                (format!("{stem}/index.html"), page_url(&format!("{stem}/")))
             };
+            let output_path = config.output_dir.join(&output_file);
 
-            write(&output_path, html)
-                .with_context(|| format!("failed to write HTML to {output_path}"))?;
+            // Read the content of the fragment's named slots
+            let slot_texts = fragment
+                .slots
+                .iter()
+                .map(|(name, path)| {
+                    let text = vfs.read_to_string(path).with_context(|| {
+                        format!("failed to read content of the slot \"{name}\" at {path}")
+                    })?;
+                    Ok((&**name, normalize_input(text, path)))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let slots: Vec<_> = slot_texts
+                .iter()
+                .map(|(name, text)| (*name, text.as_str()))
+                .collect();
+
+            let cache_key = FragmentCache::key(
+                &fragment.title,
+                &url,
+                &fragment_text,
+                &slots,
+                &builder_fingerprint,
+            );
+            if !fragment_cache.copy_to(&cache_key, &output_path)? {
+                let html = page_builder
+                    .build_page_with_slots(
+                        &fragment.title,
+                        &url,
+                        &fragment_text,
+                        PageKind::Fragment,
+                        &slots,
+                    )
+                    .context("failed to build fragment page")?;
+                fragment_cache.insert(&cache_key, &html)?;
+                vfs.write(&output_path, html.as_bytes(), false)
+                    .with_context(|| format!("failed to write HTML to {output_path}"))?;
+            }
+            dependencies.add(
+                &output_file,
+                page_inputs
+                    .iter()
+                    .copied()
+                    .chain([&*fragment.path])
+                    .chain(fragment.slots.values().map(AsRef::as_ref)),
+            );
 
             Ok(())
         })()
         .with_context(|| format!("failed to process fragment at {}", fragment.path))?;
     }
 
-    let mut article_slugs = HashSet::new();
-
-    // Build a page linking to all articles
-    let mut archive_builder = ArchiveBuilder::new();
+    let fragment_cache_stats = fragment_cache.finish()?;
+    if config.cache_dir.is_some() {
+        build_report.set_fragment_cache(fragment_cache_stats);
+    }
 
-    let syntax_highlighter = SyntaxHighlighter::new(&config.code_theme);
-    let latex_converter =
-        LatexConverter::new().context("failed to initialize LaTeX-to-HTML converter")?;
+    // Keep the results of building every article, so later builds in watch mode can reuse them
+    let mut built_articles = HashMap::new();
 
     // Process all articles
-    let article_match_pattern: Utf8PathBuf = [config.articles_dir.as_str(), "**", "*.md"]
-        .into_iter()
-        .collect();
+    for Article {
+        path,
+        text,
+        frontmatter,
+    } in articles
+    {
+        let input_article_dir = path.parent().expect("article file path should have parent");
 
-    for entry in glob(article_match_pattern.as_str()).expect("article glob pattern is valid") {
-        #[allow(clippy::unnecessary_debug_formatting)]
-        let entry_path = Utf8PathBuf::from_path_buf(
-            entry.context("failed to access entry in articles directory")?,
-        )
-        .map_err(|path| {
-            anyhow!("name of entry in articles directory is not valid UTF-8: {path:?}")
-        })?;
+        (|| -> Result<()> {
+            let output_article_dir = config
+                .output_dir
+                .join(OUTPUT_CONTENT_DIR)
+                .join(&*frontmatter.slug);
+            let output_file = format!("{OUTPUT_CONTENT_DIR}{}/index.html", frontmatter.slug);
 
-        let input_article_dir = entry_path
-            .parent()
-            .expect("article file path should have parent");
+            let reused_article = match &mut previous_build {
+                Some(previous_build) => previous_build.reuse_article(
+                    &path,
+                    &frontmatter.slug,
+                    &output_file,
+                    &output_article_dir,
+                    vfs,
+                )?,
+                None => None,
+            };
 
-        if !input_article_dir.is_dir() {
-            continue;
-        }
+            let article = if let Some(article) = reused_article {
+                // The page around the body depends on the build (e.g. its build stamp and age warning)
+                let html = build_article_page(&frontmatter, &article, &context)
+                    .context("failed to build article HTML")?;
+                let output_path = output_article_dir.join("index.html");
+                vfs.write(&output_path, html.as_bytes(), false)
+                    .with_context(|| format!("failed to write article HTML to {output_path}"))?;
+                article
+            } else {
+                vfs.create_dir_all(&output_article_dir).with_context(|| {
+                    format!("failed to create output article directory at {output_article_dir}")
+                })?;
 
-        (|| {
-            let article_text =
-                read_to_string(&entry_path).context("failed to read article file")?;
+                // Convert article from Markdown to HTML
+                let mut profile = ArticleProfile::start();
+                let BuiltArticle {
+                    html,
+                    article,
+                    stylesheet,
+                    warnings,
+                } = build_article(
+                    &text,
+                    &frontmatter,
+                    input_article_dir,
+                    &output_article_dir,
+                    &context,
+                    &mut math_cache,
+                    &mut profile,
+                )
+                .context("failed to build article HTML")?;
+                for warning in warnings {
+                    eprintln!("Warning: {path}: {warning}");
+                }
 
-            let article_frontmatter = Frontmatter::from_text(&article_text)
-                .context("failed to read article frontmatter")?;
+                profile.time(Stage::Write, || -> Result<()> {
+                    let output_path = output_article_dir.join("index.html");
+                    vfs.write(&output_path, html.as_bytes(), false)
+                        .with_context(|| {
+                            format!("failed to write article HTML to {output_path}")
+                        })?;
+                    if let Some(stylesheet) = &stylesheet {
+                        let output_path = output_article_dir.join(ARTICLE_STYLESHEET_FILE);
+                        vfs.write(&output_path, stylesheet.as_bytes(), false)
+                            .with_context(|| {
+                                format!("failed to write article stylesheet to {output_path}")
+                            })?;
+                    }
+                    Ok(())
+                })?;
+                profile.finish();
+                build_profile.add_article(&frontmatter.slug, profile);
+                article
+            };
 
-            // Check for article slug collisions to ensure every article has a unique output directory
-            if !article_slugs.insert(article_frontmatter.slug.clone()) {
-                bail!("duplicate article slug found: {}", article_frontmatter.slug);
+            if article.has_stylesheet {
+                dependencies.add(
+                    &format!(
+                        "{OUTPUT_CONTENT_DIR}{}/{ARTICLE_STYLESHEET_FILE}",
+                        frontmatter.slug
+                    ),
+                    [input_article_dir.join(ARTICLE_STYLESHEET_FILE)],
+                );
             }
 
-            let output_article_dir = config
-                .output_dir
-                .join(OUTPUT_CONTENT_DIR)
-                .join(&*article_frontmatter.slug);
-
-            create_dir(&output_article_dir).with_context(|| {
-                format!("failed to create output article directory at {output_article_dir}")
-            })?;
+            dependencies.add(
+                &output_file,
+                page_inputs
+                    .iter()
+                    .copied()
+                    .chain([path.as_path()])
+                    .chain(config.code_theme_file.as_deref())
+                    .chain(config.code_theme_dark_file.as_deref())
+                    .chain(article.inputs.iter().map(AsRef::as_ref))
+                    .chain(
+                        frontmatter
+                            .template
+                            .as_ref()
+                            .and_then(|name| article_templates.get(name))
+                            .into_iter()
+                            .flat_map(|template| template.inputs.iter().map(AsRef::as_ref)),
+                    ),
+            );
 
-            // Convert article from Markdown to HTML
-            let article_html = build_article(
-                &article_text,
-                &article_frontmatter,
-                &syntax_highlighter,
-                &latex_converter,
-                input_article_dir,
-                &output_article_dir,
-                &page_builder,
-            )
-            .context("failed to build article HTML")?;
+            changelog_builder.add_article(
+                &frontmatter.title,
+                &frontmatter.slug,
+                frontmatter.created,
+                frontmatter.updated,
+            );
+            if let Some(feed_config) = &config.feed {
+                let license = frontmatter
+                    .license
+                    .as_ref()
+                    .or(config.default_license.as_ref());
+                feed_builder
+                    .add_article(feed_config, &frontmatter, license, &article.body)
+                    .context("failed to add article to feed")?;
+            }
 
-            let output_article_path = output_article_dir.join("index.html");
-            write(&output_article_path, article_html).with_context(|| {
-                format!("failed to write article HTML to {output_article_path}")
-            })?;
+            if config.search_index {
+                search_index.add_article(&frontmatter, &article.plain_text);
+            }
 
+            build_report.add_article(
+                &frontmatter.slug,
+                &frontmatter.title,
+                article.metrics.clone(),
+            );
+            let markdown_options = frontmatter.markdown_options();
             archive_builder.add_article(
-                article_frontmatter.title,
-                article_frontmatter.slug,
-                article_frontmatter.created,
+                &frontmatter,
+                article.thumbnail.clone(),
+                config
+                    .archive_excerpts
+                    .then(|| {
+                        frontmatter
+                            .excerpt
+                            .clone()
+                            .or_else(|| article_excerpt(&text, markdown_options).map(Into::into))
+                    })
+                    .flatten(),
+                config
+                    .reading_speed_wpm
+                    .map(|wpm| article.metrics.reading_minutes(wpm)),
             );
+            built_articles.insert(path.clone(), article);
 
             Ok(())
         })()
-        .with_context(|| format!("failed to process article at {entry_path}"))?;
+        .with_context(|| format!("failed to process article at {path}"))?;
+    }
+
+    // Math in reused articles was not looked up, so only remove unused cached math if no article was reused
+    let math_cache_stats = math_cache.finish(
+        previous_build
+            .as_ref()
+            .is_none_or(|build| build.reused_count == 0),
+    )?;
+    if config.cache_dir.is_some() {
+        build_report.set_math_cache(math_cache_stats);
+    }
+
+    let tag_pages = archive_builder.tag_pages_html(&page_builder, config.feed.as_ref());
+    if !tag_pages.is_empty() {
+        let tags_dir = config.output_dir.join(OUTPUT_TAGS_DIR);
+        for (tag, tag_html) in tag_pages {
+            let output_dir = tags_dir.join(&*tag);
+            vfs.create_dir_all(&output_dir)
+                .with_context(|| format!("failed to create directory at {output_dir}"))?;
+            let output_path = output_dir.join("index.html");
+            vfs.write(&output_path, tag_html.as_bytes(), false)
+                .with_context(|| format!("failed to write tag page HTML to {output_path}"))?;
+            dependencies.add(
+                &format!("{OUTPUT_TAGS_DIR}{tag}/index.html"),
+                page_inputs
+                    .iter()
+                    .copied()
+                    .chain(article_paths.iter().map(AsRef::as_ref)),
+            );
+        }
+
+        if let Some(feed_config) = config.feed.as_ref().filter(|config| config.tags) {
+            for (tag, atom, rss) in feed_builder.tag_xml(feed_config) {
+                let output_dir = tags_dir.join(&*tag);
+                let output_path = output_dir.join(TAG_ATOM_FEED_FILE_NAME);
+                vfs.write(&output_path, atom.as_bytes(), false)
+                    .with_context(|| format!("failed to write tag Atom feed to {output_path}"))?;
+                dependencies.add(
+                    &format!("{OUTPUT_TAGS_DIR}{tag}/{TAG_ATOM_FEED_FILE_NAME}"),
+                    &article_paths,
+                );
+                if let Some(rss) = rss {
+                    let output_path = output_dir.join(TAG_RSS_FEED_FILE_NAME);
+                    vfs.write(&output_path, rss.as_bytes(), false)
+                        .with_context(|| {
+                            format!("failed to write tag RSS feed to {output_path}")
+                        })?;
+                    dependencies.add(
+                        &format!("{OUTPUT_TAGS_DIR}{tag}/{TAG_RSS_FEED_FILE_NAME}"),
+                        &article_paths,
+                    );
+                }
+            }
+        }
     }
 
     let archive_html = archive_builder.into_html(&page_builder);
@@ -181,50 +826,563 @@ fn main() -> Result<()> {
         .output_dir
         .join(OUTPUT_CONTENT_DIR)
         .join("index.html");
-    write(&output_path, archive_html)
+    vfs.write(&output_path, archive_html.as_bytes(), false)
         .with_context(|| format!("failed to write article archive HTML to {output_path}"))?;
+    dependencies.add(
+        &format!("{OUTPUT_CONTENT_DIR}index.html"),
+        page_inputs
+            .iter()
+            .copied()
+            .chain(article_paths.iter().map(AsRef::as_ref)),
+    );
+
+    if let Some(limit) = config.changelog_entries {
+        let changelog_html = changelog_builder.into_html(limit, &page_builder);
+        let output_dir = config.output_dir.join(OUTPUT_CHANGELOG_DIR);
+        vfs.create_dir_all(&output_dir)
+            .with_context(|| format!("failed to create directory at {output_dir}"))?;
+        let output_path = output_dir.join("index.html");
+        vfs.write(&output_path, changelog_html.as_bytes(), false)
+            .with_context(|| format!("failed to write changelog HTML to {output_path}"))?;
+        dependencies.add(
+            &format!("{OUTPUT_CHANGELOG_DIR}index.html"),
+            page_inputs
+                .iter()
+                .copied()
+                .chain(article_paths.iter().map(AsRef::as_ref)),
+        );
+    }
+
+    if let Some(feed_config) = &config.feed {
+        let (atom, rss) = feed_builder.into_xml(feed_config);
+        let output_path = config.output_dir.join(OUTPUT_ATOM_FEED_FILE);
+        vfs.write(&output_path, atom.as_bytes(), false)
+            .with_context(|| format!("failed to write Atom feed to {output_path}"))?;
+        dependencies.add(OUTPUT_ATOM_FEED_FILE, &article_paths);
+        if let Some(rss) = rss {
+            let output_path = config.output_dir.join(OUTPUT_RSS_FEED_FILE);
+            vfs.write(&output_path, rss.as_bytes(), false)
+                .with_context(|| format!("failed to write RSS feed to {output_path}"))?;
+            dependencies.add(OUTPUT_RSS_FEED_FILE, &article_paths);
+        }
+    }
+
+    if config.search_index {
+        let output_path = config.output_dir.join(OUTPUT_SEARCH_INDEX_FILE);
+        vfs.write(&output_path, search_index.into_json()?.as_bytes(), false)
+            .with_context(|| format!("failed to write search index to {output_path}"))?;
+        dependencies.add(OUTPUT_SEARCH_INDEX_FILE, &article_paths);
+    }
+
+    // Redirect sources must not be generated pages, which would be served instead of the redirects
+    let redirects: Vec<_> = config
+        .redirects
+        .iter()
+        .map(|redirect| (redirect, None))
+        .chain(
+            alias_redirects
+                .iter()
+                .map(|(redirect, path)| (redirect, Some(path.as_path()))),
+        )
+        .collect();
+    let mut redirect_pages = HashSet::new();
+    for &(redirect, input) in &redirects {
+        let page_path = redirect.page_path();
+        if !redirect_pages.insert(page_path.clone()) {
+            bail!("found multiple redirects from {}", redirect.from);
+        } else if dependencies.contains_output(&page_path) {
+            bail!(
+                "redirect from {} conflicts with a page generated by the site",
+                redirect.from
+            );
+        }
+
+        if config.redirect_style == RedirectStyle::Html {
+            let output_path = config.output_dir.join(&page_path);
+            let output_dir = output_path
+                .parent()
+                .expect("redirect page path should have parent");
+            vfs.create_dir_all(output_dir)
+                .with_context(|| format!("failed to create directory at {output_dir}"))?;
+            vfs.write(&output_path, redirect.page_html().as_bytes(), false)
+                .with_context(|| format!("failed to write redirect page to {output_path}"))?;
+            dependencies.add(&page_path, input);
+        }
+    }
+    if config.redirect_style != RedirectStyle::Html && !redirects.is_empty() {
+        let output_path = config.output_dir.join(OUTPUT_REDIRECTS_FILE);
+        let redirects = redirects_file(redirects.iter().map(|&(redirect, _)| redirect));
+        vfs.write(&output_path, redirects.as_bytes(), false)
+            .with_context(|| format!("failed to write redirects file to {output_path}"))?;
+        dependencies.add(OUTPUT_REDIRECTS_FILE, &article_paths);
+    }
+
+    if let Some(manifest) = &config.manifest {
+        manifest.save(&config.output_dir, &mut dependencies, vfs)?;
+    }
+
+    if let Some(robots) = &config.robots {
+        let output_path = config.output_dir.join(OUTPUT_ROBOTS_FILE);
+        vfs.write(&output_path, robots.robots_txt().as_bytes(), false)
+            .with_context(|| format!("failed to write robots.txt to {output_path}"))?;
+        // The rules only come from the config file, which every output depends on
+        dependencies.add::<&Utf8Path>(OUTPUT_ROBOTS_FILE, []);
+    }
+
+    if let Some(path) = &config.build_report_file {
+        vfs.write(path, build_report.to_json()?.as_bytes(), false)
+            .with_context(|| format!("failed to write build report to {path}"))?;
+    }
+
+    if let Some(path) = &config.profile_file {
+        vfs.write(path, build_profile.to_trace_json()?.as_bytes(), false)
+            .with_context(|| format!("failed to write build profile to {path}"))?;
+    }
+
+    if let Some(cache_dir) = &config.cache_dir
+        && let Some(previous) =
+            DependencyGraph::load(&cache_dir.join(CACHE_DEPENDENCIES_FILE), vfs)?
+    {
+        for url in removed_article_urls(&dependencies, &previous, &redirect_pages) {
+            eprintln!(
+                "Warning: the article page at {url} no longer exists; if the article was renamed, add this URL to its `aliases`"
+            );
+        }
+    }
+
+    join_thread(math_assets_thread)
+        .context("failed to write fonts and math CSS to output destination")?;
+    check_font_files(
+        &config.output_dir,
+        &font_urls,
+        config.katex.output.needs_stylesheet(),
+        vfs,
+    )?;
+
+    // Copy static files once every generated file exists, so files that would replace generated files are found
+    if let Some(static_dir) = &config.static_dir {
+        let count = save_static_files(static_dir, &config.output_dir, &mut dependencies, vfs)
+            .context("failed to copy static files to output destination")?;
+        println!("Copied {count} static file(s)");
+
+        // Hosts serve static files instead of following redirects from their paths
+        if let Some((redirect, _)) = redirects.iter().find(|(redirect, _)| {
+            config.redirect_style != RedirectStyle::Html
+                && dependencies.contains_output(&redirect.page_path())
+        }) {
+            bail!(
+                "redirect from {} conflicts with a static file",
+                redirect.from
+            );
+        }
+    }
+
+    // Persist the dependency graph for future builds, including static files
+    if let Some(cache_dir) = &config.cache_dir {
+        vfs.create_dir_all(cache_dir)
+            .with_context(|| format!("failed to create cache directory at {cache_dir}"))?;
+        let path = cache_dir.join(CACHE_DEPENDENCIES_FILE);
+        vfs.write(&path, dependencies.to_json()?.as_bytes(), false)
+            .with_context(|| format!("failed to write dependency graph to {path}"))?;
+    }
+
+    // Prune the site CSS once every page exists, including HTML files copied from the static directory
+    if config.prune_site_css {
+        let pages = vfs
+            .files_in(&config.output_dir)?
+            .into_iter()
+            .filter(|path| path.extension() == Some("html"))
+            .map(|path| {
+                vfs.read_to_string(&path)
+                    .map(|html| Html::parse_document(&html))
+                    .with_context(|| format!("failed to read generated page at {path}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let (pruned_css, removed_rules) = prune_css(&css, &pages, &config.prune_site_css_keep)
+            .context("failed to prune site CSS")?;
+        vfs.write(&site_css_path, pruned_css.as_bytes(), false)
+            .with_context(|| format!("failed to write pruned site CSS to {site_css_path}"))?;
+        println!(
+            "Site CSS: {removed_rules} unused rule(s) pruned ({} B to {} B)",
+            css.len(),
+            pruned_css.len()
+        );
+    }
+
+    // Sync the output once nothing else is written to it, including converted images and static files
+    if config.fsync_output {
+        vfs.sync_tree(&config.output_dir)
+            .context("failed to sync output files")?;
+    }
+
+    // Check links once every output file exists, including images and other assets
+    if config.link_check != LinkCheckPolicy::Off {
+        let broken_links = find_broken_links(&config.output_dir, vfs, &redirect_pages)
+            .context("failed to check links in generated pages")?;
+        if config.link_check == LinkCheckPolicy::Warn {
+            for link in &broken_links {
+                eprintln!("Warning: {link}");
+            }
+        } else if !broken_links.is_empty() {
+            bail!(
+                "found broken links in generated pages:\n{}",
+                broken_links
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+    }
+
+    if let Some(previous_build) = previous_build {
+        println!(
+            "Reused the output of {} unaffected article(s)",
+            previous_build.reused_count
+        );
+        vfs.remove_dir_all(&previous_build.output_dir)
+            .with_context(|| {
+                format!(
+                    "failed to remove previous output directory at {}",
+                    previous_build.output_dir
+                )
+            })?;
+    }
+
+    println!("{build_report}");
+    if config.profile_file.is_some() {
+        println!("{build_profile}");
+    }
+
+    Ok(SiteBuild {
+        dependencies,
+        articles: built_articles,
+    })
+}
+
+/// Returns the URLs of article pages that a previous build generated but `dependencies` has no record of,
+/// and that no redirect page replaces. Links to these pages break (e.g. after renaming a slug without an alias).
+fn removed_article_urls(
+    dependencies: &DependencyGraph,
+    previous: &DependencyGraph,
+    redirect_pages: &HashSet<String>,
+) -> Vec<String> {
+    dependencies
+        .removed_outputs(previous)
+        .into_iter()
+        .filter(|output| !redirect_pages.contains(*output))
+        .filter_map(|output| {
+            output
+                .strip_prefix(OUTPUT_CONTENT_DIR)
+                .and_then(|path| path.strip_suffix("/index.html"))
+                .filter(|slug| !slug.contains('/'))
+        })
+        .map(article_url)
+        .collect()
+}
+
+/// Checks the frontmatter of every article without building the site, printing every problem found.
+/// Unlike a build, the check doesn't stop at the first problem, and also reports unknown keys and future dates.
+fn check_frontmatter(config: &Config, vfs: &dyn Vfs, now: Timestamp) -> Result<()> {
+    let mut problems = Vec::new();
+    let article_count = frontmatter_problems(
+        config,
+        vfs,
+        now.to_zoned(TimeZone::UTC).date(),
+        &mut problems,
+    )?;
+
+    for problem in &problems {
+        eprintln!("{problem}");
+    }
+    println!("Checked frontmatter of {article_count} article(s)");
+
+    if !problems.is_empty() {
+        bail!("found {} frontmatter problem(s)", problems.len());
+    }
+
+    Ok(())
+}
+
+/// Checks the configuration and the content of every article without writing any output, printing every problem found.
+/// Besides checking frontmatter like [`check_frontmatter()`], this runs a whole build of the site
+/// with every generated file kept in memory, so the check fails exactly when a build would.
+fn check_site(config: &Config, vfs: &dyn Vfs, now: Timestamp) -> Result<()> {
+    let mut problems = Vec::new();
+    let article_count = frontmatter_problems(
+        config,
+        vfs,
+        now.to_zoned(TimeZone::UTC).date(),
+        &mut problems,
+    )?;
+
+    // A build stops at the first invalid article, so it's only run once every article's frontmatter is valid
+    if problems.is_empty() {
+        // The output directory of a previous build is hidden, as a build requires it to not exist
+        let dry_run_fs = OverlayFs::new(vfs).with_hidden(config.output_dir.clone());
+        if let Err(e) = build_site(config, &dry_run_fs, None, now) {
+            problems.push(format!("{e:#}"));
+        }
+    }
+
+    for problem in &problems {
+        eprintln!("{problem}");
+    }
+    println!("Checked {article_count} article(s)");
+
+    if !problems.is_empty() {
+        bail!("found {} problem(s)", problems.len());
+    }
 
     Ok(())
 }
 
+/// Results of a successful site build that later builds can make use of
+struct SiteBuild {
+    dependencies: DependencyGraph,
+    // Built articles, keyed by input path
+    articles: HashMap<Utf8PathBuf, ReusableArticle>,
+}
+
+/// Output of a previous build, moved aside so a rebuild can reuse unaffected outputs
+struct PreviousBuild {
+    output_dir: Utf8PathBuf,
+    // Outputs (relative to the output directory) that depend on changed inputs
+    affected_outputs: BTreeSet<Box<str>>,
+    articles: HashMap<Utf8PathBuf, ReusableArticle>,
+    reused_count: usize,
+}
+
+/// Results of building an article, besides the files in its output directory
+struct ReusableArticle {
+    slug: String,
+    // HTML of the article body alone
+    body: String,
+    metrics: ArticleMetrics,
+    // Text content of the article body, for the search index
+    plain_text: String,
+    // Files read while building the article besides its Markdown file (e.g. images)
+    inputs: Vec<Utf8PathBuf>,
+    thumbnail: Option<Thumbnail>,
+    // Whether the article's output directory contains a processed stylesheet
+    has_stylesheet: bool,
+    social_image_url: Option<String>,
+    // Names and content of the article's named slots
+    slots: Vec<(Box<str>, String)>,
+}
+
+impl PreviousBuild {
+    /// Moves an article's output directory from the previous build to `output_article_dir`,
+    /// if the article was built with the same slug and its output is not affected by changes.
+    /// This function returns `Ok(None)` if the article cannot be reused.
+    fn reuse_article(
+        &mut self,
+        path: &Utf8Path,
+        slug: &str,
+        output_file: &str,
+        output_article_dir: &Utf8Path,
+        vfs: &dyn Vfs,
+    ) -> Result<Option<ReusableArticle>> {
+        if self.affected_outputs.contains(output_file)
+            || self
+                .articles
+                .get(path)
+                .is_none_or(|article| article.slug != slug)
+        {
+            return Ok(None);
+        }
+
+        let previous_dir = self.output_dir.join(OUTPUT_CONTENT_DIR).join(slug);
+        vfs.rename(&previous_dir, output_article_dir)
+            .with_context(|| {
+                format!("failed to move article output from {previous_dir} to {output_article_dir}")
+            })?;
+
+        self.reused_count += 1;
+        Ok(self.articles.remove(path))
+    }
+}
+
+/// Waits for a thread to finish and returns its result, resuming any panic from the thread.
+fn join_thread<T>(handle: ScopedJoinHandle<'_, T>) -> T {
+    handle
+        .join()
+        .unwrap_or_else(|payload| resume_unwind(payload))
+}
+
+struct Article {
+    path: Utf8PathBuf,
+    text: String,
+    frontmatter: Frontmatter,
+}
+
+/// Site-wide state shared by all article builds
+struct ArticleContext<'a> {
+    config: &'a Config,
+    syntax_highlighter: &'a SyntaxHighlighter,
+    latex_converter: &'a LatexConverter,
+    page_builder: &'a PageBuilder,
+    // Page builders for alternate body templates, keyed by template name
+    article_templates: &'a HashMap<Box<str>, ArticleTemplate>,
+    wiki_links: &'a WikiLinks,
+    vfs: &'a dyn Vfs,
+}
+
+/// Page builder for an alternate body template selected by articles
+struct ArticleTemplate {
+    page_builder: PageBuilder,
+    // Paths of the template file and the partials it includes
+    inputs: Vec<Utf8PathBuf>,
+}
+
+/// Output of converting an article from Markdown
+struct BuiltArticle {
+    // Complete HTML document for the article page
+    html: String,
+    article: ReusableArticle,
+    // Processed stylesheet from the article's directory, which is written next to the article page
+    stylesheet: Option<String>,
+    // Problems with the article's content that don't prevent building it (e.g. images without useful alt text)
+    warnings: Vec<String>,
+}
+
 fn build_article(
     markdown: &str,
     frontmatter: &Frontmatter,
-    syntax_highlighter: &SyntaxHighlighter,
-    latex_converter: &LatexConverter,
     input_dir: &Utf8Path,
     output_dir: &Utf8Path,
-    page_builder: &PageBuilder,
-) -> Result<String> {
+    context: &ArticleContext<'_>,
+    math_cache: &mut MathCache<'_>,
+    profile: &mut ArticleProfile,
+) -> Result<BuiltArticle> {
+    let ArticleContext {
+        config,
+        syntax_highlighter,
+        latex_converter,
+        wiki_links,
+        vfs,
+        ..
+    } = context;
+    let asset_urls = config.asset_urls();
+
     let mut events = Vec::new();
 
-    // Check for duplicate image links to avoid redundant processing
-    let mut image_links = HashMap::new();
+    // Convert or copy every image once, as it is found
+    let mut images = ArticleImages::new(input_dir, output_dir, &frontmatter.slug, config, *vfs);
 
     // Track image parsing state for image alt text
     let mut active_image_state: Option<ActiveImageState<'_>> = None;
+    // Source and line of the current image, for warning about alt text that doesn't describe it
+    let mut active_image_src: Option<(CowStr<'_>, usize)> = None;
+    let mut warnings = Vec::new();
 
     // Track code block parsing state for syntax highlighting
     let mut is_in_code_block = false;
-    let mut code_language = None;
+    let mut code_block_info = CodeBlockInfo::default();
+    let mut has_code_block_wrapper = false;
+
+    // Track whether the current code block is a chart specification, which replaces the entire code block
+    let mut is_in_chart_block = false;
 
+    // Track whether the current code block is pre-rendered math, which is inserted as is
+    let mut is_in_raw_math_block = false;
+
+    // Label footnotes in the configured style; definitions are rendered once all references have been numbered
+    let mut footnotes = Footnotes::new(config.footnote_style);
     let mut footnote_references = HashSet::new();
     let mut footnote_definitions = HashSet::new();
 
-    let mut contains_math = false;
-
-    for (event, offset) in TextMergeWithOffset::new(
-        Parser::new_ext(
-            markdown,
-            Options::ENABLE_TABLES
-                | Options::ENABLE_FOOTNOTES
-                | Options::ENABLE_STRIKETHROUGH
-                | Options::ENABLE_SMART_PUNCTUATION
-                | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
-                | Options::ENABLE_MATH,
-        )
-        .into_offset_iter(),
-    ) {
+    let mut metrics = ArticleMetrics::default();
+    let mut inputs = Vec::new();
+
+    // Collect the article's prose (without code blocks, math, or image alt text) for the search index
+    let mut plain_text = String::new();
+    let mut is_in_metadata_block = false;
+
+    // Track whether the current paragraph is a shortcode or component marker, which replaces the entire paragraph
+    let mut is_in_shortcode = false;
+
+    // Closing HTML of every component whose content is being parsed, from outermost to innermost
+    let mut open_components = Vec::new();
+
+    // Articles can disable Markdown extensions (e.g. smart punctuation) in their frontmatter
+    let markdown_options = frontmatter.markdown_options();
+
+    // Number labeled figures and listings ahead of time so prose can reference them before they appear
+    let cross_references = CrossReferences::collect(markdown, markdown_options)
+        .context("failed to collect figure and listing labels")?;
+    let mut active_figure: Option<&LabeledItem> = None;
+    let mut is_in_listing = false;
+
+    // Number equations ahead of time so prose can reference them before they appear
+    let equations = Equations::collect(markdown, markdown_options)
+        .context("failed to collect equation labels")?;
+
+    // Paragraphs consisting of a single image with a title are replaced by a captioned `<figure>`
+    let captioned_images = captioned_image_paragraphs(markdown, markdown_options);
+    let mut is_in_captioned_image = false;
+
+    // Give every heading a unique anchor ID and a permalink, and track headings for the table of contents,
+    // which is rendered at a `[TOC]` marker or at the start of the article if enabled in the frontmatter
+    let mut table_of_contents = TableOfContents::new();
+    let mut active_heading: Option<(usize, String)> = None;
+    let mut headings = Vec::new();
+    let mut toc_marker_index = None;
+
+    // Adapt smart punctuation to the article's locale, falling back to the site's locale
+    let mut quote_transformer =
+        QuoteTransformer::new(frontmatter.quote_locale.unwrap_or(config.quote_locale));
+
+    // Pages already display the title, so a leading heading duplicating it is stripped or demoted if configured
+    let duplicate_title = match config.duplicate_title {
+        DuplicateTitlePolicy::Warn => None,
+        policy => duplicate_title_range(markdown, &frontmatter.title, markdown_options)
+            .map(|range| (policy, range)),
+    };
+    let demote_headings = matches!(duplicate_title, Some((DuplicateTitlePolicy::Demote, _)));
+    let mut outline = HeadingOutline::new(config.heading_outline);
+    let lines = LineIndex::new(markdown);
+
+    for (event, offset) in
+        TextMergeWithOffset::new(Parser::new_ext(markdown, markdown_options).into_offset_iter())
+    {
+        if let Some((DuplicateTitlePolicy::Strip, range)) = &duplicate_title
+            && range.contains(&offset.start)
+        {
+            continue;
+        }
+
+        let event = match event {
+            Event::Start(Tag::Heading {
+                level,
+                id,
+                classes,
+                attrs,
+            }) => {
+                let level = if demote_headings {
+                    demote_heading(level)
+                } else {
+                    level
+                };
+                let line = lines.line(offset.start);
+                Event::Start(Tag::Heading {
+                    level: outline.add_heading(level, line),
+                    id,
+                    classes,
+                    attrs,
+                })
+            }
+            Event::End(TagEnd::Heading(_)) => Event::End(TagEnd::Heading(outline.last_level())),
+            event => event,
+        };
+
+        if active_image_state.is_none()
+            && let Event::Start(Tag::Image { dest_url, .. }) = &event
+        {
+            let line = lines.line(offset.start);
+            active_image_src = Some((dest_url.clone(), line));
+        }
+
         if let Some(state) = &mut active_image_state {
             match event {
                 Event::Start(Tag::Image { .. }) => state.nest(),
@@ -236,42 +1394,216 @@ fn build_article(
                 state.update_alt_text_range(offset);
             } else {
                 // SAFETY: At this point, `active_image_state` is guaranteed to be `Some(_)`.
-                let html = unsafe {
-                    active_image_state
-                        .take()
-                        .unwrap_unchecked()
-                        .into_html(markdown)
-                };
-                events.push(html_to_event(html));
+                let state = unsafe { active_image_state.take().unwrap_unchecked() };
+                if let Some((src, line)) = active_image_src.take()
+                    && let Some(problem) = alt_text_problem(state.alt_text(markdown), &src)
+                {
+                    warnings.push(format!("line {line}: image {src} {problem}"));
+                }
+                events.push(html_to_event(
+                    state.into_html(markdown, is_in_captioned_image),
+                ));
             }
 
             continue;
         }
 
-        events.push(match event {
+        if is_in_shortcode {
+            if matches!(event, Event::End(TagEnd::Paragraph)) {
+                is_in_shortcode = false;
+            }
+            continue;
+        }
+
+        if let Some(figure) = active_figure {
+            match event {
+                // The label following the image is not displayed
+                Event::Text(_) | Event::SoftBreak => continue,
+                Event::End(TagEnd::Paragraph) => {
+                    events.push(html_to_event(figure.caption_html()));
+                    events.push(html_to_event("</figure>".into()));
+                    active_figure = None;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some((_, heading_text)) = &mut active_heading
+            && let Event::Text(text) | Event::Code(text) = &event
+        {
+            heading_text.push_str(text);
+        }
+
+        match &event {
+            Event::Start(Tag::MetadataBlock(_)) => is_in_metadata_block = true,
+            Event::End(TagEnd::MetadataBlock(_)) => is_in_metadata_block = false,
+            Event::Code(code) => plain_text.push_str(code),
+            // Keep the text of separate lines and blocks from running together
+            Event::SoftBreak
+            | Event::HardBreak
+            | Event::End(
+                TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::TableCell,
+            ) => plain_text.push(' '),
+            _ => {}
+        }
+
+        if let Event::Text(text) = &event
+            && !is_in_code_block
+            && !is_in_chart_block
+            && !is_in_raw_math_block
+        {
+            metrics.words += text.split_whitespace().count();
+            if !is_in_metadata_block {
+                plain_text.push_str(text);
+            }
+
+            let text = quote_transformer.transform(text);
+
+            if let Some(resolved) = cross_references
+                .resolve_references(&text)
+                .context("failed to resolve cross-reference")?
+            {
+                events.extend(resolved);
+                continue;
+            }
+            if let Cow::Owned(text) = text {
+                events.push(Event::Text(text.into()));
+                continue;
+            }
+        }
+
+        // Quotes cannot span multiple blocks
+        if let Event::End(
+            TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::TableCell,
+        ) = event
+        {
+            quote_transformer.reset();
+        }
+
+        let event = match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang)))
+                if lang.as_ref() == CHART_LANGUAGE =>
+            {
+                is_in_chart_block = true;
+                continue;
+            }
+            Event::End(TagEnd::CodeBlock) if is_in_chart_block => {
+                is_in_chart_block = false;
+                continue;
+            }
+            Event::Text(text) if is_in_chart_block => ChartSpec::parse(&text)
+                .and_then(|spec| spec.to_html())
+                .context("failed to render chart")
+                .map(html_to_event)?,
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref lang)))
+                if lang.as_ref() == RAW_MATH_LANGUAGE =>
+            {
+                // Pre-rendered math still needs the KaTeX stylesheet if it uses KaTeX's classes
+                metrics.math_expressions += 1;
+                is_in_raw_math_block = true;
+                html_to_event(raw_math_opening_html())
+            }
+            Event::End(TagEnd::CodeBlock) if is_in_raw_math_block => {
+                is_in_raw_math_block = false;
+                html_to_event("</div>".into())
+            }
+            Event::Text(text) if is_in_raw_math_block => Event::Html(text),
             Event::Start(Tag::CodeBlock(ref kind)) => {
                 is_in_code_block = true;
-                code_language = match kind {
-                    CodeBlockKind::Indented => None,
-                    CodeBlockKind::Fenced(lang) => Some(lang.clone()),
+                code_block_info = match kind {
+                    CodeBlockKind::Indented => CodeBlockInfo::default(),
+                    CodeBlockKind::Fenced(info) => CodeBlockInfo::parse(strip_label(info))
+                        .context("failed to parse code block info string")?,
                 };
-                event
+                metrics.add_code_block(code_block_info.language.as_deref());
+                if let Some(listing) = cross_references.at(offset.start) {
+                    is_in_listing = true;
+                    events.push(html_to_event(listing.opening_html()));
+                    events.push(html_to_event(listing.caption_html()));
+                }
+                if let Some(html) = syntax_highlighter.code_block_opening_html(
+                    code_block_info.language.as_deref(),
+                    config.code_language_labels,
+                    config.code_copy_buttons,
+                )? {
+                    has_code_block_wrapper = true;
+                    events.push(html_to_event(html));
+                }
+                // Attributes and labels are removed from the info string, leaving only the language
+                match kind {
+                    CodeBlockKind::Indented => event,
+                    CodeBlockKind::Fenced(_) => {
+                        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(
+                            code_block_info.language.clone().unwrap_or_default().into(),
+                        )))
+                    }
+                }
             }
             Event::End(TagEnd::CodeBlock) => {
                 is_in_code_block = false;
-                event
+                let mut closing_html = String::new();
+                if has_code_block_wrapper {
+                    has_code_block_wrapper = false;
+                    closing_html.push_str("</div>");
+                }
+                if is_in_listing {
+                    is_in_listing = false;
+                    closing_html.push_str("</figure>");
+                }
+                if closing_html.is_empty() {
+                    event
+                } else {
+                    events.push(event);
+                    html_to_event(closing_html)
+                }
             }
-            Event::Text(text) if is_in_code_block => syntax_highlighter
-                .highlight_block(&text, code_language.as_deref())
+            Event::Text(text) if is_in_code_block => profile
+                .time(Stage::Highlight, || {
+                    syntax_highlighter.highlight_block(&text, &code_block_info)
+                })
                 .context("failed to highlight code block")
                 .map(html_to_event)?,
-            Event::Code(text) => syntax_highlighter
-                .highlight_segment(&text)
+            Event::Code(text) => profile
+                .time(Stage::Highlight, || {
+                    syntax_highlighter.highlight_segment(&text)
+                })
                 .context("failed to highlight inline code segment")
                 .map(html_to_event)?,
-            Event::FootnoteReference(ref id) => {
-                footnote_references.insert(id.clone());
-                event
+            Event::Start(Tag::Link {
+                link_type: LinkType::WikiLink { .. },
+                dest_url,
+                title,
+                id,
+            }) => {
+                let (url, path) = wiki_links.resolve(&dest_url)?;
+                // The linked article's title determines the link target, so this article depends on it
+                inputs.push(path.to_path_buf());
+                Event::Start(Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url: url.into(),
+                    title,
+                    id,
+                })
+            }
+            Event::TaskListMarker(checked) => {
+                // The marker directly follows the start of its list item, which gets a class for styling
+                if let Some(item) = events
+                    .iter_mut()
+                    .rev()
+                    .find(|event| matches!(event, Event::Start(Tag::Item)))
+                {
+                    *item = html_to_event(r#"<li class="__task-item">"#.into());
+                }
+                html_to_event(format!(
+                    r#"<input type="checkbox" class="__task-checkbox" disabled{}>"#,
+                    if checked { " checked" } else { "" }
+                ))
+            }
+            Event::FootnoteReference(id) => {
+                let html = footnotes.reference_html(&id);
+                footnote_references.insert(id);
+                html_to_event(html)
             }
             Event::Start(Tag::FootnoteDefinition(ref id)) => {
                 if !footnote_definitions.insert(id.clone()) {
@@ -287,65 +1619,161 @@ fn build_article(
             }) => {
                 debug_assert!(active_image_state.is_none());
 
-                validate_image_src(&dest_url).context("image source is invalid")?;
+                active_image_state = Some(images.start_image(
+                    &dest_url,
+                    title,
+                    id,
+                    &mut metrics,
+                    &mut inputs,
+                    profile,
+                )?);
 
-                let input_path = input_dir.join(&*dest_url);
-                let input_handle = Handle::from_path(&input_path)
-                    .with_context(|| format!("failed to open file at {input_path}"))?;
-
-                let new_state = if input_path
-                    .extension()
-                    .is_some_and(|ext| ext == OUTPUT_IMAGE_EXTENSION || ext == "svg")
+                continue;
+            }
+            Event::Start(Tag::Paragraph) if captioned_images.contains(&offset.start) => {
+                is_in_captioned_image = true;
+                continue;
+            }
+            Event::End(TagEnd::Paragraph) if is_in_captioned_image => {
+                is_in_captioned_image = false;
+                continue;
+            }
+            Event::Start(Tag::Paragraph)
+                if let Some(figure) = cross_references.at(offset.start) =>
+            {
+                active_figure = Some(figure);
+                html_to_event(figure.opening_html())
+            }
+            Event::Start(Tag::Paragraph) if markdown[offset.clone()].trim() == TOC_MARKER => {
+                if toc_marker_index.is_some() {
+                    bail!("found more than one table of contents marker");
+                }
+                is_in_shortcode = true;
+                // This placeholder is replaced once all headings have been collected
+                toc_marker_index = Some(events.len());
+                html_to_event(String::new())
+            }
+            Event::Start(Tag::Heading { .. }) => {
+                active_heading = Some((events.len(), String::new()));
+                event
+            }
+            Event::End(TagEnd::Heading(level)) => {
+                if let Some((index, text)) = active_heading.take() {
+                    table_of_contents.add_heading(level, &text);
+                    // This placeholder is replaced by a permalink once all headings have been collected
+                    headings.push((index, events.len()));
+                    events.push(html_to_event(String::new()));
+                }
+                event
+            }
+            Event::Start(Tag::Paragraph) => {
+                let source = &markdown[offset];
+                if let Some(marker) =
+                    ComponentMarker::parse(source).context("failed to parse component marker")?
                 {
-                    let output_path = output_dir.join(&*dest_url);
-                    copy(&input_path, &output_path)
-                        .with_context(|| {
-                            format!("failed to copy file from {input_path} to {output_path}")
-                        })
-                        .context("failed to process image")?;
-
-                    ActiveImageState::new(dest_url, None, title, id)
+                    is_in_shortcode = true;
+                    match marker {
+                        ComponentMarker::Open(usage) => {
+                            let (open, close) = render_component(&config.components, &usage, true)
+                                .with_context(|| {
+                                    format!("failed to render component `{}`", usage.name)
+                                })?;
+                            open_components.push(close);
+                            html_to_event(open)
+                        }
+                        ComponentMarker::Close => {
+                            open_components.pop().map(html_to_event).ok_or_else(|| {
+                                anyhow!(
+                                    "found a component closing marker without an opening marker"
+                                )
+                            })?
+                        }
+                    }
                 } else {
-                    // Check if image has already been processed
-                    let dimensions = match image_links.entry(input_handle) {
-                        Entry::Occupied(entry) => *entry.get(),
-                        Entry::Vacant(entry) => {
-                            let dimensions = convert_image(input_dir, output_dir, &dest_url)
-                                .context("failed to process image")?;
-                            *entry.insert(dimensions)
+                    match Shortcode::parse(source).context("failed to parse shortcode")? {
+                        Some(shortcode) => {
+                            is_in_shortcode = true;
+                            render_shortcode(
+                                &shortcode,
+                                input_dir,
+                                output_dir,
+                                &frontmatter.slug,
+                                config,
+                                &mut inputs,
+                                *vfs,
+                            )
+                            .with_context(|| {
+                                format!("failed to render shortcode `{}`", shortcode.name)
+                            })
+                            .map(html_to_event)?
                         }
-                    };
-
-                    let output_path = Utf8Path::new(&dest_url)
-                        .with_extension(OUTPUT_IMAGE_EXTENSION)
-                        .into_string()
-                        .into_boxed_str();
-
-                    ActiveImageState::new(CowStr::Boxed(output_path), Some(dimensions), title, id)
-                };
-
-                active_image_state = Some(new_state);
-
-                continue;
+                        None => event,
+                    }
+                }
             }
             Event::InlineMath(src) => {
-                contains_math = true;
-                latex_converter
-                    .latex_to_html(&src, RenderMode::Inline)
-                    .context("failed to convert LaTeX to HTML")
-                    .map(html_to_event)?
+                // Inline math consisting of a single equation reference becomes a link to the equation
+                if let Some(html) = equations.reference_html(&src)? {
+                    html_to_event(html)
+                } else {
+                    metrics.math_expressions += 1;
+                    let src = equations.resolve_references(&src)?;
+                    let html = profile
+                        .time(Stage::Math, || {
+                            latex_converter.render_cached(
+                                &src,
+                                RenderMode::Inline,
+                                &frontmatter.katex,
+                                math_cache,
+                            )
+                        })
+                        .context("failed to convert LaTeX to HTML")?;
+                    if let Some(error) = math_error(&html) {
+                        let line = lines.line(offset.start);
+                        warnings.push(format!("line {line}: invalid math: {error}"));
+                    }
+                    html_to_event(html)
+                }
             }
             Event::DisplayMath(src) => {
-                contains_math = true;
-                latex_converter
-                    .latex_to_html(&src, RenderMode::Display)
-                    .context("failed to convert LaTeX to HTML")
-                    .map(html_to_event)?
+                metrics.math_expressions += 1;
+                let numbered_math = equations.at(offset.start);
+                let src =
+                    equations.resolve_references(numbered_math.map_or(&src, NumberedMath::src))?;
+                let html = profile
+                    .time(Stage::Math, || {
+                        // The cache key includes equation numbers, since they change the output
+                        latex_converter.render_cached(
+                            &src,
+                            RenderMode::Display,
+                            &frontmatter.katex,
+                            math_cache,
+                        )
+                    })
+                    .context("failed to convert LaTeX to HTML")?;
+                if let Some(error) = math_error(&html) {
+                    let line = lines.line(offset.start);
+                    warnings.push(format!("line {line}: invalid math: {error}"));
+                }
+                match numbered_math {
+                    Some(numbered_math) => html_to_event(numbered_math.anchors_html() + &html),
+                    None => html_to_event(html),
+                }
             }
             _ => event,
-        });
+        };
+
+        events.push(event);
     }
 
+    if !open_components.is_empty() {
+        bail!("found a component opening marker without a closing marker");
+    }
+
+    outline.finish()?;
+
+    metrics.footnotes = footnote_definitions.len();
+
     // Check for footnote references without definitions
     for id in footnote_references {
         if !footnote_definitions.remove(&id) {
@@ -358,22 +1786,524 @@ fn build_article(
         bail!("found a footnote definition ID without references: {id}");
     }
 
-    let mut article_body = String::with_capacity(markdown.len() * 3 / 2);
-    push_html(&mut article_body, events.into_iter());
+    // Heading IDs avoid the IDs of figures, listings, and footnotes, which are fixed by their labels
+    let reserved_ids = cross_references
+        .ids()
+        .map(str::to_owned)
+        .chain(footnotes.anchor_ids())
+        .collect();
+    let heading_ids = table_of_contents.assign_ids(&reserved_ids);
+    for ((index, anchor_index), heading_id) in headings.into_iter().zip(heading_ids) {
+        events[anchor_index] = html_to_event(heading_anchor_html(&heading_id));
+        if let Event::Start(Tag::Heading { id, .. }) = &mut events[index] {
+            *id = Some(heading_id.into());
+        }
+    }
+
+    // Headings are indexed by event position, so events are only inserted before them afterward
+    if let Some(position) = frontmatter.figure_index
+        && let Some(index) = cross_references.index_html()
+    {
+        match position {
+            IndexPosition::Top => events.insert(0, html_to_event(index)),
+            IndexPosition::Bottom => events.push(html_to_event(index)),
+        }
+    }
+
+    if (frontmatter.toc || toc_marker_index.is_some())
+        && let Some(toc) = table_of_contents.to_html()
+    {
+        match toc_marker_index {
+            Some(index) => events[index] = html_to_event(toc),
+            None => events.insert(0, html_to_event(toc)),
+        }
+    }
+
+    footnotes.render_definitions(&mut events, config.footnote_heading.as_deref());
+
+    let thumbnail = match &frontmatter.cover {
+        Some(cover) => {
+            let (thumbnail, output_bytes) = profile
+                .time(Stage::ImageEncode, || {
+                    convert_cover_thumbnail(
+                        input_dir,
+                        output_dir,
+                        cover,
+                        &frontmatter.slug,
+                        config.image_options(),
+                        &asset_urls,
+                        *vfs,
+                    )
+                })
+                .context("failed to process cover image")?;
+            inputs.push(input_dir.join(&**cover));
+            metrics.images_converted += 1;
+            metrics.image_bytes += output_bytes;
+            Some(thumbnail)
+        }
+        None => None,
+    };
+
+    let social_image_url = match &frontmatter.cover {
+        Some(cover) => {
+            copy_social_image(input_dir, output_dir, cover, &frontmatter.slug, config, *vfs)
+                .context("failed to copy cover image for link previews")?
+            .or_else(|| {
+                warnings.push(format!(
+                    "cover image {cover} isn't a JPEG, PNG, GIF, or WebP image, so link previews won't show it"
+                ));
+                None
+            })
+        }
+        None => None,
+    };
+
+    // Read the content of the article's named slots
+    let slot_texts = frontmatter
+        .slots
+        .iter()
+        .map(|(name, path)| {
+            let path = input_dir.join(&**path);
+            let text = vfs.read_to_string(&path).with_context(|| {
+                format!("failed to read content of the slot \"{name}\" at {path}")
+            })?;
+            let text = normalize_input(text, &path);
+            inputs.push(path);
+            Ok((name.clone(), text))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Process the stylesheet in the article's directory, which is tracked even if it doesn't exist yet,
+    // so adding one rebuilds the article
+    let stylesheet_path = input_dir.join(ARTICLE_STYLESHEET_FILE);
+    let stylesheet = if vfs.is_file(&stylesheet_path) {
+        let source = vfs
+            .read_to_string(&stylesheet_path)
+            .with_context(|| format!("failed to read article stylesheet at {stylesheet_path}"))?;
+        let CssOutput { css, font_css, .. } =
+            transform_css(&source, &asset_urls, &config.css_targets).with_context(|| {
+                format!("failed to minify article stylesheet at {stylesheet_path}")
+            })?;
+        Some(font_css + &css)
+    } else {
+        None
+    };
+    inputs.push(stylesheet_path);
+
+    profile.time(Stage::Serialize, || {
+        let mut body = String::with_capacity(markdown.len() * 3 / 2);
+        push_html(&mut body, events.into_iter());
+
+        let article = ReusableArticle {
+            slug: frontmatter.slug.clone(),
+            body,
+            metrics,
+            plain_text,
+            inputs,
+            thumbnail,
+            has_stylesheet: stylesheet.is_some(),
+            social_image_url,
+            slots: slot_texts,
+        };
+        build_article_page(frontmatter, &article, context).map(|html| BuiltArticle {
+            html,
+            article,
+            stylesheet,
+            warnings,
+        })
+    })
+}
+
+/// Builds the complete HTML document for an article page around its body.
+/// Reused articles are built again with this, so their pages reflect the current build (e.g. its build stamp).
+fn build_article_page(
+    frontmatter: &Frontmatter,
+    article: &ReusableArticle,
+    context: &ArticleContext<'_>,
+) -> Result<String> {
+    let config = context.config;
+
+    // Articles with an alternate body template are built with that template's page builder
+    let page_builder = match &frontmatter.template {
+        Some(name) => {
+            &context
+                .article_templates
+                .get(name)
+                .expect("page builder should exist for every article template")
+                .page_builder
+        }
+        None => context.page_builder,
+    };
+
+    let stylesheet_url = article.has_stylesheet.then(|| {
+        config
+            .asset_urls()
+            .article_asset(&frontmatter.slug, ARTICLE_STYLESHEET_FILE)
+    });
+    let slots: Vec<_> = article
+        .slots
+        .iter()
+        .map(|(name, text)| (&**name, text.as_str()))
+        .collect();
 
     page_builder
-        .build_page(
+        .build_page_with_slots(
             &frontmatter.title,
-            &article_body,
+            &article_url(&frontmatter.slug),
+            &article.body,
             PageKind::Article {
-                contains_math,
+                contains_math: article.metrics.math_expressions > 0,
                 created: frontmatter.created,
                 updated: frontmatter.updated,
+                license: frontmatter
+                    .license
+                    .as_ref()
+                    .or(config.default_license.as_ref()),
+                description: frontmatter.description.as_deref(),
+                image_url: article.social_image_url.as_deref(),
+                reading_minutes: config
+                    .reading_speed_wpm
+                    .map(|wpm| article.metrics.reading_minutes(wpm)),
+                author: frontmatter.author.as_deref(),
+                noindex: frontmatter.noindex,
+                head: frontmatter.head.as_deref(),
+                stylesheet_url: stylesheet_url.as_deref(),
             },
+            &slots,
         )
-        .context("failed to parse processed article body as valid HTML")
+        .context("failed to build article page")
+}
+
+// Size of inlined site CSS, in bytes, above which a warning is printed;
+// roughly what fits in the first round trip of a connection along with the rest of a page's `<head>`
+const INLINE_CSS_WARNING_SIZE: usize = 14 * 1024;
+// Name of the optional stylesheet in an article's directory, which is processed and linked from the article's page
+const ARTICLE_STYLESHEET_FILE: &str = "style.css";
+
+/// Returns the time of the build. For reproducible builds, the time can be pinned with the `SOURCE_DATE_EPOCH`
+/// environment variable, in seconds since the Unix epoch (https://reproducible-builds.org/specs/source-date-epoch/).
+fn build_time() -> Result<Timestamp> {
+    let Ok(epoch) = var("SOURCE_DATE_EPOCH") else {
+        return Ok(Timestamp::now());
+    };
+
+    epoch
+        .trim()
+        .parse()
+        .ok()
+        .and_then(|seconds| Timestamp::from_second(seconds).ok())
+        .with_context(|| format!("`SOURCE_DATE_EPOCH` is not a valid Unix timestamp ({epoch})"))
 }
 
 fn html_to_event<'a>(html: String) -> Event<'a> {
     Event::InlineHtml(html.into())
 }
+
+#[cfg(test)]
+mod test {
+    use super::{PreviousBuild, build_site, check_site, join_thread, removed_article_urls};
+    use camino::Utf8Path;
+    use foldhash::{HashSet, HashSetExt};
+    use image::{ImageFormat, Rgb, RgbImage};
+    use jiff::Timestamp;
+    use ssg::{CheckCommand, CliArgs, Config, DependencyGraph, MemoryFs, Vfs};
+    use std::{
+        collections::BTreeSet,
+        io::Cursor,
+        panic::{AssertUnwindSafe, catch_unwind},
+        thread::scope,
+    };
+
+    /// Utility function for creating a site in memory with a single article, whose Markdown content is `article`
+    fn minimal_site(article: &str) -> MemoryFs {
+        MemoryFs::new()
+            .with_file(
+                "/site/config.toml",
+                r#"
+                    output_dir = "out/"
+                    site_css_file = "site.css"
+                    head_template_html_file = "head.html"
+                    body_template_html_file = "body.html"
+                    articles_dir = "articles/"
+                    code_theme = "base16-mocha.dark"
+                    fragments = []
+                "#,
+            )
+            .with_file("/site/site.css", "main { color: red; }")
+            .with_file("/site/head.html", "")
+            .with_file("/site/body.html", "<main></main>")
+            .with_file(
+                "/site/articles/post/post.md",
+                format!("---\ntitle: Post\nslug: post\ncreated: 2024-01-01\n---\n\n{article}\n"),
+            )
+    }
+
+    // Time of every test build, which build stamps and age warnings depend on (2030-01-01T00:00:00Z)
+    const NOW: Timestamp = Timestamp::constant(1_893_456_000, 0);
+
+    /// Utility function for loading the config of a site in memory, for a build or a check
+    fn load_config(vfs: &MemoryFs, check: Option<CheckCommand>) -> Config {
+        let args = CliArgs {
+            config_path: Utf8Path::new("/site/config.toml").into(),
+            include_drafts: false,
+            watch: false,
+            profile_file: None,
+            check,
+        };
+        Config::load(&args, vfs).unwrap()
+    }
+
+    #[test]
+    fn removed_article_pages() {
+        let mut previous = DependencyGraph::new();
+        for output in [
+            "writing/a/index.html",
+            "writing/b/index.html",
+            "writing/c/index.html",
+            "writing/c/cat.avif",
+            "tags/rust/index.html",
+        ] {
+            previous.add::<&Utf8Path>(output, []);
+        }
+        let mut dependencies = DependencyGraph::new();
+        dependencies.add::<&Utf8Path>("writing/a/index.html", []);
+
+        // The page of an article renamed with an alias is replaced by a redirect page
+        let mut redirect_pages = HashSet::new();
+        redirect_pages.insert("writing/b/index.html".to_owned());
+        assert_eq!(
+            removed_article_urls(&dependencies, &previous, &redirect_pages),
+            ["/writing/c/"]
+        );
+    }
+
+    #[test]
+    fn build_in_memory() {
+        let mut png = Cursor::new(Vec::new());
+        RgbImage::from_pixel(8, 8, Rgb([255, 0, 0]))
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+
+        let vfs = MemoryFs::new()
+            .with_file(
+                "/site/config.toml",
+                r#"
+                    output_dir = "out/"
+                    cache_dir = "cache/"
+                    site_css_file = "site.css"
+                    head_template_html_file = "head.html"
+                    body_template_html_file = "body.html"
+                    fragments = [{ title = "Welcome", path = "fragments/index.html" }]
+                    articles_dir = "articles/"
+                    code_theme = "base16-mocha.dark"
+                    link_check = "error"
+                    age_warning_years = 5
+                    fsync_output = true
+
+                    [robots]
+
+                    [manifest]
+                    name = "Example"
+                "#,
+            )
+            .with_file("/site/site.css", "main { color: red; }")
+            .with_file("/site/head.html", "")
+            .with_file(
+                "/site/body.html",
+                r#"<main></main><aside data-slot="sidebar"></aside><include src="partials/footer.html"><build-stamp></build-stamp>"#,
+            )
+            .with_file("/site/partials/footer.html", "<footer>Footer</footer>")
+            .with_file(
+                "/site/fragments/index.html",
+                r#"<a href="/writing/post/">Post</a>"#,
+            )
+            .with_file(
+                "/site/articles/post/post.md",
+                "---\ntitle: Post\nslug: post\ncreated: 2024-01-01\nslots: { sidebar: sidebar.html }\n---\n\n\
+                ![A red square](red.png)\n\n![A logo](logo.svg)\n\nSome $x^2$ math.\n",
+            )
+            .with_file("/site/articles/post/red.png", png.into_inner())
+            .with_file(
+                "/site/articles/post/logo.svg",
+                r#"<svg xmlns="http://www.w3.org/2000/svg">  <rect width="1" height="1"/>  </svg>"#,
+            )
+            .with_file("/site/articles/post/style.css", "p { margin: 0; }")
+            .with_file("/site/articles/post/sidebar.html", "\u{feff}<p>Si\u{7}de</p>");
+        let config = load_config(&vfs, None);
+
+        // Everything that depends on the time of the build uses the given time
+        let dependencies = build_site(&config, &vfs, None, NOW).unwrap().dependencies;
+        assert!(dependencies.contains_output("writing/post/index.html"));
+        assert!(dependencies.contains_output("writing/post/style.css"));
+        assert!(dependencies.contains_output("robots.txt"));
+        assert!(dependencies.contains_output("site.webmanifest"));
+
+        let read = |path: &str| vfs.read_to_string(Utf8Path::new(path)).unwrap();
+        assert!(read("/site/out/index.html").contains("<footer>Footer</footer>"));
+        let article = read("/site/out/writing/post/index.html");
+        assert!(article.contains("2030-01-01"));
+        assert!(article.contains("__article-age-warning"));
+        assert!(article.contains("red.avif"));
+        assert!(article.contains("katex"));
+        // Slot files are normalized like other input files
+        assert!(article.contains(r#"<aside data-slot="sidebar"><p>Side</p></aside>"#));
+        for path in [
+            "/site/out/writing/post/red.avif",
+            "/site/out/writing/post/logo.svg",
+            "/site/out/writing/post/style.css",
+            "/site/out/writing/index.html",
+            "/site/out/stylesheets/site.css",
+            "/site/out/stylesheets/katex.css",
+            "/site/cache/dependencies.json",
+            "/site/cache/math.json",
+        ] {
+            assert!(vfs.is_file(Utf8Path::new(path)), "{path} was not written");
+        }
+        assert!(
+            !vfs.files_in(Utf8Path::new("/site/out/fonts"))
+                .unwrap()
+                .is_empty()
+        );
+
+        // Nothing was written to the real file system
+        assert!(!Utf8Path::new("/site").exists());
+    }
+
+    #[test]
+    fn reused_articles() {
+        let vfs = minimal_site("Some text.").with_file(
+            "/site/body.html",
+            "<main></main><build-stamp></build-stamp>",
+        );
+        let mut config = load_config(&vfs, None);
+        config.age_warning_years = Some(5);
+        let build =
+            build_site(&config, &vfs, None, "2025-06-01T00:00:00Z".parse().unwrap()).unwrap();
+
+        // An unaffected article is moved from the previous output, but its page is built for the new build
+        vfs.rename(
+            Utf8Path::new("/site/out"),
+            Utf8Path::new("/site/out.previous"),
+        )
+        .unwrap();
+        let previous_build = PreviousBuild {
+            output_dir: "/site/out.previous".into(),
+            affected_outputs: BTreeSet::new(),
+            articles: build.articles,
+            reused_count: 0,
+        };
+        build_site(&config, &vfs, Some(previous_build), NOW).unwrap();
+
+        let article = vfs
+            .read_to_string(Utf8Path::new("/site/out/writing/post/index.html"))
+            .unwrap();
+        assert!(article.contains("2030-01-01"));
+        assert!(!article.contains("2025-06-01"));
+        assert!(article.contains("__article-age-warning"));
+    }
+
+    #[test]
+    fn check_matches_build() {
+        let check =
+            |vfs: &MemoryFs| check_site(&load_config(vfs, Some(CheckCommand::Site)), vfs, NOW);
+        let build = |vfs: &MemoryFs| build_site(&load_config(vfs, None), vfs, None, NOW).map(drop);
+
+        // A missing video file only fails when its shortcode is rendered
+        for (article, is_valid) in [
+            ("Some $x^2$ math.", true),
+            (r#"{{ video src="missing.mp4" }}"#, false),
+            (r"Some $\frac{1}$ math.", false),
+        ] {
+            // The check ignores the output of a previous build, and doesn't write anything
+            let vfs = minimal_site(article).with_file("/site/out/index.html", "previous");
+            assert_eq!(check(&vfs).is_ok(), is_valid, "{article}");
+            assert_eq!(
+                vfs.files_in(Utf8Path::new("/site/out")).unwrap(),
+                [Utf8Path::new("/site/out/index.html")]
+            );
+
+            assert_eq!(build(&minimal_site(article)).is_ok(), is_valid, "{article}");
+        }
+    }
+
+    #[test]
+    fn frontmatter_excerpts() {
+        // The `excerpt` frontmatter field takes precedence over the article's text
+        let vfs = minimal_site("");
+        let config = vfs
+            .read_to_string(Utf8Path::new("/site/config.toml"))
+            .unwrap()
+            .replace("fragments = []", "fragments = []\narchive_excerpts = true");
+        let vfs = vfs.with_file("/site/config.toml", config).with_file(
+            "/site/articles/post/post.md",
+            "---\ntitle: Post\nslug: post\ncreated: 2024-01-01\nexcerpt: Summary\n---\n\nText\n",
+        );
+        build_site(&load_config(&vfs, None), &vfs, None, NOW).unwrap();
+        let archive = vfs
+            .read_to_string(Utf8Path::new("/site/out/writing/index.html"))
+            .unwrap();
+        assert!(archive.contains(r#"<p class="__article-excerpt">Summary</p>"#));
+        assert!(!archive.contains("Text"));
+    }
+
+    #[test]
+    fn task_lists() {
+        let build = |vfs: &MemoryFs| {
+            build_site(&load_config(vfs, None), vfs, None, NOW).unwrap();
+            vfs.read_to_string(Utf8Path::new("/site/out/writing/post/index.html"))
+                .unwrap()
+        };
+
+        // Task list items get a class and a disabled checkbox, while other items in the list are unchanged
+        let html = build(&minimal_site("- [ ] A\n- [x] B\n- C"));
+        assert!(html.contains(concat!(
+            r#"<li class="__task-item"><input type="checkbox" class="__task-checkbox" disabled="">A</li>"#,
+            "\n",
+            r#"<li class="__task-item"><input type="checkbox" class="__task-checkbox" disabled="" checked="">B</li>"#,
+            "\n<li>C</li>"
+        )));
+
+        // Articles can disable the extension
+        let vfs = minimal_site("").with_file(
+            "/site/articles/post/post.md",
+            "---\ntitle: Post\nslug: post\ncreated: 2024-01-01\ndisable_markdown: [tasklists]\n---\n\n- [ ] A\n",
+        );
+        let html = build(&vfs);
+        assert!(html.contains("<li>[ ] A</li>") && !html.contains("checkbox"));
+    }
+
+    #[test]
+    fn render_math_errors() {
+        let vfs = minimal_site(r"Some $\frac{1}$ math.");
+        assert!(build_site(&load_config(&vfs, None), &vfs, None, NOW).is_err());
+
+        // With `render_errors`, invalid math is rendered as error text instead of failing the build
+        let config = vfs
+            .read_to_string(Utf8Path::new("/site/config.toml"))
+            .unwrap()
+            + "[katex]\nrender_errors = true\n";
+        let vfs = minimal_site(r"Some $\frac{1}$ math.").with_file("/site/config.toml", config);
+        assert!(build_site(&load_config(&vfs, None), &vfs, None, NOW).is_ok());
+        assert!(
+            vfs.read_to_string(Utf8Path::new("/site/out/writing/post/index.html"))
+                .unwrap()
+                .contains(r#"<span class="katex-error" title="KaTeX parse error: "#)
+        );
+    }
+
+    #[test]
+    fn setup_threads() {
+        // Results and panics of setup threads are passed on to the build
+        scope(|scope| {
+            assert_eq!(join_thread(scope.spawn(|| 1)), 1);
+            let handle = scope.spawn(|| -> u8 { panic!("setup failed") });
+            assert!(catch_unwind(AssertUnwindSafe(|| join_thread(handle))).is_err());
+        });
+
+        let vfs = minimal_site("Text").with_file("/site/site.css", "main[ { color: red; }");
+        let Err(error) = build_site(&load_config(&vfs, None), &vfs, None, NOW) else {
+            panic!("build should fail on invalid site CSS");
+        };
+        assert!(format!("{error:#}").contains("failed to minify site CSS"));
+    }
+}
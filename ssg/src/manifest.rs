@@ -0,0 +1,260 @@
+//! Code for generating a web app manifest, which browsers read to install the site as an app
+//! (e.g. its name and icon on a phone's home screen).
+
+use crate::{OUTPUT_ICONS_DIR, depgraph::DependencyGraph, url::page_url, vfs::Vfs};
+use anyhow::{Context, Result, bail};
+use camino::Utf8Path;
+use foldhash::{HashSet, HashSetExt};
+use serde::{Deserialize, Serialize};
+
+// Path of the manifest in the output directory
+pub const OUTPUT_MANIFEST_FILE: &str = "site.webmanifest";
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestConfig {
+    // Name of the site as an app
+    pub name: Box<str>,
+    // Shorter name for places with little space (e.g. below a home screen icon); defaults to `name`
+    #[serde(default)]
+    pub short_name: Option<Box<str>>,
+    // Color of browser and operating system UI around the site, which is also set on every page
+    #[serde(default)]
+    pub theme_color: Option<Box<str>>,
+    #[serde(default)]
+    pub icons: Box<[ManifestIcon]>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestIcon {
+    // Path to the icon file, which is copied to the output icons directory
+    pub path: Box<Utf8Path>,
+    // Dimensions of the icon (e.g. `192x192`), or `any` for scalable icons
+    pub sizes: Box<str>,
+}
+
+#[derive(Serialize)]
+struct WebManifest<'a> {
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    short_name: Option<&'a str>,
+    start_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    theme_color: Option<&'a str>,
+    icons: Vec<WebManifestIcon<'a>>,
+}
+
+#[derive(Serialize)]
+struct WebManifestIcon<'a> {
+    src: String,
+    sizes: &'a str,
+    #[serde(rename = "type")]
+    mime: &'static str,
+}
+
+impl ManifestConfig {
+    /// Validates the manifest settings.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the name or short name is empty
+    /// - the theme color is not a hex color or a color keyword
+    /// - an icon file does not exist, is not a PNG, JPEG, WebP, SVG, or ICO file, or has the same file name as another icon
+    /// - the sizes of an icon are not `any` or a space-separated list of dimensions like `192x192`
+    pub fn validate(&self, vfs: &dyn Vfs) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("name cannot be empty");
+        } else if self
+            .short_name
+            .as_deref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            bail!("short name cannot be empty");
+        } else if let Some(color) = &self.theme_color
+            && !is_color(color)
+        {
+            bail!("theme color must be a hex color or a color keyword (got \"{color}\")");
+        }
+
+        let mut file_names = HashSet::with_capacity(self.icons.len());
+        for icon in &self.icons {
+            (|| {
+                if !vfs.is_file(&icon.path) {
+                    bail!("icon file could not be opened or does not point to a file");
+                }
+                icon_mime(&icon.path)?;
+                let file_name = icon
+                    .path
+                    .file_name()
+                    .context("icon path has no file name")?;
+                if !file_names.insert(file_name) {
+                    bail!("found multiple icons with the file name {file_name}");
+                } else if !is_icon_sizes(&icon.sizes) {
+                    bail!(
+                        "icon sizes must be `any` or dimensions like `192x192` (got \"{}\")",
+                        icon.sizes
+                    );
+                }
+                Ok(())
+            })()
+            .with_context(|| format!("icon at {} is invalid", icon.path))?;
+        }
+        Ok(())
+    }
+
+    /// Returns the contents of a web app manifest with the settings.
+    ///
+    /// # Errors
+    /// This function returns an error if an icon is not a supported image format
+    /// or the manifest cannot be serialized.
+    pub fn to_json(&self) -> Result<String> {
+        let icons = self
+            .icons
+            .iter()
+            .map(|icon| {
+                Ok(WebManifestIcon {
+                    src: icon.url(),
+                    sizes: &icon.sizes,
+                    mime: icon_mime(&icon.path)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        serde_json::to_string(&WebManifest {
+            name: &self.name,
+            short_name: self.short_name.as_deref(),
+            start_url: page_url(""),
+            theme_color: self.theme_color.as_deref(),
+            icons,
+        })
+        .context("failed to serialize web app manifest")
+    }
+
+    /// Writes the web app manifest to `output_dir` and copies its icons to the icons directory in `output_dir`,
+    /// recording them in `dependencies`.
+    ///
+    /// # Errors
+    /// This function returns an error if the manifest cannot be generated or a file cannot be written.
+    ///
+    /// # Panics
+    /// This function panics if an icon path has no file name, which [`Self::validate()`] rules out.
+    pub fn save(
+        &self,
+        output_dir: &Utf8Path,
+        dependencies: &mut DependencyGraph,
+        vfs: &dyn Vfs,
+    ) -> Result<()> {
+        let output_path = output_dir.join(OUTPUT_MANIFEST_FILE);
+        vfs.write(&output_path, self.to_json()?.as_bytes(), false)
+            .with_context(|| format!("failed to write web app manifest to {output_path}"))?;
+        // The manifest only lists icons by file name, so like robots.txt, it only depends on the config file
+        dependencies.add::<&Utf8Path>(OUTPUT_MANIFEST_FILE, []);
+
+        if self.icons.is_empty() {
+            return Ok(());
+        }
+        let icons_dir = output_dir.join(OUTPUT_ICONS_DIR);
+        vfs.create_dir_all(&icons_dir)
+            .with_context(|| format!("failed to create directory at {icons_dir}"))?;
+        for icon in &self.icons {
+            let file_name = icon
+                .path
+                .file_name()
+                .expect("icon path should include file name if validation was successful");
+            vfs.copy(&icon.path, &icons_dir.join(file_name))
+                .with_context(|| format!("failed to copy icon at {}", icon.path))?;
+            dependencies.add(&format!("{OUTPUT_ICONS_DIR}{file_name}"), [&*icon.path]);
+        }
+        Ok(())
+    }
+}
+
+impl ManifestIcon {
+    /// Returns the root-relative URL of the icon in the output icons directory.
+    #[must_use]
+    pub fn url(&self) -> String {
+        page_url(&format!(
+            "{OUTPUT_ICONS_DIR}{}",
+            self.path.file_name().unwrap_or_default()
+        ))
+    }
+}
+
+/// Returns the MIME type of an icon based on its file extension.
+fn icon_mime(path: &Utf8Path) -> Result<&'static str> {
+    let extension = path.extension().unwrap_or_default().to_ascii_lowercase();
+    Ok(match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        _ => bail!("icon must be a PNG, JPEG, WebP, SVG, or ICO file"),
+    })
+}
+
+/// Returns a Boolean indicating if a string is a hex color (e.g. `#1a2b3c`) or a color keyword (e.g. `navy`).
+fn is_color(color: &str) -> bool {
+    match color.strip_prefix('#') {
+        Some(hex) => {
+            matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => !color.is_empty() && color.chars().all(|c| c.is_ascii_alphabetic()),
+    }
+}
+
+/// Returns a Boolean indicating if a string is a valid value of an icon's `sizes`.
+fn is_icon_sizes(sizes: &str) -> bool {
+    sizes == "any"
+        || !sizes.is_empty()
+            && sizes.split(' ').all(|size| {
+                size.split_once('x').is_some_and(|(width, height)| {
+                    [width, height].iter().all(|dimension| {
+                        !dimension.is_empty()
+                            && !dimension.starts_with('0')
+                            && dimension.chars().all(|c| c.is_ascii_digit())
+                    })
+                })
+            })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ManifestConfig, ManifestIcon, is_color, is_icon_sizes};
+    use camino::Utf8Path;
+
+    #[test]
+    fn manifest() {
+        let config = ManifestConfig {
+            name: "Example site".into(),
+            short_name: Some("Example".into()),
+            theme_color: Some("#1a2b3c".into()),
+            icons: Box::new([
+                ManifestIcon {
+                    path: Utf8Path::new("assets/icon-192.png").into(),
+                    sizes: "192x192".into(),
+                },
+                ManifestIcon {
+                    path: Utf8Path::new("assets/icon.svg").into(),
+                    sizes: "any".into(),
+                },
+            ]),
+        };
+        assert_eq!(
+            config.to_json().unwrap(),
+            r##"{"name":"Example site","short_name":"Example","start_url":"/","theme_color":"#1a2b3c","icons":[{"src":"/icons/icon-192.png","sizes":"192x192","type":"image/png"},{"src":"/icons/icon.svg","sizes":"any","type":"image/svg+xml"}]}"##
+        );
+
+        assert!(is_color("#abc"));
+        assert!(is_color("rebeccapurple"));
+        assert!(!is_color("#abcde"));
+        assert!(!is_color("rgb(0, 0, 0)"));
+
+        assert!(is_icon_sizes("any"));
+        assert!(is_icon_sizes("16x16 32x32"));
+        assert!(!is_icon_sizes("16"));
+        assert!(!is_icon_sizes("0x16"));
+        assert!(!is_icon_sizes("16x16  32x32"));
+    }
+}
@@ -0,0 +1,127 @@
+//! Code for cleaning up input text before it is parsed.
+//!
+//! Text pasted from word processors or saved by some editors can contain byte order marks, CRLF line endings,
+//! and invisible control characters. Left alone, these can break YAML parsing of frontmatter
+//! or end up in the output verbatim.
+
+use std::fmt::{self, Display, Formatter};
+
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// Counts of the fixes made while normalizing input text.
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub struct TextFixes {
+    pub byte_order_mark: bool,
+    // Number of CRLF or lone CR line endings converted to LF
+    pub line_endings: usize,
+    // Number of control characters (other than tabs and line endings) removed
+    pub control_chars: usize,
+}
+
+impl TextFixes {
+    /// Checks if no fixes were made.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Display for TextFixes {
+    /// Formats a comma-separated list of the fixes made.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut fixes = Vec::with_capacity(3);
+        if self.byte_order_mark {
+            fixes.push("removed byte order mark".to_owned());
+        }
+        if self.line_endings > 0 {
+            fixes.push(format!(
+                "converted {} CRLF/CR line ending(s) to LF",
+                self.line_endings
+            ));
+        }
+        if self.control_chars > 0 {
+            fixes.push(format!(
+                "removed {} control character(s)",
+                self.control_chars
+            ));
+        }
+        write!(f, "{}", fixes.join(", "))
+    }
+}
+
+/// Removes a leading byte order mark, converts CRLF and lone CR line endings to LF,
+/// and removes control characters other than tabs and line feeds (including stray byte order marks).
+/// Returns the normalized text along with the fixes made.
+#[must_use]
+pub fn normalize_text(input: String) -> (String, TextFixes) {
+    let mut fixes = TextFixes::default();
+
+    let text = match input.strip_prefix(BYTE_ORDER_MARK) {
+        Some(text) => {
+            fixes.byte_order_mark = true;
+            text
+        }
+        None => &input,
+    };
+
+    // Avoid copying the text in the common case where it is already clean
+    if !text.chars().any(needs_fix) {
+        return if fixes.byte_order_mark {
+            (text.to_owned(), fixes)
+        } else {
+            (input, fixes)
+        };
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\r' => {
+                chars.next_if_eq(&'\n');
+                output.push('\n');
+                fixes.line_endings += 1;
+            }
+            c if needs_fix(c) => fixes.control_chars += 1,
+            c => output.push(c),
+        }
+    }
+
+    (output, fixes)
+}
+
+fn needs_fix(c: char) -> bool {
+    (c.is_control() && c != '\t' && c != '\n') || c == BYTE_ORDER_MARK
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TextFixes, normalize_text};
+
+    #[test]
+    fn normalize() {
+        let (text, fixes) = normalize_text("---\ntitle: a\n---\nb\tc\n".into());
+        assert_eq!(text, "---\ntitle: a\n---\nb\tc\n");
+        assert!(fixes.is_empty());
+
+        let (text, fixes) =
+            normalize_text("\u{feff}---\r\ntitle: a\r\n---\r\nb\u{0}c\u{feff}\u{85}\rd".into());
+        assert_eq!(text, "---\ntitle: a\n---\nbc\nd");
+        assert_eq!(
+            fixes,
+            TextFixes {
+                byte_order_mark: true,
+                line_endings: 4,
+                control_chars: 3,
+            }
+        );
+        assert_eq!(
+            fixes.to_string(),
+            "removed byte order mark, converted 4 CRLF/CR line ending(s) to LF, removed 3 control character(s)"
+        );
+
+        let (text, fixes) = normalize_text("\u{feff}a".into());
+        assert_eq!(text, "a");
+        assert!(fixes.byte_order_mark);
+    }
+}
@@ -0,0 +1,147 @@
+//! Code for enforcing a well-formed heading outline in articles.
+//!
+//! Article pages display the title in an `<h1>` element, so headings in article bodies should start at level 2
+//! and never skip a level (e.g. from `<h2>` to `<h4>`). Skipped levels confuse screen reader users
+//! navigating by heading and produce oddly nested tables of contents.
+
+use crate::title::demote_heading;
+use anyhow::{Result, bail};
+use pulldown_cmark::HeadingLevel;
+use serde::Deserialize;
+
+/// How to handle headings that don't fit a well-formed outline.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlinePolicy {
+    /// Leave headings as they are
+    #[default]
+    Off,
+    /// Change heading levels to fit the outline, keeping the nesting of headings
+    Adjust,
+    /// Fail the build, listing every heading that doesn't fit the outline
+    Error,
+}
+
+/// Tracks the headings of an article in order, checking or adjusting their levels according to a policy.
+pub struct HeadingOutline {
+    policy: OutlinePolicy,
+    // Original and adjusted levels of the headings containing the current position
+    sections: Vec<(HeadingLevel, HeadingLevel)>,
+    // Level of the last heading, as returned by `add_heading()`
+    last_level: HeadingLevel,
+    // Descriptions of headings that don't fit the outline
+    problems: Vec<String>,
+}
+
+impl HeadingOutline {
+    #[must_use]
+    pub fn new(policy: OutlinePolicy) -> Self {
+        Self {
+            policy,
+            sections: Vec::new(),
+            last_level: HeadingLevel::H1,
+            problems: Vec::new(),
+        }
+    }
+
+    /// Adds a heading found on the given line of the article, returning the level it should be rendered at.
+    pub fn add_heading(&mut self, level: HeadingLevel, line: usize) -> HeadingLevel {
+        let output_level = match self.policy {
+            OutlinePolicy::Off => level,
+            OutlinePolicy::Adjust => {
+                // A heading closes every section at its level or deeper, then nests within the remaining section
+                while self
+                    .sections
+                    .last()
+                    .is_some_and(|&(original, _)| original >= level)
+                {
+                    self.sections.pop();
+                }
+                let parent_level = self
+                    .sections
+                    .last()
+                    .map_or(HeadingLevel::H1, |&(_, adjusted)| adjusted);
+                let adjusted = demote_heading(parent_level);
+                self.sections.push((level, adjusted));
+                adjusted
+            }
+            OutlinePolicy::Error => {
+                if level == HeadingLevel::H1 {
+                    self.problems.push(format!(
+                        "line {line}: level-1 heading is reserved for the article title"
+                    ));
+                } else if level > demote_heading(self.last_level) {
+                    self.problems.push(format!(
+                        "line {line}: heading skips from level {} to level {}",
+                        self.last_level as usize, level as usize
+                    ));
+                }
+                level
+            }
+        };
+
+        self.last_level = output_level;
+        output_level
+    }
+
+    /// Returns the level of the last heading added, for closing it.
+    #[must_use]
+    pub fn last_level(&self) -> HeadingLevel {
+        self.last_level
+    }
+
+    /// Checks that every heading fits the outline.
+    ///
+    /// # Errors
+    /// This function returns an error listing the headings that don't fit the outline, if any.
+    pub fn finish(self) -> Result<()> {
+        if !self.problems.is_empty() {
+            bail!(
+                "found {} heading(s) not fitting the article outline: {}",
+                self.problems.len(),
+                self.problems.join("; ")
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HeadingOutline, OutlinePolicy};
+    use pulldown_cmark::HeadingLevel::{self, H1, H2, H3, H4, H5, H6};
+
+    fn levels(policy: OutlinePolicy, levels: &[HeadingLevel]) -> (Vec<HeadingLevel>, bool) {
+        let mut outline = HeadingOutline::new(policy);
+        let output = levels
+            .iter()
+            .enumerate()
+            .map(|(line, &level)| outline.add_heading(level, line + 1))
+            .collect();
+        (output, outline.finish().is_ok())
+    }
+
+    #[test]
+    fn adjust() {
+        assert_eq!(
+            levels(OutlinePolicy::Adjust, &[H1, H3, H3, H2, H1, H4]),
+            (vec![H2, H3, H3, H3, H2, H3], true)
+        );
+        assert_eq!(
+            levels(OutlinePolicy::Adjust, &[H2, H3, H4, H6, H2]),
+            (vec![H2, H3, H4, H5, H2], true)
+        );
+    }
+
+    #[test]
+    fn error() {
+        assert_eq!(
+            levels(OutlinePolicy::Error, &[H2, H3, H2, H3, H4]),
+            (vec![H2, H3, H2, H3, H4], true)
+        );
+        assert!(!levels(OutlinePolicy::Error, &[H1]).1);
+        assert!(!levels(OutlinePolicy::Error, &[H3]).1);
+        assert!(!levels(OutlinePolicy::Error, &[H2, H4]).1);
+        assert!(levels(OutlinePolicy::Off, &[H1, H4]).1);
+    }
+}
@@ -0,0 +1,257 @@
+//! Code for inlining partial files included by HTML templates.
+
+use crate::vfs::Vfs;
+use anyhow::{Context, Result, bail};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use std::collections::BTreeSet;
+
+const INCLUDE_TAG_START: &str = "<include";
+const INCLUDE_COMMENT_START: &str = "<!-- include:";
+const COMMENT_END: &str = "-->";
+
+/// The text of an HTML template, along with the path it was read from.
+/// Partials included by the template are resolved relative to the template's path and read from `vfs`.
+#[derive(Clone, Copy)]
+pub struct Template<'a> {
+    pub text: &'a str,
+    pub path: &'a Utf8Path,
+    pub vfs: &'a dyn Vfs,
+}
+
+/// Replaces every `<include src="...">` tag and `<!-- include: ... -->` comment in a template
+/// with the contents of the partial file it refers to.
+/// Partials can include other partials; every included path is resolved relative to the file including it.
+/// The normalized paths of all included partials are added to `partials`.
+///
+/// # Errors
+/// This function returns an error if:
+/// - an `<include>` tag or include comment is malformed
+/// - a partial file cannot be read
+/// - partials include each other in a cycle
+pub(crate) fn resolve_includes(
+    template: Template<'_>,
+    partials: &mut BTreeSet<Utf8PathBuf>,
+) -> Result<String> {
+    let mut stack = vec![normalize_path(template.path)];
+    inline_partials(template.text, template.vfs, &mut stack, partials)
+}
+
+/// Resolves `.` and `..` components of a path lexically, so different paths to the same partial compare equal.
+fn normalize_path(path: &Utf8Path) -> Utf8PathBuf {
+    let mut normalized = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir if normalized.file_name().is_some() => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Inlines the partials included by `text`, which was read from the last path in `stack`.
+/// The stack contains the chain of files that led to `text` being included, for detecting cycles.
+fn inline_partials(
+    text: &str,
+    vfs: &dyn Vfs,
+    stack: &mut Vec<Utf8PathBuf>,
+    partials: &mut BTreeSet<Utf8PathBuf>,
+) -> Result<String> {
+    let path = stack.last().expect("stack should not be empty").clone();
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = [INCLUDE_TAG_START, INCLUDE_COMMENT_START]
+        .into_iter()
+        .filter_map(|marker| rest.find(marker))
+        .min()
+    {
+        output.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let src = if let Some(comment) = rest.strip_prefix(INCLUDE_COMMENT_START) {
+            let comment_end = comment
+                .find(COMMENT_END)
+                .with_context(|| format!("unterminated include comment in {path}"))?;
+            let src = comment[..comment_end].trim();
+            if src.is_empty() {
+                bail!("include comment in {path} has no path");
+            }
+            rest = &comment[comment_end + COMMENT_END.len()..];
+            src
+        } else {
+            // Skip over other elements whose names start with "include"
+            let is_include_tag = rest[INCLUDE_TAG_START.len()..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_whitespace() || c == '>' || c == '/');
+            if !is_include_tag {
+                output.push_str(INCLUDE_TAG_START);
+                rest = &rest[INCLUDE_TAG_START.len()..];
+                continue;
+            }
+
+            let tag_end = rest
+                .find('>')
+                .with_context(|| format!("unterminated `<include>` tag in {path}"))?;
+            let tag = &rest[..=tag_end];
+            rest = &rest[tag_end + 1..];
+
+            parse_include_src(tag).with_context(|| format!("invalid tag `{tag}` in {path}"))?
+        };
+        let partial_path = normalize_path(&path.parent().unwrap_or(Utf8Path::new("")).join(src));
+
+        if let Some(cycle_start) = stack.iter().position(|file| *file == partial_path) {
+            let cycle: Vec<_> = stack[cycle_start..]
+                .iter()
+                .chain([&partial_path])
+                .map(|file| file.as_str())
+                .collect();
+            bail!(
+                "partials include each other in a cycle: {}",
+                cycle.join(" -> ")
+            );
+        }
+
+        let partial_text = vfs.read_to_string(&partial_path).with_context(|| {
+            format!("failed to read partial at {partial_path} included by {path}")
+        })?;
+        stack.push(partial_path);
+        output.push_str(&inline_partials(&partial_text, vfs, stack, partials)?);
+        partials.extend(stack.pop());
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Extracts the `src` attribute value from an `<include src="...">` tag.
+fn parse_include_src(tag: &str) -> Result<&str> {
+    let attrs = tag
+        .trim_start_matches(INCLUDE_TAG_START)
+        .trim_end_matches('>')
+        .trim_end_matches('/')
+        .trim();
+
+    let Some(value) = attrs.strip_prefix("src=") else {
+        bail!("expected a `src` attribute as the only attribute");
+    };
+    let Some(quote) = value.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+        bail!("expected the `src` attribute value to be quoted");
+    };
+
+    match value[1..].split_once(quote) {
+        Some((src, "")) if !src.is_empty() => Ok(src),
+        Some((_, "")) => bail!("expected the `src` attribute value to not be empty"),
+        Some(_) => bail!("expected a `src` attribute as the only attribute"),
+        None => bail!("expected the `src` attribute value to be closed with a quote"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Template, normalize_path, parse_include_src, resolve_includes};
+    use crate::vfs::MemoryFs;
+    use camino::Utf8Path;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn include_src() {
+        assert_eq!(
+            parse_include_src(r#"<include src="a.html">"#).unwrap(),
+            "a.html"
+        );
+        assert_eq!(
+            parse_include_src("<include  src='partials/a.html' />").unwrap(),
+            "partials/a.html"
+        );
+        assert!(parse_include_src("<include>").is_err());
+        assert!(parse_include_src(r#"<include src="">"#).is_err());
+        assert!(parse_include_src(r#"<include src="a.html" id="b">"#).is_err());
+        assert!(parse_include_src("<include src=a.html>").is_err());
+    }
+
+    #[test]
+    fn includes() {
+        let vfs = MemoryFs::new()
+            .with_file(
+                "/site/partials/footer.html",
+                r#"<footer><include src="../partials/./links.html"></footer>"#,
+            )
+            .with_file("/site/partials/links.html", "<a href=\"/\">Home</a>")
+            .with_file("/site/partials/a.html", r#"<include src="b.html">"#)
+            .with_file("/site/partials/b.html", r#"<include src="a.html">"#);
+
+        let template_path = Utf8Path::new("/site/body.html");
+        let mut partials = BTreeSet::new();
+        let html = resolve_includes(
+            Template {
+                text: r#"<main></main><include-list></include-list><include src="partials/footer.html"><include src="partials/footer.html">"#,
+                path: template_path,
+                vfs: &vfs,
+            },
+            &mut partials,
+        )
+        .unwrap();
+        assert_eq!(
+            html,
+            "<main></main><include-list></include-list>\
+            <footer><a href=\"/\">Home</a></footer><footer><a href=\"/\">Home</a></footer>"
+        );
+        assert_eq!(partials.len(), 2);
+        assert!(partials.contains(Utf8Path::new("/site/partials/links.html")));
+
+        // Partials can also be included with comments, and other comments are left as is
+        assert_eq!(
+            resolve_includes(
+                Template {
+                    text: "<!-- nav --><!-- include: partials/links.html --><!-- include:partials/links.html-->",
+                    path: template_path,
+                    vfs: &vfs,
+                },
+                &mut partials,
+            )
+            .unwrap(),
+            "<!-- nav --><a href=\"/\">Home</a><a href=\"/\">Home</a>"
+        );
+        for text in ["<!-- include: -->", "<!-- include: partials/links.html"] {
+            assert!(
+                resolve_includes(
+                    Template {
+                        text,
+                        path: template_path,
+                        vfs: &vfs,
+                    },
+                    &mut partials,
+                )
+                .is_err()
+            );
+        }
+
+        let error = resolve_includes(
+            Template {
+                text: r#"<include src="partials/a.html">"#,
+                path: template_path,
+                vfs: &vfs,
+            },
+            &mut partials,
+        )
+        .unwrap_err();
+        assert!(format!("{error:?}").contains("cycle"));
+
+        assert!(
+            resolve_includes(
+                Template {
+                    text: r#"<include src="partials/missing.html">"#,
+                    path: template_path,
+                    vfs: &vfs,
+                },
+                &mut partials,
+            )
+            .is_err()
+        );
+        assert_eq!(normalize_path(Utf8Path::new("/a/./b/../c")), "/a/c");
+    }
+}
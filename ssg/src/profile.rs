@@ -0,0 +1,222 @@
+//! Code for measuring how long each stage of building an article takes.
+//!
+//! Profiles are written in the Chrome trace event format, which can be viewed as a flame graph
+//! in tools like Perfetto (<https://ui.perfetto.dev>) and speedscope (<https://www.speedscope.app>).
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    fmt::{self, Display, Formatter},
+    time::{Duration, Instant},
+};
+
+/// A stage of building an article.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Parsing Markdown and processing everything not covered by other stages
+    Parse,
+    /// Converting LaTeX to HTML
+    Math,
+    /// Highlighting code
+    Highlight,
+    /// Converting images to AVIF
+    ImageEncode,
+    /// Serializing the article as a complete HTML page
+    Serialize,
+    /// Writing the article page to the output directory
+    Write,
+}
+
+impl Stage {
+    const ALL: [Self; 6] = [
+        Self::Parse,
+        Self::Math,
+        Self::Highlight,
+        Self::ImageEncode,
+        Self::Serialize,
+        Self::Write,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Parse => "parse",
+            Self::Math => "math",
+            Self::Highlight => "highlight",
+            Self::ImageEncode => "image encode",
+            Self::Serialize => "serialize",
+            Self::Write => "write",
+        }
+    }
+}
+
+/// Timings of the stages of building one article.
+/// Time not spent in any timed stage is attributed to parsing.
+pub struct ArticleProfile {
+    start: Instant,
+    end: Option<Instant>,
+    spans: Vec<(Stage, Instant, Duration)>,
+}
+
+impl ArticleProfile {
+    /// Starts profiling an article build.
+    #[must_use]
+    pub fn start() -> Self {
+        Self {
+            start: Instant::now(),
+            end: None,
+            spans: Vec::new(),
+        }
+    }
+
+    /// Runs a function, attributing the time it takes to a stage.
+    pub fn time<T>(&mut self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let output = f();
+        self.spans.push((stage, start, start.elapsed()));
+        output
+    }
+
+    /// Stops profiling the article build.
+    pub fn finish(&mut self) {
+        self.end = Some(Instant::now());
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.end
+            .map_or_else(|| self.start.elapsed(), |end| end - self.start)
+    }
+
+    /// Returns the total time spent in a stage.
+    fn total(&self, stage: Stage) -> Duration {
+        if stage == Stage::Parse {
+            let timed: Duration = self.spans.iter().map(|(_, _, duration)| *duration).sum();
+            self.elapsed().saturating_sub(timed)
+        } else {
+            self.spans
+                .iter()
+                .filter(|(span_stage, ..)| *span_stage == stage)
+                .map(|(_, _, duration)| *duration)
+                .sum()
+        }
+    }
+}
+
+/// Timings of every article built.
+pub struct BuildProfile {
+    epoch: Instant,
+    articles: Vec<(Box<str>, ArticleProfile)>,
+}
+
+/// An event in the Chrome trace event format
+#[derive(Serialize)]
+struct TraceEvent<'a> {
+    name: &'a str,
+    cat: &'static str,
+    ph: &'static str,
+    // Start time and duration in microseconds
+    ts: u128,
+    dur: u128,
+    pid: u32,
+    tid: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Trace<'a> {
+    trace_events: Vec<TraceEvent<'a>>,
+    display_time_unit: &'static str,
+}
+
+impl BuildProfile {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            articles: Vec::new(),
+        }
+    }
+
+    pub fn add_article(&mut self, slug: &str, profile: ArticleProfile) {
+        self.articles.push((slug.into(), profile));
+    }
+
+    /// Serializes the profile as a trace in the Chrome trace event format.
+    /// Every article is a span containing spans for its timed stages.
+    ///
+    /// # Errors
+    /// This function returns an error if the trace cannot be serialized.
+    pub fn to_trace_json(&self) -> Result<String> {
+        let mut trace_events = Vec::new();
+
+        for (slug, profile) in &self.articles {
+            trace_events.push(TraceEvent {
+                name: slug,
+                cat: "article",
+                ph: "X",
+                ts: (profile.start - self.epoch).as_micros(),
+                dur: profile.elapsed().as_micros(),
+                pid: 1,
+                tid: 1,
+            });
+            for (stage, start, duration) in &profile.spans {
+                trace_events.push(TraceEvent {
+                    name: stage.name(),
+                    cat: "stage",
+                    ph: "X",
+                    ts: (*start - self.epoch).as_micros(),
+                    dur: duration.as_micros(),
+                    pid: 1,
+                    tid: 1,
+                });
+            }
+        }
+
+        serde_json::to_string(&Trace {
+            trace_events,
+            display_time_unit: "ms",
+        })
+        .context("failed to serialize build profile")
+    }
+}
+
+impl Display for BuildProfile {
+    /// Formats the total time spent in each stage for every article, with one line per article.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Build profile:")?;
+
+        for (slug, profile) in &self.articles {
+            write!(f, "\n  {slug}: {:.1?} total", profile.elapsed())?;
+            for stage in Stage::ALL {
+                write!(f, ", {} {:.1?}", stage.name(), profile.total(stage))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ArticleProfile, BuildProfile, Stage};
+    use std::{thread::sleep, time::Duration};
+
+    #[test]
+    fn stage_totals() {
+        let mut profile = ArticleProfile::start();
+        profile.time(Stage::Math, || sleep(Duration::from_millis(2)));
+        profile.time(Stage::Math, || sleep(Duration::from_millis(2)));
+        assert_eq!(profile.time(Stage::Write, || 1), 1);
+        profile.finish();
+
+        assert!(profile.total(Stage::Math) >= Duration::from_millis(4));
+        assert_eq!(profile.total(Stage::ImageEncode), Duration::ZERO);
+        assert!(profile.total(Stage::Parse) < profile.elapsed());
+
+        let mut build = BuildProfile::new();
+        build.add_article("a", profile);
+        let trace = build.to_trace_json().unwrap();
+        assert!(trace.starts_with(r#"{"traceEvents":[{"name":"a","cat":"article","ph":"X""#));
+        assert_eq!(trace.matches(r#""name":"math""#).count(), 2);
+        assert!(build.to_string().starts_with("Build profile:\n  a: "));
+    }
+}
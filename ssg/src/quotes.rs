@@ -0,0 +1,131 @@
+//! Code for adapting curly quotes from smart punctuation to the conventions of a locale.
+
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// Locale whose quotation mark conventions are used for smart punctuation.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(test, derive(Debug))]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteLocale {
+    /// “English” and ‘English’
+    #[default]
+    En,
+    /// „German“ and ‚German‘
+    De,
+    /// « French » and ‹ French ›
+    Fr,
+}
+
+/// Converts the English-style curly quotes produced by smart punctuation to those of another locale.
+///
+/// Closing single quotes and apostrophes are the same character in English,
+/// so the transformer tracks whether a single quote is open across the text of a block.
+#[derive(Clone, Copy)]
+pub struct QuoteTransformer {
+    locale: QuoteLocale,
+    is_in_single_quote: bool,
+}
+
+impl QuoteTransformer {
+    #[must_use]
+    pub fn new(locale: QuoteLocale) -> Self {
+        Self {
+            locale,
+            is_in_single_quote: false,
+        }
+    }
+
+    /// Forgets any open single quote. This should be called at the end of every block (e.g. a paragraph).
+    pub fn reset(&mut self) {
+        self.is_in_single_quote = false;
+    }
+
+    /// Replaces the curly quotes in a piece of text.
+    /// The text is borrowed unchanged if the locale is English or the text contains no curly quotes.
+    pub fn transform<'a>(&mut self, text: &'a str) -> Cow<'a, str> {
+        if self.locale == QuoteLocale::En || !text.contains(['‘', '’', '“', '”']) {
+            return Cow::Borrowed(text);
+        }
+
+        let mut output = String::with_capacity(text.len() + 8);
+        let mut chars = text.chars().peekable();
+        let mut prev = None;
+
+        while let Some(c) = chars.next() {
+            match c {
+                '“' => output.push_str(self.marks().0),
+                '”' => output.push_str(self.marks().1),
+                '‘' => {
+                    self.is_in_single_quote = true;
+                    output.push_str(self.marks().2);
+                }
+                // An apostrophe within a word (e.g. "don’t") never closes a quote
+                '’' if self.is_in_single_quote
+                    && !(prev.is_some_and(char::is_alphanumeric)
+                        && chars.peek().copied().is_some_and(char::is_alphanumeric)) =>
+                {
+                    self.is_in_single_quote = false;
+                    output.push_str(self.marks().3);
+                }
+                _ => output.push(c),
+            }
+            prev = Some(c);
+        }
+
+        Cow::Owned(output)
+    }
+
+    /// Returns the opening double, closing double, opening single, and closing single quotes of the locale.
+    fn marks(self) -> (&'static str, &'static str, &'static str, &'static str) {
+        match self.locale {
+            QuoteLocale::En => ("“", "”", "‘", "’"),
+            QuoteLocale::De => ("„", "“", "‚", "‘"),
+            // Guillemets are separated from their contents by narrow no-break spaces
+            QuoteLocale::Fr => ("«\u{202F}", "\u{202F}»", "‹\u{202F}", "\u{202F}›"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{QuoteLocale, QuoteTransformer};
+
+    #[test]
+    fn english() {
+        let mut transformer = QuoteTransformer::new(QuoteLocale::En);
+        assert_eq!(transformer.transform("“a” ‘b’"), "“a” ‘b’");
+    }
+
+    #[test]
+    fn german() {
+        let mut transformer = QuoteTransformer::new(QuoteLocale::De);
+        assert_eq!(
+            transformer.transform("Er sagte “Hallo” und ‘gib’s mir’."),
+            "Er sagte „Hallo“ und ‚gib’s mir‘."
+        );
+        // Apostrophes outside of single quotes are kept
+        assert_eq!(transformer.transform("Andreas’ Buch"), "Andreas’ Buch");
+    }
+
+    #[test]
+    fn french() {
+        let mut transformer = QuoteTransformer::new(QuoteLocale::Fr);
+        assert_eq!(
+            transformer.transform("“Bonjour” et ‘l’ami’"),
+            "«\u{202F}Bonjour\u{202F}» et ‹\u{202F}l’ami\u{202F}›"
+        );
+    }
+
+    #[test]
+    fn across_texts() {
+        // Quotes can span multiple pieces of text, e.g. around emphasized words
+        let mut transformer = QuoteTransformer::new(QuoteLocale::De);
+        assert_eq!(transformer.transform("‘a "), "‚a ");
+        assert_eq!(transformer.transform("b’"), "b‘");
+
+        transformer.transform("‘a");
+        transformer.reset();
+        assert_eq!(transformer.transform("b’"), "b’");
+    }
+}
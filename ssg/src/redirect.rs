@@ -0,0 +1,200 @@
+//! Code for redirecting old URLs (e.g. of restructured pages or renamed articles) to current URLs.
+//!
+//! Redirects are either listed in a `_redirects` file, which hosts like Netlify and Cloudflare Pages read,
+//! or emitted as stub pages that redirect with a `<meta http-equiv="refresh">` element, which work on any host.
+
+use crate::component::escape_html;
+use anyhow::{Result, bail};
+use camino::Utf8Path;
+use serde::Deserialize;
+use std::fmt::Write;
+
+// Path of the redirects file in the output directory, as expected by hosts that support one
+pub const OUTPUT_REDIRECTS_FILE: &str = "_redirects";
+
+// HTTP status codes that redirects can have
+const REDIRECT_STATUSES: [u16; 5] = [301, 302, 303, 307, 308];
+
+/// A redirect from an old URL of the site to another URL.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Redirect {
+    // Root-relative path of the old URL (e.g. `/blog/post/`)
+    pub from: Box<str>,
+    // Root-relative path or absolute URL to redirect to
+    pub to: Box<str>,
+    // HTTP status code of the redirect, for hosts that read a redirects file
+    #[serde(default = "default_status")]
+    pub status: u16,
+}
+
+const fn default_status() -> u16 {
+    301
+}
+
+/// How redirects are emitted in the output directory.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedirectStyle {
+    /// A stub page for every redirect, which redirects with a `<meta http-equiv="refresh">` element.
+    /// These work on any host, but are always served with a 200 status code.
+    #[default]
+    Html,
+    /// A `_redirects` file in Netlify's format.
+    Netlify,
+    /// A `_redirects` file in Cloudflare Pages' format, which is a subset of Netlify's.
+    Cloudflare,
+}
+
+impl Redirect {
+    /// Initializes a permanent redirect, like the ones from article aliases to articles.
+    #[must_use]
+    pub fn permanent(from: &str, to: String) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            status: default_status(),
+        }
+    }
+
+    /// Validates the redirect.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the source path is invalid (see [`validate_redirect_path()`])
+    /// - the target is neither a root-relative path nor an absolute HTTP(S) URL, or contains whitespace
+    /// - the status code is not a redirect status code
+    pub fn validate(&self) -> Result<()> {
+        validate_redirect_path(&self.from)?;
+
+        let is_root_relative = self.to.starts_with('/') && !self.to.starts_with("//");
+        if !is_root_relative
+            && !["https://", "http://"]
+                .iter()
+                .any(|scheme| self.to.starts_with(scheme))
+        {
+            bail!(
+                "redirect target must be a root-relative path or an absolute HTTP(S) URL (got \"{}\")",
+                self.to
+            );
+        } else if self.to.contains(char::is_whitespace) {
+            bail!(
+                "redirect target cannot contain whitespace (got \"{}\")",
+                self.to
+            );
+        } else if !REDIRECT_STATUSES.contains(&self.status) {
+            bail!(
+                "redirect status must be one of 301, 302, 303, 307, and 308 (got {})",
+                self.status
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the path (relative to the output directory) of the page served at the redirect's source path,
+    /// which is where a stub page is emitted and which would shadow the redirect if the site generated it.
+    #[must_use]
+    pub fn page_path(&self) -> String {
+        page_path(&self.from)
+    }
+
+    /// Returns a stub page that redirects to the redirect's target.
+    #[must_use]
+    pub fn page_html(&self) -> String {
+        let to = escape_html(&self.to);
+        format!(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Redirecting\u{2026}</title>\
+            <meta name=\"robots\" content=\"noindex\"><link rel=\"canonical\" href=\"{to}\">\
+            <meta http-equiv=\"refresh\" content=\"0; url={to}\"></head>\
+            <body><p>This page has moved to <a href=\"{to}\">{to}</a>.</p></body></html>"
+        )
+    }
+}
+
+/// Checks that a path can be redirected from.
+///
+/// # Errors
+/// This function returns an error if the path is not root-relative, or contains whitespace,
+/// wildcards (`*`), query strings, or fragments, which not every redirect style supports.
+pub(crate) fn validate_redirect_path(path: &str) -> Result<()> {
+    if !path.starts_with('/') || path.starts_with("//") {
+        bail!("redirect source must be a root-relative path (got \"{path}\")");
+    } else if path.contains(|c: char| c.is_whitespace() || matches!(c, '*' | '?' | '#')) {
+        bail!(
+            "redirect source cannot contain whitespace, wildcards, query strings, or fragments (got \"{path}\")"
+        );
+    }
+    Ok(())
+}
+
+/// Returns the path (relative to the output directory) of the page that hosts serve at a root-relative path.
+pub(crate) fn page_path(path: &str) -> String {
+    let path = path.trim_start_matches('/');
+    if path.is_empty() || path.ends_with('/') {
+        format!("{path}index.html")
+    } else if Utf8Path::new(path)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("html"))
+    {
+        path.to_owned()
+    } else {
+        format!("{path}/index.html")
+    }
+}
+
+/// Returns the contents of a `_redirects` file listing the redirects, one per line.
+#[must_use]
+pub fn redirects_file<'a>(redirects: impl IntoIterator<Item = &'a Redirect>) -> String {
+    let mut file = String::new();
+    for Redirect { from, to, status } in redirects {
+        writeln!(file, "{from} {to} {status}").expect("writing to a string should succeed");
+    }
+    file
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Redirect, redirects_file, validate_redirect_path};
+
+    #[test]
+    fn redirects() {
+        let redirect = |from: &str, to: &str, status| Redirect {
+            from: from.into(),
+            to: to.into(),
+            status,
+        };
+
+        assert!(redirect("/old/", "/new/", 301).validate().is_ok());
+        assert!(
+            redirect("/old", "https://example.com/", 308)
+                .validate()
+                .is_ok()
+        );
+        assert!(redirect("old/", "/new/", 301).validate().is_err());
+        assert!(redirect("/old/", "new/", 301).validate().is_err());
+        assert!(redirect("/old/", "//example.com/", 301).validate().is_err());
+        assert!(redirect("/old/", "/new/", 200).validate().is_err());
+        assert!(validate_redirect_path("/blog/*").is_err());
+        assert!(validate_redirect_path("/a b/").is_err());
+
+        assert_eq!(redirect("/", "/a/", 301).page_path(), "index.html");
+        assert_eq!(redirect("/old/", "/a/", 301).page_path(), "old/index.html");
+        assert_eq!(redirect("/old", "/a/", 301).page_path(), "old/index.html");
+        assert_eq!(redirect("/old.html", "/a/", 301).page_path(), "old.html");
+
+        let html = redirect("/old/", "/new/?a=1&b=2", 301).page_html();
+        assert!(
+            html.contains("<meta http-equiv=\"refresh\" content=\"0; url=/new/?a=1&amp;b=2\">")
+        );
+        assert!(html.contains("<link rel=\"canonical\" href=\"/new/?a=1&amp;b=2\">"));
+
+        assert_eq!(
+            redirects_file(&[
+                redirect("/old/", "/new/", 301),
+                Redirect::permanent("/a", "/writing/a/".into()),
+                redirect("/b", "https://example.com/", 302),
+            ]),
+            "/old/ /new/ 301\n/a /writing/a/ 301\n/b https://example.com/ 302\n"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+//! Code for summarizing the contents of a site build, for humans and for tools.
+
+use crate::{builder::KatexCssLoading, cache::CacheStats};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+};
+
+/// Counts of the content in an article that affect the weight of its page.
+#[derive(Serialize, Default, Clone)]
+pub struct ArticleMetrics {
+    pub words: usize,
+    pub math_expressions: usize,
+    // Number of code blocks for each language; code blocks without a language are counted under "plain"
+    pub code_blocks: BTreeMap<Box<str>, usize>,
+    pub images_converted: usize,
+    pub images_copied: usize,
+    // Total size of image files written to the output directory
+    pub image_bytes: u64,
+    pub footnotes: usize,
+}
+
+impl ArticleMetrics {
+    /// Records a code block with an optional language.
+    pub fn add_code_block(&mut self, language: Option<&str>) {
+        let language = language.filter(|lang| !lang.is_empty()).unwrap_or("plain");
+        *self.code_blocks.entry(language.into()).or_default() += 1;
+    }
+
+    /// Returns the estimated time to read the article in whole minutes (at least 1), at a reading speed in words per minute.
+    #[must_use]
+    pub fn reading_minutes(&self, words_per_minute: u32) -> usize {
+        self.words.div_ceil(words_per_minute as usize).max(1)
+    }
+}
+
+#[derive(Serialize)]
+pub struct BuildReport {
+    // Absent if math doesn't need the KaTeX stylesheet
+    katex_css_loading: Option<KatexCssLoading>,
+    articles: Vec<ArticleReport>,
+    // Hit statistics of the fragment page cache; absent if no cache directory is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fragment_cache: Option<CacheStats>,
+    // Hit statistics of the math render cache; absent if no cache directory is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    math_cache: Option<CacheStats>,
+}
+
+#[derive(Serialize)]
+struct ArticleReport {
+    slug: Box<str>,
+    title: Box<str>,
+    #[serde(flatten)]
+    metrics: ArticleMetrics,
+}
+
+impl BuildReport {
+    #[must_use]
+    pub fn new(katex_css_loading: Option<KatexCssLoading>) -> Self {
+        Self {
+            katex_css_loading,
+            articles: Vec::new(),
+            fragment_cache: None,
+            math_cache: None,
+        }
+    }
+
+    pub fn set_fragment_cache(&mut self, stats: CacheStats) {
+        self.fragment_cache = Some(stats);
+    }
+
+    pub fn set_math_cache(&mut self, stats: CacheStats) {
+        self.math_cache = Some(stats);
+    }
+
+    pub fn add_article(&mut self, slug: &str, title: &str, metrics: ArticleMetrics) {
+        self.articles.push(ArticleReport {
+            slug: slug.into(),
+            title: title.into(),
+            metrics,
+        });
+    }
+
+    /// Serializes the report as a JSON manifest.
+    ///
+    /// # Errors
+    /// This function returns an error if the report cannot be serialized.
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("failed to serialize build report")
+    }
+}
+
+impl Display for BuildReport {
+    /// Formats the report as a human-readable summary, with one line per article.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Built {} article(s)", self.articles.len())?;
+
+        for ArticleReport { slug, metrics, .. } in &self.articles {
+            write!(
+                f,
+                "  {slug}: {} word(s), {} math expression(s), ",
+                metrics.words, metrics.math_expressions
+            )?;
+
+            let code_blocks: usize = metrics.code_blocks.values().sum();
+            write!(f, "{code_blocks} code block(s)")?;
+            if code_blocks > 0 {
+                let languages: Vec<_> = metrics
+                    .code_blocks
+                    .iter()
+                    .map(|(lang, count)| format!("{lang} {count}"))
+                    .collect();
+                write!(f, " ({})", languages.join(", "))?;
+            }
+
+            writeln!(
+                f,
+                ", {} image(s) ({} converted, {} copied; {}), {} footnote(s)",
+                metrics.images_converted + metrics.images_copied,
+                metrics.images_converted,
+                metrics.images_copied,
+                format_bytes(metrics.image_bytes),
+                metrics.footnotes
+            )?;
+        }
+
+        let math_pages = self
+            .articles
+            .iter()
+            .filter(|article| article.metrics.math_expressions > 0)
+            .count();
+
+        if let Some(stats) = &self.fragment_cache {
+            writeln!(f, "Fragment cache: {stats}")?;
+        }
+        if let Some(stats) = &self.math_cache {
+            writeln!(f, "Math cache: {stats}")?;
+        }
+
+        match self.katex_css_loading {
+            Some(KatexCssLoading::Blocking) => write!(
+                f,
+                "KaTeX stylesheet: render-blocking on {math_pages} article(s) with math \
+                (set `katex_css_loading = \"deferred\"` to display pages before it loads)"
+            ),
+            Some(KatexCssLoading::Deferred) => write!(
+                f,
+                "KaTeX stylesheet: deferred on {math_pages} article(s) with math \
+                (math may briefly display without styles)"
+            ),
+            None => write!(
+                f,
+                "KaTeX stylesheet: not needed on {math_pages} article(s) with math (math is rendered as MathML only)"
+            ),
+        }
+    }
+}
+
+#[expect(
+    clippy::cast_precision_loss,
+    reason = "sizes are only displayed approximately"
+)]
+fn format_bytes(bytes: u64) -> String {
+    const KIB: u64 = 1024;
+    const MIB: u64 = 1024 * KIB;
+
+    if bytes >= MIB {
+        format!("{:.1} MiB", bytes as f64 / MIB as f64)
+    } else if bytes >= KIB {
+        format!("{:.1} KiB", bytes as f64 / KIB as f64)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ArticleMetrics, BuildReport, format_bytes};
+    use crate::builder::KatexCssLoading;
+
+    #[test]
+    fn reading_minutes() {
+        let metrics = |words| ArticleMetrics {
+            words,
+            ..ArticleMetrics::default()
+        };
+        assert_eq!(metrics(0).reading_minutes(200), 1);
+        assert_eq!(metrics(200).reading_minutes(200), 1);
+        assert_eq!(metrics(201).reading_minutes(200), 2);
+        assert_eq!(metrics(1000).reading_minutes(250), 4);
+    }
+
+    #[test]
+    fn bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MiB");
+    }
+
+    #[test]
+    fn report() {
+        let mut metrics = ArticleMetrics {
+            words: 100,
+            math_expressions: 2,
+            ..ArticleMetrics::default()
+        };
+        metrics.add_code_block(Some("rust"));
+        metrics.add_code_block(Some("rust"));
+        metrics.add_code_block(None);
+
+        let mut report = BuildReport::new(Some(KatexCssLoading::Blocking));
+        report.add_article("abc", "ABC", metrics);
+
+        assert_eq!(
+            report.to_string(),
+            "Built 1 article(s)\n  abc: 100 word(s), 2 math expression(s), 3 code block(s) (plain 1, rust 2), \
+            0 image(s) (0 converted, 0 copied; 0 B), 0 footnote(s)\n\
+            KaTeX stylesheet: render-blocking on 1 article(s) with math \
+            (set `katex_css_loading = \"deferred\"` to display pages before it loads)"
+        );
+
+        let json = report.to_json().unwrap();
+        assert!(json.contains(r#""katex_css_loading": "blocking""#));
+        assert!(json.contains(r#""rust": 2"#));
+        assert!(json.contains(r#""slug": "abc""#));
+    }
+}
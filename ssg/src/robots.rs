@@ -0,0 +1,104 @@
+//! Code for generating a `robots.txt` file, which tells web crawlers which paths of the site they may visit.
+
+use anyhow::{Result, bail};
+use serde::Deserialize;
+use std::fmt::Write;
+
+// Path of the robots file in the output directory, where crawlers look for it
+pub const OUTPUT_ROBOTS_FILE: &str = "robots.txt";
+
+/// Rules for web crawlers, which apply to every crawler.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RobotsConfig {
+    // Root-relative paths (or path prefixes) that crawlers may visit, overriding `disallow` for more specific paths
+    #[serde(default)]
+    pub allow: Box<[Box<str>]>,
+    // Root-relative paths (or path prefixes) that crawlers should not visit
+    #[serde(default)]
+    pub disallow: Box<[Box<str>]>,
+    // Absolute URL of the site's sitemap
+    #[serde(default)]
+    pub sitemap: Option<Box<str>>,
+}
+
+impl RobotsConfig {
+    /// Validates the rules.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - a path is not root-relative or contains whitespace
+    /// - the sitemap URL is not an absolute HTTP(S) URL or contains whitespace
+    pub fn validate(&self) -> Result<()> {
+        for path in self.allow.iter().chain(&self.disallow) {
+            if !path.starts_with('/') {
+                bail!("robots path must be root-relative (got \"{path}\")");
+            } else if path.contains(char::is_whitespace) {
+                bail!("robots path cannot contain whitespace (got \"{path}\")");
+            }
+        }
+
+        if let Some(sitemap) = &self.sitemap
+            && (!["https://", "http://"]
+                .iter()
+                .any(|scheme| sitemap.starts_with(scheme))
+                || sitemap.contains(char::is_whitespace))
+        {
+            bail!("sitemap must be an absolute HTTP(S) URL (got \"{sitemap}\")");
+        }
+        Ok(())
+    }
+
+    /// Returns the contents of a `robots.txt` file with the rules.
+    #[must_use]
+    pub fn robots_txt(&self) -> String {
+        let mut file = String::from("User-agent: *\n");
+        for path in &self.allow {
+            writeln!(file, "Allow: {path}").expect("writing to a string should succeed");
+        }
+        for path in &self.disallow {
+            writeln!(file, "Disallow: {path}").expect("writing to a string should succeed");
+        }
+        // A group needs at least one rule, and an empty rule disallows nothing
+        if self.allow.is_empty() && self.disallow.is_empty() {
+            file.push_str("Disallow:\n");
+        }
+        if let Some(sitemap) = &self.sitemap {
+            writeln!(file, "\nSitemap: {sitemap}").expect("writing to a string should succeed");
+        }
+        file
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RobotsConfig;
+
+    #[test]
+    fn robots() {
+        let robots = |allow: &[&str], disallow: &[&str], sitemap: Option<&str>| RobotsConfig {
+            allow: allow.iter().map(|&path| path.into()).collect(),
+            disallow: disallow.iter().map(|&path| path.into()).collect(),
+            sitemap: sitemap.map(Into::into),
+        };
+
+        assert_eq!(
+            robots(&[], &[], None).robots_txt(),
+            "User-agent: *\nDisallow:\n"
+        );
+        assert_eq!(
+            robots(
+                &["/writing/public/"],
+                &["/writing/"],
+                Some("https://example.com/sitemap.xml")
+            )
+            .robots_txt(),
+            "User-agent: *\nAllow: /writing/public/\nDisallow: /writing/\n\nSitemap: https://example.com/sitemap.xml\n"
+        );
+
+        assert!(robots(&["/a/*.pdf$"], &["/"], None).validate().is_ok());
+        assert!(robots(&["a/"], &[], None).validate().is_err());
+        assert!(robots(&[], &["/a b/"], None).validate().is_err());
+        assert!(robots(&[], &[], Some("/sitemap.xml")).validate().is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! Code for generating an index of article contents, which a client-side search page can load.
+
+use crate::{frontmatter::Frontmatter, url::article_url};
+use anyhow::{Context, Result};
+use jiff::civil::Date;
+use serde::Serialize;
+
+/// Builder for a JSON search index of articles.
+#[derive(Default)]
+pub struct SearchIndex(Vec<SearchEntry>);
+
+#[derive(Serialize)]
+struct SearchEntry {
+    title: Box<str>,
+    slug: String,
+    // Root-relative URL of the article page
+    url: String,
+    tags: Vec<Box<str>>,
+    created: Date,
+    // Text content of the article, with whitespace collapsed to single spaces
+    text: String,
+}
+
+impl SearchIndex {
+    /// Initializes an empty search index.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an article to the search index, with its plain-text content.
+    pub fn add_article(&mut self, frontmatter: &Frontmatter, text: &str) {
+        self.0.push(SearchEntry {
+            title: frontmatter.title.clone(),
+            slug: frontmatter.slug.clone(),
+            url: article_url(&frontmatter.slug),
+            tags: frontmatter.tags.clone(),
+            created: frontmatter.created,
+            text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+        });
+    }
+
+    /// Consumes the search index and returns it as a JSON array, with the most recent articles first.
+    ///
+    /// # Errors
+    /// This function returns an error if the index cannot be serialized.
+    pub fn into_json(mut self) -> Result<String> {
+        self.0
+            .sort_unstable_by(|a, b| b.created.cmp(&a.created).then_with(|| a.slug.cmp(&b.slug)));
+        serde_json::to_string(&self.0).context("failed to serialize search index")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SearchIndex;
+    use crate::frontmatter::Frontmatter;
+
+    #[test]
+    fn search_index() {
+        let frontmatter = |slug: &str, created: &str| {
+            Frontmatter::from_text(&format!(
+                "---\ntitle: \"Post {slug}\"\nslug: \"{slug}\"\ncreated: {created}\ntags: [rust]\n---\n"
+            ))
+            .unwrap()
+        };
+
+        let mut index = SearchIndex::new();
+        index.add_article(
+            &frontmatter("old", "2024-01-01"),
+            "Some  text\n\nacross blocks ",
+        );
+        index.add_article(&frontmatter("new", "2024-02-01"), "Newer text");
+
+        assert_eq!(
+            index.into_json().unwrap(),
+            r#"[{"title":"Post new","slug":"new","url":"/writing/new/","tags":["rust"],"created":"2024-02-01","text":"Newer text"},{"title":"Post old","slug":"old","url":"/writing/old/","tags":["rust"],"created":"2024-01-01","text":"Some text across blocks"}]"#
+        );
+    }
+}
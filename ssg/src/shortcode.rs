@@ -0,0 +1,221 @@
+//! Parser for shortcodes, which are directives of the form `{{ name key="value" }}` in articles.
+
+#[cfg(feature = "images")]
+use crate::embed::Embed;
+#[cfg(feature = "maps")]
+use crate::geo::TrackMap;
+use crate::{
+    audio::Audio, component::render_component, config::Config, table::DataTable, vfs::Vfs,
+    video::Video,
+};
+use anyhow::{Result, anyhow, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct Shortcode<'a> {
+    pub name: &'a str,
+    args: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Shortcode<'a> {
+    /// Parses a shortcode from the input text.
+    /// This function returns `Ok(None)` if the text is not delimited by `{{` and `}}`.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the shortcode name is empty or contains characters other than ASCII alphanumerics, `-`, and `_`
+    /// - an argument is not of the form `key="value"`
+    /// - an argument key appears more than once
+    pub fn parse(input: &'a str) -> Result<Option<Self>> {
+        let Some(inner) = input
+            .trim()
+            .strip_prefix("{{")
+            .and_then(|rest| rest.strip_suffix("}}"))
+        else {
+            return Ok(None);
+        };
+
+        Self::parse_inner(inner).map(Some)
+    }
+
+    /// Parses the contents of a shortcode without its delimiters (e.g. `name key="value"`).
+    ///
+    /// # Errors
+    /// This function returns an error under the same conditions as `Shortcode::parse()`.
+    pub fn parse_inner(inner: &'a str) -> Result<Self> {
+        let inner = inner.trim();
+        let (name, mut rest) =
+            inner.split_at(inner.find(char::is_whitespace).unwrap_or(inner.len()));
+
+        if !is_valid_identifier(name) {
+            bail!("shortcode name is invalid: \"{name}\"");
+        }
+
+        let mut args: Vec<(&str, &str)> = Vec::new();
+
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+
+            let (key, after_key) = rest
+                .split_once('=')
+                .ok_or_else(|| anyhow!("shortcode argument is not of the form key=\"value\""))?;
+
+            if !is_valid_identifier(key) {
+                bail!("shortcode argument key is invalid: \"{key}\"");
+            }
+
+            let (value, after_value) = after_key
+                .strip_prefix('"')
+                .and_then(|value| value.split_once('"'))
+                .ok_or_else(|| anyhow!("shortcode argument `{key}` has no quoted value"))?;
+
+            if args.iter().any(|(existing, _)| *existing == key) {
+                bail!("shortcode argument `{key}` is specified more than once");
+            }
+
+            args.push((key, value));
+            rest = after_value;
+        }
+
+        Ok(Self { name, args })
+    }
+
+    /// Returns the value of the argument with the provided key, if it exists.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.args
+            .iter()
+            .find_map(|(k, v)| (*k == key).then_some(*v))
+    }
+
+    /// Returns the value of the argument with the provided key.
+    ///
+    /// # Errors
+    /// This function returns an error if the argument does not exist.
+    pub fn require(&self, key: &str) -> Result<&'a str> {
+        self.get(key)
+            .ok_or_else(|| anyhow!("shortcode `{}` requires the argument `{key}`", self.name))
+    }
+
+    /// Checks that every argument has one of the provided keys.
+    ///
+    /// # Errors
+    /// This function returns an error if an argument has an unknown key.
+    pub fn expect_args(&self, keys: &[&str]) -> Result<()> {
+        if let Some((key, _)) = self.args.iter().find(|(key, _)| !keys.contains(key)) {
+            bail!("shortcode `{}` has unknown argument `{key}`", self.name);
+        }
+        Ok(())
+    }
+}
+
+/// Renders a shortcode as a string of HTML for the page of the article with `slug`,
+/// saving any associated files to `output_dir`. Paths of files read from `input_dir` are added to `inputs`.
+/// Shortcodes that aren't built in are rendered as components from the config file's registry.
+///
+/// # Errors
+/// This function returns an error if the shortcode's arguments are invalid, if its files cannot be processed,
+/// or if it is neither a built-in shortcode nor a component without content.
+pub fn render_shortcode(
+    shortcode: &Shortcode<'_>,
+    input_dir: &Utf8Path,
+    output_dir: &Utf8Path,
+    slug: &str,
+    config: &Config,
+    inputs: &mut Vec<Utf8PathBuf>,
+    vfs: &dyn Vfs,
+) -> Result<String> {
+    #[cfg(feature = "images")]
+    if let Some(embed) = Embed::from_shortcode(shortcode)? {
+        return embed.into_html(
+            output_dir,
+            slug,
+            &config.asset_urls(),
+            config.cache_dir.as_deref(),
+            !config.offline,
+            vfs,
+        );
+    }
+    #[cfg(feature = "maps")]
+    if let Some(map) = TrackMap::from_shortcode(shortcode)? {
+        inputs.extend(shortcode.get("src").map(|src| input_dir.join(src)));
+        return map.into_html(input_dir, output_dir, slug, &config.asset_urls(), vfs);
+    }
+
+    if let Some(table) = DataTable::from_shortcode(shortcode)? {
+        inputs.extend(shortcode.get("src").map(|src| input_dir.join(src)));
+        table.into_html(input_dir, vfs)
+    } else if let Some(video) = Video::from_shortcode(shortcode)? {
+        inputs.extend(
+            ["src", "poster"]
+                .into_iter()
+                .filter_map(|key| shortcode.get(key))
+                .map(|path| input_dir.join(path)),
+        );
+        video.into_html(input_dir, output_dir, slug, &config.asset_urls(), vfs)
+    } else if let Some(audio) = Audio::from_shortcode(shortcode)? {
+        inputs.extend(shortcode.get("src").map(|src| input_dir.join(src)));
+        audio.into_html(input_dir, output_dir, slug, &config.asset_urls(), vfs)
+    } else {
+        render_component(&config.components, shortcode, false).map(|(html, _)| html)
+    }
+}
+
+/// Names of shortcodes that are built into `ssg`.
+pub(crate) const BUILTIN_SHORTCODES: &[&str] = &[
+    "youtube", "vimeo", "tweet", "map", "table", "video", "audio",
+];
+
+pub(crate) fn is_valid_identifier(input: &str) -> bool {
+    !input.is_empty()
+        && input
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::Shortcode;
+
+    #[test]
+    fn not_shortcode() {
+        assert_eq!(Shortcode::parse("abc").unwrap(), None);
+        assert_eq!(Shortcode::parse("{{ abc").unwrap(), None);
+        assert_eq!(Shortcode::parse("abc }}").unwrap(), None);
+    }
+
+    #[test]
+    fn no_args() {
+        let shortcode = Shortcode::parse("{{ abc }}").unwrap().unwrap();
+        assert_eq!(shortcode.name, "abc");
+        assert_eq!(shortcode.get("def"), None);
+        assert!(shortcode.require("def").is_err());
+    }
+
+    #[test]
+    fn args() {
+        let shortcode = Shortcode::parse("  {{abc x=\"1\"  y-z=\"two words\" empty=\"\"}}\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(shortcode.name, "abc");
+        assert_eq!(shortcode.get("x"), Some("1"));
+        assert_eq!(shortcode.get("y-z"), Some("two words"));
+        assert_eq!(shortcode.get("empty"), Some(""));
+        assert!(shortcode.expect_args(&["x", "y-z", "empty"]).is_ok());
+        assert!(shortcode.expect_args(&["x", "y-z"]).is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Shortcode::parse("{{ }}").is_err());
+        assert!(Shortcode::parse("{{ a/b }}").is_err());
+        assert!(Shortcode::parse("{{ abc x }}").is_err());
+        assert!(Shortcode::parse("{{ abc x=1 }}").is_err());
+        assert!(Shortcode::parse("{{ abc x=\"1 }}").is_err());
+        assert!(Shortcode::parse("{{ abc =\"1\" }}").is_err());
+        assert!(Shortcode::parse("{{ abc x=\"1\" x=\"2\" }}").is_err());
+    }
+}
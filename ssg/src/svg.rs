@@ -0,0 +1,240 @@
+//! Utility for minifying SVG images in articles before they are copied to the output directory.
+//!
+//! Vector editors fill SVG files with data that browsers never use: comments, `<metadata>` elements,
+//! editor-specific elements and attributes (e.g. `inkscape:*` and `sodipodi:*`), and indentation.
+//! Minification removes these while keeping everything that affects rendering.
+
+use anyhow::{Context, Result};
+use foldhash::{HashSet, HashSetExt};
+use roxmltree::{Document, Node, ParsingOptions};
+use std::fmt::Write;
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+// Namespaces of elements and attributes that vector editors add for their own use
+const EDITOR_NAMESPACES: [&str; 6] = [
+    "http://www.inkscape.org/namespaces/inkscape",
+    "http://sodipodi.sourceforge.net/DTD/sodipodi-0.dtd",
+    "http://www.bohemiancoding.com/sketch/ns",
+    "http://www.serif.com/",
+    "http://ns.adobe.com/AdobeIllustrator/10.0/",
+    "http://ns.adobe.com/Graphs/1.0/",
+];
+
+// Elements whose whitespace-only text is rendered as a space between words
+const TEXT_ELEMENTS: [&str; 3] = ["text", "tspan", "textPath"];
+
+/// Minifies an SVG document, removing comments, processing instructions, metadata, editor-specific data,
+/// and insignificant whitespace. Runs of whitespace in text are collapsed to a single space,
+/// except within elements with `xml:space="preserve"`.
+///
+/// # Errors
+/// This function returns an error if the input is not a well-formed XML document.
+pub fn minify_svg(src: &str) -> Result<String> {
+    let document = Document::parse_with_options(
+        src,
+        ParsingOptions {
+            // Some editors (e.g. Adobe Illustrator) add a document type declaration
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        },
+    )
+    .context("failed to parse SVG")?;
+
+    // Namespace declarations are only kept if an element or attribute in the output uses them
+    let mut used_namespaces = HashSet::new();
+    for node in document.descendants().filter(|node| is_kept_element(*node)) {
+        used_namespaces.extend(node.tag_name().namespace());
+        used_namespaces.extend(
+            node.attributes()
+                .filter_map(|attr| attr.namespace())
+                .filter(|namespace| !EDITOR_NAMESPACES.contains(namespace)),
+        );
+    }
+
+    let mut output = String::with_capacity(src.len());
+    write_element(
+        &mut output,
+        document.root_element(),
+        &used_namespaces,
+        false,
+        false,
+    );
+    Ok(output)
+}
+
+/// Returns a Boolean indicating if a node is an element that is kept in the output, along with its ancestors.
+fn is_kept_element(node: Node<'_, '_>) -> bool {
+    node.ancestors()
+        .filter(Node::is_element)
+        .all(|node| match node.tag_name().namespace() {
+            Some(namespace) if EDITOR_NAMESPACES.contains(&namespace) => false,
+            Some(SVG_NAMESPACE) => node.tag_name().name() != "metadata",
+            _ => true,
+        })
+        && node.is_element()
+}
+
+/// Writes an element and its kept descendants to `output`.
+/// `preserve_space` and `in_text` indicate if the element is within an element
+/// with `xml:space="preserve"` or a text element, respectively.
+fn write_element(
+    output: &mut String,
+    node: Node<'_, '_>,
+    used_namespaces: &HashSet<&str>,
+    preserve_space: bool,
+    in_text: bool,
+) {
+    let src = node.document().input_text();
+
+    // The original qualified name keeps the element's namespace prefix, if any
+    let qname = src[node.range().start + 1..]
+        .split(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>')
+        .next()
+        .unwrap_or_default();
+    output.push('<');
+    output.push_str(qname);
+
+    // Declare namespaces introduced by this element
+    let parent_namespaces: Vec<_> = node
+        .parent_element()
+        .map(|parent| parent.namespaces().collect())
+        .unwrap_or_default();
+    for namespace in node.namespaces() {
+        if namespace.uri() == XML_NAMESPACE
+            || parent_namespaces.contains(&namespace)
+            || (namespace.name().is_some() && !used_namespaces.contains(namespace.uri()))
+        {
+            continue;
+        }
+        output.push_str(" xmlns");
+        if let Some(prefix) = namespace.name() {
+            output.push(':');
+            output.push_str(prefix);
+        }
+        output.push_str("=\"");
+        push_escaped(output, namespace.uri(), true);
+        output.push('"');
+    }
+
+    for attr in node.attributes() {
+        if attr
+            .namespace()
+            .is_some_and(|namespace| EDITOR_NAMESPACES.contains(&namespace))
+        {
+            continue;
+        }
+        output.push(' ');
+        output.push_str(&src[attr.range_qname()]);
+        output.push_str("=\"");
+        push_escaped(output, attr.value(), true);
+        output.push('"');
+    }
+
+    let preserve_space = match node.attribute((XML_NAMESPACE, "space")) {
+        Some(space) => space == "preserve",
+        None => preserve_space,
+    };
+    let in_text = in_text
+        || node.tag_name().namespace() == Some(SVG_NAMESPACE)
+            && TEXT_ELEMENTS.contains(&node.tag_name().name());
+
+    let mut has_content = false;
+    for child in node.children() {
+        if child.is_element() {
+            if !is_kept_element(child) {
+                continue;
+            }
+            open_content(output, &mut has_content);
+            write_element(output, child, used_namespaces, preserve_space, in_text);
+        } else if child.is_text()
+            && let Some(text) = child.text()
+        {
+            if preserve_space {
+                open_content(output, &mut has_content);
+                push_escaped(output, text, false);
+                continue;
+            }
+            let collapsed = text.split_ascii_whitespace().collect::<Vec<_>>().join(" ");
+            if collapsed.is_empty() && !in_text {
+                continue;
+            }
+            open_content(output, &mut has_content);
+            // Whitespace at the edges of text separates it from neighboring text
+            if text.starts_with(|c: char| c.is_ascii_whitespace()) && !collapsed.is_empty() {
+                output.push(' ');
+            }
+            push_escaped(output, &collapsed, false);
+            if text.ends_with(|c: char| c.is_ascii_whitespace()) {
+                output.push(' ');
+            }
+        }
+        // Comments and processing instructions are dropped
+    }
+
+    if has_content {
+        write!(output, "</{qname}>").expect("writing to a string should succeed");
+    } else {
+        output.push_str("/>");
+    }
+}
+
+/// Closes the start tag of the element being written, if it hasn't been closed yet.
+fn open_content(output: &mut String, has_content: &mut bool) {
+    if !*has_content {
+        output.push('>');
+        *has_content = true;
+    }
+}
+
+/// Appends text to `output`, escaping characters that are special in XML text or attribute values.
+fn push_escaped(output: &mut String, text: &str, is_attribute: bool) {
+    for c in text.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' if !is_attribute => output.push_str("&gt;"),
+            '"' if is_attribute => output.push_str("&quot;"),
+            c => output.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::minify_svg;
+
+    #[test]
+    fn minify() {
+        let src = r##"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Created with Inkscape -->
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"
+     xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape"
+     xmlns:sodipodi="http://sodipodi.sourceforge.net/DTD/sodipodi-0.dtd"
+     xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#"
+     width="10" height="10" inkscape:version="1.3">
+  <sodipodi:namedview id="view" inkscape:zoom="1"/>
+  <metadata>
+    <rdf:RDF/>
+  </metadata>
+  <g inkscape:label="Layer 1">
+    <rect width="10" height="10" fill="red"/>
+    <use xlink:href="#a"/>
+    <text x="0" y="5">A &amp;
+      <tspan>B</tspan> <tspan>C</tspan></text>
+    <text xml:space="preserve">  D  </text>
+  </g>
+</svg>
+"##;
+        assert_eq!(
+            minify_svg(src).unwrap(),
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"10\" height=\"10\">\
+            <g><rect width=\"10\" height=\"10\" fill=\"red\"/><use xlink:href=\"#a\"/>\
+            <text x=\"0\" y=\"5\">A &amp; <tspan>B</tspan> <tspan>C</tspan></text>\
+            <text xml:space=\"preserve\">  D  </text></g></svg>"
+        );
+
+        assert!(minify_svg("<svg").is_err());
+    }
+}
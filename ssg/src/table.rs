@@ -0,0 +1,259 @@
+//! Utility for rendering CSV and TSV files as HTML tables.
+
+use crate::{
+    builder::{create_el, create_el_with_attrs, create_text, tree_to_html},
+    local::validate_local_src,
+    shortcode::Shortcode,
+    vfs::Vfs,
+};
+use anyhow::{Context, Result, bail};
+use camino::Utf8Path;
+use csv::{ReaderBuilder, StringRecord, Trim};
+use ego_tree::Tree;
+
+const NUMERIC_CLASS: &str = "__table-numeric";
+
+pub struct DataTable<'a> {
+    src: &'a str,
+    caption: Option<&'a str>,
+    header: HeaderMode,
+    limit: Option<usize>,
+}
+
+#[derive(Clone, Copy)]
+enum HeaderMode {
+    Auto,
+    Present,
+    Absent,
+}
+
+impl<'a> DataTable<'a> {
+    /// Interprets a shortcode of the form `{{ table src="..." caption="..." header="..." limit="..." }}` as a table.
+    /// This function returns `Ok(None)` if the shortcode is not named `table`.
+    ///
+    /// # Errors
+    /// This function returns an error if the shortcode has missing, unknown, or invalid arguments.
+    pub fn from_shortcode(shortcode: &Shortcode<'a>) -> Result<Option<Self>> {
+        if shortcode.name != "table" {
+            return Ok(None);
+        }
+
+        shortcode.expect_args(&["src", "caption", "header", "limit"])?;
+
+        let src = shortcode.require("src")?;
+        validate_local_src(src, "table source")?;
+
+        let header = match shortcode.get("header") {
+            None | Some("auto") => HeaderMode::Auto,
+            Some("true") => HeaderMode::Present,
+            Some("false") => HeaderMode::Absent,
+            Some(other) => {
+                bail!("table `header` must be \"auto\", \"true\", or \"false\" (got \"{other}\")")
+            }
+        };
+
+        let limit = shortcode
+            .get("limit")
+            .map(|limit| match limit.parse() {
+                Ok(0) | Err(_) => {
+                    bail!("table `limit` must be a positive integer (got \"{limit}\")")
+                }
+                Ok(limit) => Ok(limit),
+            })
+            .transpose()?;
+
+        Ok(Some(Self {
+            src,
+            caption: shortcode.get("caption"),
+            header,
+            limit,
+        }))
+    }
+
+    /// Consumes the table, reading its source file from `input_dir` and returning a `<table>` as a string of HTML.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the source file does not have a `.csv` or `.tsv` extension
+    /// - the source file cannot be read or parsed
+    /// - the rows of the source file have differing lengths
+    pub fn into_html(self, input_dir: &Utf8Path, vfs: &dyn Vfs) -> Result<String> {
+        let input_path = input_dir.join(self.src);
+
+        let delimiter = match input_path.extension() {
+            Some("csv") => b',',
+            Some("tsv") => b'\t',
+            _ => bail!("table source must have a .csv or .tsv extension"),
+        };
+
+        let data = vfs
+            .read(&input_path)
+            .with_context(|| format!("failed to read table source at {input_path}"))?;
+
+        let rows = ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .trim(Trim::All)
+            .from_reader(data.as_slice())
+            .into_records()
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("failed to parse table source at {input_path}"))?;
+
+        Ok(render_table(rows, self.caption, self.header, self.limit))
+    }
+}
+
+fn render_table(
+    mut rows: Vec<StringRecord>,
+    caption: Option<&str>,
+    header: HeaderMode,
+    limit: Option<usize>,
+) -> String {
+    let has_header = match header {
+        HeaderMode::Present => true,
+        HeaderMode::Absent => false,
+        // Headers are labels, so assume the first row is a header if none of its cells are numbers
+        HeaderMode::Auto => rows.first().is_some_and(|row| !row.iter().any(is_numeric)),
+    };
+
+    let header_row = (has_header && !rows.is_empty()).then(|| rows.remove(0));
+
+    if let Some(limit) = limit {
+        rows.truncate(limit);
+    }
+
+    // A column is numeric if all of its non-empty body cells are numbers
+    let column_count = header_row
+        .as_ref()
+        .or(rows.first())
+        .map_or(0, StringRecord::len);
+    let numeric_columns: Vec<bool> = (0..column_count)
+        .map(|i| {
+            let mut cells = rows
+                .iter()
+                .filter_map(|row| row.get(i))
+                .filter(|cell| !cell.is_empty())
+                .peekable();
+            cells.peek().is_some() && cells.all(is_numeric)
+        })
+        .collect();
+
+    let create_cell = |name, column: usize| {
+        if numeric_columns[column] {
+            create_el_with_attrs(name, &[("class", NUMERIC_CLASS)])
+        } else {
+            create_el(name)
+        }
+    };
+
+    let mut html = Tree::new(create_el("table"));
+    let mut table = html.root_mut();
+
+    if let Some(caption) = caption {
+        table
+            .append(create_el("caption"))
+            .append(create_text(caption));
+    }
+
+    if let Some(header_row) = header_row {
+        let mut head = table.append(create_el("thead"));
+        let mut row_el = head.append(create_el("tr"));
+        for (i, cell) in header_row.iter().enumerate() {
+            let mut cell_el = row_el.append(create_cell("th", i));
+            cell_el.append(create_text(cell));
+        }
+    }
+
+    let mut body = table.append(create_el("tbody"));
+    for row in &rows {
+        let mut row_el = body.append(create_el("tr"));
+        for (i, cell) in row.iter().enumerate() {
+            let mut cell_el = row_el.append(create_cell("td", i));
+            cell_el.append(create_text(cell));
+        }
+    }
+
+    tree_to_html(html)
+}
+
+/// Checks if a table cell contains a number, allowing for thousands separators, percentages, and currency signs.
+fn is_numeric(cell: &str) -> bool {
+    let cell = cell
+        .strip_prefix(['+', '-', '−'])
+        .unwrap_or(cell)
+        .trim_start_matches(['$', '€', '£', '¥'])
+        .trim_end_matches('%')
+        .replace(',', "");
+    !cell.is_empty() && cell.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HeaderMode, NUMERIC_CLASS, is_numeric, render_table};
+    use csv::StringRecord;
+
+    fn rows(rows: &[&[&str]]) -> Vec<StringRecord> {
+        rows.iter()
+            .map(|row| StringRecord::from(row.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn numeric_cells() {
+        for cell in ["0", "-1.5", "1,234", "+3e4", "$12.50", "45%", "−2"] {
+            assert!(is_numeric(cell), "{cell}");
+        }
+        for cell in ["", "abc", "1.2.3", "NaN", "inf", "%", "2024-01-01"] {
+            assert!(!is_numeric(cell), "{cell}");
+        }
+    }
+
+    #[test]
+    fn header_detection() {
+        let data = &[&["name", "count"][..], &["a", "1"], &["b", "2"]];
+
+        let html = render_table(rows(data), None, HeaderMode::Auto, None);
+        assert!(html.contains("<thead><tr><th>name</th>"));
+        assert!(html.contains(&format!(r#"<td class="{NUMERIC_CLASS}">1</td>"#)));
+
+        let html = render_table(rows(data), None, HeaderMode::Absent, None);
+        assert!(!html.contains("<thead>"));
+        // The "count" cell makes the second column non-numeric
+        assert!(!html.contains(NUMERIC_CLASS));
+
+        let html = render_table(
+            rows(&[&["1", "2"], &["3", "4"]]),
+            None,
+            HeaderMode::Auto,
+            None,
+        );
+        assert!(!html.contains("<thead>"));
+
+        let html = render_table(
+            rows(&[&["1", "2"], &["3", "4"]]),
+            None,
+            HeaderMode::Present,
+            None,
+        );
+        assert!(html.contains(&format!(r#"<th class="{NUMERIC_CLASS}">1</th>"#)));
+    }
+
+    #[test]
+    fn caption_and_limit() {
+        let data = &[&["x"][..], &["<a>"], &["b"], &["c"]];
+        let html = render_table(rows(data), Some("A & B"), HeaderMode::Auto, Some(2));
+
+        assert_eq!(
+            html,
+            "<table><caption>A &amp; B</caption><thead><tr><th>x</th></tr></thead><tbody><tr><td>&lt;a&gt;</td></tr><tr><td>b</td></tr></tbody></table>"
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(
+            render_table(Vec::new(), None, HeaderMode::Auto, None),
+            "<table><tbody></tbody></table>"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Code for detecting article bodies that start with a heading duplicating the article title.
+//!
+//! Article pages already display the title from the frontmatter in an `<h1>` element,
+//! so a leading `# Title` in the Markdown results in two visible titles.
+
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::Deserialize;
+use std::ops::Range;
+
+/// What to do with a level-1 heading at the start of an article body that duplicates the article title.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateTitlePolicy {
+    /// Print a warning and leave the heading as is
+    #[default]
+    Warn,
+    /// Remove the heading from the article
+    Strip,
+    /// Demote every heading in the article by one level, so the heading becomes an `<h2>`
+    Demote,
+}
+
+/// Returns the byte range in `markdown` of a level-1 heading that duplicates `title`,
+/// if the heading is the first block of the article. Titles are compared ignoring case and extra whitespace.
+#[must_use]
+pub fn duplicate_title_range(
+    markdown: &str,
+    title: &str,
+    options: Options,
+) -> Option<Range<usize>> {
+    let mut heading: Option<(Range<usize>, String)> = None;
+    let mut is_in_metadata = false;
+
+    for (event, offset) in Parser::new_ext(markdown, options).into_offset_iter() {
+        match event {
+            // Frontmatter comes before the body
+            Event::Start(Tag::MetadataBlock(_)) => is_in_metadata = true,
+            Event::End(TagEnd::MetadataBlock(_)) => is_in_metadata = false,
+            _ if is_in_metadata => {}
+            Event::Start(Tag::Heading {
+                level: HeadingLevel::H1,
+                ..
+            }) if heading.is_none() => heading = Some((offset, String::new())),
+            Event::End(TagEnd::Heading(_)) => {
+                let (range, text) = heading?;
+                return normalize(&text).eq(normalize(title)).then_some(range);
+            }
+            Event::Text(text) | Event::Code(text) if heading.is_some() => {
+                if let Some((_, heading_text)) = &mut heading {
+                    heading_text.push_str(&text);
+                }
+            }
+            _ if heading.is_some() => {}
+            // The first block is something other than a level-1 heading
+            _ => return None,
+        }
+    }
+
+    None
+}
+
+/// Returns the words of a title in lowercase, for comparing titles regardless of case and whitespace.
+fn normalize(text: &str) -> impl Iterator<Item = String> {
+    text.split_whitespace().map(str::to_lowercase)
+}
+
+/// Returns the heading level one below `level`, keeping the lowest level unchanged.
+#[must_use]
+pub fn demote_heading(level: HeadingLevel) -> HeadingLevel {
+    match level {
+        HeadingLevel::H1 => HeadingLevel::H2,
+        HeadingLevel::H2 => HeadingLevel::H3,
+        HeadingLevel::H3 => HeadingLevel::H4,
+        HeadingLevel::H4 => HeadingLevel::H5,
+        HeadingLevel::H5 | HeadingLevel::H6 => HeadingLevel::H6,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::duplicate_title_range;
+    use pulldown_cmark::Options;
+
+    #[test]
+    fn duplicate_title() {
+        let range = |markdown| {
+            duplicate_title_range(
+                markdown,
+                "Hello world",
+                Options::ENABLE_YAML_STYLE_METADATA_BLOCKS,
+            )
+        };
+
+        assert_eq!(
+            range("---\ntitle: a\n---\n\n# hello  world\n\nText"),
+            Some(18..33)
+        );
+        assert_eq!(range("# Hello `world`"), Some(0..15));
+        assert_eq!(range("# Hello\n\nText"), None);
+        assert_eq!(range("## Hello world"), None);
+        assert_eq!(range("Text\n\n# Hello world"), None);
+        assert_eq!(range(""), None);
+    }
+}
@@ -0,0 +1,265 @@
+//! Code for assigning anchor IDs to the headings in articles and generating tables of contents from them.
+
+use crate::builder::{create_el, create_el_with_attrs, create_text, tree_to_html};
+use ego_tree::{NodeMut, Tree, tree};
+use foldhash::{HashSet, HashSetExt};
+use pulldown_cmark::HeadingLevel;
+use scraper::Node;
+
+/// Paragraph that is replaced with the table of contents
+pub const TOC_MARKER: &str = "[TOC]";
+
+const TOC_CLASS: &str = "__toc";
+const HEADING_ANCHOR_CLASS: &str = "__heading-anchor";
+
+struct TocEntry {
+    level: HeadingLevel,
+    id: String,
+    text: String,
+}
+
+pub struct TableOfContents {
+    entries: Vec<TocEntry>,
+}
+
+impl TableOfContents {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds a heading to the table of contents.
+    /// Its anchor ID is assigned once every heading has been added, by `assign_ids()`.
+    pub fn add_heading(&mut self, level: HeadingLevel, text: &str) {
+        self.entries.push(TocEntry {
+            level,
+            id: String::new(),
+            text: text.trim().to_owned(),
+        });
+    }
+
+    /// Assigns a unique anchor ID to every heading, returning the IDs in the order the headings were added.
+    /// `reserved` contains IDs of other elements on the page (e.g. figures and footnotes), which headings don't use.
+    ///
+    /// IDs are derived from content rather than position, so they stay the same when headings are added elsewhere,
+    /// keeping links to headings valid. A heading's ID is the slug of its text (e.g. `setup`) if no other heading
+    /// could have that ID. Otherwise, the slugs of its enclosing headings are prepended one by one
+    /// (e.g. `linux-setup`) until the ID is unique. Only headings with the same text under the same enclosing headings
+    /// are told apart by a numeric suffix, in order.
+    pub fn assign_ids(&mut self, reserved: &HashSet<String>) -> Vec<String> {
+        let candidates: Vec<_> = (0..self.entries.len())
+            .map(|index| self.candidate_ids(index))
+            .collect();
+        let is_shared = |index: usize, id: &String| {
+            reserved.contains(id)
+                || candidates
+                    .iter()
+                    .enumerate()
+                    .any(|(other, ids)| other != index && ids.contains(id))
+        };
+
+        let mut used = HashSet::new();
+        for (index, ids) in candidates.iter().enumerate() {
+            let id = if let Some(id) = ids.iter().find(|id| !is_shared(index, id)) {
+                id.clone()
+            } else {
+                // The most qualified ID is as specific as possible, so only a suffix can tell it apart
+                // from the IDs of headings with the same text under the same enclosing headings
+                let base = ids.last().cloned().unwrap_or_default();
+                let is_taken = |id: &String| {
+                    used.contains(id)
+                        || reserved.contains(id)
+                        || candidates
+                            .iter()
+                            .any(|other| other != ids && other.contains(id))
+                };
+                let mut id = base.clone();
+                let mut suffix = 2;
+                while is_taken(&id) {
+                    id = format!("{base}-{suffix}");
+                    suffix += 1;
+                }
+                id
+            };
+            used.insert(id.clone());
+            self.entries[index].id = id;
+        }
+
+        self.entries.iter().map(|entry| entry.id.clone()).collect()
+    }
+
+    /// Returns the possible anchor IDs of a heading, from least to most qualified:
+    /// the slug of its text, then that slug prefixed by the slugs of each enclosing heading in turn.
+    fn candidate_ids(&self, index: usize) -> Vec<String> {
+        let entry = &self.entries[index];
+        let mut id = slugify(&entry.text);
+        let mut ids = vec![id.clone()];
+
+        let mut level = entry.level;
+        for ancestor in self.entries[..index].iter().rev() {
+            if ancestor.level < level {
+                id = format!("{}-{id}", slugify(&ancestor.text));
+                ids.push(id.clone());
+                level = ancestor.level;
+            }
+        }
+
+        ids
+    }
+
+    /// Returns a `<nav>` element with nested lists of links to every heading, as a string of HTML.
+    /// This function returns `None` if there are no headings. It should be called after `assign_ids()`.
+    #[must_use]
+    pub fn to_html(&self) -> Option<String> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        let mut html = Tree::new(create_el_with_attrs(
+            "nav",
+            &[("class", TOC_CLASS), ("aria-label", "Table of contents")],
+        ));
+        append_list(html.root_mut(), &self.entries);
+
+        Some(tree_to_html(html))
+    }
+}
+
+/// Returns a permalink to a heading with the given anchor ID, as a string of HTML.
+/// The link is meant to be placed at the end of the heading's content.
+#[must_use]
+pub fn heading_anchor_html(id: &str) -> String {
+    let href = format!("#{id}");
+    tree_to_html(tree! {
+        create_el_with_attrs("a", &[
+            ("class", HEADING_ANCHOR_CLASS),
+            ("href", &href),
+            ("aria-label", "Link to this section"),
+        ]) => { create_text("#") }
+    })
+}
+
+/// Appends a list of headings to a node.
+/// Headings at a deeper level than the preceding heading are nested in a list within that heading's entry.
+fn append_list(mut parent: NodeMut<'_, Node>, entries: &[TocEntry]) {
+    let mut list = parent.append(create_el("ol"));
+    let mut rest = entries;
+
+    while let Some((first, tail)) = rest.split_first() {
+        let child_count = tail
+            .iter()
+            .take_while(|entry| entry.level > first.level)
+            .count();
+
+        let href = format!("#{}", first.id);
+        let mut item = list.append(create_el("li"));
+        item.append_subtree(tree! {
+            create_el_with_attrs("a", &[("href", &href)]) => { create_text(&first.text) }
+        });
+        if child_count > 0 {
+            append_list(item, &tail[..child_count]);
+        }
+
+        rest = &tail[child_count..];
+    }
+}
+
+/// Converts heading text into a slug for use as an anchor ID (e.g. "Hello, World!" becomes "hello-world").
+#[must_use]
+fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || matches!(c, '-' | '_')) && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "section".into()
+    } else {
+        slug.into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TableOfContents, heading_anchor_html, slugify};
+    use foldhash::{HashSet, HashSetExt};
+    use pulldown_cmark::HeadingLevel;
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  Rust's `async` -- fn  "), "rusts-async-fn");
+        assert_eq!(slugify("Über Größe"), "über-größe");
+        assert_eq!(slugify("???"), "section");
+    }
+
+    #[test]
+    fn nesting_and_duplicates() {
+        let mut toc = TableOfContents::new();
+        toc.add_heading(HeadingLevel::H2, "Intro");
+        toc.add_heading(HeadingLevel::H3, "Setup");
+        toc.add_heading(HeadingLevel::H3, "Setup");
+        toc.add_heading(HeadingLevel::H2, "<End>");
+        assert_eq!(
+            toc.assign_ids(&HashSet::new()),
+            ["intro", "intro-setup", "intro-setup-2", "end"]
+        );
+
+        assert_eq!(
+            toc.to_html().unwrap(),
+            r##"<nav class="__toc" aria-label="Table of contents"><ol><li><a href="#intro">Intro</a><ol><li><a href="#intro-setup">Setup</a></li><li><a href="#intro-setup-2">Setup</a></li></ol></li><li><a href="#end">&lt;End&gt;</a></li></ol></nav>"##
+        );
+
+        assert_eq!(TableOfContents::new().to_html(), None);
+    }
+
+    #[test]
+    fn stable_ids() {
+        let ids = |headings: &[(HeadingLevel, &str)], reserved: &[&str]| {
+            let mut toc = TableOfContents::new();
+            for (level, text) in headings {
+                toc.add_heading(*level, text);
+            }
+            toc.assign_ids(&reserved.iter().map(|&id| id.to_owned()).collect())
+        };
+        let (h2, h3, h4) = (HeadingLevel::H2, HeadingLevel::H3, HeadingLevel::H4);
+
+        let headings = [(h2, "Linux"), (h3, "Setup"), (h2, "macOS"), (h3, "Setup")];
+        assert_eq!(
+            ids(&headings, &[]),
+            ["linux", "linux-setup", "macos", "macos-setup"]
+        );
+        // Adding headings elsewhere doesn't change existing IDs
+        assert_eq!(
+            ids(&[(h2, "Intro"), (h3, "Setup"), (h4, "Setup")], &[]),
+            ["intro", "intro-setup", "setup-setup"]
+        );
+        assert_eq!(
+            ids(&[(h2, "Notes"), headings[0], headings[1]], &[]),
+            ["notes", "linux", "setup"]
+        );
+        // IDs of other elements on the page are avoided
+        assert_eq!(ids(&[(h2, "Fn 1")], &["fn-1"]), ["fn-1-2"]);
+        // Suffixes don't clash with other headings
+        assert_eq!(
+            ids(&[(h2, "A"), (h2, "A"), (h2, "A 2")], &[]),
+            ["a", "a-3", "a-2"]
+        );
+    }
+
+    #[test]
+    fn anchors() {
+        assert_eq!(
+            heading_anchor_html("setup-2"),
+            r##"<a class="__heading-anchor" href="#setup-2" aria-label="Link to this section">#</a>"##
+        );
+    }
+}
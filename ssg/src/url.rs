@@ -0,0 +1,159 @@
+//! Utility for generating the URLs of pages and assets in the output directory.
+//!
+//! Pages are always linked with root-relative URLs, so they stay on the site's own host.
+//! Heavy assets (images and fonts) can instead be served from another origin, such as a CDN,
+//! by configuring an asset base URL.
+
+use crate::{OUTPUT_CONTENT_DIR, OUTPUT_TAGS_DIR};
+use std::borrow::Cow;
+
+/// Returns the root-relative URL of a page or file in the output directory (e.g. `/changelog/` for `changelog/`).
+#[must_use]
+pub fn page_url(path: &str) -> String {
+    format!("/{path}")
+}
+
+/// Returns the root-relative URL of an article's page.
+#[must_use]
+pub fn article_url(slug: &str) -> String {
+    format!("/{OUTPUT_CONTENT_DIR}{slug}/")
+}
+
+/// Returns the root-relative URL of the page listing articles with a tag.
+#[must_use]
+pub fn tag_url(tag: &str) -> String {
+    format!("/{OUTPUT_TAGS_DIR}{tag}/")
+}
+
+/// Generates URLs of assets, which are served from an asset base URL if one is configured
+/// and from the site's own host otherwise.
+#[derive(Clone, Default)]
+pub struct AssetUrls {
+    // Absolute URL without a trailing slash (e.g. `https://cdn.example.com`)
+    base_url: Option<Box<str>>,
+}
+
+impl AssetUrls {
+    /// Initializes a URL generator for assets served from `base_url`, or from the site's own host if absent.
+    #[must_use]
+    pub fn new(base_url: Option<&str>) -> Self {
+        Self {
+            base_url: base_url.map(|url| url.trim_end_matches('/').into()),
+        }
+    }
+
+    /// Returns a Boolean indicating if assets are served from another origin than pages.
+    #[must_use]
+    pub const fn is_cross_origin(&self) -> bool {
+        self.base_url.is_some()
+    }
+
+    /// Returns the asset base URL, if one is configured.
+    #[must_use]
+    pub fn base_url(&self) -> Option<&str> {
+        self.base_url.as_deref()
+    }
+
+    /// Returns the URL of an asset at a path in the output directory (e.g. `fonts/a.woff2`).
+    #[must_use]
+    pub fn asset(&self, path: &str) -> String {
+        match &self.base_url {
+            Some(base_url) => format!("{base_url}/{path}"),
+            None => page_url(path),
+        }
+    }
+
+    /// Returns the URL of a file in an article's output directory, for use on any page.
+    #[must_use]
+    pub fn article_asset(&self, slug: &str, file: &str) -> String {
+        self.asset(&format!("{OUTPUT_CONTENT_DIR}{slug}/{file}"))
+    }
+
+    /// Returns the URL of a file in an article's output directory, for use on the article's own page.
+    /// The URL is relative unless assets are served from another origin.
+    #[must_use]
+    pub fn article_local_asset<'a>(&self, slug: &str, file: &'a str) -> Cow<'a, str> {
+        if self.is_cross_origin() {
+            Cow::Owned(self.article_asset(slug, file))
+        } else {
+            Cow::Borrowed(file)
+        }
+    }
+
+    /// Applies [`Self::article_local_asset()`] to every image candidate in a `srcset` attribute value.
+    #[must_use]
+    pub fn article_local_srcset<'a>(&self, slug: &str, srcset: &'a str) -> Cow<'a, str> {
+        if !self.is_cross_origin() {
+            return Cow::Borrowed(srcset);
+        }
+        Cow::Owned(
+            srcset
+                .split(", ")
+                .map(|candidate| self.article_asset(slug, candidate))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+
+    /// Rewrites a root-relative URL (e.g. `/fonts/a.woff2`) so the file is served from the asset base URL.
+    /// Other URLs are returned unchanged.
+    #[must_use]
+    pub fn rewrite_root_relative<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        match (&self.base_url, url.strip_prefix('/')) {
+            (Some(_), Some(path)) if !path.starts_with('/') => Cow::Owned(self.asset(path)),
+            _ => Cow::Borrowed(url),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AssetUrls, article_url, tag_url};
+
+    #[test]
+    fn urls() {
+        assert_eq!(article_url("post"), "/writing/post/");
+        assert_eq!(tag_url("rust"), "/writing/tags/rust/");
+
+        let local = AssetUrls::default();
+        assert!(!local.is_cross_origin());
+        assert_eq!(local.asset("fonts/a.woff2"), "/fonts/a.woff2");
+        assert_eq!(
+            local.article_asset("post", "a.avif"),
+            "/writing/post/a.avif"
+        );
+        assert_eq!(local.article_local_asset("post", "a.avif"), "a.avif");
+        assert_eq!(
+            local.article_local_srcset("post", "a-16w.avif 16w, a.avif 32w"),
+            "a-16w.avif 16w, a.avif 32w"
+        );
+        assert_eq!(
+            local.rewrite_root_relative("/fonts/a.woff2"),
+            "/fonts/a.woff2"
+        );
+
+        let cdn = AssetUrls::new(Some("https://cdn.example.com/"));
+        assert!(cdn.is_cross_origin());
+        assert_eq!(
+            cdn.asset("fonts/a.woff2"),
+            "https://cdn.example.com/fonts/a.woff2"
+        );
+        assert_eq!(
+            cdn.article_local_asset("post", "a.avif"),
+            "https://cdn.example.com/writing/post/a.avif"
+        );
+        assert_eq!(
+            cdn.article_local_srcset("post", "a-16w.avif 16w, a.avif 32w"),
+            "https://cdn.example.com/writing/post/a-16w.avif 16w, https://cdn.example.com/writing/post/a.avif 32w"
+        );
+        assert_eq!(
+            cdn.rewrite_root_relative("/fonts/a.woff2"),
+            "https://cdn.example.com/fonts/a.woff2"
+        );
+        assert_eq!(cdn.rewrite_root_relative("a.woff2"), "a.woff2");
+        assert_eq!(
+            cdn.rewrite_root_relative("//other.example.com/a.woff2"),
+            "//other.example.com/a.woff2"
+        );
+    }
+}
@@ -0,0 +1,601 @@
+//! Abstraction over the file system, so site inputs and outputs can be kept in memory (e.g. in tests).
+//!
+//! Every file that a build reads or writes goes through a `Vfs`, including the output directory itself,
+//! converted images, KaTeX assets, fonts, and caches. Only watch mode, which waits for changes on disk,
+//! requires the real file system.
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Write},
+    sync::Mutex,
+};
+
+/// A file system that site inputs are read from and generated files are written to.
+pub trait Vfs: Sync {
+    /// Reads the contents of a file.
+    ///
+    /// # Errors
+    /// This function returns an error if the file cannot be read.
+    fn read(&self, path: &Utf8Path) -> io::Result<Vec<u8>>;
+
+    /// Reads the contents of a UTF-8 text file.
+    ///
+    /// # Errors
+    /// This function returns an error if the file cannot be read or is not valid UTF-8.
+    fn read_to_string(&self, path: &Utf8Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes a file, replacing its contents if it exists. With `sync`, the file is stored durably before returning.
+    ///
+    /// # Errors
+    /// This function returns an error if the file cannot be written.
+    fn write(&self, path: &Utf8Path, contents: &[u8], sync: bool) -> io::Result<()>;
+
+    /// Copies a file, replacing the contents of the destination if it exists. This function outputs the file's size.
+    ///
+    /// # Errors
+    /// This function returns an error if the file cannot be read or the destination cannot be written.
+    fn copy(&self, from: &Utf8Path, to: &Utf8Path) -> io::Result<u64>;
+
+    /// Moves a file or directory to a path that doesn't exist.
+    ///
+    /// # Errors
+    /// This function returns an error if the source cannot be moved.
+    fn rename(&self, from: &Utf8Path, to: &Utf8Path) -> io::Result<()>;
+
+    /// Creates a directory and all of its missing parents.
+    ///
+    /// # Errors
+    /// This function returns an error if a directory cannot be created.
+    fn create_dir_all(&self, path: &Utf8Path) -> io::Result<()>;
+
+    /// Removes a file.
+    ///
+    /// # Errors
+    /// This function returns an error if the file cannot be removed.
+    fn remove_file(&self, path: &Utf8Path) -> io::Result<()>;
+
+    /// Removes a directory and everything within it.
+    ///
+    /// # Errors
+    /// This function returns an error if the directory or something within it cannot be removed.
+    fn remove_dir_all(&self, path: &Utf8Path) -> io::Result<()>;
+
+    /// Returns the size of a file in bytes.
+    ///
+    /// # Errors
+    /// This function returns an error if the file's metadata cannot be read.
+    fn file_size(&self, path: &Utf8Path) -> io::Result<u64>;
+
+    /// Returns a Boolean indicating if a path points to a file.
+    fn is_file(&self, path: &Utf8Path) -> bool;
+
+    /// Returns a Boolean indicating if a path points to a directory.
+    fn is_dir(&self, path: &Utf8Path) -> bool;
+
+    /// Returns the paths of all files within a directory and its subdirectories, in sorted order.
+    ///
+    /// # Errors
+    /// This function returns an error if the directory or one of its subdirectories cannot be read.
+    fn files_in(&self, dir: &Utf8Path) -> Result<Vec<Utf8PathBuf>>;
+
+    /// Syncs every file within a directory, then the directory itself, so new directory entries are durable.
+    ///
+    /// # Errors
+    /// This function returns an error if a file or directory cannot be synced.
+    fn sync_tree(&self, dir: &Utf8Path) -> Result<()>;
+}
+
+/// The file system of the operating system.
+#[derive(Clone, Copy)]
+pub struct RealFs;
+
+impl Vfs for RealFs {
+    fn read(&self, path: &Utf8Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_to_string(&self, path: &Utf8Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Utf8Path, contents: &[u8], sync: bool) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(contents)?;
+        if sync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn copy(&self, from: &Utf8Path, to: &Utf8Path) -> io::Result<u64> {
+        fs::copy(from, to)
+    }
+
+    fn rename(&self, from: &Utf8Path, to: &Utf8Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn create_dir_all(&self, path: &Utf8Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn remove_file(&self, path: &Utf8Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Utf8Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn file_size(&self, path: &Utf8Path) -> io::Result<u64> {
+        fs::metadata(path).map(|metadata| metadata.len())
+    }
+
+    fn is_file(&self, path: &Utf8Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Utf8Path) -> bool {
+        path.is_dir()
+    }
+
+    fn files_in(&self, dir: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+        let mut files = Vec::new();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in dir
+                .read_dir_utf8()
+                .with_context(|| format!("failed to read directory at {dir}"))?
+            {
+                let entry =
+                    entry.with_context(|| format!("failed to read entry in directory at {dir}"))?;
+                // Symbolic links are followed, like with other file operations
+                if entry.path().is_dir() {
+                    dirs.push(entry.into_path());
+                } else {
+                    files.push(entry.into_path());
+                }
+            }
+        }
+        files.sort_unstable();
+        Ok(files)
+    }
+
+    fn sync_tree(&self, dir: &Utf8Path) -> Result<()> {
+        for entry in dir
+            .read_dir_utf8()
+            .with_context(|| format!("failed to read directory at {dir}"))?
+        {
+            let path = entry
+                .with_context(|| format!("failed to read entry in directory at {dir}"))?
+                .into_path();
+
+            if path.is_dir() {
+                self.sync_tree(&path)?;
+            } else {
+                File::open(&path)
+                    .and_then(|file| file.sync_all())
+                    .with_context(|| format!("failed to sync file at {path}"))?;
+            }
+        }
+
+        // Directories cannot be opened as files on some platforms
+        if cfg!(unix) {
+            File::open(dir)
+                .and_then(|file| file.sync_all())
+                .with_context(|| format!("failed to sync directory at {dir}"))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A file system kept in memory, in which directories exist if they contain a file.
+/// Creating a directory is therefore a no-op, and moving or removing a directory affects the files within it.
+#[derive(Default)]
+pub struct MemoryFs {
+    files: Mutex<BTreeMap<Utf8PathBuf, Vec<u8>>>,
+}
+
+impl MemoryFs {
+    /// Initializes an empty in-memory file system.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file to the file system, replacing its contents if it exists.
+    ///
+    /// # Panics
+    /// This function panics if another thread panicked while accessing the file system.
+    #[must_use]
+    pub fn with_file(self, path: impl Into<Utf8PathBuf>, contents: impl Into<Vec<u8>>) -> Self {
+        self.files
+            .lock()
+            .expect("file system lock should not be poisoned")
+            .insert(path.into(), contents.into());
+        self
+    }
+
+    /// Returns the contents of a file, if it exists.
+    ///
+    /// # Panics
+    /// This function panics if another thread panicked while accessing the file system.
+    #[must_use]
+    pub fn file(&self, path: &Utf8Path) -> Option<Vec<u8>> {
+        self.files
+            .lock()
+            .expect("file system lock should not be poisoned")
+            .get(path)
+            .cloned()
+    }
+
+    /// Returns the contents of a file, or a "not found" error if it doesn't exist.
+    fn existing_file(&self, path: &Utf8Path) -> io::Result<Vec<u8>> {
+        self.file(path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{path} not found")))
+    }
+}
+
+impl Vfs for MemoryFs {
+    fn read(&self, path: &Utf8Path) -> io::Result<Vec<u8>> {
+        self.existing_file(path)
+    }
+
+    fn write(&self, path: &Utf8Path, contents: &[u8], _sync: bool) -> io::Result<()> {
+        self.files
+            .lock()
+            .expect("file system lock should not be poisoned")
+            .insert(path.to_path_buf(), contents.to_vec());
+        Ok(())
+    }
+
+    fn copy(&self, from: &Utf8Path, to: &Utf8Path) -> io::Result<u64> {
+        let contents = self.existing_file(from)?;
+        self.write(to, &contents, false)?;
+        Ok(contents.len() as u64)
+    }
+
+    fn rename(&self, from: &Utf8Path, to: &Utf8Path) -> io::Result<()> {
+        let mut files = self
+            .files
+            .lock()
+            .expect("file system lock should not be poisoned");
+        let moved: Vec<_> = files
+            .keys()
+            .filter(|file| file.starts_with(from))
+            .cloned()
+            .collect();
+        if moved.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{from} not found"),
+            ));
+        }
+        for file in moved {
+            let contents = files.remove(&file).expect("moved file should exist");
+            let relative = file
+                .strip_prefix(from)
+                .expect("moved file should be within source");
+            files.insert(to.join(relative), contents);
+        }
+        Ok(())
+    }
+
+    fn create_dir_all(&self, _path: &Utf8Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Utf8Path) -> io::Result<()> {
+        self.files
+            .lock()
+            .expect("file system lock should not be poisoned")
+            .remove(path)
+            .map(drop)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{path} not found")))
+    }
+
+    fn remove_dir_all(&self, path: &Utf8Path) -> io::Result<()> {
+        self.files
+            .lock()
+            .expect("file system lock should not be poisoned")
+            .retain(|file, _| !file.starts_with(path));
+        Ok(())
+    }
+
+    fn file_size(&self, path: &Utf8Path) -> io::Result<u64> {
+        self.existing_file(path)
+            .map(|contents| contents.len() as u64)
+    }
+
+    fn is_file(&self, path: &Utf8Path) -> bool {
+        self.file(path).is_some()
+    }
+
+    fn is_dir(&self, path: &Utf8Path) -> bool {
+        self.files
+            .lock()
+            .expect("file system lock should not be poisoned")
+            .keys()
+            .any(|file| file != path && file.starts_with(path))
+    }
+
+    fn files_in(&self, dir: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+        Ok(self
+            .files
+            .lock()
+            .expect("file system lock should not be poisoned")
+            .keys()
+            .filter(|file| *file != dir && file.starts_with(dir))
+            .cloned()
+            .collect())
+    }
+
+    // Memory is not durable, so there is nothing to sync
+    fn sync_tree(&self, _dir: &Utf8Path) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A file system that reads from another file system, but keeps every change in memory, e.g. for dry runs of a build.
+/// Removed files and directories are hidden instead of being removed from the underlying file system.
+pub struct OverlayFs<'a> {
+    base: &'a dyn Vfs,
+    changes: MemoryFs,
+    // Paths in the underlying file system that are treated as not existing, along with everything within them
+    hidden: Mutex<Vec<Utf8PathBuf>>,
+}
+
+impl<'a> OverlayFs<'a> {
+    /// Initializes a file system that reads from `base` without changing it.
+    #[must_use]
+    pub fn new(base: &'a dyn Vfs) -> Self {
+        Self {
+            base,
+            changes: MemoryFs::new(),
+            hidden: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Hides a file or directory of the underlying file system, as if it didn't exist.
+    ///
+    /// # Panics
+    /// This function panics if another thread panicked while accessing the file system.
+    #[must_use]
+    pub fn with_hidden(self, path: impl Into<Utf8PathBuf>) -> Self {
+        self.hide(path.into());
+        self
+    }
+
+    fn hide(&self, path: Utf8PathBuf) {
+        self.hidden
+            .lock()
+            .expect("file system lock should not be poisoned")
+            .push(path);
+    }
+
+    /// Returns a Boolean indicating if a path of the underlying file system is visible through the overlay.
+    fn is_visible(&self, path: &Utf8Path) -> bool {
+        !self
+            .hidden
+            .lock()
+            .expect("file system lock should not be poisoned")
+            .iter()
+            .any(|hidden| path.starts_with(hidden))
+    }
+
+    fn is_base_file(&self, path: &Utf8Path) -> bool {
+        self.is_visible(path) && self.base.is_file(path)
+    }
+
+    fn is_base_dir(&self, path: &Utf8Path) -> bool {
+        self.is_visible(path) && self.base.is_dir(path)
+    }
+}
+
+impl Vfs for OverlayFs<'_> {
+    fn read(&self, path: &Utf8Path) -> io::Result<Vec<u8>> {
+        if self.changes.is_file(path) || !self.is_visible(path) {
+            self.changes.read(path)
+        } else {
+            self.base.read(path)
+        }
+    }
+
+    fn write(&self, path: &Utf8Path, contents: &[u8], sync: bool) -> io::Result<()> {
+        self.changes.write(path, contents, sync)
+    }
+
+    fn copy(&self, from: &Utf8Path, to: &Utf8Path) -> io::Result<u64> {
+        let contents = self.read(from)?;
+        self.write(to, &contents, false)?;
+        Ok(contents.len() as u64)
+    }
+
+    fn rename(&self, from: &Utf8Path, to: &Utf8Path) -> io::Result<()> {
+        if self.is_file(from) {
+            self.copy(from, to)?;
+            return self.remove_file(from);
+        }
+
+        let files = self.files_in(from).map_err(io::Error::other)?;
+        if files.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{from} not found"),
+            ));
+        }
+        for file in files {
+            let relative = file
+                .strip_prefix(from)
+                .expect("moved file should be within source");
+            self.copy(&file, &to.join(relative))?;
+        }
+        self.remove_dir_all(from)
+    }
+
+    fn create_dir_all(&self, path: &Utf8Path) -> io::Result<()> {
+        self.changes.create_dir_all(path)
+    }
+
+    fn remove_file(&self, path: &Utf8Path) -> io::Result<()> {
+        let in_base = self.is_base_file(path);
+        let removed = self.changes.remove_file(path);
+        if in_base {
+            self.hide(path.to_path_buf());
+            Ok(())
+        } else {
+            removed
+        }
+    }
+
+    fn remove_dir_all(&self, path: &Utf8Path) -> io::Result<()> {
+        self.changes.remove_dir_all(path)?;
+        self.hide(path.to_path_buf());
+        Ok(())
+    }
+
+    fn file_size(&self, path: &Utf8Path) -> io::Result<u64> {
+        if self.changes.is_file(path) || !self.is_visible(path) {
+            self.changes.file_size(path)
+        } else {
+            self.base.file_size(path)
+        }
+    }
+
+    fn is_file(&self, path: &Utf8Path) -> bool {
+        self.changes.is_file(path) || self.is_base_file(path)
+    }
+
+    fn is_dir(&self, path: &Utf8Path) -> bool {
+        self.changes.is_dir(path) || self.is_base_dir(path)
+    }
+
+    fn files_in(&self, dir: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+        let mut files = self.changes.files_in(dir)?;
+        if self.is_base_dir(dir) {
+            files.extend(
+                self.base
+                    .files_in(dir)?
+                    .into_iter()
+                    .filter(|file| self.is_visible(file)),
+            );
+        }
+        files.sort_unstable();
+        files.dedup();
+        Ok(files)
+    }
+
+    // Changes are only kept in memory, so there is nothing to sync
+    fn sync_tree(&self, _dir: &Utf8Path) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MemoryFs, OverlayFs, RealFs, Vfs};
+    use camino::{Utf8Path, Utf8PathBuf};
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all},
+    };
+
+    /// Utility function for checking that a file system behaves as expected within a directory
+    fn check_vfs(vfs: &impl Vfs, dir: &Utf8Path) {
+        vfs.write(&dir.join("a.md"), b"a", false).unwrap();
+        vfs.write(&dir.join("nested/b.md"), b"b", false).unwrap();
+
+        assert_eq!(vfs.read_to_string(&dir.join("a.md")).unwrap(), "a");
+        assert!(vfs.read_to_string(&dir.join("c.md")).is_err());
+        assert!(vfs.is_file(&dir.join("nested/b.md")));
+        assert!(!vfs.is_file(&dir.join("nested")));
+        assert!(vfs.is_dir(&dir.join("nested")));
+        assert!(!vfs.is_dir(&dir.join("a.md")));
+        assert_eq!(
+            vfs.files_in(dir).unwrap(),
+            [dir.join("a.md"), dir.join("nested/b.md")]
+        );
+
+        vfs.create_dir_all(&dir.join("copies/deep")).unwrap();
+        assert_eq!(
+            vfs.copy(&dir.join("a.md"), &dir.join("copies/deep/a.md"))
+                .unwrap(),
+            1
+        );
+        assert_eq!(vfs.file_size(&dir.join("copies/deep/a.md")).unwrap(), 1);
+        vfs.rename(&dir.join("copies"), &dir.join("moved")).unwrap();
+        assert!(!vfs.is_file(&dir.join("copies/deep/a.md")));
+        assert_eq!(vfs.read(&dir.join("moved/deep/a.md")).unwrap(), b"a");
+        vfs.remove_file(&dir.join("a.md")).unwrap();
+        assert!(vfs.remove_file(&dir.join("a.md")).is_err());
+        vfs.remove_dir_all(&dir.join("moved")).unwrap();
+        assert_eq!(vfs.files_in(dir).unwrap(), [dir.join("nested/b.md")]);
+    }
+
+    #[test]
+    fn memory_fs() {
+        check_vfs(&MemoryFs::new(), Utf8Path::new("/site"));
+        assert!(
+            MemoryFs::new()
+                .with_file("/site/a.md", "a")
+                .is_dir(Utf8Path::new("/site"))
+        );
+    }
+
+    #[test]
+    fn overlay_fs() {
+        check_vfs(&OverlayFs::new(&MemoryFs::new()), Utf8Path::new("/site"));
+
+        let base = MemoryFs::new()
+            .with_file("/site/a.md", "a")
+            .with_file("/site/out/index.html", "old");
+        let overlay = OverlayFs::new(&base).with_hidden("/site/out");
+        assert_eq!(overlay.read(Utf8Path::new("/site/a.md")).unwrap(), b"a");
+        assert!(!overlay.is_dir(Utf8Path::new("/site/out")));
+        assert!(!overlay.is_file(Utf8Path::new("/site/out/index.html")));
+
+        // Changes are visible through the overlay, but don't affect the underlying file system
+        overlay
+            .write(Utf8Path::new("/site/out/index.html"), b"new", false)
+            .unwrap();
+        overlay
+            .rename(Utf8Path::new("/site/a.md"), Utf8Path::new("/site/b.md"))
+            .unwrap();
+        assert_eq!(
+            overlay.files_in(Utf8Path::new("/site")).unwrap(),
+            [
+                Utf8Path::new("/site/b.md"),
+                Utf8Path::new("/site/out/index.html")
+            ]
+        );
+        assert_eq!(
+            overlay.read(Utf8Path::new("/site/out/index.html")).unwrap(),
+            b"new"
+        );
+        assert_eq!(base.file(Utf8Path::new("/site/a.md")).unwrap(), b"a");
+        assert_eq!(
+            base.file(Utf8Path::new("/site/out/index.html")).unwrap(),
+            b"old"
+        );
+        assert!(!base.is_file(Utf8Path::new("/site/b.md")));
+    }
+
+    #[test]
+    fn real_fs() {
+        let dir = Utf8PathBuf::try_from(temp_dir())
+            .unwrap()
+            .join(format!("ssg-vfs-test-{}", std::process::id()));
+        create_dir_all(dir.join("nested")).unwrap();
+
+        check_vfs(&RealFs, &dir);
+
+        remove_dir_all(dir).unwrap();
+    }
+}
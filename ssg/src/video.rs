@@ -0,0 +1,182 @@
+//! Utility for embedding local video files in articles.
+
+use crate::{
+    builder::{create_el_with_attrs, create_text, tree_to_html},
+    local::{copy_local_file, validate_local_src},
+    shortcode::Shortcode,
+    url::AssetUrls,
+    vfs::Vfs,
+};
+use anyhow::{Context, Result, bail};
+use camino::Utf8Path;
+use ego_tree::tree;
+
+// Extensions of poster images, which are copied as is since browsers load them before any video
+const POSTER_EXTENSIONS: [&str; 6] = ["avif", "gif", "jpeg", "jpg", "png", "webp"];
+
+pub struct Video<'a> {
+    src: &'a str,
+    poster: Option<&'a str>,
+    width: Option<u32>,
+    height: Option<u32>,
+    title: Option<&'a str>,
+}
+
+impl<'a> Video<'a> {
+    /// Interprets a shortcode of the form `{{ video src="..." poster="..." width="..." height="..." title="..." }}`
+    /// as a video. This function returns `Ok(None)` if the shortcode is not named `video`.
+    ///
+    /// # Errors
+    /// This function returns an error if the shortcode has missing, unknown, or invalid arguments.
+    pub fn from_shortcode(shortcode: &Shortcode<'a>) -> Result<Option<Self>> {
+        if shortcode.name != "video" {
+            return Ok(None);
+        }
+
+        shortcode.expect_args(&["src", "poster", "width", "height", "title"])?;
+        let src = shortcode.require("src")?;
+        let poster = shortcode.get("poster");
+        for path in [Some(src), poster].into_iter().flatten() {
+            validate_local_src(path, "video source or poster")?;
+        }
+
+        let dimension = |key| {
+            shortcode
+                .get(key)
+                .map(|value| {
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|&pixels| pixels > 0)
+                        .with_context(|| format!("video {key} is not a positive integer ({value})"))
+                })
+                .transpose()
+        };
+
+        Ok(Some(Self {
+            src,
+            poster,
+            width: dimension("width")?,
+            height: dimension("height")?,
+            title: shortcode.get("title"),
+        }))
+    }
+
+    /// Consumes the video, returning a `<video>` element as a string of HTML for the page of the article with `slug`.
+    /// The video file and poster image are copied from `input_dir` to `output_dir`.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - the video file is not an MP4, WebM, Ogg, or QuickTime file
+    /// - the poster image is not an AVIF, GIF, JPEG, PNG, or WebP file
+    /// - the video file or poster image cannot be copied
+    pub fn into_html(
+        self,
+        input_dir: &Utf8Path,
+        output_dir: &Utf8Path,
+        slug: &str,
+        asset_urls: &AssetUrls,
+        vfs: &dyn Vfs,
+    ) -> Result<String> {
+        let mime = video_mime(self.src)?;
+        if let Some(poster) = self.poster
+            && !Utf8Path::new(poster)
+                .extension()
+                .is_some_and(|extension| POSTER_EXTENSIONS.contains(&&*extension.to_lowercase()))
+        {
+            bail!("video poster must be an AVIF, GIF, JPEG, PNG, or WebP image ({poster})");
+        }
+
+        for path in [Some(self.src), self.poster].into_iter().flatten() {
+            copy_local_file(path, input_dir, output_dir, vfs)?;
+        }
+
+        Ok(self.html(mime, slug, asset_urls))
+    }
+
+    /// Returns a `<video>` element with controls, which loads only the video's metadata until it is played.
+    /// Browsers that can't play the video show a download link instead.
+    fn html(&self, mime: &str, slug: &str, asset_urls: &AssetUrls) -> String {
+        let src = asset_urls.article_local_asset(slug, self.src);
+        let poster = self
+            .poster
+            .map(|poster| asset_urls.article_local_asset(slug, poster));
+        let width = self.width.map(|width| width.to_string());
+        let height = self.height.map(|height| height.to_string());
+
+        let mut attrs = vec![
+            ("class", "__video"),
+            ("controls", ""),
+            ("preload", "metadata"),
+        ];
+        attrs.extend(poster.as_deref().map(|poster| ("poster", poster)));
+        attrs.extend(width.as_deref().map(|width| ("width", width)));
+        attrs.extend(height.as_deref().map(|height| ("height", height)));
+        attrs.extend(self.title.map(|title| ("aria-label", title)));
+
+        tree_to_html(tree! {
+            create_el_with_attrs("video", &attrs) => {
+                create_el_with_attrs("source", &[("src", &src), ("type", mime)]),
+                create_el_with_attrs("a", &[("href", &src), ("download", "")]) => {
+                    create_text("Download video")
+                }
+            }
+        })
+    }
+}
+
+/// Returns the MIME type of a video based on its file extension.
+fn video_mime(src: &str) -> Result<&'static str> {
+    let extension = Utf8Path::new(src)
+        .extension()
+        .unwrap_or_default()
+        .to_lowercase();
+    Ok(match extension.as_str() {
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "mov" => "video/quicktime",
+        _ => bail!("video must be an MP4, WebM, Ogg, or QuickTime file ({src})"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Video, video_mime};
+    use crate::{shortcode::Shortcode, url::AssetUrls};
+
+    #[test]
+    fn video() {
+        let video_with_urls = |input, asset_urls: &AssetUrls| {
+            Video::from_shortcode(&Shortcode::parse(input).unwrap().unwrap()).map(|video| {
+                video
+                    .unwrap()
+                    .html(video_mime("a.mp4").unwrap(), "post", asset_urls)
+            })
+        };
+        let video = |input| video_with_urls(input, &AssetUrls::default());
+
+        assert_eq!(
+            video(
+                r#"{{ video src="clips/a.mp4" poster="a.jpg" width="640" height="360" title="A & B" }}"#
+            )
+            .unwrap(),
+            r#"<video class="__video" controls="" preload="metadata" poster="a.jpg" width="640" height="360" aria-label="A &amp; B"><source src="clips/a.mp4" type="video/mp4"><a href="clips/a.mp4" download="">Download video</a></video>"#
+        );
+        // Files are served from the asset base URL if one is configured
+        assert_eq!(
+            video_with_urls(
+                r#"{{ video src="a.mp4" poster="a.jpg" }}"#,
+                &AssetUrls::new(Some("https://cdn.example.com/"))
+            )
+            .unwrap(),
+            r#"<video class="__video" controls="" preload="metadata" poster="https://cdn.example.com/writing/post/a.jpg"><source src="https://cdn.example.com/writing/post/a.mp4" type="video/mp4"><a href="https://cdn.example.com/writing/post/a.mp4" download="">Download video</a></video>"#
+        );
+        assert!(video(r#"{{ video src="a.mp4" width="0" }}"#).is_err());
+        assert!(video(r#"{{ video src="../a.mp4" }}"#).is_err());
+        assert!(video(r#"{{ video src="a.mp4" autoplay="true" }}"#).is_err());
+        assert!(video("{{ video }}").is_err());
+        assert!(video_mime("a.WEBM").is_ok());
+        assert!(video_mime("a.gif").is_err());
+    }
+}
@@ -0,0 +1,369 @@
+//! Code for watching the input files of a site for changes.
+
+use crate::{Config, DependencyGraph};
+use anyhow::{Context, Result, anyhow};
+use camino::{Utf8Path, Utf8PathBuf};
+use glob::Pattern;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display, Formatter},
+    sync::mpsc::{Receiver, RecvTimeoutError, channel},
+    time::Duration,
+};
+
+// Changes are coalesced until no new changes have occurred for this long,
+// since editors often write a file several times when saving it
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(200);
+
+// File name patterns for temporary files created by editors
+const EDITOR_TEMP_PATTERNS: &[&str] = &[
+    "*.swp",
+    "*.swo",
+    "*.swx",
+    "*~",
+    ".#*",
+    "#*#",
+    "4913",
+    ".DS_Store",
+    "*.tmp",
+];
+
+/// Watches the config file, site CSS file, templates, fragments, and articles of a site.
+pub struct SiteWatcher {
+    // Dropping the watcher stops it, so it is kept alive alongside its receiver
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    paths: WatchedPaths,
+    // Directories watched non-recursively
+    file_dirs: BTreeSet<Utf8PathBuf>,
+}
+
+/// Input paths of a site, used for determining whether a changed path affects the site.
+struct WatchedPaths {
+    config_file: Utf8PathBuf,
+    // Site CSS file, templates, fragment files, and other inputs outside the articles directory (e.g. partials)
+    site_files: Vec<Utf8PathBuf>,
+    articles_dir: Utf8PathBuf,
+    output_dir: Utf8PathBuf,
+    ignore_patterns: Vec<Pattern>,
+}
+
+/// A set of changed input paths.
+pub struct ChangeSet<'a> {
+    paths: BTreeSet<Utf8PathBuf>,
+    watched: &'a WatchedPaths,
+}
+
+impl SiteWatcher {
+    /// Starts watching the input files of a site.
+    ///
+    /// # Errors
+    /// This function returns an error if:
+    /// - an input path cannot be resolved
+    /// - the file system watcher cannot be started
+    pub fn new(config: &Config, config_path: &Utf8Path) -> Result<Self> {
+        let paths = WatchedPaths::new(config, config_path)?;
+
+        let (sender, events) = channel();
+        let mut watcher =
+            notify::recommended_watcher(sender).context("failed to start file watcher")?;
+
+        watcher
+            .watch(paths.articles_dir.as_std_path(), RecursiveMode::Recursive)
+            .with_context(|| format!("failed to watch directory at {}", paths.articles_dir))?;
+
+        let mut site_watcher = Self {
+            watcher,
+            events,
+            paths,
+            file_dirs: BTreeSet::new(),
+        };
+
+        // Editors often replace files instead of modifying them, so watch the directories containing individual files
+        let files: Vec<_> = [&site_watcher.paths.config_file]
+            .into_iter()
+            .chain(&site_watcher.paths.site_files)
+            .cloned()
+            .collect();
+        for file in &files {
+            site_watcher.watch_file_dir(file)?;
+        }
+
+        Ok(site_watcher)
+    }
+
+    /// Additionally watches the input files in a dependency graph that are outside the articles directory
+    /// and are not already watched, such as partials included by templates.
+    ///
+    /// # Errors
+    /// This function returns an error if the directory containing such a file cannot be watched.
+    pub fn watch_dependencies(&mut self, graph: &DependencyGraph) -> Result<()> {
+        for input in graph.inputs() {
+            if input.starts_with(&self.paths.articles_dir)
+                || input.starts_with(&self.paths.output_dir)
+                || self.paths.site_files.iter().any(|file| file == input)
+            {
+                continue;
+            }
+            self.watch_file_dir(input)?;
+            self.paths.site_files.push(input.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Watches the directory containing a file, if it is not already watched.
+    fn watch_file_dir(&mut self, file: &Utf8Path) -> Result<()> {
+        if let Some(dir) = file.parent()
+            && dir.is_dir()
+            && !self.file_dirs.contains(dir)
+        {
+            self.watcher
+                .watch(dir.as_std_path(), RecursiveMode::NonRecursive)
+                .with_context(|| format!("failed to watch directory at {dir}"))?;
+            self.file_dirs.insert(dir.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Blocks until input files of the site change, returning the changed paths.
+    /// Changes are coalesced until no new changes have occurred for a short time.
+    ///
+    /// # Errors
+    /// This function returns an error if the file system watcher fails.
+    pub fn wait_for_changes(&self) -> Result<ChangeSet<'_>> {
+        let mut paths = BTreeSet::new();
+
+        // Wait indefinitely for the first relevant change, then until changes stop
+        while paths.is_empty() {
+            let event = self
+                .events
+                .recv()
+                .map_err(|_| anyhow!("file watcher stopped unexpectedly"))?;
+            self.add_event_paths(event, &mut paths)?;
+        }
+        loop {
+            match self.events.recv_timeout(DEBOUNCE_DURATION) {
+                Ok(event) => self.add_event_paths(event, &mut paths)?,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(anyhow!("file watcher stopped unexpectedly"));
+                }
+            }
+        }
+
+        Ok(ChangeSet {
+            paths,
+            watched: &self.paths,
+        })
+    }
+
+    fn add_event_paths(
+        &self,
+        event: notify::Result<notify::Event>,
+        paths: &mut BTreeSet<Utf8PathBuf>,
+    ) -> Result<()> {
+        let event = event.context("failed to watch for changes")?;
+        if matches!(event.kind, EventKind::Access(_)) {
+            return Ok(());
+        }
+
+        paths.extend(
+            event
+                .paths
+                .into_iter()
+                .filter_map(|path| Utf8PathBuf::from_path_buf(path).ok())
+                .filter(|path| self.paths.is_relevant(path)),
+        );
+        Ok(())
+    }
+}
+
+impl WatchedPaths {
+    fn new(config: &Config, config_path: &Utf8Path) -> Result<Self> {
+        let ignore_patterns = EDITOR_TEMP_PATTERNS
+            .iter()
+            .copied()
+            .chain(config.watch_ignore.iter().map(AsRef::as_ref))
+            .map(Pattern::new)
+            .collect::<Result<_, _>>()
+            .context("failed to parse ignore pattern")?;
+
+        Ok(Self {
+            config_file: canonicalize(config_path)?,
+            site_files: config
+                .site_css_files
+                .iter()
+                .chain([
+                    &config.head_template_html_file,
+                    &config.body_template_html_file,
+                ])
+                .chain(config.fragments.iter().map(|fragment| &fragment.path))
+                .chain(&config.code_theme_file)
+                .chain(&config.code_theme_dark_file)
+                .map(|path| canonicalize(path))
+                .collect::<Result<_>>()?,
+            articles_dir: canonicalize(&config.articles_dir)?,
+            // The output directory does not exist before the site is built, but its parent must
+            output_dir: match (config.output_dir.parent(), config.output_dir.file_name()) {
+                (Some(parent), Some(name)) if !parent.as_str().is_empty() => {
+                    canonicalize(parent)?.join(name)
+                }
+                _ => canonicalize(Utf8Path::new("."))?.join(&*config.output_dir),
+            },
+            ignore_patterns,
+        })
+    }
+
+    /// Checks if a changed path is an input of the site and is not ignored.
+    fn is_relevant(&self, path: &Utf8Path) -> bool {
+        if path.starts_with(&self.output_dir) {
+            return false;
+        }
+
+        if path == self.config_file || self.site_files.iter().any(|file| path == file) {
+            return !self.is_ignored(path.file_name().unwrap_or_default());
+        }
+
+        // Like `.gitignore` files, patterns without a slash match any part of the path,
+        // while patterns with a slash match the path relative to the articles directory
+        path.strip_prefix(&self.articles_dir).is_ok_and(|relative| {
+            !self.is_ignored(relative.as_str())
+                && !relative
+                    .components()
+                    .any(|component| self.is_ignored(component.as_str()))
+        })
+    }
+
+    fn is_ignored(&self, path: &str) -> bool {
+        let is_component = !path.contains('/');
+        self.ignore_patterns.iter().any(|pattern| {
+            // Patterns with a slash only match relative paths
+            (is_component || pattern.as_str().contains('/'))
+                && pattern.matches(path.trim_start_matches('/'))
+        })
+    }
+}
+
+impl ChangeSet<'_> {
+    /// Returns the outputs affected by the changes, according to the dependency graph of the last build.
+    /// This function returns `None` if every output may be affected, which is the case if:
+    /// - the config file changed
+    /// - an article file that the last build did not read (e.g. a new article) changed
+    #[must_use]
+    pub fn affected_outputs<'g>(&self, graph: &'g DependencyGraph) -> Option<BTreeSet<&'g str>> {
+        for path in &self.paths {
+            if *path == self.watched.config_file
+                || (path.extension() == Some("md")
+                    && path.starts_with(&self.watched.articles_dir)
+                    && !graph.contains_input(path))
+            {
+                return None;
+            }
+        }
+
+        Some(graph.affected_outputs(self.paths.iter().map(AsRef::as_ref)))
+    }
+}
+
+impl Display for ChangeSet<'_> {
+    /// Formats a list of the changed paths.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Detected changes to {} file(s):", self.paths.len())?;
+        for path in &self.paths {
+            write!(f, "\n  {path}")?;
+        }
+        Ok(())
+    }
+}
+
+fn canonicalize(path: &Utf8Path) -> Result<Utf8PathBuf> {
+    path.canonicalize_utf8()
+        .with_context(|| format!("failed to resolve path {path}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChangeSet, EDITOR_TEMP_PATTERNS, WatchedPaths};
+    use crate::DependencyGraph;
+    use camino::Utf8PathBuf;
+    use glob::Pattern;
+    use std::collections::BTreeSet;
+
+    fn watched_paths(ignore: &[&str]) -> WatchedPaths {
+        WatchedPaths {
+            config_file: "/site/config.toml".into(),
+            site_files: vec!["/site/site.css".into(), "/site/about.html".into()],
+            articles_dir: "/site/articles".into(),
+            output_dir: "/site/out".into(),
+            ignore_patterns: EDITOR_TEMP_PATTERNS
+                .iter()
+                .chain(ignore)
+                .map(|pattern| Pattern::new(pattern).unwrap())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn relevance() {
+        let watched = watched_paths(&["drafts", "2024/*.txt"]);
+
+        for path in [
+            "/site/config.toml",
+            "/site/about.html",
+            "/site/articles/a.md",
+            "/site/articles/2024/image.png",
+            "/site/articles/2023/notes.txt",
+        ] {
+            assert!(watched.is_relevant(path.into()), "{path}");
+        }
+        for path in [
+            "/site/README.md",
+            "/site/out/index.html",
+            "/site/articles/.a.md.swp",
+            "/site/articles/a.md~",
+            "/site/articles/drafts/a.md",
+            "/site/articles/2024/notes.txt",
+        ] {
+            assert!(!watched.is_relevant(path.into()), "{path}");
+        }
+    }
+
+    #[test]
+    fn affected_outputs() {
+        let watched = watched_paths(&[]);
+        let changes = |paths: &[&str]| ChangeSet {
+            paths: paths.iter().copied().map(Utf8PathBuf::from).collect(),
+            watched: &watched,
+        };
+
+        let mut graph = DependencyGraph::new();
+        graph.add("about/index.html", ["/site/about.html", "/site/site.css"]);
+        graph.add(
+            "writing/a/index.html",
+            ["/site/articles/2024/a.md", "/site/site.css"],
+        );
+        graph.add("writing/index.html", ["/site/articles/2024/a.md"]);
+
+        assert_eq!(
+            changes(&["/site/about.html", "/site/articles/2024/a.md"]).affected_outputs(&graph),
+            Some(BTreeSet::from([
+                "about/index.html",
+                "writing/a/index.html",
+                "writing/index.html"
+            ]))
+        );
+        assert_eq!(
+            changes(&["/site/articles/2024/image.png"]).affected_outputs(&graph),
+            Some(BTreeSet::new())
+        );
+        assert_eq!(
+            changes(&["/site/articles/2024/b.md"]).affected_outputs(&graph),
+            None
+        );
+        assert_eq!(
+            changes(&["/site/config.toml"]).affected_outputs(&graph),
+            None
+        );
+    }
+}
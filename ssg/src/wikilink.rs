@@ -0,0 +1,89 @@
+//! Code for resolving wiki-style links like `[[Other Article Title]]` to the articles with those titles.
+
+use crate::url::article_url;
+use anyhow::{Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::collections::BTreeMap;
+
+/// Titles of every article being built, for resolving wiki-style links.
+#[derive(Default)]
+pub struct WikiLinks {
+    // Slugs and paths of the articles with every title, keyed by normalized title
+    articles: BTreeMap<String, Vec<(Box<str>, Utf8PathBuf)>>,
+}
+
+impl WikiLinks {
+    /// Initializes an empty set of link targets.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an article as a link target.
+    pub fn add_article(&mut self, title: &str, slug: &str, path: &Utf8Path) {
+        self.articles
+            .entry(normalize_title(title))
+            .or_default()
+            .push((slug.into(), path.to_path_buf()));
+    }
+
+    /// Returns the root-relative URL and the file path of the article with a title.
+    /// Titles match regardless of case and of whitespace between words.
+    ///
+    /// # Errors
+    /// This function returns an error if no article or more than one article has the title.
+    pub fn resolve(&self, title: &str) -> Result<(String, &Utf8Path)> {
+        match self
+            .articles
+            .get(&normalize_title(title))
+            .map(Vec::as_slice)
+        {
+            Some([(slug, path)]) => Ok((article_url(slug), path)),
+            Some(articles) => bail!(
+                "found a wiki link to \"{title}\", which is the title of multiple articles: {}",
+                articles
+                    .iter()
+                    .map(|(slug, _)| &**slug)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            None => {
+                bail!("found a wiki link to \"{title}\", which is not the title of any article")
+            }
+        }
+    }
+}
+
+fn normalize_title(title: &str) -> String {
+    title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use super::WikiLinks;
+    use camino::Utf8Path;
+
+    #[test]
+    fn resolve() {
+        let mut links = WikiLinks::new();
+        links.add_article("All About Animals", "animals", Utf8Path::new("a.md"));
+        links.add_article("Notes", "notes-1", Utf8Path::new("b.md"));
+        links.add_article("notes", "notes-2", Utf8Path::new("c.md"));
+
+        let (url, path) = links.resolve("all about  animals").unwrap();
+        assert_eq!(url, "/writing/animals/");
+        assert_eq!(path, "a.md");
+        assert!(
+            links
+                .resolve("Notes")
+                .unwrap_err()
+                .to_string()
+                .contains("notes-1, notes-2")
+        );
+        assert!(links.resolve("Plants").is_err());
+    }
+}